@@ -1,8 +1,10 @@
 use std::{env, error::Error};
 
-fn main() -> Result<(), Box<Error>> {
+fn main() -> Result<(), Box<dyn Error>> {
     let target = env::var("TARGET")?;
 
+    println!("cargo:rustc-check-cfg=cfg(armv7m)");
+
     match &*target {
         "thumbv7m-none-eabi" | "thumbv7em-none-eabi" | "thumbv7em-none-eabihf" => {
             println!("cargo:rustc-cfg=armv7m")
@@ -11,11 +11,19 @@ main() {
     if [ $TARGET = x86_64-unknown-linux-gnu ]; then
         cargo test --target $TARGET
         cargo test --target $TARGET --release
+        cargo test --target $TARGET --features 'leak-guard'
 
         if [ $TRAVIS_RUST_VERSION = nightly ]; then
             cargo test --target $TARGET --features 'union'
             cargo test --target $TARGET --release --features 'union'
 
+            # some modules are only compiled for targets where `Pool` is `Sync`; check that each
+            # feature builds, without dead code, on its own
+            local feature
+            for feature in $CLIPPY_FEATURES; do
+                cargo clippy --target $TARGET --all-targets --features $feature -- -D warnings
+            done
+
             export RUSTFLAGS="-Z sanitizer=address"
             export ASAN_OPTIONS="detect_odr_violation=0"
 
@@ -23,6 +31,17 @@ main() {
             cargo test --target $TARGET --release
         fi
     else
+        # build each feature on its MSRV
+        if [ $TRAVIS_RUST_VERSION != nightly ]; then
+            local feature version
+            for feature in $CLIPPY_FEATURES; do
+                version=$(msrv $feature)
+                if [ $version = $TRAVIS_RUST_VERSION ]; then
+                    cargo check --target $TARGET --features $feature
+                fi
+            done
+        fi
+
         if [ $TRAVIS_RUST_VERSION = nightly ]; then
             cargo check --target $TARGET --features 'arch'
             cargo check --target $TARGET --features 'arch union'
@@ -32,6 +51,43 @@ main() {
     fi
 }
 
+# every feature that builds on stable
+CLIPPY_FEATURES='
+    arena binary-heap bitmap broadcast buddy bytemuck bytes channel collections composite cortex-m
+    cortex-m-rt defmt defmt-logger dma dma-ring fifo global-alloc handles heapless heapless-compat
+    leak-guard logger mailbox maybe-uninit mpsc mpu panic-report partition per-core persist poison
+    pooled postcard quarantine recycle registry rtic scoped serde slab smoltcp stable_deref_trait
+    static-pool stats stream sub-pool task-pool timer track-callers untyped usb-device work-queue
+    x86-sync-hazard
+'
+
+# the minimum Rust version that each feature supports; keep in sync with the crate level docs
+msrv() {
+    case $1 in
+        arena | bitmap | buddy | bytemuck | bytes | channel | collections | composite | cortex-m | \
+        cortex-m-rt | defmt | defmt-logger | dma | dma-ring | fifo | global-alloc | handles | \
+        heapless | heapless-compat | leak-guard | logger | mailbox | maybe-uninit | mpsc | mpu | \
+        partition | per-core | persist | poison | pooled | postcard | quarantine | recycle | \
+        rtic | scoped | serde | slab | stable_deref_trait | stats | stream | sub-pool | timer | \
+        track-callers | untyped | x86-sync-hazard)
+            echo 1.60.0
+            ;;
+        binary-heap | broadcast | panic-report | registry | task-pool | usb-device | work-queue)
+            echo 1.61.0
+            ;;
+        smoltcp)
+            echo 1.80.0
+            ;;
+        static-pool)
+            echo 1.83.0
+            ;;
+        *)
+            echo "feature $1 has no MSRV" >&2
+            exit 1
+            ;;
+    esac
+}
+
 # fake Travis variables to be able to run this on a local machine
 if [ -z ${TRAVIS_BRANCH-} ]; then
     TRAVIS_BRANCH=auto
@@ -0,0 +1,171 @@
+//! Procedural macros for the `lifo` crate
+//!
+//! Use these through the re-exports in `lifo`; see the `pooled` feature of that crate.
+
+#![deny(missing_docs)]
+#![deny(warnings)]
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    DeriveInput, Error, Expr, Ident, Token,
+};
+
+/// Declares a global singleton pool dedicated to the annotated type
+///
+/// `#[pooled] struct Msg { .. }` declares the pool `MsgPool` with `Msg` as its data type, using
+/// `lifo::pool!`, and adds these items to `Msg`:
+///
+/// - `Msg::alloc()`, which claims a memory block from the pool
+/// - `Msg::BLOCK_SIZE`, the number of bytes used by each memory block
+///
+/// The attribute accepts these options, separated by commas:
+///
+/// - `pool = MsgQueue` names the pool `MsgQueue` instead of `MsgPool`
+/// - `capacity = 16` declares a static buffer for 16 memory blocks, like the `capacity` option of
+///   `pool!`, and adds `Msg::init()`, which grows the pool with it, and the `Msg::CAPACITY` and
+///   `Msg::MEMORY_SIZE` constants
+/// - `link_section = ".ccmram"` and `wakers = 4` are forwarded to `pool!`
+///
+/// The pool and the generated items take the visibility of the type. The type can't be generic.
+#[proc_macro_attribute]
+pub fn pooled(args: TokenStream, input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as Args);
+    let item = parse_macro_input!(input as DeriveInput);
+
+    match expand(args, item) {
+        Ok(ts) => ts.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+struct Args {
+    options: Punctuated<Arg, Token![,]>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Ok(Args {
+            options: Punctuated::parse_terminated(input)?,
+        })
+    }
+}
+
+struct Arg {
+    name: Ident,
+    value: Expr,
+}
+
+impl Parse for Arg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let value = input.parse()?;
+
+        Ok(Arg { name, value })
+    }
+}
+
+fn expand(args: Args, item: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    if !item.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &item.generics,
+            "`#[pooled]` types can't be generic",
+        ));
+    }
+
+    let ident = &item.ident;
+    let vis = &item.vis;
+
+    let mut pool = format_ident!("{}Pool", ident);
+    let mut capacity = None;
+    let mut options = vec![];
+    for arg in args.options {
+        match &*arg.name.to_string() {
+            "pool" => pool = expr_to_ident(&arg.value)?,
+            "capacity" => {
+                capacity = Some(arg.value.clone());
+                options.push(arg);
+            }
+            "link_section" | "wakers" => options.push(arg),
+            _ => {
+                return Err(Error::new(
+                    arg.name.span(),
+                    format!("unknown `#[pooled]` option: `{}`", arg.name),
+                ))
+            }
+        }
+    }
+
+    // the pool inherits the `cfg`s of the type
+    let cfgs = item
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("cfg"))
+        .collect::<Vec<_>>();
+    let pool_doc = format!("Global singleton pool of `{}` values", ident);
+    let option_names = options.iter().map(|arg| &arg.name);
+    let option_values = options.iter().map(|arg| &arg.value);
+
+    let capacity_items = capacity.map(|capacity| {
+        quote!(
+            /// Number of values the statically allocated memory of the pool can hold
+            #vis const CAPACITY: usize = #capacity;
+
+            /// Size, in bytes, of the statically allocated memory of the pool
+            #vis const MEMORY_SIZE: usize = ::lifo::__memory_size::<Self>(#capacity);
+
+            /// Grows the pool with its statically allocated memory
+            ///
+            /// Only the first call has an effect
+            #vis fn init() {
+                #pool::init()
+            }
+        )
+    });
+
+    Ok(quote!(
+        #item
+
+        ::lifo::pool!(
+            #(#cfgs)*
+            #[doc = #pool_doc]
+            #vis #pool: #ident #(; #option_names = #option_values)*
+        );
+
+        #(#cfgs)*
+        #[allow(dead_code)]
+        impl #ident {
+            /// Number of bytes used by each memory block of the pool, including the pool's
+            /// bookkeeping
+            #vis const BLOCK_SIZE: usize = ::lifo::__block_size::<Self>();
+
+            #capacity_items
+
+            /// Claims a memory block from the pool
+            ///
+            /// Returns `None` when the pool is observed as exhausted
+            #vis fn alloc() -> Option<::lifo::singleton::Box<#pool, ::lifo::Uninit>> {
+                <#pool as ::lifo::singleton::Pool>::alloc()
+            }
+        }
+    ))
+}
+
+fn expr_to_ident(expr: &Expr) -> syn::Result<Ident> {
+    if let Expr::Path(path) = expr {
+        if let Some(ident) = path.path.get_ident() {
+            return Ok(ident.clone());
+        }
+    }
+
+    Err(Error::new_spanned(
+        expr,
+        "the `pool` option expects an identifier",
+    ))
+}
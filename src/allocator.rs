@@ -0,0 +1,106 @@
+//! `Allocator` implementation for `Pool`
+
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    mem::{self, MaybeUninit},
+    ptr::{self, NonNull},
+};
+
+use crate::{Node, Pool};
+
+impl<T> Pool<T> {
+    // Returns `true` if a memory block can hold an allocation with the given `layout`
+    fn fits(layout: Layout) -> bool {
+        layout.size() <= mem::size_of::<T>() && layout.align() <= mem::align_of::<T>()
+    }
+
+    fn data(node: NonNull<Node<T>>) -> NonNull<[u8]> {
+        let data = unsafe { node.as_ref().data.get() as *mut u8 };
+
+        // NOTE(unsafe) `UnsafeCell::get` never returns a null pointer
+        unsafe { NonNull::new_unchecked(ptr::slice_from_raw_parts_mut(data, mem::size_of::<T>())) }
+    }
+
+    fn node(data: NonNull<u8>) -> NonNull<Node<T>> {
+        let node = MaybeUninit::<Node<T>>::uninit();
+        let base = node.as_ptr();
+        // NOTE(unsafe) no reference to the uninitialized node is created
+        let offset = unsafe { ptr::addr_of!((*base).data) as usize - base as usize };
+
+        unsafe { NonNull::new_unchecked(data.as_ptr().sub(offset) as *mut Node<T>) }
+    }
+}
+
+// NOTE(unsafe) memory blocks are never reused while they are allocated and they live in `'static`
+// memory so they stay valid even if the pool is moved
+unsafe impl<T> Allocator for Pool<T> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if !Self::fits(layout) {
+            return Err(AllocError);
+        }
+
+        let block = self.alloc().ok_or(AllocError)?;
+
+        Ok(Self::data(block.into_node()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, _: Layout) {
+        // NOTE the memory block holds no `T` value
+        self.release(Self::node(ptr))
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        _: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // all memory blocks have the same size so there's nothing to grow into
+        if Self::fits(new_layout) {
+            Ok(Self::data(Self::node(ptr)))
+        } else {
+            Err(AllocError)
+        }
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        _: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if Self::fits(new_layout) {
+            Ok(Self::data(Self::node(ptr)))
+        } else {
+            Err(AllocError)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::memory, Pool};
+
+    #[test]
+    fn allocator() {
+        static POOL: Pool<[u64; 2]> = Pool::new();
+
+        POOL.grow(memory::<[u64; 2]>(1));
+
+        let mut v = Vec::new_in(&POOL);
+        v.push(1u32);
+        // one memory block holds up to 4 `u32`s
+        assert_eq!(v.capacity(), 4);
+        v.extend_from_slice(&[2, 3, 4]);
+        assert!(v.try_reserve(1).is_err());
+
+        let b = Box::try_new_in(0u8, &POOL);
+        assert!(b.is_err());
+
+        drop(v);
+        assert_eq!(*Box::new_in(5u8, &POOL), 5);
+
+        // `u128` requires a larger alignment than the memory blocks provide
+        assert!(Box::try_new_in(0u128, &POOL).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! Bump allocator for frame-based workloads
+//!
+//! An `Arena` hands out values from one or more static memory regions by bumping a pointer, and
+//! frees them all at once with `reset`. This fits workloads that allocate scratch values in each
+//! iteration of a loop, e.g. while handling a request or rendering a frame, and drop them all at
+//! the end of it.
+//!
+//! ``` ignore
+//! use lifo::arena::Arena;
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     static mut MEMORY: [u8; 1024] = [0; 1024];
+//!
+//!     let mut arena = Arena::new();
+//!     arena.grow(MEMORY);
+//!
+//!     loop {
+//!         let request = arena.alloc(Request::receive()).ok().unwrap();
+//!         let response = arena.alloc(Response::for_request(request)).ok().unwrap();
+//!         response.send();
+//!
+//!         // all the values allocated in this iteration are freed here
+//!         arena.reset();
+//!     }
+//! }
+//! ```
+//!
+//! The destructors of the values allocated from an arena never run. An `Arena` is meant to be used
+//! from a single context so, unlike `Pool`, it's not `Sync`.
+
+use core::{
+    cell::Cell,
+    fmt,
+    mem::{self, MaybeUninit},
+    ptr,
+};
+
+// Header stored at the start of each memory region
+struct Region {
+    next: *mut Region,
+    // one past the last byte of the region
+    end: usize,
+}
+
+/// A bump allocator over static memory regions
+pub struct Arena {
+    first: Cell<*mut Region>,
+    current: Cell<*mut Region>,
+    // address of the next free byte of the current region
+    cursor: Cell<usize>,
+}
+
+impl Arena {
+    /// Creates a new empty arena
+    pub const fn new() -> Self {
+        Arena {
+            first: Cell::new(ptr::null_mut()),
+            current: Cell::new(ptr::null_mut()),
+            cursor: Cell::new(0),
+        }
+    }
+
+    /// Moves `val` into the arena
+    ///
+    /// Returns `val` back when the arena is observed as exhausted
+    // NOTE each call hands out a different part of the arena so the returned references don't alias
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T>(&self, val: T) -> Result<&mut T, T> {
+        match self.claim(mem::size_of::<T>(), mem::align_of::<T>()) {
+            Some(addr) => unsafe {
+                let p = addr as *mut T;
+                p.write(val);
+                Ok(&mut *p)
+            },
+            None => Err(val),
+        }
+    }
+
+    /// Frees all the values allocated from the arena, without running their destructors
+    pub fn reset(&mut self) {
+        let first = self.first.get();
+
+        self.current.set(first);
+        self.cursor.set(Self::start(first));
+    }
+
+    /// Increases the capacity of the arena
+    ///
+    /// This method might *not* fully utilize the given memory block due to alignment requirements
+    pub fn grow(&self, memory: &'static mut [u8]) {
+        // NOTE(unsafe) see `Pool::grow`
+        self.grow_uninit(unsafe { &mut *(memory as *mut [u8] as *mut [MaybeUninit<u8>]) })
+    }
+
+    /// Increases the capacity of the arena using memory that has not been initialized
+    ///
+    /// This method might *not* fully utilize the given memory block due to alignment requirements
+    pub fn grow_uninit(&self, memory: &'static mut [MaybeUninit<u8>]) {
+        let start = memory.as_mut_ptr() as usize;
+        let end = start + memory.len();
+        let header = align_up(start, mem::align_of::<Region>());
+
+        if header + mem::size_of::<Region>() > end {
+            // memory block is too small to hold the header
+            return;
+        }
+
+        let region = header as *mut Region;
+        unsafe {
+            region.write(Region {
+                next: ptr::null_mut(),
+                end,
+            })
+        }
+
+        // append the region to the list so `reset` starts over from the first region
+        let mut last = self.first.get();
+        if last.is_null() {
+            self.first.set(region);
+            self.current.set(region);
+            self.cursor.set(Self::start(region));
+        } else {
+            unsafe {
+                while !(*last).next.is_null() {
+                    last = (*last).next;
+                }
+                (*last).next = region;
+            }
+        }
+    }
+
+    // Claims `size` bytes aligned to `align`; moves on to the next region if they don't fit in the
+    // current one
+    fn claim(&self, size: usize, align: usize) -> Option<usize> {
+        loop {
+            let region = self.current.get();
+            if region.is_null() {
+                return None;
+            }
+
+            let addr = align_up(self.cursor.get(), align);
+            if addr <= unsafe { (*region).end } && size <= unsafe { (*region).end } - addr {
+                self.cursor.set(addr + size);
+                return Some(addr);
+            }
+
+            // NOTE the rest of the current region is wasted until the next `reset`
+            let next = unsafe { (*region).next };
+            if next.is_null() {
+                return None;
+            }
+
+            self.current.set(next);
+            self.cursor.set(Self::start(next));
+        }
+    }
+
+    // Returns the address of the first byte after the header of `region`
+    fn start(region: *mut Region) -> usize {
+        region as usize + mem::size_of::<Region>()
+    }
+}
+
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Arena::new()
+    }
+}
+
+impl fmt::Debug for Arena {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Arena").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem;
+
+    use super::Arena;
+
+    #[test]
+    fn arena() {
+        #[repr(align(8))]
+        struct Memory([u8; 4 * mem::size_of::<usize>()]);
+
+        let mut arena = Arena::new();
+        assert_eq!(arena.alloc(0u8).err(), Some(0));
+
+        // each region has room for two `usize`s after its header
+        arena.grow(&mut Box::leak(Box::new(Memory([0; 4 * mem::size_of::<usize>()]))).0);
+        arena.grow(&mut Box::leak(Box::new(Memory([0; 4 * mem::size_of::<usize>()]))).0);
+
+        let a = arena.alloc(1u8).unwrap();
+        let b = arena.alloc(2usize).unwrap();
+        // doesn't fit in the first region
+        let c = arena.alloc(3usize).unwrap();
+        *a += 10;
+        assert_eq!((*a, *b, *c), (11, 2, 3));
+        assert_eq!(arena.alloc([0usize; 2]).err(), Some([0; 2]));
+
+        arena.reset();
+        assert_eq!(*arena.alloc([4usize; 2]).unwrap(), [4; 2]);
+        assert_eq!(*arena.alloc([5usize; 2]).unwrap(), [5; 2]);
+        assert!(arena.alloc(6u8).is_err());
+    }
+}
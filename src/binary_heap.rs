@@ -0,0 +1,257 @@
+//! Priority queue of memory blocks
+//!
+//! `BinaryHeap<T, K, N>` orders up to `N` `Box<T>`es by their `T` values. The heap only stores the
+//! pointers to the memory blocks, not the values, so reordering it never copies a `T` value around,
+//! which keeps `push` and `pop` cheap even if `T` is large.
+//!
+//! ``` ignore
+//! use lifo::{binary_heap::{BinaryHeap, Min}, Pool};
+//!
+//! static JOBS: Pool<Job> = Pool::new();
+//!
+//! // jobs are ordered by deadline, then by priority
+//! let mut queue = BinaryHeap::<Job, Min, 8>::new();
+//!
+//! queue.push(JOBS.alloc_init(Job { deadline: 10, priority: 1 }).ok().unwrap()).ok().unwrap();
+//! queue.push(JOBS.alloc_init(Job { deadline: 5, priority: 0 }).ok().unwrap()).ok().unwrap();
+//!
+//! let next = queue.pop().unwrap();
+//! assert_eq!(next.deadline, 5);
+//! JOBS.free(next);
+//! ```
+
+use core::{cmp::Ordering, fmt, marker::PhantomData, mem::MaybeUninit};
+
+use crate::Box;
+
+/// The order of a `BinaryHeap`
+pub trait Kind: sealed::Sealed {
+    #[doc(hidden)]
+    fn ordering() -> Ordering;
+}
+
+/// Max-heap: `pop` returns the greatest value
+pub enum Max {}
+
+/// Min-heap: `pop` returns the smallest value
+pub enum Min {}
+
+impl Kind for Max {
+    fn ordering() -> Ordering {
+        Ordering::Greater
+    }
+}
+
+impl Kind for Min {
+    fn ordering() -> Ordering {
+        Ordering::Less
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for super::Max {}
+    impl Sealed for super::Min {}
+}
+
+/// A fixed-capacity priority queue of up to `N` `Box<T>`es
+///
+/// Boxes still in the heap when it's dropped are leaked
+pub struct BinaryHeap<T, K, const N: usize>
+where
+    K: Kind,
+{
+    _kind: PhantomData<K>,
+    data: [MaybeUninit<Box<T>>; N],
+    len: usize,
+}
+
+impl<T, K, const N: usize> BinaryHeap<T, K, N>
+where
+    K: Kind,
+{
+    // only used to initialize `BinaryHeap.data`
+    const UNINIT: MaybeUninit<Box<T>> = MaybeUninit::uninit();
+
+    /// Creates a new empty heap
+    pub const fn new() -> Self {
+        BinaryHeap {
+            _kind: PhantomData,
+            data: [Self::UNINIT; N],
+            len: 0,
+        }
+    }
+
+    /// Returns the number of boxes in the heap
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the heap is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the maximum number of boxes the heap can hold
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    // NOTE(unsafe) `index` must be less than `self.len`
+    unsafe fn get(&self, index: usize) -> &T {
+        &*self.data.get_unchecked(index).as_ptr()
+    }
+
+    // Returns `true` if the value at `a` must be closer to the top of the heap than the one at `b`
+    fn before(&self, a: usize, b: usize) -> bool
+    where
+        T: Ord,
+    {
+        unsafe { self.get(a).cmp(self.get(b)) == K::ordering() }
+    }
+
+    fn sift_up(&mut self, mut index: usize)
+    where
+        T: Ord,
+    {
+        while index > 0 {
+            let parent = (index - 1) / 2;
+            if !self.before(index, parent) {
+                break;
+            }
+
+            self.data.swap(index, parent);
+            index = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut index: usize)
+    where
+        T: Ord,
+    {
+        loop {
+            let mut top = index;
+            for child in [2 * index + 1, 2 * index + 2].iter().copied() {
+                if child < self.len && self.before(child, top) {
+                    top = child;
+                }
+            }
+
+            if top == index {
+                break;
+            }
+
+            self.data.swap(index, top);
+            index = top;
+        }
+    }
+
+    /// Returns the value at the top of the heap: the greatest one for a `Max` heap and the smallest
+    /// one for a `Min` heap
+    pub fn peek(&self) -> Option<&T> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(unsafe { self.get(0) })
+        }
+    }
+
+    /// Pushes `value` onto the heap
+    ///
+    /// Returns `value` back if the heap is full
+    pub fn push(&mut self, value: Box<T>) -> Result<(), Box<T>>
+    where
+        T: Ord,
+    {
+        if self.len == N {
+            return Err(value);
+        }
+
+        self.data[self.len] = MaybeUninit::new(value);
+        self.len += 1;
+        self.sift_up(self.len - 1);
+
+        Ok(())
+    }
+
+    /// Removes the box at the top of the heap and returns it
+    pub fn pop(&mut self) -> Option<Box<T>>
+    where
+        T: Ord,
+    {
+        if self.is_empty() {
+            return None;
+        }
+
+        self.len -= 1;
+        self.data.swap(0, self.len);
+        let top = unsafe { self.data[self.len].as_ptr().read() };
+        self.sift_down(0);
+
+        Some(top)
+    }
+
+    /// Returns an iterator over the values in the heap, in arbitrary order
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        (0..self.len).map(move |index| unsafe { self.get(index) })
+    }
+}
+
+impl<T, K, const N: usize> Default for BinaryHeap<T, K, N>
+where
+    K: Kind,
+{
+    fn default() -> Self {
+        BinaryHeap::new()
+    }
+}
+
+impl<T, K, const N: usize> fmt::Debug for BinaryHeap<T, K, N>
+where
+    K: Kind,
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BinaryHeap, Max, Min};
+    use crate::{tests::memory, Pool};
+
+    #[test]
+    fn binary_heap() {
+        static POOL: Pool<u8> = Pool::new();
+
+        POOL.grow(memory::<u8>(6));
+
+        let mut min = BinaryHeap::<u8, Min, 5>::new();
+        for i in [3, 1, 4, 1, 5].iter().copied() {
+            min.push(POOL.alloc_init(i).ok().unwrap()).ok().unwrap();
+        }
+        assert_eq!(min.peek(), Some(&1));
+
+        let x = POOL.alloc_init(9).ok().unwrap();
+        let x = min.push(x).err().unwrap();
+        POOL.free(x);
+
+        let mut max = BinaryHeap::<u8, Max, 5>::new();
+        let mut order = vec![];
+        while let Some(x) = min.pop() {
+            order.push(*x);
+            max.push(x).ok().unwrap();
+        }
+        assert_eq!(order, [1, 1, 3, 4, 5]);
+
+        order.clear();
+        while let Some(x) = max.pop() {
+            order.push(*x);
+            POOL.free(x);
+        }
+        assert_eq!(order, [5, 4, 3, 1, 1]);
+        assert!(max.is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+//! Pools backed by a bitmap instead of a free list
+//!
+//! `BitmapPool<T, N>` stores `N` memory blocks inline and keeps track of which ones are in use with
+//! one atomic flag per memory block. Unlike `Pool`, claiming a memory block has bounded execution
+//! time, at most `N` atomic swaps, and it doesn't rely on LL/SC semantics to avoid the ABA problem,
+//! so the pool is `Sync` on all targets that have atomic swaps.
+//!
+//! ``` ignore
+//! use lifo::bitmap::BitmapPool;
+//!
+//! static REQUESTS: BitmapPool<Request, 16> = BitmapPool::new();
+//!
+//! // e.g. from an interrupt handler
+//! let request = REQUESTS.alloc_init(Request::new()).ok().unwrap();
+//!
+//! // the memory block is returned to the pool when `request` is dropped
+//! drop(request);
+//! ```
+//!
+//! The trade-off is that claiming a memory block scans the flags, so it gets slower as the pool
+//! fills up, whereas `Pool::alloc` takes the same time regardless of the number of free memory
+//! blocks.
+
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+struct Slot<T> {
+    used: AtomicBool,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> Slot<T> {
+    // only used to initialize `BitmapPool.slots`
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: Slot<T> = Slot {
+        used: AtomicBool::new(false),
+        value: UnsafeCell::new(MaybeUninit::uninit()),
+    };
+}
+
+/// A lock-free pool of `N` memory blocks of type `T` that tracks free memory blocks with a bitmap
+pub struct BitmapPool<T, const N: usize> {
+    slots: [Slot<T>; N],
+}
+
+// NOTE(unsafe) values can be moved into the pool in one context and dropped in another
+unsafe impl<T, const N: usize> Sync for BitmapPool<T, N> where T: Send {}
+
+impl<T, const N: usize> BitmapPool<T, N> {
+    /// Creates a new pool with all its memory blocks free
+    pub const fn new() -> Self {
+        BitmapPool {
+            slots: [Slot::EMPTY; N],
+        }
+    }
+
+    /// Returns the number of memory blocks of the pool
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of memory blocks that are currently free
+    ///
+    /// *NOTE:* This method scans the whole bitmap
+    pub fn free_blocks(&self) -> usize {
+        self.slots
+            .iter()
+            .filter(|slot| !slot.used.load(Ordering::Relaxed))
+            .count()
+    }
+
+    /// Claims a memory block from the pool and moves `val` into it
+    ///
+    /// Returns `val` back when the pool is observed as exhausted
+    ///
+    /// This method has bounded execution time: it performs at most `N` atomic swaps
+    pub fn alloc_init(&'static self, val: T) -> Result<BitmapBox<T>, T> {
+        for slot in &self.slots {
+            // NOTE skip the swap, a read-modify-write operation, for slots that are in use
+            if !slot.used.load(Ordering::Relaxed) && !slot.used.swap(true, Ordering::Acquire) {
+                unsafe { slot.value.get().write(MaybeUninit::new(val)) }
+
+                return Ok(BitmapBox {
+                    used: &slot.used,
+                    value: unsafe { &mut *(*slot.value.get()).as_mut_ptr() },
+                });
+            }
+        }
+
+        Err(val)
+    }
+}
+
+impl<T, const N: usize> Default for BitmapPool<T, N> {
+    fn default() -> Self {
+        BitmapPool::new()
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for BitmapPool<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BitmapPool")
+            .field("capacity", &N)
+            .field("free_blocks", &self.free_blocks())
+            .finish()
+    }
+}
+
+/// A value stored in a memory block of a `BitmapPool`
+///
+/// Dropping this box drops the value and returns the memory block to its pool
+pub struct BitmapBox<T>
+where
+    T: 'static,
+{
+    used: &'static AtomicBool,
+    value: &'static mut T,
+}
+
+impl<T> Deref for BitmapBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> DerefMut for BitmapBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<T> Drop for BitmapBox<T> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.value as *mut T) }
+
+        self.used.store(false, Ordering::Release)
+    }
+}
+
+impl<T> fmt::Debug for BitmapBox<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <T as fmt::Debug>::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BitmapPool;
+
+    #[test]
+    fn bitmap_pool() {
+        static POOL: BitmapPool<u32, 2> = BitmapPool::new();
+
+        let a = POOL.alloc_init(1).unwrap();
+        let mut b = POOL.alloc_init(2).unwrap();
+        assert_eq!(POOL.alloc_init(3).err(), Some(3));
+        assert_eq!(POOL.free_blocks(), 0);
+
+        *b += 1;
+        assert_eq!((*a, *b), (1, 3));
+
+        // the freed memory block is reused
+        drop(a);
+        assert_eq!(POOL.free_blocks(), 1);
+        assert_eq!(*POOL.alloc_init(4).unwrap(), 4);
+    }
+}
@@ -0,0 +1,523 @@
+//! Buddy allocator for variable-size memory blocks
+//!
+//! `Buddy` manages a single static memory region and hands out memory blocks whose size is a
+//! power-of-two multiple of `MIN_BLOCK_SIZE`. A request is served from the smallest free block
+//! that fits, splitting larger blocks in halves ("buddies") as needed; freed blocks are merged
+//! back with their buddy when it's also free. This wastes less memory than a `Pool` when the size
+//! of the allocations varies a lot, at the cost of slower `alloc` and `free` operations.
+//!
+//! ``` ignore
+//! use lifo::buddy::Buddy;
+//!
+//! static HEAP: Buddy = Buddy::new();
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     static mut MEMORY: [u8; 16 * 1024] = [0; 16 * 1024];
+//!
+//!     HEAP.init(MEMORY);
+//!
+//!     let mut frame = HEAP.alloc(300).unwrap();
+//!     frame.copy_from_slice(&[0; 300]);
+//!
+//!     // returns the memory block, a 512-byte one, to `HEAP`
+//!     drop(frame);
+//!
+//!     // ..
+//! }
+//! ```
+//!
+//! # Interrupt safety
+//!
+//! The free lists of the allocator are protected by a lock that's never waited on: an `alloc`
+//! call that preempts another operation on the same allocator, e.g. from an interrupt handler,
+//! observes the allocator as exhausted and returns `None`. A `free` that preempts another
+//! operation is deferred: the memory block is pushed onto a lock-free list and returned to the
+//! free lists by the preempted operation before it completes. Neither operation ever blocks.
+//!
+//! Unlike `Pool`, `Buddy` doesn't rely on LL/SC semantics so it's `Sync` on all targets.
+
+use core::{
+    cell::UnsafeCell,
+    fmt, mem,
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+    slice,
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering},
+};
+
+/// Size, in bytes, of the smallest memory block handed out by `Buddy`
+///
+/// Every memory block is big enough to hold the free list bookkeeping: two pointers
+pub const MIN_BLOCK_SIZE: usize = 2 * mem::size_of::<usize>();
+
+// Maximum number of block sizes; more than any address space can hold
+const ORDERS: usize = 8 * mem::size_of::<usize>();
+
+/// A buddy allocator over a static memory region
+pub struct Buddy {
+    locked: AtomicBool,
+    // memory blocks freed while the allocator was locked
+    pending: AtomicPtr<Pending>,
+    inner: UnsafeCell<Inner>,
+}
+
+// NOTE(unsafe) `inner` is only accessed while holding the lock
+unsafe impl Sync for Buddy {}
+
+struct Inner {
+    // start of the managed memory; null when the allocator has not been initialized
+    base: *mut u8,
+    // number of bytes managed starting at `base`
+    len: usize,
+    // the managed memory fits in a single block of this order
+    max_order: usize,
+    // one "free" bit per memory block per order
+    bitmap: *mut u8,
+    // free lists; one per order
+    free: [*mut Free; ORDERS],
+}
+
+// Header of a memory block in a free list
+struct Free {
+    prev: *mut Free,
+    next: *mut Free,
+}
+
+// Header of a memory block whose `free` was deferred
+struct Pending {
+    next: *mut Pending,
+    order: usize,
+}
+
+impl Buddy {
+    /// Creates a new allocator with no memory
+    pub const fn new() -> Self {
+        Buddy {
+            locked: AtomicBool::new(false),
+            pending: AtomicPtr::new(ptr::null_mut()),
+            inner: UnsafeCell::new(Inner {
+                base: ptr::null_mut(),
+                len: 0,
+                max_order: 0,
+                bitmap: ptr::null_mut(),
+                free: [ptr::null_mut(); ORDERS],
+            }),
+        }
+    }
+
+    /// Hands `memory` to the allocator
+    ///
+    /// A small part of `memory` is used to keep track of the free memory blocks: about one bit per
+    /// `MIN_BLOCK_SIZE / 2` bytes. Only the first call has an effect.
+    pub fn init(&self, memory: &'static mut [u8]) {
+        if let Some(mut inner) = self.lock() {
+            if inner.base.is_null() {
+                inner.init(memory);
+            }
+        }
+    }
+
+    /// Claims a memory block that can hold at least `size` bytes
+    ///
+    /// Returns `None` when no free memory block is big enough or when this call preempts another
+    /// operation on the allocator (see the module documentation)
+    pub fn alloc(&'static self, size: usize) -> Option<BuddyBox> {
+        let mut order = 0;
+        while MIN_BLOCK_SIZE << order < size {
+            order += 1;
+
+            if order == ORDERS - 1 {
+                return None;
+            }
+        }
+
+        let ptr = self.lock()?.alloc(order)?;
+
+        Some(BuddyBox {
+            buddy: self,
+            ptr,
+            order,
+            len: size,
+        })
+    }
+
+    fn free(&self, ptr: NonNull<u8>, order: usize) {
+        if let Some(mut inner) = self.lock() {
+            // NOTE(unsafe) `ptr` was allocated from this allocator
+            unsafe { inner.free(ptr.as_ptr(), order) }
+        } else {
+            // NOTE(unsafe) the memory block is not in use and it can hold a `Pending` header
+            let pending = ptr.as_ptr() as *mut Pending;
+            let mut head = self.pending.load(Ordering::Relaxed);
+            loop {
+                unsafe {
+                    pending.write(Pending { next: head, order });
+                }
+
+                // NOTE nodes are only ever removed all at once so the ABA problem doesn't apply
+                match self.pending.compare_exchange_weak(
+                    head,
+                    pending,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => break,
+                    // list was changed by some interrupt handler
+                    Err(new_head) => head = new_head,
+                }
+            }
+
+            // the lock may have been released before the memory block was pushed
+            drop(self.lock());
+        }
+    }
+
+    fn lock(&self) -> Option<Guard<'_>> {
+        if self.locked.swap(true, Ordering::Acquire) {
+            None
+        } else {
+            Some(Guard { buddy: self })
+        }
+    }
+}
+
+impl Default for Buddy {
+    fn default() -> Self {
+        Buddy::new()
+    }
+}
+
+// Exclusive access to the free lists of a `Buddy`
+struct Guard<'a> {
+    buddy: &'a Buddy,
+}
+
+impl Deref for Guard<'_> {
+    type Target = Inner;
+
+    fn deref(&self) -> &Inner {
+        unsafe { &*self.buddy.inner.get() }
+    }
+}
+
+impl DerefMut for Guard<'_> {
+    fn deref_mut(&mut self) -> &mut Inner {
+        unsafe { &mut *self.buddy.inner.get() }
+    }
+}
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        let buddy = self.buddy;
+
+        loop {
+            // return the memory blocks whose `free` was deferred
+            let mut pending = buddy.pending.swap(ptr::null_mut(), Ordering::Acquire);
+            while !pending.is_null() {
+                unsafe {
+                    let Pending { next, order } = pending.read();
+                    (*buddy.inner.get()).free(pending as *mut u8, order);
+                    pending = next;
+                }
+            }
+
+            buddy.locked.store(false, Ordering::Release);
+
+            // some interrupt handler may have deferred a `free` after we drained the list; if it
+            // didn't process it itself then we take care of it
+            if buddy.pending.load(Ordering::Relaxed).is_null()
+                || buddy.locked.swap(true, Ordering::Acquire)
+            {
+                break;
+            }
+        }
+    }
+}
+
+impl Inner {
+    fn init(&mut self, memory: &'static mut [u8]) {
+        let start = memory.as_mut_ptr();
+        let end = start as usize + memory.len();
+
+        // the managed memory, which is smaller than `memory`, fits in a block of this order
+        let mut max_order = 0;
+        while (MIN_BLOCK_SIZE << max_order) < memory.len() {
+            max_order += 1;
+        }
+
+        // 2 ** (max_order + 1) bits, i.e. 2 ** (max_order - 2) bytes
+        let bitmap_len = if max_order < 2 {
+            1
+        } else {
+            1 << (max_order - 2)
+        };
+        let base = (start as usize + bitmap_len + MIN_BLOCK_SIZE - 1) & !(MIN_BLOCK_SIZE - 1);
+        if base >= end {
+            // `memory` is too small
+            return;
+        }
+
+        unsafe { ptr::write_bytes(start, 0, bitmap_len) }
+
+        self.bitmap = start;
+        self.base = base as *mut u8;
+        self.len = end - base;
+        self.max_order = max_order;
+
+        // carve the managed memory into the biggest blocks possible
+        let mut offset = 0;
+        while offset + MIN_BLOCK_SIZE <= self.len {
+            let mut order = max_order;
+            while offset % (MIN_BLOCK_SIZE << order) != 0
+                || offset + (MIN_BLOCK_SIZE << order) > self.len
+            {
+                order -= 1;
+            }
+
+            self.push(offset, order);
+            offset += MIN_BLOCK_SIZE << order;
+        }
+    }
+
+    fn alloc(&mut self, order: usize) -> Option<NonNull<u8>> {
+        if order > self.max_order || self.base.is_null() {
+            return None;
+        }
+
+        let mut current = order;
+        while self.free[current].is_null() {
+            current += 1;
+
+            if current > self.max_order {
+                // allocator is exhausted
+                return None;
+            }
+        }
+
+        let offset = self.free[current] as usize - self.base as usize;
+        self.remove(offset, current);
+
+        // split the block, keeping the lower half, until it has the requested size
+        while current > order {
+            current -= 1;
+            self.push(offset + (MIN_BLOCK_SIZE << current), current);
+        }
+
+        NonNull::new(unsafe { self.base.add(offset) })
+    }
+
+    // NOTE(unsafe) `ptr` must point to a memory block of the given `order` that was allocated
+    // from this allocator
+    unsafe fn free(&mut self, ptr: *mut u8, mut order: usize) {
+        let mut offset = ptr as usize - self.base as usize;
+
+        // merge the block with its buddy for as long as the buddy is free
+        while order < self.max_order {
+            let buddy = offset ^ (MIN_BLOCK_SIZE << order);
+
+            // NOTE memory blocks that lie outside the managed memory are never marked as free
+            if !self.is_free(buddy, order) {
+                break;
+            }
+
+            self.remove(buddy, order);
+            offset &= !(MIN_BLOCK_SIZE << order);
+            order += 1;
+        }
+
+        self.push(offset, order)
+    }
+
+    fn push(&mut self, offset: usize, order: usize) {
+        let block = unsafe { self.base.add(offset) } as *mut Free;
+        let head = self.free[order];
+
+        unsafe {
+            block.write(Free {
+                prev: ptr::null_mut(),
+                next: head,
+            });
+
+            if !head.is_null() {
+                (*head).prev = block;
+            }
+        }
+
+        self.free[order] = block;
+        self.set_free(offset, order, true);
+    }
+
+    fn remove(&mut self, offset: usize, order: usize) {
+        unsafe {
+            let block = self.base.add(offset) as *mut Free;
+            let Free { prev, next } = block.read();
+
+            if prev.is_null() {
+                self.free[order] = next;
+            } else {
+                (*prev).next = next;
+            }
+
+            if !next.is_null() {
+                (*next).prev = prev;
+            }
+        }
+
+        self.set_free(offset, order, false);
+    }
+
+    // Position, in the bitmap, of the "free" bit of the memory block at `offset`
+    fn bit(&self, offset: usize, order: usize) -> (usize, u8) {
+        // orders are laid out one after the other, starting with order 0: `2 ** max_order` bits,
+        // then `2 ** (max_order - 1)` bits and so on
+        let start = (1 << (self.max_order + 1)) - (1 << (self.max_order + 1 - order));
+        let index = start + offset / (MIN_BLOCK_SIZE << order);
+
+        (index / 8, 1 << (index % 8))
+    }
+
+    fn is_free(&self, offset: usize, order: usize) -> bool {
+        let (byte, mask) = self.bit(offset, order);
+
+        unsafe { *self.bitmap.add(byte) & mask != 0 }
+    }
+
+    fn set_free(&mut self, offset: usize, order: usize, free: bool) {
+        let (byte, mask) = self.bit(offset, order);
+
+        unsafe {
+            let byte = &mut *self.bitmap.add(byte);
+
+            if free {
+                *byte |= mask;
+            } else {
+                *byte &= !mask;
+            }
+        }
+    }
+}
+
+/// A memory block claimed from a `Buddy` allocator
+///
+/// Dropping the box returns the memory block to its allocator
+pub struct BuddyBox {
+    buddy: &'static Buddy,
+    ptr: NonNull<u8>,
+    order: usize,
+    len: usize,
+}
+
+impl BuddyBox {
+    /// Returns the size, in bytes, of the memory block
+    ///
+    /// This is the requested size rounded up to a power-of-two multiple of `MIN_BLOCK_SIZE`
+    pub fn capacity(&self) -> usize {
+        MIN_BLOCK_SIZE << self.order
+    }
+
+    /// Changes the length of the slice the box dereferences to
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `len` is greater than the capacity of the memory block
+    pub fn set_len(&mut self, len: usize) {
+        assert!(len <= self.capacity(), "length exceeds the block capacity");
+
+        self.len = len;
+    }
+}
+
+impl Deref for BuddyBox {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // NOTE(unsafe) the memory handed to `init` was initialized
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for BuddyBox {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl AsRef<[u8]> for BuddyBox {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl AsMut<[u8]> for BuddyBox {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+impl Drop for BuddyBox {
+    fn drop(&mut self) {
+        self.buddy.free(self.ptr, self.order)
+    }
+}
+
+unsafe impl Send for BuddyBox {}
+
+unsafe impl Sync for BuddyBox {}
+
+impl fmt::Debug for BuddyBox {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <[u8] as fmt::Debug>::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Buddy, MIN_BLOCK_SIZE};
+
+    #[repr(align(16))]
+    struct Memory([u8; 5 * MIN_BLOCK_SIZE]);
+
+    #[test]
+    fn buddy() {
+        static HEAP: Buddy = Buddy::new();
+
+        // the bitmap takes the first block and the other 4 blocks are managed
+        HEAP.init(&mut Box::leak(Box::new(Memory([0; 5 * MIN_BLOCK_SIZE]))).0);
+
+        let mut a = HEAP.alloc(1).unwrap();
+        assert_eq!(a.capacity(), MIN_BLOCK_SIZE);
+        a.copy_from_slice(&[1]);
+
+        let b = HEAP.alloc(MIN_BLOCK_SIZE + 1).unwrap();
+        assert_eq!(b.capacity(), 2 * MIN_BLOCK_SIZE);
+        assert!(HEAP.alloc(2 * MIN_BLOCK_SIZE).is_none());
+
+        let c = HEAP.alloc(MIN_BLOCK_SIZE).unwrap();
+        assert!(HEAP.alloc(1).is_none());
+
+        // freed blocks are merged with their buddies
+        drop(a);
+        drop(c);
+        drop(b);
+        let d = HEAP.alloc(4 * MIN_BLOCK_SIZE).unwrap();
+        assert_eq!(d.len(), 4 * MIN_BLOCK_SIZE);
+    }
+
+    #[test]
+    fn deferred_free() {
+        static HEAP: Buddy = Buddy::new();
+
+        HEAP.init(&mut Box::leak(Box::new(Memory([0; 5 * MIN_BLOCK_SIZE]))).0);
+
+        let a = HEAP.alloc(4 * MIN_BLOCK_SIZE).unwrap();
+
+        // simulate an interrupt handler preempting an operation on the allocator
+        let guard = HEAP.lock().unwrap();
+        assert!(HEAP.alloc(1).is_none());
+        drop(a);
+        drop(guard);
+
+        assert!(HEAP.alloc(4 * MIN_BLOCK_SIZE).is_some());
+    }
+}
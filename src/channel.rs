@@ -0,0 +1,311 @@
+//! Single-producer single-consumer channel of memory blocks
+//!
+//! `Channel<T, N>` bundles a `Pool<T>` with a wait-free ring buffer that holds up to `N` `Box<T>`es.
+//! It covers the common case of an interrupt handler that fills buffers and hands them over to a
+//! task: the sender claims memory blocks from the channel's pool and sends them, and the receiver
+//! returns them to the pool once it's done with them.
+//!
+//! ``` ignore
+//! use lifo::channel::Channel;
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     static mut MEMORY: [u8; 1024] = [0; 1024];
+//!     static mut CHANNEL: Channel<[u8; 64], 8> = Channel::new();
+//!
+//!     CHANNEL.grow(MEMORY);
+//!     let (sender, mut receiver) = CHANNEL.split();
+//!     // move `sender` into the UART interrupt handler, which does:
+//!     // let mut frame = sender.alloc().unwrap().init([0; 64]);
+//!     // uart.read(&mut *frame);
+//!     // sender.send(frame).ok().unwrap();
+//!
+//!     loop {
+//!         if let Some(frame) = receiver.recv() {
+//!             process(&frame);
+//!             receiver.free(frame);
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Unlike with `Mpsc`, `send` and `recv` have bounded execution time, but there can only be one
+//! sender and the queue has a fixed capacity.
+
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{Box, Node, Pool, Uninit};
+
+struct Slot<T> {
+    node: UnsafeCell<*mut Node<T>>,
+}
+
+impl<T> Slot<T> {
+    // only used to initialize `Channel.ring`
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: Slot<T> = Slot {
+        node: UnsafeCell::new(ptr::null_mut()),
+    };
+}
+
+/// A pool of memory blocks bundled with a wait-free single-producer single-consumer queue of the
+/// `Box`es claimed from it
+///
+/// The queue holds up to `N` boxes. Boxes still in the queue when the channel is dropped are
+/// leaked.
+pub struct Channel<T, const N: usize> {
+    pool: Pool<T>,
+    // `head` and `tail` count modulo `2 * N` so a full queue can be told apart from an empty one
+    // index of the oldest box in the queue; only written by the receiver
+    head: AtomicUsize,
+    // index of the next free slot; only written by the sender
+    tail: AtomicUsize,
+    ring: [Slot<T>; N],
+}
+
+unsafe impl<T, const N: usize> Sync for Channel<T, N> where T: Send {}
+
+unsafe impl<T, const N: usize> Send for Channel<T, N> where T: Send {}
+
+impl<T, const N: usize> Channel<T, N> {
+    /// Creates a new channel with an empty pool
+    pub const fn new() -> Self {
+        Channel {
+            pool: Pool::new(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            ring: [Slot::EMPTY; N],
+        }
+    }
+
+    /// Increases the capacity of the channel's pool
+    ///
+    /// See `Pool::grow`
+    pub fn grow(&self, memory: &'static mut [u8]) {
+        self.pool.grow(memory)
+    }
+
+    /// Increases the capacity of the channel's pool using memory that has not been initialized
+    ///
+    /// See `Pool::grow_uninit`
+    pub fn grow_uninit(&self, memory: &'static mut [MaybeUninit<u8>]) {
+        self.pool.grow_uninit(memory)
+    }
+
+    /// Splits the channel into its sender and receiver endpoints
+    pub fn split(&mut self) -> (Sender<'_, T, N>, Receiver<'_, T, N>) {
+        (
+            Sender {
+                channel: self,
+                _not_sync: PhantomData,
+            },
+            Receiver {
+                channel: self,
+                _not_sync: PhantomData,
+            },
+        )
+    }
+
+    fn len(head: usize, tail: usize) -> usize {
+        if tail >= head {
+            tail - head
+        } else {
+            tail + 2 * N - head
+        }
+    }
+
+    fn next(index: usize) -> usize {
+        if index + 1 == 2 * N {
+            0
+        } else {
+            index + 1
+        }
+    }
+}
+
+impl<T, const N: usize> Default for Channel<T, N> {
+    fn default() -> Self {
+        Channel::new()
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Channel<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Channel").field("capacity", &N).finish()
+    }
+}
+
+/// The sending endpoint of a `Channel`
+pub struct Sender<'a, T, const N: usize> {
+    channel: &'a Channel<T, N>,
+    // `tail` is not synchronized
+    _not_sync: PhantomData<*const ()>,
+}
+
+unsafe impl<T, const N: usize> Send for Sender<'_, T, N> where T: Send {}
+
+impl<T, const N: usize> Sender<'_, T, N> {
+    /// Claims a memory block from the channel's pool
+    ///
+    /// Returns `None` when the pool is observed as exhausted
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc(&self) -> Option<Box<T, Uninit>> {
+        self.channel.pool.alloc()
+    }
+
+    /// Returns a memory block that won't be sent to the channel's pool
+    ///
+    /// See `Pool::free`
+    pub fn free<S>(&self, value: Box<T, S>)
+    where
+        S: 'static,
+    {
+        self.channel.pool.free(value)
+    }
+
+    /// Sends `value` through the channel
+    ///
+    /// Returns `value` back when the queue is observed as full
+    pub fn send(&mut self, value: Box<T>) -> Result<(), Box<T>> {
+        let channel = self.channel;
+        let tail = channel.tail.load(Ordering::Relaxed);
+        let head = channel.head.load(Ordering::Acquire);
+
+        if Channel::<T, N>::len(head, tail) == N {
+            return Err(value);
+        }
+
+        // NOTE(unsafe) the receiver doesn't read this slot until `tail` is bumped
+        unsafe { *channel.ring[tail % N].node.get() = value.into_node().as_ptr() }
+        channel
+            .tail
+            .store(Channel::<T, N>::next(tail), Ordering::Release);
+
+        Ok(())
+    }
+
+    /// Returns `true` if the queue is observed as full
+    pub fn is_full(&self) -> bool {
+        let channel = self.channel;
+
+        Channel::<T, N>::len(
+            channel.head.load(Ordering::Relaxed),
+            channel.tail.load(Ordering::Relaxed),
+        ) == N
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Sender<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Sender").finish()
+    }
+}
+
+/// The receiving endpoint of a `Channel`
+pub struct Receiver<'a, T, const N: usize> {
+    channel: &'a Channel<T, N>,
+    // `head` is not synchronized
+    _not_sync: PhantomData<*const ()>,
+}
+
+unsafe impl<T, const N: usize> Send for Receiver<'_, T, N> where T: Send {}
+
+impl<T, const N: usize> Receiver<'_, T, N> {
+    /// Receives the oldest box in the queue
+    ///
+    /// Returns `None` when the queue is observed as empty
+    pub fn recv(&mut self) -> Option<Box<T>> {
+        let channel = self.channel;
+        let head = channel.head.load(Ordering::Relaxed);
+        let tail = channel.tail.load(Ordering::Acquire);
+
+        if head == tail {
+            return None;
+        }
+
+        // NOTE(unsafe) the sender doesn't write to this slot until `head` is bumped
+        let node = unsafe { NonNull::new_unchecked(*channel.ring[head % N].node.get()) };
+        channel
+            .head
+            .store(Channel::<T, N>::next(head), Ordering::Release);
+
+        Some(Box {
+            _state: PhantomData,
+            node,
+        })
+    }
+
+    /// Returns a memory block to the channel's pool
+    ///
+    /// See `Pool::free`
+    pub fn free<S>(&self, value: Box<T, S>)
+    where
+        S: 'static,
+    {
+        self.channel.pool.free(value)
+    }
+
+    /// Returns `true` if the queue is observed as empty
+    pub fn is_empty(&self) -> bool {
+        let channel = self.channel;
+
+        channel.head.load(Ordering::Relaxed) == channel.tail.load(Ordering::Relaxed)
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Receiver<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Receiver").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Channel;
+    use crate::tests::memory;
+
+    #[test]
+    fn channel() {
+        let mut channel = Channel::<u8, 2>::new();
+        channel.grow(memory::<u8>(3));
+
+        let (mut sender, mut receiver) = channel.split();
+        assert!(receiver.is_empty());
+
+        for i in 0..2 {
+            sender.send(sender.alloc().unwrap().init(i)).ok().unwrap();
+        }
+        assert!(sender.is_full());
+
+        // the queue is full
+        let x = sender.send(sender.alloc().unwrap().init(2)).err().unwrap();
+
+        let y = receiver.recv().unwrap();
+        assert_eq!(*y, 0);
+        receiver.free(y);
+        sender.send(x).ok().unwrap();
+
+        // wraps around the ring
+        let y = receiver.recv().unwrap();
+        assert_eq!(*y, 1);
+        receiver.free(y);
+        sender.send(sender.alloc().unwrap().init(3)).ok().unwrap();
+
+        for i in 2..4 {
+            let y = receiver.recv().unwrap();
+            assert_eq!(*y, i);
+            receiver.free(y);
+        }
+
+        assert!(receiver.recv().is_none());
+    }
+}
@@ -0,0 +1,185 @@
+//! A primary pool with a fallback
+//!
+//! `CompositePool` combines two pools of the same type: memory blocks are claimed from the
+//! primary pool, e.g. backed by fast tightly coupled memory, and only come from the secondary
+//! pool, e.g. backed by slower SRAM, when the primary one is exhausted. `free` tells the two apart
+//! by address so memory blocks always return to the pool they were claimed from.
+//!
+//! ``` ignore
+//! use lifo::composite::CompositePool;
+//!
+//! static BUFFERS: CompositePool<[u8; 256]> = CompositePool::new();
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     #[link_section = ".dtcm"]
+//!     static mut FAST: [u8; 2048] = [0; 2048];
+//!     static mut SLOW: [u8; 8192] = [0; 8192];
+//!
+//!     BUFFERS.grow_primary(FAST);
+//!     BUFFERS.grow_secondary(SLOW);
+//!
+//!     let buffer = BUFFERS.alloc().unwrap().init([0; 256]);
+//!     // ..
+//!     BUFFERS.free(buffer);
+//! }
+//! ```
+//!
+//! The composite pool keeps track of the span of addresses between the lowest and the highest
+//! byte of the memory given to the primary pool, so memory given to the secondary pool must lie
+//! outside of that span. This is the case when the two pools live in different memories.
+
+use core::{
+    fmt,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{Box, Pool, Uninit};
+
+/// A pool that falls back to a secondary pool when its primary pool is exhausted
+pub struct CompositePool<T> {
+    primary: Pool<T>,
+    secondary: Pool<T>,
+    // the span of the memory given to `primary`; empty while `start >= end`
+    start: AtomicUsize,
+    end: AtomicUsize,
+}
+
+impl<T> CompositePool<T> {
+    /// Creates a new composite pool whose two pools are empty
+    pub const fn new() -> Self {
+        CompositePool {
+            primary: Pool::new(),
+            secondary: Pool::new(),
+            start: AtomicUsize::new(usize::MAX),
+            end: AtomicUsize::new(0),
+        }
+    }
+
+    /// Claims a memory block from the primary pool or, if it's observed as exhausted, from the
+    /// secondary pool
+    ///
+    /// Returns `None` when both pools are observed as exhausted
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains CAS loops
+    pub fn alloc(&self) -> Option<Box<T, Uninit>> {
+        self.primary.alloc().or_else(|| self.secondary.alloc())
+    }
+
+    /// Returns a memory block to the pool it was claimed from
+    ///
+    /// See `Pool::free`
+    pub fn free<S>(&self, value: Box<T, S>)
+    where
+        S: 'static,
+    {
+        if self.is_primary(&value) {
+            self.primary.free(value)
+        } else {
+            self.secondary.free(value)
+        }
+    }
+
+    /// Returns `true` if `value` was claimed from the primary pool
+    pub fn is_primary<S>(&self, value: &Box<T, S>) -> bool {
+        let addr = value.id();
+
+        self.start.load(Ordering::Relaxed) <= addr && addr < self.end.load(Ordering::Relaxed)
+    }
+
+    /// Increases the capacity of the primary pool
+    ///
+    /// See `Pool::grow`
+    pub fn grow_primary(&self, memory: &'static mut [u8]) {
+        // NOTE(unsafe) see `Pool::grow`
+        self.grow_primary_uninit(unsafe { &mut *(memory as *mut [u8] as *mut [MaybeUninit<u8>]) })
+    }
+
+    /// Increases the capacity of the primary pool using memory that has not been initialized
+    ///
+    /// See `Pool::grow_uninit`
+    pub fn grow_primary_uninit(&self, memory: &'static mut [MaybeUninit<u8>]) {
+        // the span must cover the new memory blocks before they can be claimed
+        let start = memory.as_ptr() as usize;
+        self.start.fetch_min(start, Ordering::Relaxed);
+        self.end.fetch_max(start + memory.len(), Ordering::Relaxed);
+
+        self.primary.grow_uninit(memory)
+    }
+
+    /// Increases the capacity of the secondary pool
+    ///
+    /// See `Pool::grow`
+    pub fn grow_secondary(&self, memory: &'static mut [u8]) {
+        self.secondary.grow(memory)
+    }
+
+    /// Increases the capacity of the secondary pool using memory that has not been initialized
+    ///
+    /// See `Pool::grow_uninit`
+    pub fn grow_secondary_uninit(&self, memory: &'static mut [MaybeUninit<u8>]) {
+        self.secondary.grow_uninit(memory)
+    }
+
+    /// Returns a snapshot of the statistics of the primary pool
+    #[cfg(feature = "stats")]
+    pub fn primary_stats(&self) -> crate::Stats {
+        self.primary.stats()
+    }
+
+    /// Returns a snapshot of the statistics of the secondary pool
+    #[cfg(feature = "stats")]
+    pub fn secondary_stats(&self) -> crate::Stats {
+        self.secondary.stats()
+    }
+}
+
+impl<T> Default for CompositePool<T> {
+    fn default() -> Self {
+        CompositePool::new()
+    }
+}
+
+impl<T> fmt::Debug for CompositePool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CompositePool")
+            .field("start", &self.start.load(Ordering::Relaxed))
+            .field("end", &self.end.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompositePool;
+    use crate::tests::memory;
+
+    #[test]
+    fn composite() {
+        static POOL: CompositePool<u32> = CompositePool::new();
+
+        POOL.grow_primary(memory::<u32>(1));
+        POOL.grow_secondary(memory::<u32>(1));
+
+        let a = POOL.alloc().unwrap().init(0);
+        assert!(POOL.is_primary(&a));
+
+        // the primary pool is exhausted
+        let b = POOL.alloc().unwrap().init(1);
+        assert!(!POOL.is_primary(&b));
+        assert!(POOL.alloc().is_none());
+
+        // each memory block returns to its own pool
+        POOL.free(b);
+        let b = POOL.alloc().unwrap();
+        assert!(!POOL.is_primary(&b));
+
+        POOL.free(a);
+        let a = POOL.alloc().unwrap();
+        assert!(POOL.is_primary(&a));
+
+        POOL.free(a);
+        POOL.free(b);
+    }
+}
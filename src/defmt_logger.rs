@@ -0,0 +1,350 @@
+//! A `defmt` global logger that defers output through a pool
+//!
+//! Enabling the `defmt-logger` feature registers this module's logger as the `defmt` global
+//! logger. Each log frame is encoded into a memory block of the logger's pool, in the context that
+//! logged it, and then sent through an `Mpsc` queue; a low-priority task drains the queue and
+//! writes the frames out, e.g. over RTT or a UART. Logging never disables interrupts nor waits on
+//! the I/O: a frame started by an interrupt handler that preempts another frame simply gets its
+//! own memory block. When the pool is exhausted frames are discarded, and counted, instead of
+//! blocking the caller.
+//!
+//! ``` ignore
+//! use lifo::defmt_logger;
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     static mut MEMORY: [u8; 16 * defmt_logger::BLOCK_SIZE] = [0; 16 * defmt_logger::BLOCK_SIZE];
+//!
+//!     defmt_logger::grow(MEMORY);
+//!     let mut drain = defmt_logger::drain().unwrap();
+//!
+//!     loop {
+//!         if let Some(frame) = drain.recv() {
+//!             uart.bwrite_all(frame.as_bytes()).ok();
+//!             // the memory block returns to the pool when `frame` is dropped
+//!         }
+//!     }
+//! }
+//!
+//! #[interrupt]
+//! fn EXTI0() {
+//!     defmt::info!("button pressed");
+//! }
+//! ```
+//!
+//! Frames longer than `FRAME_SIZE` bytes, once encoded, are discarded as well. The logger relies on
+//! interrupt handlers running to completion so it can't be shared by several cores.
+
+use core::{
+    fmt,
+    marker::PhantomData,
+    mem,
+    ops::Deref,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering},
+};
+
+use defmt::Encoder;
+
+use crate::{
+    mpsc::{Consumer, Mpsc},
+    Box, Node, Pool,
+};
+
+/// The maximum size, in bytes, of an encoded log frame
+pub const FRAME_SIZE: usize = 128;
+
+/// The size of the memory blocks of the logger's pool
+///
+/// `grow` needs this many bytes, plus alignment padding, for each frame that can be in flight
+pub const BLOCK_SIZE: usize = mem::size_of::<Node<Frame>>();
+
+/// An encoded log frame; the type of the memory blocks of the logger's pool
+pub struct Frame {
+    encoder: Encoder,
+    // the frame, and the number of discarded frames, that were being encoded in the current
+    // context when this frame was started
+    prev: *mut Node<Frame>,
+    skipped: usize,
+    output: Output,
+}
+
+// NOTE(unsafe) `prev` is only used while the frame is being encoded, in the context that started it
+unsafe impl Send for Frame {}
+
+struct Output {
+    // the encoded frame didn't fit in `buf`
+    overflow: bool,
+    len: usize,
+    buf: [u8; FRAME_SIZE],
+}
+
+impl Output {
+    fn push(&mut self, bytes: &[u8]) {
+        if self.overflow || bytes.len() > FRAME_SIZE - self.len {
+            self.overflow = true;
+            return;
+        }
+
+        self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+}
+
+impl Frame {
+    /// Returns the encoded frame, ready to be sent to the host
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.output.buf[..self.output.len]
+    }
+}
+
+impl fmt::Debug for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Frame")
+            .field("bytes", &self.as_bytes())
+            .finish()
+    }
+}
+
+static POOL: Pool<Frame> = Pool::new();
+static QUEUE: Mpsc<Frame> = Mpsc::new();
+// the frame being encoded in the current context
+static CURRENT: AtomicPtr<Node<Frame>> = AtomicPtr::new(ptr::null_mut());
+// the number of frames being discarded in the current context, which started after `CURRENT`
+static SKIPPED: AtomicUsize = AtomicUsize::new(0);
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+static DRAIN_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Increases the capacity of the logger's pool
+///
+/// See `Pool::grow`
+pub fn grow(memory: &'static mut [u8]) {
+    POOL.grow(memory)
+}
+
+/// Returns the number of frames that were discarded because the pool was exhausted or because
+/// they were too long
+pub fn dropped() -> usize {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+/// Returns the endpoint that drains the logged frames
+///
+/// Returns `None` if it has already been taken
+pub fn drain() -> Option<Drain> {
+    if DRAIN_TAKEN.swap(true, Ordering::Relaxed) {
+        None
+    } else {
+        Some(Drain {
+            // NOTE(unsafe) `DRAIN_TAKEN` makes this the only consumer
+            consumer: unsafe { QUEUE.consumer() },
+        })
+    }
+}
+
+/// The endpoint that drains the frames logged through the global logger
+pub struct Drain {
+    consumer: Consumer<'static, Frame>,
+}
+
+impl Drain {
+    /// Receives the oldest logged frame
+    ///
+    /// Returns `None` if the queue is observed as empty
+    pub fn recv(&mut self) -> Option<Entry> {
+        Some(Entry {
+            frame: Some(self.consumer.recv()?),
+        })
+    }
+
+    /// Returns `true` if there are no logged frames to receive
+    pub fn is_empty(&self) -> bool {
+        self.consumer.is_empty()
+    }
+}
+
+impl fmt::Debug for Drain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Drain").finish()
+    }
+}
+
+/// A logged frame
+///
+/// Dropping the entry returns its memory block to the logger's pool
+pub struct Entry {
+    // always `Some` until dropped
+    frame: Option<Box<Frame>>,
+}
+
+impl Deref for Entry {
+    type Target = Frame;
+
+    fn deref(&self) -> &Frame {
+        // NOTE(unwrap) only `None` in the destructor
+        self.frame.as_ref().unwrap()
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        if let Some(frame) = self.frame.take() {
+            POOL.free(frame)
+        }
+    }
+}
+
+impl fmt::Debug for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <Frame as fmt::Debug>::fmt(self, f)
+    }
+}
+
+// Interrupt handlers run to completion so any frame that preempts the current one is released,
+// and `CURRENT` and `SKIPPED` restored, before the preempted context resumes
+#[defmt::global_logger]
+struct GlobalLogger;
+
+unsafe impl defmt::Logger for GlobalLogger {
+    fn acquire() {
+        let frame = match POOL.alloc() {
+            Some(block) => block.init(Frame {
+                encoder: Encoder::new(),
+                prev: CURRENT.load(Ordering::Relaxed),
+                skipped: SKIPPED.load(Ordering::Relaxed),
+                output: Output {
+                    overflow: false,
+                    len: 0,
+                    buf: [0; FRAME_SIZE],
+                },
+            }),
+            None => {
+                SKIPPED.store(SKIPPED.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+                return;
+            }
+        };
+
+        let node = frame.into_node();
+
+        SKIPPED.store(0, Ordering::Relaxed);
+        CURRENT.store(node.as_ptr(), Ordering::Relaxed);
+
+        // NOTE(unsafe) only this context accesses the frame until it's released
+        let Frame {
+            encoder, output, ..
+        } = unsafe { &mut *node.as_ref().data.get() };
+        encoder.start_frame(|bytes| output.push(bytes));
+    }
+
+    unsafe fn flush() {
+        // the frames are written out by the `Drain`, possibly from a lower priority context
+    }
+
+    unsafe fn release() {
+        let skipped = SKIPPED.load(Ordering::Relaxed);
+        if skipped != 0 {
+            SKIPPED.store(skipped - 1, Ordering::Relaxed);
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        let node = NonNull::new_unchecked(CURRENT.load(Ordering::Relaxed));
+        let frame = &mut *node.as_ref().data.get();
+
+        let Frame {
+            encoder, output, ..
+        } = frame;
+        encoder.end_frame(|bytes| output.push(bytes));
+
+        CURRENT.store(frame.prev, Ordering::Relaxed);
+        SKIPPED.store(frame.skipped, Ordering::Relaxed);
+
+        let overflow = frame.output.overflow;
+        let frame = Box {
+            _state: PhantomData,
+            node,
+        };
+
+        if overflow {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+            POOL.free(frame);
+        } else {
+            QUEUE.producer().send(frame);
+        }
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        if SKIPPED.load(Ordering::Relaxed) != 0 {
+            return;
+        }
+
+        let node = CURRENT.load(Ordering::Relaxed);
+        let Frame {
+            encoder, output, ..
+        } = &mut *(*node).data.get();
+        encoder.write(bytes, |bytes| output.push(bytes));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use defmt::{Encoder, Logger};
+
+    use super::{Frame, GlobalLogger, FRAME_SIZE};
+    use crate::tests::memory;
+
+    fn encode(data: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![];
+        let mut encoder = Encoder::new();
+        encoder.start_frame(|bytes| encoded.extend_from_slice(bytes));
+        encoder.write(data, |bytes| encoded.extend_from_slice(bytes));
+        encoder.end_frame(|bytes| encoded.extend_from_slice(bytes));
+        encoded
+    }
+
+    #[test]
+    fn defmt_logger() {
+        super::grow(memory::<Frame>(2));
+
+        let mut drain = super::drain().unwrap();
+        assert!(super::drain().is_none());
+        assert!(drain.is_empty());
+
+        unsafe {
+            GlobalLogger::acquire();
+            GlobalLogger::write(&[1, 2]);
+
+            // an interrupt handler preempts the frame
+            GlobalLogger::acquire();
+            GlobalLogger::write(&[3]);
+
+            // and is preempted in turn while the pool is exhausted
+            GlobalLogger::acquire();
+            GlobalLogger::write(&[4]);
+            GlobalLogger::release();
+
+            GlobalLogger::write(&[5]);
+            GlobalLogger::release();
+
+            GlobalLogger::write(&[6]);
+            GlobalLogger::release();
+        }
+
+        assert_eq!(super::dropped(), 1);
+
+        // frames are received in the order they were completed
+        assert_eq!(drain.recv().unwrap().as_bytes(), &encode(&[3, 5])[..]);
+        assert_eq!(drain.recv().unwrap().as_bytes(), &encode(&[1, 2, 6])[..]);
+        assert!(drain.recv().is_none());
+
+        // frames that don't fit in a memory block are discarded
+        unsafe {
+            GlobalLogger::acquire();
+            GlobalLogger::write(&[0xff; FRAME_SIZE]);
+            GlobalLogger::release();
+        }
+
+        assert_eq!(super::dropped(), 2);
+        assert!(drain.recv().is_none());
+    }
+}
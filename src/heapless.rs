@@ -0,0 +1,200 @@
+//! `heapless::pool` compatibility layer
+//!
+//! This module mirrors the API of the `pool` module of `heapless` v0.7 on top of this crate's
+//! pools, so code written against `heapless` can switch to this crate by replacing its
+//! `heapless::pool` paths with `lifo::heapless::pool`, and its `heapless::pool!` invocations with
+//! `lifo::pool!`.
+//!
+//! ``` ignore
+//! // was: use heapless::{pool, pool::singleton::{Box, Pool}};
+//! use lifo::{heapless::pool::singleton::{Box, Pool}, pool};
+//!
+//! pool!(A: [u8; 128]);
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     static mut MEMORY: [u8; 1024] = [0; 1024];
+//!
+//!     let n = A::grow(MEMORY);
+//!     let x: Box<A> = A::alloc().unwrap().init([0; 128]);
+//!     // ..
+//! }
+//! ```
+//!
+//! The `Box` types are this crate's own so values can be moved between code that uses this module
+//! and code that uses the rest of the crate. `singleton::Box::freeze` and `singleton::Box::forget`,
+//! as well as the `arc` module, have no counterpart here.
+
+pub mod pool {
+    //! Mirror of `heapless::pool`
+
+    #[cfg(feature = "maybe-uninit")]
+    use core::mem;
+    use core::mem::MaybeUninit;
+
+    #[cfg(feature = "maybe-uninit")]
+    use as_slice::AsMutSlice;
+
+    #[cfg(feature = "maybe-uninit")]
+    pub use crate::Node;
+    pub use crate::{Box, Init, Uninit};
+
+    /// A lock-free memory pool; a thin wrapper around `lifo::Pool`
+    pub struct Pool<T> {
+        inner: crate::Pool<T>,
+    }
+
+    impl<T> Pool<T> {
+        /// Creates a new empty pool
+        pub const fn new() -> Self {
+            Pool {
+                inner: crate::Pool::new(),
+            }
+        }
+
+        /// Claims a memory block from the pool
+        ///
+        /// See `lifo::Pool::alloc`
+        pub fn alloc(&self) -> Option<Box<T, Uninit>> {
+            self.inner.alloc()
+        }
+
+        /// Returns a memory block to the pool
+        ///
+        /// See `lifo::Pool::free`
+        pub fn free<S>(&self, value: Box<T, S>)
+        where
+            S: 'static,
+        {
+            self.inner.free(value)
+        }
+
+        /// Increases the capacity of the pool
+        ///
+        /// This method might *not* fully utilize the given memory block due to alignment
+        /// requirements.
+        ///
+        /// This method returns the number of *new* blocks that can be allocated.
+        pub fn grow(&self, memory: &'static mut [u8]) -> usize {
+            // NOTE(unsafe) see `lifo::Pool::grow`
+            self.inner
+                .extend(unsafe { &mut *(memory as *mut [u8] as *mut [MaybeUninit<u8>]) })
+        }
+
+        /// Increases the capacity of the pool
+        ///
+        /// This method returns the number of *new* blocks that can be allocated.
+        #[cfg(feature = "maybe-uninit")]
+        pub fn grow_exact<A>(&self, memory: &'static mut MaybeUninit<A>) -> usize
+        where
+            A: AsMutSlice<Element = Node<T>>,
+        {
+            self.inner.grow_exact(memory);
+
+            mem::size_of::<A>() / mem::size_of::<Node<T>>()
+        }
+
+        /// Returns the wrapped pool
+        pub fn inner(&self) -> &crate::Pool<T> {
+            &self.inner
+        }
+    }
+
+    impl<T> Default for Pool<T> {
+        fn default() -> Self {
+            Pool::new()
+        }
+    }
+
+    pub mod singleton {
+        //! Mirror of `heapless::pool::singleton`
+
+        #[cfg(feature = "maybe-uninit")]
+        use core::mem;
+        use core::mem::MaybeUninit;
+
+        #[cfg(feature = "maybe-uninit")]
+        use as_slice::AsMutSlice;
+
+        #[cfg(feature = "maybe-uninit")]
+        use super::Node;
+        use super::Uninit;
+        pub use crate::singleton::Box;
+
+        /// A global singleton memory pool
+        ///
+        /// Implemented for all the pools declared with `lifo::pool!`. Import this trait instead of
+        /// `lifo::singleton::Pool` to get the `heapless` signatures.
+        pub trait Pool: crate::singleton::Pool {
+            /// Claims a memory block from the pool
+            ///
+            /// Returns `None` when the pool is observed as exhausted
+            ///
+            /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS
+            /// loop
+            fn alloc() -> Option<Box<Self, Uninit>>
+            where
+                Self: Sized,
+            {
+                <Self as crate::singleton::Pool>::alloc()
+            }
+
+            /// Increases the capacity of the pool
+            ///
+            /// This method might *not* fully utilize the given memory block due to alignment
+            /// requirements
+            ///
+            /// This method returns the number of *new* blocks that can be allocated.
+            fn grow(memory: &'static mut [u8]) -> usize {
+                // NOTE(unsafe) see `lifo::Pool::grow`
+                Self::ptr().extend(unsafe { &mut *(memory as *mut [u8] as *mut [MaybeUninit<u8>]) })
+            }
+
+            /// Increases the capacity of the pool
+            ///
+            /// This method returns the number of *new* blocks that can be allocated.
+            #[cfg(feature = "maybe-uninit")]
+            fn grow_exact<A>(memory: &'static mut MaybeUninit<A>) -> usize
+            where
+                A: AsMutSlice<Element = Node<Self::Data>>,
+            {
+                Self::ptr().grow_exact(memory);
+
+                mem::size_of::<A>() / mem::size_of::<Node<Self::Data>>()
+            }
+        }
+
+        impl<P> Pool for P where P: crate::singleton::Pool {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pool::{
+        singleton::{Box, Pool as _},
+        Pool,
+    };
+    use crate::tests::{memory, RESERVED};
+
+    #[test]
+    fn pool() {
+        static P: Pool<u32> = Pool::new();
+
+        let n = P.grow(memory::<u32>(2));
+        assert_eq!(n, 2 + RESERVED);
+
+        let x = P.alloc().unwrap().init(1);
+        assert_eq!(*x, 1);
+        P.free(x);
+    }
+
+    #[test]
+    fn singleton() {
+        crate::pool!(A: u32);
+
+        assert_eq!(A::grow(memory::<u32>(2)), 2 + RESERVED);
+
+        let x: Box<A> = A::alloc().unwrap().init(1);
+        assert_eq!(*x, 1);
+    }
+}
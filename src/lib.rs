@@ -33,6 +33,9 @@
 //!     // increase the capacity of the pool by ~8 blocks
 //!     A::grow(MEMORY);
 //!
+//!     // alternatively, `pool!(A: [u8; 128]; capacity = 8)` declares a static buffer for exactly 8
+//!     // blocks, which is handed to the pool by calling `A::init()`
+//!
 //!     // claim a block of memory
 //!     // note that the type is `Box<A>`, and not `Box<[u8; 128]>`
 //!     // `A` is the "name" of the pool
@@ -55,7 +58,7 @@
 //! ```
 //!
 //! - `std` -- note that, when using `std`, `Pool` does *not* implement `Sync` and `pool!` is not
-//! available
+//!   available (unless the `x86-sync-hazard` feature is enabled)
 //!
 //! ```
 //! use lifo::Pool;
@@ -120,17 +123,27 @@
 //! The only counter measure against the ABA problem that this library currently takes is relying on
 //! LL/SC (Link-local / Store-conditional) instructions being used to implement CAS loops on the
 //! target architecture (see section on ['Soundness'](#soundness) for more information). For this
-//! reason, `Pool` only implements `Sync` when compiling for ARM Cortex-M.
+//! reason, `Pool` only implements `Sync` when compiling for ARM Cortex-M (unless the
+//! `x86-sync-hazard` feature is enabled).
 //!
 //! Also note that ARMv6-M lacks the primitives for CAS loops so this library will *not* compile for
 //! `thumbv6m-none-eabi`.
 //!
 //! # MSRV
 //!
-//! This crate compiles on stable Rust 1.31.0 or newer.
+//! This crate compiles on stable Rust 1.60.0 or newer. So do its Cargo features, except the ones
+//! whose section below states that they require a newer compiler.
 //!
 //! # Cargo features
 //!
+//! ## `allocator-api`
+//!
+//! Enabling this feature implements the unstable `Allocator` trait for `Pool` so collections that
+//! are generic over their allocator, like `alloc::vec::Vec`, can store their contents in a memory
+//! block of a pool, e.g. `Vec::new_in(&POOL)`. Each container uses a single memory block, so an
+//! allocation fails if it's larger, or requires a larger alignment, than `T`. This feature
+//! requires a nightly compiler.
+//!
 //! ## `arch`
 //!
 //! Replaces the internal implementation, which uses `AtomicPtr`, with an ARM architecture specific
@@ -139,14 +152,367 @@
 //! from 3 to 2 instructions. For reference, the critical section of `alloc` (`pop`) is 3
 //! instructions for both implementations.
 //!
+//! ## `arena`
+//!
+//! Enabling this feature adds the `arena` module and its `Arena`, a bump allocator over static
+//! memory regions that frees all its values at once with `reset`. It's meant for scratch
+//! allocations that only live for one iteration of a loop and it uses the same `grow` API as
+//! `Pool`.
+//!
+//! ## `binary-heap`
+//!
+//! Enabling this feature adds the `binary_heap` module and its `BinaryHeap`, a fixed-capacity
+//! priority queue of `Box`es ordered by their values, e.g. for schedulers and deadline-based
+//! dispatchers. The heap only stores pointers so values are never moved while it's reordered. This
+//! feature requires Rust 1.61.0 or newer.
+//!
+//! ## `bitmap`
+//!
+//! Enabling this feature adds the `bitmap` module and its `BitmapPool`, an alternative to `Pool`
+//! that stores its memory blocks inline and tracks the free ones with an atomic flag per memory
+//! block instead of a free list. Claiming a memory block takes bounded time and doesn't rely on
+//! LL/SC semantics to avoid the ABA problem.
+//!
+//! ## `broadcast`
+//!
+//! Enabling this feature adds the `singleton::broadcast` module and its `Broadcast`, which sends
+//! each message published by a single publisher to several subscribers as clones of a
+//! `singleton::arc::Arc`. Each subscriber has its own fixed-capacity queue. This feature requires
+//! Rust 1.61.0 or newer.
+//!
+//! ## `buddy`
+//!
+//! Enabling this feature adds the `buddy` module and its `Buddy` allocator, which hands out memory
+//! blocks of variable size (powers of two) from a single static memory region. It's meant for
+//! workloads where the size of the allocations varies too much for fixed-size memory blocks. Like
+//! `Pool`, the allocator can be used from interrupt handlers and never blocks.
+//!
+//! ## `bytemuck`
+//!
+//! Enabling this feature adds checked casts between `singleton::Box`es of `Pod` data, like
+//! `[u8; N]`, and `singleton::DynBox`es of other `Pod` types of the same size, so byte buffers
+//! can be reinterpreted as fixed-layout structures without copies.
+//!
+//! ## `bytes`
+//!
+//! Enabling this feature implements the `bytes::Buf` and `bytes::BufMut` traits for
+//! `singleton::Payload`.
+//!
+//! ## `channel`
+//!
+//! Enabling this feature adds the `channel` module and its `Channel`, a pool bundled with a
+//! wait-free single-producer single-consumer queue of the `Box`es claimed from it, for handing
+//! buffers from an interrupt handler over to a task.
+//!
+//! ## `collections`
+//!
+//! Enabling this feature adds collections whose storage is a chain of memory blocks of a global
+//! singleton pool: `singleton::vec::PoolVec`, a growable vector for workloads where the number of
+//! elements varies too much for a fixed-capacity vector, and `singleton::string::PoolString`, a
+//! UTF-8 string that implements `fmt::Write`, e.g. to format log messages or JSON documents
+//! without a maximum length, and `singleton::chain::Chain`, a packet made of several byte buffers
+//! with room to prepend headers, like the `mbuf`s of network stacks. The memory blocks are linked
+//! through their otherwise unused `next` pointer so this feature can't be used together with the
+//! `union` feature.
+//!
+//! ## `composite`
+//!
+//! Enabling this feature adds the `composite` module and its `CompositePool`, which claims memory
+//! blocks from a primary pool, e.g. in fast tightly coupled memory, and falls back to a secondary
+//! pool, e.g. in slower SRAM, when the primary one is exhausted. Freed memory blocks are routed
+//! back to the pool they came from by address.
+//!
+//! ## `cortex-m`
+//!
+//! Enabling this feature adds data cache maintenance methods to `Box` and `singleton::Box`, for
+//! Cortex-M7 devices with the D-cache enabled: `clean_dcache` before a DMA transfer reads from a
+//! memory block, `clean_invalidate_dcache` before one writes to it and `invalidate_dcache` after it
+//! has. Each method operates on the address range of the value stored in the memory block, which
+//! leaves out the bookkeeping data the pool keeps next to it.
+//!
+//! ## `cortex-m-rt`
+//!
+//! Enabling this feature adds the `init_pools!` macro, meant to be called at the start of the
+//! `#[entry]` function of `cortex-m-rt` applications, which grows a set of singleton pools in a
+//! single critical section. Calling it before enabling any interrupt guarantees that interrupt
+//! handlers never observe a pool that has not been initialized yet.
+//!
+//! ## `defmt`
+//!
+//! Enabling this feature implements `defmt::Format` for `Box`, `singleton::Box` and
+//! `singleton::DynBox` (forwarding to the contained value) and for `Stats` and `Snapshot`.
+//!
+//! ## `defmt-logger`
+//!
+//! Enabling this feature adds the `defmt_logger` module and registers its logger as the `defmt`
+//! global logger. Log frames are encoded into memory blocks of the logger's pool, without
+//! disabling interrupts, and queued; a low-priority task drains the queue and writes the frames out
+//! over RTT, a UART, etc. This feature implies the `defmt` and `mpsc` features. Like `pool!`, the
+//! module is only available when `Pool` implements `Sync`.
+//!
+//! ## `dma`
+//!
+//! Enabling this feature implements the `embedded-dma` buffer traits so pooled buffers can be
+//! handed to DMA transfer APIs. Initialized boxes implement `ReadBuffer` and `WriteBuffer`;
+//! uninitialized boxes only implement `WriteBuffer`, as the DMA must fill them in before they can
+//! be read.
+//!
+//! ## `dma-ring`
+//!
+//! Enabling this feature adds the `singleton::ring` module and its `DescriptorRing`, which keeps a
+//! ring of hardware DMA descriptors stocked with memory blocks of a pool and hands the buffers that
+//! the DMA has filled in to the application as `Box`es. This feature implies the `dma` feature.
+//!
+//! ## `fifo`
+//!
+//! Enabling this feature turns the free list into a queue so memory blocks are recycled in FIFO
+//! order, i.e. the memory block that was freed most recently is the last one to be claimed again.
+//! This maximizes the time a freed memory block stays unused, which makes use-after-free bugs
+//! easier to catch, at the cost of slower `alloc` and `free` operations. The queue always keeps
+//! one memory block as a sentinel so one of the memory blocks handed to the pool can't be claimed.
+//! This feature is meant for debugging and can't be used together with the `arch` feature.
+//!
+//! ## `global-alloc`
+//!
+//! Enabling this feature adds `singleton::PoolAlloc`, an implementation of `GlobalAlloc` on top of
+//! the size classes of a `singleton::ClassPool`, so crates that need the `alloc` crate can run on
+//! fixed-size memory blocks instead of a general purpose heap.
+//!
+//! ## `handles`
+//!
+//! Enabling this feature adds a generation counter to every memory block and the
+//! `singleton::Handle` type: a weak reference to a pooled value that can detect when the value has
+//! been freed, even if its memory block has since been reused. This feature can't be used
+//! together with the `union` feature.
+//!
+//! ## `heapless`
+//!
+//! Enabling this feature adds `Pool::alloc_many_into` and `singleton::Pool::alloc_many_into`, which
+//! fill in the spare capacity of a `heapless::Vec` with memory blocks in a single call, e.g. to
+//! stock a ring of RX descriptors at boot.
+//!
+//! ## `heapless-compat`
+//!
+//! Enabling this feature adds the `heapless` module, which mirrors the API of `heapless::pool`
+//! (v0.7) on top of this crate's pools so code written against `heapless` can switch to this
+//! crate, and back, by only changing its import paths.
+//!
+//! ## `leak-guard`
+//!
+//! Dropping a `Box` leaks its memory block, and skips the destructor of its value, because the
+//! box doesn't know which pool it belongs to. Enabling this feature turns that silent leak into a
+//! panic so leaks can be caught in tests. This feature doesn't affect `singleton::Box`, which
+//! returns its memory block to the pool when dropped. On targets with an operating system, where
+//! this feature links to `std`, boxes dropped while the thread is already panicking are not
+//! reported.
+//!
+//! Note that `mem::forget` still leaks the memory block without triggering the guard.
+//!
+//! ## `logger`
+//!
+//! Enabling this feature adds the `logger` module and its `Logger`, a `log::Log` implementation
+//! that formats records into memory blocks of a pool in the caller's context and queues them, with
+//! an `Mpsc` queue, for a low-priority task to drain. Logging from an interrupt handler then costs
+//! the formatting but never waits on the I/O. This feature enables the `mpsc` feature.
+//!
+//! ## `mailbox`
+//!
+//! Enabling this feature adds the `singleton::mailbox` module and its `Mailbox`, which moves
+//! `Box`es of a pool shared by two cores from one core to the other through a FIFO of words, like
+//! the inter-processor FIFOs of the RP2040, or through a pair of slots in shared memory.
+//!
 //! ## `maybe-uninit`
 //!
 //! Enabling this features adds a `grow_exact` method to `Pool` and `singleton::Pool`. Like `grow`,
 //! this method can be used increase the capacity of a pool; however, the given buffer will be fully
 //! utilized.
 //!
-//! The argument of `grow_exact` is a static reference to `MaybeUninit`. As the `MaybeUninit` is
-//! still unstable this feature requires using a nightly compiler.
+//! The argument of `grow_exact` is a static reference to `MaybeUninit`. This feature exposes the
+//! `Node` implementation detail as part of the public API.
+//!
+//! ## `mpsc`
+//!
+//! Enabling this feature adds the `mpsc` module and its `Mpsc` queue, which passes `Box`es from
+//! several producers, e.g. interrupt handlers, to a single consumer by linking them through their
+//! otherwise unused `next` pointer, so no extra storage is needed per message. This feature can't
+//! be used together with the `union` feature.
+//!
+//! ## `mpu`
+//!
+//! Enabling this feature, which implies the `quarantine` feature, adds the `mpu` module. Once
+//! `mpu::enable` has been called, memory blocks in quarantine are covered by MPU regions with no
+//! access permissions so dereferencing a stale pointer to one of them faults immediately. This
+//! feature is meant for debug builds and only supports the ARMv7-M MPU.
+//!
+//! ## `panic-report`
+//!
+//! Enabling this feature, which implies the `registry` feature, adds the `report_panics!` macro.
+//! It installs a panic handler that writes the panic message, followed by a `panic_report` of the
+//! registered pools, to a `core::fmt::Write` sink such as a UART so that post-mortem logs show
+//! whether some pool ran out of memory blocks. This feature requires Rust 1.61.0 or newer.
+//!
+//! ## `partition`
+//!
+//! Enabling this feature adds the `partition!` macro, which splits one statically allocated
+//! buffer, e.g. placed in a `.uninit` linker section, among several singleton pools at boot and
+//! checks at compile time that the requested capacities fit in it.
+//!
+//! ## `per-core`
+//!
+//! Enabling this feature adds the `per_core` module and its `PerCorePool`, which gives each core
+//! its own local pool and only touches a reserve shared by all the cores to move batches of memory
+//! blocks in or out of a local pool.
+//!
+//! ## `persist`
+//!
+//! Enabling this feature adds the `persist` module and its `PersistentPool`, whose memory blocks
+//! survive a warm reboot: the pool stores a header and the state of each memory block in its
+//! memory region so that, after a reset, `recover` can validate the region and rebuild the free
+//! list, handing back the memory blocks that were in use.
+//!
+//! ## `poison`
+//!
+//! Enabling this feature makes the pool fill free memory blocks with a known byte pattern. When a
+//! memory block is claimed again the pattern is checked and `alloc` panics if it was modified,
+//! which indicates that some stale pointer was used to write to the memory block after it was
+//! freed. This feature is meant for debugging as it makes the cost of `alloc` and `free`
+//! proportional to `mem::size_of::<T>()`.
+//!
+//! ## `pooled`
+//!
+//! Enabling this feature adds the `#[pooled]` attribute which, applied to a type, declares a
+//! global singleton pool dedicated to that type and adds an `alloc` constructor and sizing
+//! constants to the type, e.g. `#[pooled(capacity = 16)] struct Msg { .. }` declares the pool
+//! `MsgPool`, `Msg::init()` and `Msg::alloc()`. Like `pool!`, the attribute is only available when
+//! `Pool` implements `Sync`.
+//!
+//! ## `postcard`
+//!
+//! Enabling this feature adds `Pool::decode_postcard` and `Pool::encode_postcard`, and their
+//! `singleton::Pool` counterparts, which decode a `postcard` message into a memory block claimed
+//! from the pool, or encode a value into a pooled byte buffer, and return the `Box` along with the
+//! length of the message, so the codec path doesn't need any other buffer. This feature implies the
+//! `serde` feature.
+//!
+//! ## `quarantine`
+//!
+//! Enabling this feature, which implies the `poison` feature, makes freed memory blocks sit in a
+//! quarantine before they are returned to the free list. Combined with poisoning, this makes it
+//! much more likely that writes through stale pointers are caught. The depth of the quarantine
+//! can be changed at runtime using `Pool::set_quarantine_depth`. Memory blocks in quarantine are
+//! still claimed when the free list runs out of memory blocks.
+//!
+//! ## `recycle`
+//!
+//! Enabling this feature adds the `recycle` module and its `RecyclePool`: a pool that keeps an
+//! object constructed in each of its memory blocks. Freeing an object resets it, using the `Reset`
+//! trait, instead of dropping it so the cost of constructing it is only paid once. This feature
+//! can't be used together with the `union` feature.
+//!
+//! ## `registry`
+//!
+//! Enabling this feature, which implies the `stats` feature, adds a registry of global singleton
+//! pools. Pools declared with `pool!` can be added to it using `singleton::Pool::register` and
+//! `singleton::registry::pools` lists the name, block size and statistics of every registered
+//! pool, e.g. to print a memory usage table from a debug shell, and `panic_report` writes the
+//! state of every registered pool to a `core::fmt::Write` sink. This feature requires Rust 1.61.0
+//! or newer.
+//!
+//! ## `rtic`
+//!
+//! Enabling this feature adds the `rtic` module which helps using singleton pools in RTIC 1.x and
+//! 2.x applications. Its `Initialized` token can only be created from the critical section token
+//! that RTIC passes to `init` so tasks can't claim memory blocks from a pool that has not been
+//! grown yet. See the module documentation for examples.
+//!
+//! ## `scoped`
+//!
+//! Enabling this feature adds the `scoped` module, whose `scope` function creates a pool backed
+//! by memory that is not `'static`, e.g. a stack buffer, for the duration of a closure. The boxes
+//! claimed from the pool can't escape the closure. This is mainly meant for unit tests and for
+//! subsystems that only run for a bounded amount of time.
+//!
+//! ## `serde`
+//!
+//! Enabling this feature implements `Serialize` for `Box`, `singleton::Box` and
+//! `singleton::DynBox` (forwarding to the contained value), `DeserializeSeed` for `&Pool<T>`,
+//! which deserializes a value directly into a memory block claimed from the pool, and
+//! `Deserialize` for `singleton::Box`, which claims the memory block from the singleton pool.
+//! Together with the `stats` feature, it also implements `Serialize` for `Snapshot`.
+//!
+//! ## `slab`
+//!
+//! Enabling this feature adds the `slab` module and its `Slab`: a fixed-capacity store of values
+//! that are identified by 16-bit keys, with generation bits that catch stale keys, instead of by
+//! pointers.
+//!
+//! ## `smoltcp`
+//!
+//! Enabling this feature adds the `singleton::phy` module and its `PoolDevice`, an implementation
+//! of smoltcp's `phy::Device` whose received and transmitted frames are stored in memory blocks of
+//! a global singleton pool, and freed as soon as they have been processed, instead of in static
+//! slices. smoltcp needs at least one medium, protocol and socket type to build so this feature
+//! enables its `medium-ethernet`, `proto-ipv4` and `socket-raw` features. This feature requires
+//! Rust 1.80.0 or newer.
+//!
+//! ## `stable_deref_trait`
+//!
+//! Enabling this feature implements the `StableDeref` trait for `Box`, `singleton::Box` and
+//! `singleton::DynBox` so they can be used with crates that require that guarantee, like
+//! `owning_ref`.
+//!
+//! ## `static-pool`
+//!
+//! Enabling this feature adds the `static_pool!` macro which, like `pool!`, declares a global
+//! singleton pool but builds its free list at compile time so the pool needs no runtime
+//! initialization. This feature requires Rust 1.83.0 or newer.
+//!
+//! ## `stats`
+//!
+//! Enabling this feature makes `Pool` keep track of its capacity, of the number of free memory
+//! blocks and of the lowest number of free memory blocks observed (low watermark). These can be
+//! read using `Pool::stats` and the low watermark can be cleared using `Pool::reset_watermarks`.
+//! Keeping these statistics adds a few atomic operations to `alloc` and `free`.
+//!
+//! `Pool::snapshot` returns a `Snapshot`: a plain-old-data struct that adds allocation counters
+//! and the list of memory regions managed by the pool to the statistics. It implements
+//! `defmt::Format` and `serde::Serialize`, when the respective features are enabled, so a host-side
+//! tool can chart the health of the pool live, e.g. over RTT.
+//!
+//! ## `stream`
+//!
+//! Enabling this feature adds the `stream` module and its `Stream`, a single-producer
+//! single-consumer byte stream whose bytes are stored in chunks claimed from a pool. The producer
+//! fills a granted chunk and commits it, and the consumer reads the committed bytes as one
+//! contiguous slice, which suits DMA transfers. This feature implies the `channel` feature.
+//!
+//! ## `sub-pool`
+//!
+//! Enabling this feature adds the `sub_pool` module and its `SubPool`, a pool of small memory
+//! blocks carved out of the memory blocks of a parent `Pool`. Parent memory blocks are claimed on
+//! demand and returned to the parent pool once all the small memory blocks carved out of them are
+//! free, so two block sizes can share the same memory.
+//!
+//! ## `task-pool`
+//!
+//! Enabling this feature adds the `singleton::task` module. Its `Task` stores a type-erased, pinned
+//! future in a memory block of a pool, and its `TaskPool` keeps a table of spawned tasks along with
+//! wakers that mark them as ready to be polled, so a small executor can spawn tasks at runtime
+//! without a heap. This feature requires Rust 1.61.0 or newer.
+//!
+//! ## `timer`
+//!
+//! Enabling this feature adds the `timer` module and its `TimerQueue`, which keeps `Box`es sorted
+//! by the deadline stored in them so software timers can live entirely in pool memory. Timers can
+//! be scheduled from interrupt handlers. Like `mpsc`, this feature can't be used together with the
+//! `union` feature.
+//!
+//! ## `track-callers`
+//!
+//! Enabling this feature makes the pool record the location of the code that claimed each memory
+//! block that's currently allocated. `Pool::dump_live` reports these locations, which helps
+//! tracking down memory leaks. This feature increases the footprint of `Node` by two words and
+//! can't be used together with the `union` feature.
 //!
 //! ## `union`
 //!
@@ -154,6 +520,37 @@
 //! (effectively zero cost). This feature depends on the unstable `untagged_unions` feature and thus
 //! requires a nightly compiler.
 //!
+//! ## `untyped`
+//!
+//! Enabling this feature adds the `untyped` module and its `UntypedPool`, a pool of raw memory
+//! blocks from which values of different types can be allocated, so several small message types
+//! can share one memory region. Whether a type fits in a memory block is checked at compile time.
+//!
+//! ## `usb-device`
+//!
+//! Enabling this feature adds the `singleton::usb` module: `Packet`, a word-aligned buffer for one
+//! full-speed or high-speed USB packet, and `PacketQueue`, a queue of packets stored in memory
+//! blocks of a global singleton pool that reads from and writes to `usb-device` endpoints, so
+//! bulk endpoints can exchange pooled buffers instead of copying into arrays inside the class.
+//! This feature requires Rust 1.61.0 or newer.
+//!
+//! ## `work-queue`
+//!
+//! Enabling this feature adds the `singleton::work` module and its `WorkQueue`, which stores
+//! `FnOnce()` closures in memory blocks of a global singleton pool and links them into an
+//! intrusive queue. Interrupt handlers spawn closures and a low priority software interrupt, e.g.
+//! PendSV, runs them with `run_pending`. This feature implies the `mpsc` feature and requires Rust
+//! 1.61.0 or newer.
+//!
+//! ## `x86-sync-hazard`
+//!
+//! Makes `Pool` implement `Sync`, and thus makes `pool!` available, on targets other than ARM
+//! Cortex-M so firmware that uses singleton pools can be compiled and tested on the host. This is
+//! *unsound*: the pool is not protected against the ABA problem on these targets (see
+//! ['Portability'](#portability)) so it must *not* be used from more than one thread. Only enable
+//! this feature in host builds that run the pools on a single thread, e.g. simulations or tests
+//! run with `--test-threads=1`.
+//!
 //! # Soundness
 //!
 //! This pool uses a Treiber stack to keep a list of free memory blocks (nodes). Each of these
@@ -240,48 +637,155 @@
 //! # References
 //!
 //! 1. [Cortex-M3 Devices Generic User Guide (DUI 0552A)][0], Section 2.2.7 "Synchronization
-//! primitives"
+//!    primitives"
 //!
 //! [0]: http://infocenter.arm.com/help/topic/com.arm.doc.dui0552a/DUI0552A_cortex_m3_dgug.pdf
 //!
 //! 2. [ARMv7-M Architecture Reference Manual (DDI 0403E.b)][1], Section A3.4 "Synchronization and
-//! semaphores"
+//!    semaphores"
 //!
 //! [1]: https://static.docs.arm.com/ddi0403/eb/DDI0403E_B_armv7m_arm.pdf
 
 // TODO update uses of `Ordering` (check generated DMB instructions) to make this multi-core safe
 // TODO check if this also works on ARMv7-R
 
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
 #![cfg_attr(feature = "arch", feature(link_llvm_intrinsics))]
-#![cfg_attr(feature = "maybe-uninit", feature(maybe_uninit))]
 #![cfg_attr(feature = "union", allow(unions_with_drop_fields))]
 #![cfg_attr(feature = "union", feature(untagged_unions))]
 #![cfg_attr(not(test), no_std)]
 #![deny(missing_docs)]
 #![deny(warnings)]
 
-#[cfg(feature = "maybe-uninit")]
-use core::mem::MaybeUninit;
-#[cfg(not(feature = "arch"))]
-use core::sync::atomic::{AtomicPtr, Ordering};
+#[cfg(feature = "track-callers")]
+use core::panic::Location;
+#[cfg(any(
+    feature = "poison",
+    not(any(feature = "track-callers", feature = "handles"))
+))]
+use core::slice;
+#[cfg(any(not(feature = "arch"), feature = "track-callers"))]
+use core::sync::atomic::AtomicPtr;
+#[cfg(feature = "handles")]
+use core::sync::atomic::AtomicUsize;
+#[cfg(any(not(feature = "arch"), feature = "track-callers", feature = "handles"))]
+use core::sync::atomic::Ordering;
 use core::{
-    any::TypeId,
+    any::{self, TypeId},
+    borrow::{Borrow, BorrowMut},
     cell::UnsafeCell,
+    cmp, fmt,
+    hash::{Hash, Hasher},
     marker::PhantomData,
-    mem,
+    mem::{self, MaybeUninit},
     ops::{Deref, DerefMut},
+    pin::Pin,
     ptr::{self, NonNull},
 };
 
 use as_slice::{AsMutSlice, AsSlice};
 
+#[cfg(all(feature = "leak-guard", not(test), not(target_os = "none")))]
+extern crate std;
+
 pub use crate::singleton::Pool as pool;
+#[cfg(feature = "pooled")]
+pub use lifo_macros::pooled;
+
+#[cfg(feature = "cortex-m-rt")]
+#[doc(hidden)]
+pub use cortex_m as __cortex_m;
+
+#[cfg(feature = "partition")]
+#[doc(hidden)]
+pub use crate::partition::Partition as __Partition;
+
+#[cfg(feature = "static-pool")]
+#[doc(hidden)]
+pub use crate::prelinked::{node as __static_node, Memory as __StaticMemory};
 
+// lets the code generated by `#[pooled]` refer to this crate in our own tests
+#[cfg(all(test, feature = "pooled"))]
+extern crate self as lifo;
+
+#[cfg(feature = "allocator-api")]
+mod allocator;
 #[cfg(feature = "arch")]
 mod arch;
+#[cfg(feature = "arena")]
+pub mod arena;
+#[cfg(feature = "binary-heap")]
+pub mod binary_heap;
+#[cfg(feature = "bitmap")]
+pub mod bitmap;
+#[cfg(feature = "buddy")]
+pub mod buddy;
+#[cfg(feature = "channel")]
+pub mod channel;
+#[cfg(feature = "composite")]
+pub mod composite;
+#[cfg(all(
+    feature = "defmt-logger",
+    any(armv7m, test, feature = "x86-sync-hazard")
+))]
+pub mod defmt_logger;
+#[cfg(feature = "heapless-compat")]
+pub mod heapless;
+#[cfg(feature = "logger")]
+pub mod logger;
+mod message;
+#[cfg(feature = "mpsc")]
+pub mod mpsc;
+#[cfg(feature = "mpu")]
+pub mod mpu;
+#[cfg(feature = "partition")]
+mod partition;
+#[cfg(feature = "per-core")]
+pub mod per_core;
+#[cfg(feature = "persist")]
+pub mod persist;
+#[cfg(feature = "static-pool")]
+mod prelinked;
+#[cfg(feature = "quarantine")]
+mod quarantine;
+#[cfg(feature = "recycle")]
+pub mod recycle;
+#[cfg(feature = "rtic")]
+pub mod rtic;
+#[cfg(feature = "scoped")]
+pub mod scoped;
 pub mod singleton;
+#[cfg(feature = "slab")]
+pub mod slab;
+#[cfg(feature = "stats")]
+mod stats;
+#[cfg(feature = "stream")]
+pub mod stream;
+#[cfg(feature = "sub-pool")]
+pub mod sub_pool;
+// the tests leak boxes, and use `static mut` memory, on purpose
+#[allow(
+    clippy::drop_non_drop,
+    clippy::forget_non_drop,
+    unknown_lints,
+    static_mut_refs
+)]
 #[cfg(test)]
 mod tests;
+#[cfg(feature = "timer")]
+pub mod timer;
+#[cfg(feature = "untyped")]
+pub mod untyped;
+
+pub use crate::message::Message;
+#[cfg(feature = "registry")]
+pub use crate::singleton::registry::panic_report;
+#[cfg(feature = "stats")]
+pub use crate::stats::{Region, Snapshot, Stats};
+
+// Value written to every byte of a free memory block
+#[cfg(feature = "poison")]
+const POISON: u8 = 0xa5;
 
 /// A lock-free memory pool
 pub struct Pool<T> {
@@ -289,6 +793,10 @@ pub struct Pool<T> {
     #[cfg(not(feature = "arch"))]
     head: AtomicPtr<Node<T>>,
 
+    // In FIFO mode the free list is a queue; `head` points to its sentinel node
+    #[cfg(feature = "fifo")]
+    tail: AtomicPtr<Node<T>>,
+
     #[cfg(feature = "arch")]
     head: UnsafeCell<*mut Node<T>>,
 
@@ -296,28 +804,92 @@ pub struct Pool<T> {
     // struct is not `Sync` on those platforms
     #[cfg(not(feature = "arch"))]
     _not_send_or_sync: PhantomData<*const ()>,
+
+    #[cfg(feature = "stats")]
+    stats: stats::Counters,
+
+    // All the memory blocks managed by this pool; linked through `Node.managed_next`
+    #[cfg(feature = "track-callers")]
+    managed: AtomicPtr<Node<T>>,
+
+    #[cfg(feature = "quarantine")]
+    quarantine: quarantine::Quarantine<T>,
 }
 
 // NOTE: Here we lie about `Pool` implementing `Sync` on x86_64. This is not true but it lets us
 // test the `pool!` and `singleton::Pool` abstractions. We just have to be careful not to use the
-// pool in a multi-threaded context
-#[cfg(any(armv7m, test))]
+// pool in a multi-threaded context. The `x86-sync-hazard` feature extends the lie to downstream
+// crates
+#[cfg(any(armv7m, test, feature = "x86-sync-hazard"))]
 unsafe impl<T> Sync for Pool<T> {}
 
 unsafe impl<T> Send for Pool<T> {}
 
 impl<T> Pool<T> {
     /// Creates a new empty pool
+    #[allow(clippy::new_without_default)]
     pub const fn new() -> Self {
         Pool {
             #[cfg(not(feature = "arch"))]
             head: AtomicPtr::new(ptr::null_mut()),
 
+            #[cfg(feature = "fifo")]
+            tail: AtomicPtr::new(ptr::null_mut()),
+
             #[cfg(feature = "arch")]
             head: UnsafeCell::new(ptr::null_mut()),
 
             #[cfg(not(feature = "arch"))]
             _not_send_or_sync: PhantomData,
+
+            #[cfg(feature = "stats")]
+            stats: stats::Counters::new(),
+
+            #[cfg(feature = "track-callers")]
+            managed: AtomicPtr::new(ptr::null_mut()),
+
+            #[cfg(feature = "quarantine")]
+            quarantine: quarantine::Quarantine::new(),
+        }
+    }
+
+    // Creates a pool whose free list is the chain of `len` nodes that starts at `first` and ends
+    // at `last`; used by `static_pool!`
+    //
+    // NOTE(unsafe) the chain must have been built with `prelinked::node` and must not be handed to
+    // any other pool
+    #[cfg(feature = "static-pool")]
+    #[doc(hidden)]
+    pub const unsafe fn __prelinked(first: *mut Node<T>, last: *mut Node<T>, len: usize) -> Self {
+        if len == 0 {
+            return Pool::new();
+        }
+
+        #[cfg(not(feature = "fifo"))]
+        let _ = last;
+
+        Pool {
+            #[cfg(not(feature = "arch"))]
+            head: AtomicPtr::new(first),
+
+            // the first node is the sentinel
+            #[cfg(feature = "fifo")]
+            tail: AtomicPtr::new(last),
+
+            #[cfg(feature = "arch")]
+            head: UnsafeCell::new(first),
+
+            #[cfg(not(feature = "arch"))]
+            _not_send_or_sync: PhantomData,
+
+            #[cfg(feature = "stats")]
+            stats: stats::Counters::with_capacity(len),
+
+            #[cfg(feature = "track-callers")]
+            managed: AtomicPtr::new(first),
+
+            #[cfg(feature = "quarantine")]
+            quarantine: quarantine::Quarantine::new(),
         }
     }
 
@@ -326,8 +898,15 @@ impl<T> Pool<T> {
     /// Returns `None` when the pool is observed as exhausted
     ///
     /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    #[cfg_attr(feature = "track-callers", track_caller)]
     pub fn alloc(&self) -> Option<Box<T, Uninit>> {
-        if let Some(node) = self.pop() {
+        if let Some(node) = self.claim() {
+            #[cfg(feature = "stats")]
+            self.stats.on_alloc();
+
+            #[cfg(feature = "track-callers")]
+            Self::track(node, Location::caller());
+
             Some(Box {
                 node,
                 _state: PhantomData,
@@ -337,6 +916,26 @@ impl<T> Pool<T> {
         }
     }
 
+    /// Claims a memory block from the pool
+    ///
+    /// # Panics
+    ///
+    /// This method panics when the pool is observed as exhausted. The panic message includes the
+    /// location of the caller, the type of the memory blocks and the address of the pool.
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    #[track_caller]
+    pub fn alloc_or_panic(&self) -> Box<T, Uninit> {
+        match self.alloc() {
+            Some(block) => block,
+            None => panic!(
+                "pool of `{}` blocks at {:p} is exhausted",
+                any::type_name::<T>(),
+                self
+            ),
+        }
+    }
+
     /// Returns a memory block to the pool
     ///
     /// *NOTE*: `T`'s destructor (if any) will run on `value` iff `S = Init`
@@ -346,20 +945,263 @@ impl<T> Pool<T> {
     where
         S: 'static,
     {
+        let node = value.into_node();
+
         if TypeId::of::<S>() == TypeId::of::<Init>() {
             unsafe {
-                ptr::drop_in_place(value.node.as_ref().data.get());
+                ptr::drop_in_place(node.as_ref().data.get());
+            }
+        }
+
+        self.release(node)
+    }
+
+    /// Claims memory blocks from the pool until `vec` is full or the pool is observed as exhausted
+    ///
+    /// Returns the number of memory blocks pushed onto `vec`
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains CAS loops
+    #[cfg(feature = "heapless")]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_many_into<const N: usize>(
+        &self,
+        vec: &mut ::heapless::Vec<Box<T, Uninit>, N>,
+    ) -> usize {
+        let mut n = 0;
+        while !vec.is_full() {
+            match self.alloc() {
+                Some(block) => {
+                    // NOTE(unsafe) `vec` is not full
+                    unsafe { vec.push_unchecked(block) }
+                    n += 1;
+                }
+                None => break,
+            }
+        }
+        n
+    }
+
+    /// Claims a memory block from the pool and initializes it with `val`
+    ///
+    /// Returns `val` back when the pool is observed as exhausted
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_init(&self, val: T) -> Result<Box<T>, T> {
+        match self.alloc() {
+            Some(block) => Ok(block.init(val)),
+            None => Err(val),
+        }
+    }
+
+    /// Claims a memory block from the pool and initializes it in place with the value returned by
+    /// `f`
+    ///
+    /// Returns `None`, without calling `f`, when the pool is observed as exhausted. See
+    /// `Box::init_with`.
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_with<F>(&self, f: F) -> Option<Box<T>>
+    where
+        F: FnOnce() -> T,
+    {
+        self.alloc().map(|block| block.init_with(f))
+    }
+
+    /// Claims a memory block from the pool, initializes it with `val` and pins it
+    ///
+    /// Returns `None` when the pool is observed as exhausted. See `Box::into_pin` for the
+    /// guarantees the pool provides for pinned values.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_pinned(&self, val: T) -> Option<Pin<Box<T>>> {
+        self.alloc().map(|block| block.init(val).into_pin())
+    }
+
+    /// Returns a pinned memory block to the pool
+    ///
+    /// `T`'s destructor (if any) runs before the memory block is returned to the free list
+    pub fn free_pinned(&self, value: Pin<Box<T>>) {
+        // NOTE(unsafe) the value is dropped in place before its memory block is reused
+        self.free(unsafe { Pin::into_inner_unchecked(value) })
+    }
+
+    /// Claims `n` memory blocks that are adjacent in memory
+    ///
+    /// The blocks are returned as a single, wide box whose size in bytes, which is at least
+    /// `n * mem::size_of::<T>()`, can be queried with `Contiguous::len`. Use `free_contiguous` to
+    /// return the blocks to the pool.
+    ///
+    /// Returns `None` when `n` is zero or when the pool doesn't contain `n` adjacent free memory
+    /// blocks
+    ///
+    /// *NOTE:* While this method searches the free list, the pool is observed as exhausted by
+    /// other contexts (e.g. interrupt handlers). The search takes `O(N^2 * n)` time, where `N` is
+    /// the number of free memory blocks, so this method is best kept out of time critical code.
+    ///
+    /// This method is not available when the `track-callers` or `handles` features are enabled as
+    /// they keep bookkeeping data in every memory block, which would end up in the buffer.
+    #[cfg(not(any(feature = "track-callers", feature = "handles")))]
+    pub fn alloc_contiguous(&self, n: usize) -> Option<Contiguous<T>> {
+        if n == 0 {
+            return None;
+        }
+
+        let sz = mem::size_of::<Node<T>>();
+        let list = self.take_all();
+
+        let contains = |addr: usize| {
+            let mut node = list;
+            while !node.is_null() {
+                if node as usize == addr {
+                    return true;
+                }
+
+                node = unsafe { (*node).next };
+            }
+
+            false
+        };
+
+        let mut start = ptr::null_mut();
+        let mut node = list;
+        while !node.is_null() {
+            if (1..n).all(|i| contains(node as usize + i * sz)) {
+                start = node;
+                break;
+            }
+
+            node = unsafe { (*node).next };
+        }
+
+        // return the blocks we won't use to the free list
+        let lo = start as usize;
+        let hi = lo + n * sz;
+        let mut node = list;
+        while let Some(nn_node) = NonNull::new(node) {
+            node = unsafe { (*node).next };
+
+            let addr = nn_node.as_ptr() as usize;
+            if start.is_null() || addr < lo || addr >= hi {
+                self.push(nn_node);
+            }
+        }
+
+        let node = NonNull::new(start)?;
+
+        #[cfg(any(feature = "poison", feature = "stats"))]
+        for _i in 0..n {
+            #[cfg(feature = "poison")]
+            Self::check_poison(unsafe {
+                NonNull::new_unchecked((start as *mut u8).add(_i * sz) as *mut _)
+            });
+
+            #[cfg(feature = "stats")]
+            self.stats.on_alloc();
+        }
+
+        Some(Contiguous { node, n })
+    }
+
+    /// Returns memory blocks claimed with `alloc_contiguous` to the pool
+    #[cfg(not(any(feature = "track-callers", feature = "handles")))]
+    pub fn free_contiguous(&self, blocks: Contiguous<T>) {
+        let sz = mem::size_of::<Node<T>>();
+
+        for i in 0..blocks.n {
+            self.release(unsafe {
+                NonNull::new_unchecked((blocks.node.as_ptr() as *mut u8).add(i * sz) as *mut _)
+            });
+        }
+    }
+
+    /// Calls `f` on every memory block that's currently allocated
+    ///
+    /// `f` receives the location of the code that claimed the memory block and a pointer to the
+    /// memory block. Memory blocks claimed or freed while this method runs may or may not be
+    /// reported.
+    #[cfg(feature = "track-callers")]
+    pub fn dump_live<F>(&self, mut f: F)
+    where
+        F: FnMut(&'static Location<'static>, *const T),
+    {
+        let mut node = self.managed.load(Ordering::Relaxed);
+        while !node.is_null() {
+            unsafe {
+                let location = (*node).location.load(Ordering::Relaxed);
+                if !location.is_null() {
+                    f(&*location, (*node).data.get());
+                }
+
+                node = (*node).managed_next;
             }
         }
+    }
+
+    /// Sets the number of freed memory blocks that are kept in quarantine
+    ///
+    /// Memory blocks in quarantine are not reused until they are evicted by more recently freed
+    /// memory blocks or until the pool runs out of free memory blocks. The quarantine can hold up
+    /// to 16 memory blocks; that's also its default depth. A depth of zero disables the quarantine.
+    ///
+    /// Memory blocks currently in quarantine are returned to the free list.
+    #[cfg(feature = "quarantine")]
+    pub fn set_quarantine_depth(&self, depth: usize) {
+        self.quarantine.set_depth(depth, |node| self.push(node))
+    }
+
+    /// Returns a snapshot of the statistics of this pool
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.stats.snapshot()
+    }
+
+    /// Returns a snapshot of the health of this pool, including its allocation counters and the
+    /// memory regions it manages
+    #[cfg(feature = "stats")]
+    pub fn snapshot(&self) -> Snapshot {
+        self.stats.telemetry(mem::size_of::<T>())
+    }
 
-        self.push(value.node)
+    /// Resets the low watermark to the current number of free memory blocks
+    #[cfg(feature = "stats")]
+    pub fn reset_watermarks(&self) {
+        self.stats.reset_watermarks()
     }
 
     /// Increases the capacity of the pool
     ///
     /// This method might *not* fully utilize the given memory block due to alignment requirements
     pub fn grow(&self, memory: &'static mut [u8]) {
-        let mut p = memory.as_mut_ptr();
+        // NOTE(unsafe) `[u8]` and `[MaybeUninit<u8>]` have the same layout and the pool never reads
+        // back the contents of `memory` as initialized bytes
+        self.grow_uninit(unsafe { &mut *(memory as *mut [u8] as *mut [MaybeUninit<u8>]) })
+    }
+
+    /// Increases the capacity of the pool using memory that has not been initialized
+    ///
+    /// This is the same as `grow` but `memory` doesn't need to be initialized; for example, it can
+    /// be placed in a `.uninit` linker section
+    ///
+    /// This method might *not* fully utilize the given memory block due to alignment requirements
+    pub fn grow_uninit(&self, memory: &'static mut [MaybeUninit<u8>]) {
+        self.extend(memory);
+    }
+
+    // Adds the memory blocks that fit in `memory` to the pool and returns how many there are
+    pub(crate) fn extend(&self, memory: &'static mut [MaybeUninit<u8>]) -> usize {
+        #[cfg(feature = "stats")]
+        self.stats.on_region(memory.as_ptr() as usize, memory.len());
+
+        Self::split(memory, |node| self.manage(node))
+    }
+
+    // Splits `memory` into as many properly aligned nodes as possible; returns the number of nodes
+    fn split<F>(memory: &'static mut [MaybeUninit<u8>], mut f: F) -> usize
+    where
+        F: FnMut(NonNull<Node<T>>),
+    {
+        let mut p = memory.as_mut_ptr() as *mut u8;
         let mut len = memory.len();
 
         let align = mem::align_of::<Node<T>>();
@@ -374,19 +1216,23 @@ impl<T> Pool<T> {
 
             if offset >= len {
                 // slice is too small
-                return;
+                return 0;
             }
 
             p = unsafe { p.add(offset) };
             len -= offset;
         }
 
+        let mut n = 0;
         while len >= sz {
-            self.push(unsafe { NonNull::new_unchecked(p as *mut _) });
+            f(unsafe { NonNull::new_unchecked(p as *mut _) });
 
             p = unsafe { p.add(sz) };
             len -= sz;
+            n += 1;
         }
+
+        n
     }
 
     /// Increases the capacity of the pool
@@ -395,21 +1241,184 @@ impl<T> Pool<T> {
     where
         A: AsMutSlice<Element = Node<T>>,
     {
+        #[cfg(feature = "stats")]
+        self.stats
+            .on_region(memory.as_ptr() as usize, mem::size_of::<A>());
+
         for p in unsafe { (*memory.as_mut_ptr()).as_mut_slice() } {
-            self.push(NonNull::from(p))
+            self.manage(NonNull::from(p))
         }
     }
 
-    #[cfg(not(feature = "arch"))]
-    fn pop(&self) -> Option<NonNull<Node<T>>> {
-        // NOTE: currently we only support single core devices (i.e. Non-Shareable memory)
-        let fetch_order = Ordering::Relaxed;
-        let set_order = Ordering::Relaxed;
+    // Adds a new memory block to the pool
+    pub(crate) fn manage(&self, node: NonNull<Node<T>>) {
+        self.register(node);
 
-        let mut head = self.head.load(fetch_order);
-        loop {
-            if let Some(nn_head) = NonNull::new(head) {
-                let next = unsafe { (*head).next };
+        #[cfg(feature = "poison")]
+        Self::poison(node);
+
+        self.push(node)
+    }
+
+    // Adds a new memory block, which already holds a live value, to the pool without putting it in
+    // the free list
+    #[cfg(feature = "persist")]
+    pub(crate) fn adopt(&self, node: NonNull<Node<T>>) {
+        self.register(node);
+
+        #[cfg(feature = "stats")]
+        self.stats.on_alloc();
+    }
+
+    // Initializes the bookkeeping of a memory block that's new to the pool
+    #[allow(unused_variables)] // `node` is only used by some features
+    fn register(&self, node: NonNull<Node<T>>) {
+        #[cfg(feature = "stats")]
+        self.stats.on_grow(1);
+
+        #[cfg(feature = "handles")]
+        unsafe {
+            (*node.as_ptr()).generation = AtomicUsize::new(0);
+        }
+
+        #[cfg(feature = "track-callers")]
+        unsafe {
+            let node = node.as_ptr();
+            (*node).location = AtomicPtr::new(ptr::null_mut());
+
+            // NOTE nodes are never removed from this list so the ABA problem doesn't apply here
+            let mut head = self.managed.load(Ordering::Relaxed);
+            loop {
+                (*node).managed_next = head;
+
+                match self.managed.compare_exchange_weak(
+                    head,
+                    node,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => break,
+                    // list was changed by some interrupt handler
+                    Err(new_head) => head = new_head,
+                }
+            }
+        }
+    }
+
+    // Returns a previously allocated memory block to the free list; the block must not contain a
+    // live value
+    pub(crate) fn release(&self, node: NonNull<Node<T>>) {
+        #[cfg(feature = "stats")]
+        self.stats.on_free();
+
+        #[cfg(feature = "handles")]
+        unsafe {
+            node.as_ref().retire();
+        }
+
+        #[cfg(feature = "track-callers")]
+        unsafe {
+            node.as_ref()
+                .location
+                .store(ptr::null_mut(), Ordering::Relaxed);
+        }
+
+        #[cfg(feature = "poison")]
+        Self::poison(node);
+
+        #[cfg(feature = "quarantine")]
+        let node = match self.quarantine.push(node) {
+            Some(evicted) => evicted,
+            None => return,
+        };
+
+        self.push(node)
+    }
+
+    // Takes a memory block from the free list
+    fn claim(&self) -> Option<NonNull<Node<T>>> {
+        let node = self.pop();
+
+        // fall back to the quarantined memory blocks rather than reporting the pool as exhausted
+        #[cfg(feature = "quarantine")]
+        let node = node.or_else(|| {
+            self.quarantine.drain(|node| self.push(node));
+            self.pop()
+        });
+
+        #[cfg(feature = "poison")]
+        {
+            if let Some(node) = node {
+                Self::check_poison(node);
+            }
+        }
+
+        node
+    }
+
+    // Range of the memory block, as an offset and a length in bytes, that's filled with `POISON`
+    // while the block is free
+    #[cfg(feature = "poison")]
+    const fn poison_range() -> (usize, usize) {
+        let sz = mem::size_of::<T>();
+
+        // the `next` pointer overlaps the start of `data`
+        if cfg!(feature = "union") {
+            let ptr_sz = mem::size_of::<*mut Node<T>>();
+            let offset = if ptr_sz < sz { ptr_sz } else { sz };
+            (offset, sz - offset)
+        } else {
+            (0, sz)
+        }
+    }
+
+    #[cfg(feature = "poison")]
+    fn poison(node: NonNull<Node<T>>) {
+        let (offset, len) = Self::poison_range();
+
+        unsafe {
+            let p = (node.as_ref().data.get() as *mut u8).add(offset);
+            ptr::write_bytes(p, POISON, len);
+        }
+    }
+
+    #[cfg(feature = "poison")]
+    fn check_poison(node: NonNull<Node<T>>) {
+        let (offset, len) = Self::poison_range();
+
+        let bytes = unsafe {
+            slice::from_raw_parts((node.as_ref().data.get() as *const u8).add(offset), len)
+        };
+
+        if bytes.iter().any(|byte| *byte != POISON) {
+            panic!(
+                "use after free detected: memory block at {:p} was modified while it was free",
+                node.as_ptr()
+            );
+        }
+    }
+
+    // Records where `node` was allocated
+    #[cfg(feature = "track-callers")]
+    fn track(node: NonNull<Node<T>>, location: &'static Location<'static>) {
+        unsafe {
+            node.as_ref().location.store(
+                location as *const Location<'static> as *mut _,
+                Ordering::Relaxed,
+            );
+        }
+    }
+
+    #[cfg(all(not(feature = "arch"), not(feature = "fifo")))]
+    fn pop(&self) -> Option<NonNull<Node<T>>> {
+        // NOTE: currently we only support single core devices (i.e. Non-Shareable memory)
+        let fetch_order = Ordering::Relaxed;
+        let set_order = Ordering::Relaxed;
+
+        let mut head = self.head.load(fetch_order);
+        loop {
+            if let Some(nn_head) = NonNull::new(head) {
+                let next = unsafe { (*head).next };
 
                 match self
                     .head
@@ -454,7 +1463,35 @@ impl<T> Pool<T> {
         }
     }
 
-    #[cfg(not(feature = "arch"))]
+    // Detaches the whole free list, leaving the pool empty
+    #[cfg(all(not(feature = "arch"), not(feature = "fifo")))]
+    #[cfg_attr(any(feature = "track-callers", feature = "handles"), allow(dead_code))]
+    fn take_all(&self) -> *mut Node<T> {
+        // NOTE: currently we only support single core devices (i.e. Non-Shareable memory)
+        self.head.swap(ptr::null_mut(), Ordering::Relaxed)
+    }
+
+    // Detaches the whole free list, leaving the pool empty
+    #[cfg(feature = "arch")]
+    #[cfg_attr(any(feature = "track-callers", feature = "handles"), allow(dead_code))]
+    fn take_all(&self) -> *mut Node<T> {
+        unsafe {
+            loop {
+                // State: Exclusive
+                let head = arch::ldrex(self.head.get() as *const u32) as *mut Node<T>;
+
+                if arch::strex(0, self.head.get() as *mut u32) == 0 {
+                    // State: Open
+                    break head;
+                } else {
+                    // some interrupt changed our state back to Open and STREX failed
+                    continue;
+                }
+            }
+        }
+    }
+
+    #[cfg(all(not(feature = "arch"), not(feature = "fifo")))]
     fn push(&self, mut new_head: NonNull<Node<T>>) {
         // NOTE: currently we only support single core devices (i.e. Non-Shareable memory)
         let fetch_order = Ordering::Relaxed;
@@ -494,12 +1531,119 @@ impl<T> Pool<T> {
             }
         }
     }
+
+    #[cfg(all(feature = "fifo", not(feature = "arch")))]
+    fn pop(&self) -> Option<NonNull<Node<T>>> {
+        // NOTE: currently we only support single core devices (i.e. Non-Shareable memory)
+        let fetch_order = Ordering::Relaxed;
+        let set_order = Ordering::Relaxed;
+
+        loop {
+            let head = self.head.load(fetch_order);
+            let nn_head = NonNull::new(head)?;
+            let tail = self.tail.load(fetch_order);
+            let next = unsafe { Self::next(head).load(fetch_order) };
+
+            if head == tail {
+                if next.is_null() {
+                    // only the sentinel is left; queue is observed as empty
+                    break None;
+                }
+
+                // some `push` was preempted before it could update the tail; help it out
+                let _ = self
+                    .tail
+                    .compare_exchange(tail, next, set_order, fetch_order);
+            } else if self
+                .head
+                .compare_exchange_weak(head, next, set_order, fetch_order)
+                .is_ok()
+            {
+                // `next` becomes the new sentinel and the old sentinel is handed out
+                break Some(nn_head);
+            }
+        }
+    }
+
+    // Detaches the whole free list, leaving the pool empty (except for the sentinel)
+    #[cfg(all(feature = "fifo", not(feature = "arch")))]
+    #[cfg_attr(any(feature = "track-callers", feature = "handles"), allow(dead_code))]
+    fn take_all(&self) -> *mut Node<T> {
+        let mut list = ptr::null_mut();
+        while let Some(node) = self.pop() {
+            unsafe { (*node.as_ptr()).next = list }
+            list = node.as_ptr();
+        }
+        list
+    }
+
+    #[cfg(all(feature = "fifo", not(feature = "arch")))]
+    fn push(&self, new_tail: NonNull<Node<T>>) {
+        // NOTE: currently we only support single core devices (i.e. Non-Shareable memory)
+        let fetch_order = Ordering::Relaxed;
+        let set_order = Ordering::Relaxed;
+
+        let new_tail = new_tail.as_ptr();
+        unsafe { (*new_tail).next = ptr::null_mut() }
+
+        loop {
+            let tail = self.tail.load(fetch_order);
+
+            if tail.is_null() {
+                // very first node; it becomes the sentinel
+                if self
+                    .tail
+                    .compare_exchange(tail, new_tail, set_order, fetch_order)
+                    .is_ok()
+                {
+                    self.head.store(new_tail, set_order);
+                    return;
+                }
+
+                continue;
+            }
+
+            let next = unsafe { Self::next(tail) };
+            match next.compare_exchange_weak(ptr::null_mut(), new_tail, set_order, fetch_order) {
+                Ok(_) => {
+                    // if this fails then some interrupt handler already updated the tail for us
+                    let _ = self
+                        .tail
+                        .compare_exchange(tail, new_tail, set_order, fetch_order);
+                    return;
+                }
+                // the tail is lagging behind; help advance it
+                Err(next) if !next.is_null() => {
+                    let _ = self
+                        .tail
+                        .compare_exchange(tail, next, set_order, fetch_order);
+                }
+                Err(_) => {}
+            }
+        }
+    }
+
+    // Views the `next` field of `node` as an atomic
+    #[cfg(all(feature = "fifo", not(feature = "arch")))]
+    unsafe fn next<'a>(node: *mut Node<T>) -> &'a AtomicPtr<Node<T>> {
+        // NOTE(unsafe) `AtomicPtr<T>` has the same in-memory representation as `*mut T`
+        &*(&(*node).next as *const *mut Node<T> as *const AtomicPtr<Node<T>>)
+    }
 }
 
+// NOTE public, but hidden, so the code generated by `static_pool!` can name it
 #[cfg(all(not(feature = "maybe-uninit"), not(feature = "union")))]
-struct Node<T> {
+#[doc(hidden)]
+pub struct Node<T> {
     data: UnsafeCell<T>,
     next: *mut Node<T>,
+    #[cfg(feature = "track-callers")]
+    location: AtomicPtr<Location<'static>>,
+    #[cfg(feature = "track-callers")]
+    managed_next: *mut Node<T>,
+    // bumped every time the value in this memory block is dropped or moved out
+    #[cfg(feature = "handles")]
+    generation: AtomicUsize,
 }
 
 /// Unfortunate implementation detail that you need to interact with if you want to use `grow_exact`
@@ -507,10 +1651,74 @@ struct Node<T> {
 pub struct Node<T> {
     data: UnsafeCell<T>,
     next: *mut Node<T>,
+    #[cfg(feature = "track-callers")]
+    location: AtomicPtr<Location<'static>>,
+    #[cfg(feature = "track-callers")]
+    managed_next: *mut Node<T>,
+    // bumped every time the value in this memory block is dropped or moved out
+    #[cfg(feature = "handles")]
+    generation: AtomicUsize,
+}
+
+// Size, in bytes, of each memory block of a pool of `T` values; used by `#[pooled]`
+#[doc(hidden)]
+pub const fn __block_size<T>() -> usize {
+    mem::size_of::<Node<T>>()
+}
+
+// Size, in bytes, of a buffer that can hold `capacity` claimable memory blocks regardless of its
+// alignment; used by `pool!`
+#[doc(hidden)]
+pub const fn __memory_size<T>(capacity: usize) -> usize {
+    __block_count(capacity) * mem::size_of::<Node<T>>() + mem::align_of::<Node<T>>() - 1
+}
+
+// Number of memory blocks needed to have `capacity` claimable memory blocks; used by `pool!` and
+// `static_pool!`
+#[doc(hidden)]
+pub const fn __block_count(capacity: usize) -> usize {
+    // in FIFO mode one memory block is always kept as the sentinel
+    let reserved = if cfg!(feature = "fifo") { 1 } else { 0 };
+
+    capacity + reserved
+}
+
+#[cfg(all(feature = "fifo", feature = "arch"))]
+compile_error!("the `fifo` feature can't be used together with the `arch` feature");
+
+#[cfg(all(feature = "track-callers", feature = "union"))]
+compile_error!("the `track-callers` feature can't be used together with the `union` feature");
+
+#[cfg(all(feature = "handles", feature = "union"))]
+compile_error!("the `handles` feature can't be used together with the `union` feature");
+
+#[cfg(all(feature = "collections", feature = "union"))]
+compile_error!("the `collections` feature can't be used together with the `union` feature");
+
+#[cfg(all(feature = "mpsc", feature = "union"))]
+compile_error!("the `mpsc` feature can't be used together with the `union` feature");
+
+#[cfg(all(feature = "recycle", feature = "union"))]
+compile_error!("the `recycle` feature can't be used together with the `union` feature");
+
+#[cfg(all(feature = "timer", feature = "union"))]
+compile_error!("the `timer` feature can't be used together with the `union` feature");
+
+#[cfg(all(feature = "handles", not(feature = "union")))]
+impl<T> Node<T> {
+    pub(crate) fn generation(&self) -> usize {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    // Invalidates all the handles to the current value
+    pub(crate) fn retire(&self) {
+        self.generation.fetch_add(1, Ordering::Release);
+    }
 }
 
 #[cfg(all(not(feature = "maybe-uninit"), feature = "union"))]
-union Node<T> {
+#[doc(hidden)]
+pub union Node<T> {
     data: UnsafeCell<T>,
     next: *mut Node<T>,
 }
@@ -531,23 +1739,353 @@ pub struct Box<T, STATE = Init> {
 impl<T> Box<T, Uninit> {
     /// Initializes this memory block
     pub fn init(self, val: T) -> Box<T, Init> {
+        let node = self.into_node();
+
+        unsafe {
+            ptr::write(node.as_ref().data.get(), val);
+        }
+
+        Box {
+            node,
+            _state: PhantomData,
+        }
+    }
+
+    /// Initializes this memory block with the value returned by `f`
+    ///
+    /// Unlike `init`, the value is not passed by value so the compiler has a chance to construct it
+    /// directly in the memory block, avoiding a copy of large values
+    #[inline(always)]
+    pub fn init_with<F>(self, f: F) -> Box<T, Init>
+    where
+        F: FnOnce() -> T,
+    {
+        let node = self.into_node();
+
+        unsafe {
+            ptr::write(node.as_ref().data.get(), f());
+        }
+
+        Box {
+            node,
+            _state: PhantomData,
+        }
+    }
+
+    /// Tries to initialize this memory block in place using `f`
+    ///
+    /// If `f` returns an error the memory block is handed back, still uninitialized, together with
+    /// the error
+    ///
+    /// # Safety
+    ///
+    /// `f` must fully initialize the `MaybeUninit` it receives whenever it returns `Ok`
+    pub unsafe fn try_init<E, F>(mut self, f: F) -> Result<Box<T, Init>, (Box<T, Uninit>, E)>
+    where
+        F: FnOnce(&mut MaybeUninit<T>) -> Result<(), E>,
+    {
+        match f(self.as_uninit_mut()) {
+            Ok(()) => Ok(self.assume_init()),
+            Err(e) => Err((self, e)),
+        }
+    }
+
+    /// Marks this memory block as initialized without writing to it
+    ///
+    /// This is meant for memory blocks that were filled in by other means, e.g. a DMA transfer,
+    /// `write` or `as_uninit_mut`
+    ///
+    /// # Safety
+    ///
+    /// The contents of the memory block must be a valid, initialized value of type `T`
+    pub unsafe fn assume_init(self) -> Box<T, Init> {
+        Box {
+            node: self.into_node(),
+            _state: PhantomData,
+        }
+    }
+
+    /// Returns a reference to the contents of this memory block
+    pub fn as_uninit(&self) -> &MaybeUninit<T> {
+        unsafe { &*(self.node.as_ref().data.get() as *const MaybeUninit<T>) }
+    }
+
+    /// Returns a mutable reference to the contents of this memory block
+    ///
+    /// This can be used to initialize the memory block in place, e.g. field by field
+    pub fn as_uninit_mut(&mut self) -> &mut MaybeUninit<T> {
+        unsafe { &mut *(self.node.as_ref().data.get() as *mut MaybeUninit<T>) }
+    }
+
+    /// Returns a raw pointer to the contents of this memory block
+    ///
+    /// The pointer stays valid for as long as the memory block is allocated, e.g. it can be used
+    /// to program a DMA peripheral
+    pub fn as_ptr(&self) -> *const T {
+        unsafe { self.node.as_ref().data.get() }
+    }
+
+    /// Returns a raw pointer to the contents of this memory block
+    ///
+    /// See `as_ptr`
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        unsafe { self.node.as_ref().data.get() }
+    }
+
+    /// Writes `val` into this memory block and returns a mutable reference to it
+    ///
+    /// This overwrites any previous value without dropping it. The memory block is *not* marked as
+    /// initialized, so `val` won't be dropped when the memory block is freed; use `init` for that.
+    pub fn write(&mut self, val: T) -> &mut T {
+        unsafe {
+            let p = self.node.as_ref().data.get();
+            ptr::write(p, val);
+            &mut *p
+        }
+    }
+}
+
+impl<T> Box<T, Uninit>
+where
+    T: ByteArray,
+{
+    /// Zeroes this memory block and marks it as initialized
+    pub(crate) fn zeroed(mut self) -> Box<T, Init> {
         unsafe {
-            ptr::write(self.node.as_ref().data.get(), val);
+            // NOTE(unsafe) all zeros is a valid `ByteArray` value
+            self.as_mut_ptr().write_bytes(0, 1);
+            self.assume_init()
         }
+    }
+}
+
+impl<T> Box<T, Init> {
+    /// Moves the value out of this memory block
+    ///
+    /// Returns the value and the, now uninitialized, memory block so the latter can be reused or
+    /// returned to the pool
+    pub fn into_inner(self) -> (T, Box<T, Uninit>) {
+        let node = self.into_node();
+
+        // NOTE the memory block is not released so the handles must be invalidated here
+        #[cfg(feature = "handles")]
+        unsafe {
+            node.as_ref().retire();
+        }
+
+        let val = unsafe { ptr::read(node.as_ref().data.get()) };
+
+        (
+            val,
+            Box {
+                node,
+                _state: PhantomData,
+            },
+        )
+    }
+
+    /// Drops the value in this memory block and returns the, now uninitialized, memory block
+    ///
+    /// The memory block is *not* returned to the pool so it can be reused right away without
+    /// touching the free list
+    pub fn reinit(self) -> Box<T, Uninit> {
+        let node = self.into_node();
+
+        // NOTE the memory block is not released so the handles must be invalidated here
+        #[cfg(feature = "handles")]
+        unsafe {
+            node.as_ref().retire();
+        }
+
+        unsafe { ptr::drop_in_place(node.as_ref().data.get()) }
 
         Box {
-            node: self.node,
+            node,
             _state: PhantomData,
         }
     }
+
+    /// Pins the value in this memory block
+    ///
+    /// Memory blocks never move while they are allocated so pinning is free. A pinned value stays
+    /// at the same address until it's returned to the pool with `Pool::free_pinned`, which runs
+    /// its destructor before the memory block can be reused; if the pinned box is dropped instead,
+    /// the memory block (and the value) is leaked and never reused. Either way the `Pin` drop
+    /// guarantee is upheld.
+    pub fn into_pin(self) -> Pin<Box<T>> {
+        // NOTE(unsafe) see above
+        unsafe { Pin::new_unchecked(self) }
+    }
+
+    /// Clones the value into a new memory block claimed from `pool`
+    ///
+    /// Returns `None` when `pool` is observed as exhausted
+    pub fn try_clone(&self, pool: &Pool<T>) -> Option<Box<T>>
+    where
+        T: Clone,
+    {
+        pool.alloc()
+            .map(|block| block.init_with(|| self.deref().clone()))
+    }
+}
+
+impl<T, S> Box<T, S> {
+    /// Returns `true` if both boxes refer to the same memory block
+    pub fn ptr_eq(&self, other: &Box<T, S>) -> bool {
+        self.node == other.node
+    }
+
+    /// Returns an opaque identifier of the memory block
+    ///
+    /// The identifier stays the same for as long as the memory block is allocated; the same
+    /// identifier may be handed out again once the memory block has been returned to the pool
+    pub fn id(&self) -> usize {
+        self.node.as_ptr() as usize
+    }
+
+    // Consumes the box without running the leak guard
+    // NOTE `Box` only implements `Drop` when the `leak-guard` feature is enabled
+    #[allow(clippy::forget_non_drop)]
+    pub(crate) fn into_node(self) -> NonNull<Node<T>> {
+        let node = self.node;
+        mem::forget(self);
+        node
+    }
 }
 
+// Data cache maintenance (Cortex-M7)
+#[cfg(feature = "cortex-m")]
+impl<T, S> Box<T, S> {
+    /// Cleans the data cache lines that cover the value in the memory block
+    ///
+    /// Call this after the CPU has written to the memory block and before a DMA transfer reads from
+    /// it, so the DMA doesn't read stale contents from main memory
+    pub fn clean_dcache(&self, scb: &mut cortex_m::peripheral::SCB) {
+        scb.clean_dcache_by_address(self.data_addr(), mem::size_of::<T>())
+    }
+
+    /// Cleans and then invalidates the data cache lines that cover the value in the memory block
+    ///
+    /// Call this before a DMA transfer writes to the memory block, so the eviction of a dirty cache
+    /// line can't overwrite what the DMA wrote
+    pub fn clean_invalidate_dcache(&mut self, scb: &mut cortex_m::peripheral::SCB) {
+        scb.clean_invalidate_dcache_by_address(self.data_addr(), mem::size_of::<T>())
+    }
+
+    /// Invalidates the data cache lines that cover the value in the memory block
+    ///
+    /// Call this after a DMA transfer has written to the memory block and before the CPU reads from
+    /// it, so the CPU doesn't read stale contents from the cache
+    ///
+    /// # Safety
+    ///
+    /// `T` must be aligned to the cache line size, 32 bytes on the Cortex-M7, and its size must be
+    /// a multiple of it, e.g. `T` is `#[repr(align(32))]`; otherwise writes to the neighbouring
+    /// memory, which includes the bookkeeping data the pool keeps in the memory block, are
+    /// discarded as well. Writes to the memory block that have not been cleaned are discarded.
+    pub unsafe fn invalidate_dcache(&mut self, scb: &mut cortex_m::peripheral::SCB) {
+        scb.invalidate_dcache_by_address(self.data_addr(), mem::size_of::<T>())
+    }
+
+    // Address of the value in the memory block
+    fn data_addr(&self) -> usize {
+        unsafe { self.node.as_ref().data.get() as usize }
+    }
+}
+
+/// A box that owns adjacent memory blocks, which make up a single buffer
+///
+/// The buffer dereferences to a slice of (possibly) uninitialized bytes. See
+/// `Pool::alloc_contiguous`
+#[cfg(not(any(feature = "track-callers", feature = "handles")))]
+pub struct Contiguous<T> {
+    node: NonNull<Node<T>>,
+    n: usize,
+}
+
+#[cfg(not(any(feature = "track-callers", feature = "handles")))]
+impl<T> Contiguous<T> {
+    /// Returns the number of memory blocks that make up this buffer
+    pub fn blocks(&self) -> usize {
+        self.n
+    }
+
+    /// Returns the size of this buffer in bytes
+    pub fn len(&self) -> usize {
+        self.n * mem::size_of::<Node<T>>()
+    }
+
+    /// Returns `true` if the buffer has a size of zero bytes
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a raw pointer to the start of this buffer
+    pub fn as_ptr(&self) -> *const u8 {
+        self.node.as_ptr() as *const u8
+    }
+
+    /// Returns a raw pointer to the start of this buffer
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.node.as_ptr() as *mut u8
+    }
+
+    /// Returns a view into this buffer
+    ///
+    /// The contents of the buffer are *not* initialized when it's claimed from the pool
+    pub fn as_uninit_slice_mut(&mut self) -> &mut [MaybeUninit<u8>] {
+        unsafe { slice::from_raw_parts_mut(self.as_mut_ptr() as *mut _, self.len()) }
+    }
+}
+
+#[cfg(not(any(feature = "track-callers", feature = "handles")))]
+impl<T> Deref for Contiguous<T> {
+    type Target = [MaybeUninit<u8>];
+
+    fn deref(&self) -> &[MaybeUninit<u8>] {
+        unsafe { slice::from_raw_parts(self.as_ptr() as *const _, self.len()) }
+    }
+}
+
+#[cfg(not(any(feature = "track-callers", feature = "handles")))]
+impl<T> DerefMut for Contiguous<T> {
+    fn deref_mut(&mut self) -> &mut [MaybeUninit<u8>] {
+        self.as_uninit_slice_mut()
+    }
+}
+
+#[cfg(not(any(feature = "track-callers", feature = "handles")))]
+unsafe impl<T> Send for Contiguous<T> {}
+
+#[cfg(not(any(feature = "track-callers", feature = "handles")))]
+unsafe impl<T> Sync for Contiguous<T> {}
+
 /// Uninitialized type state
 pub enum Uninit {}
 
 /// Initialized type state
 pub enum Init {}
 
+/// A plain byte buffer, `[u8; N]`, for which any bit pattern, e.g. all zeros, is a valid value
+///
+/// Memory blocks that hold these types can be handed out as initialized after zeroing them. This
+/// trait is sealed so it can't be implemented for other types, like references.
+///
+/// ``` compile_fail
+/// fn byte_array<T: lifo::ByteArray>() {}
+///
+/// byte_array::<&'static mut [u8; 4]>();
+/// ```
+pub trait ByteArray: AsMutSlice<Element = u8> + sealed::Sealed {}
+
+impl<const N: usize> ByteArray for [u8; N] where [u8; N]: AsMutSlice<Element = u8> {}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl<const N: usize> Sealed for [u8; N] {}
+}
+
 unsafe impl<T, S> Send for Box<T, S> where T: Send {}
 
 unsafe impl<T, S> Sync for Box<T, S> where T: Sync {}
@@ -585,3 +2123,253 @@ impl<T> DerefMut for Box<T> {
         unsafe { &mut *self.node.as_ref().data.get() }
     }
 }
+
+impl<T> AsRef<T> for Box<T> {
+    fn as_ref(&self) -> &T {
+        self.deref()
+    }
+}
+
+impl<T> AsMut<T> for Box<T> {
+    fn as_mut(&mut self) -> &mut T {
+        self.deref_mut()
+    }
+}
+
+impl<T> Borrow<T> for Box<T> {
+    fn borrow(&self) -> &T {
+        self.deref()
+    }
+}
+
+impl<T> BorrowMut<T> for Box<T> {
+    fn borrow_mut(&mut self) -> &mut T {
+        self.deref_mut()
+    }
+}
+
+impl<T> fmt::Debug for Box<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <T as fmt::Debug>::fmt(self, f)
+    }
+}
+
+impl<T> fmt::Display for Box<T>
+where
+    T: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <T as fmt::Display>::fmt(self, f)
+    }
+}
+
+impl<T> PartialEq for Box<T>
+where
+    T: PartialEq,
+{
+    fn eq(&self, rhs: &Box<T>) -> bool {
+        <T as PartialEq>::eq(self, rhs)
+    }
+}
+
+impl<T> Eq for Box<T> where T: Eq {}
+
+impl<T> PartialOrd for Box<T>
+where
+    T: PartialOrd,
+{
+    fn partial_cmp(&self, rhs: &Box<T>) -> Option<cmp::Ordering> {
+        <T as PartialOrd>::partial_cmp(self, rhs)
+    }
+}
+
+impl<T> Ord for Box<T>
+where
+    T: Ord,
+{
+    fn cmp(&self, rhs: &Box<T>) -> cmp::Ordering {
+        <T as Ord>::cmp(self, rhs)
+    }
+}
+
+impl<T> Hash for Box<T>
+where
+    T: Hash,
+{
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        <T as Hash>::hash(self, state)
+    }
+}
+
+#[cfg(feature = "leak-guard")]
+impl<T, S> Drop for Box<T, S> {
+    fn drop(&mut self) {
+        // boxes dropped while unwinding are not reported; panicking again would abort the process.
+        // Targets without an operating system don't unwind
+        #[cfg(not(target_os = "none"))]
+        if std::thread::panicking() {
+            return;
+        }
+
+        #[cfg(test)]
+        if !tests::LEAK_GUARD.with(|armed| armed.get()) {
+            return;
+        }
+
+        panic!(
+            "memory block at {:p} leaked: `Box<{}>` must be returned to its pool with `Pool::free`",
+            self.node.as_ptr(),
+            any::type_name::<T>()
+        )
+    }
+}
+
+impl<T, S> fmt::Pointer for Box<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Pointer::fmt(&self.node.as_ptr(), f)
+    }
+}
+
+#[cfg(feature = "stable_deref_trait")]
+unsafe impl<T> stable_deref_trait::StableDeref for Box<T> {}
+
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for Box<T>
+where
+    T: serde::Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        <T as serde::Serialize>::serialize(self, serializer)
+    }
+}
+
+/// Deserializes a value into a memory block claimed from the pool
+///
+/// Deserialization fails if the pool is observed as exhausted
+#[cfg(feature = "serde")]
+impl<'de, T> serde::de::DeserializeSeed<'de> for &Pool<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    type Value = Box<T>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Box<T>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let block = self
+            .alloc()
+            .ok_or_else(|| <D::Error as serde::de::Error>::custom("memory pool is exhausted"))?;
+
+        match T::deserialize(deserializer) {
+            Ok(val) => Ok(block.init(val)),
+            Err(e) => {
+                self.free(block);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Error returned by `Pool::decode_postcard` and `Pool::encode_postcard`
+#[cfg(feature = "postcard")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PostcardError {
+    /// The pool is exhausted
+    Exhausted,
+    /// The message could not be decoded, or the value could not be encoded
+    Postcard(postcard::Error),
+}
+
+#[cfg(feature = "postcard")]
+impl From<postcard::Error> for PostcardError {
+    fn from(e: postcard::Error) -> Self {
+        PostcardError::Postcard(e)
+    }
+}
+
+#[cfg(feature = "postcard")]
+impl<T> Pool<T> {
+    /// Claims a memory block from the pool and decodes the postcard message at the start of
+    /// `bytes` into it
+    ///
+    /// Returns the initialized memory block and the number of bytes the message takes. The decoded
+    /// value can borrow from `bytes`, e.g. `&str` fields, so nothing is copied out of the message
+    /// besides its fixed-size parts.
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn decode_postcard<'de>(&self, bytes: &'de [u8]) -> Result<(Box<T>, usize), PostcardError>
+    where
+        T: serde::Deserialize<'de>,
+    {
+        let block = self.alloc().ok_or(PostcardError::Exhausted)?;
+
+        match postcard::take_from_bytes(bytes) {
+            Ok((val, rest)) => Ok((block.init(val), bytes.len() - rest.len())),
+            Err(e) => {
+                self.free(block);
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Claims a memory block from the pool and encodes `value` into it as a postcard message
+    ///
+    /// Returns the initialized memory block and the length of the message, which starts at the
+    /// beginning of the memory block. The rest of the memory block is zeroed.
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn encode_postcard<V>(&self, value: &V) -> Result<(Box<T>, usize), PostcardError>
+    where
+        T: ByteArray,
+        V: serde::Serialize + ?Sized,
+    {
+        let mut block = self.alloc().ok_or(PostcardError::Exhausted)?.zeroed();
+
+        match postcard::to_slice(value, block.as_mut_slice()) {
+            Ok(message) => {
+                let len = message.len();
+                Ok((block, len))
+            }
+            Err(e) => {
+                self.free(block);
+                Err(e.into())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<T> defmt::Format for Box<T>
+where
+    T: defmt::Format,
+{
+    fn format(&self, f: defmt::Formatter) {
+        <T as defmt::Format>::format(self, f)
+    }
+}
+
+// NOTE initialized boxes get `ReadBuffer` and `WriteBuffer` from the blanket implementations in
+// `embedded-dma`, via `StableDeref`
+#[cfg(feature = "dma")]
+unsafe impl<T> embedded_dma::WriteBuffer for Box<T, Uninit>
+where
+    T: embedded_dma::WriteTarget,
+{
+    type Word = T::Word;
+
+    unsafe fn write_buffer(&mut self) -> (*mut T::Word, usize) {
+        embedded_dma::WriteTarget::as_write_buffer(self.as_uninit_mut())
+    }
+}
@@ -0,0 +1,294 @@
+//! Deferred logging through a pool
+//!
+//! `Logger` implements `log::Log` by formatting each record into a memory block of its pool, in
+//! the context that logged it, and sending the memory block through an `Mpsc` queue. A
+//! low-priority task later drains the queue and writes the lines out, e.g. over a UART, so
+//! logging from an interrupt handler only costs the formatting and never waits on the I/O. When
+//! the pool is exhausted records are discarded, and counted, instead of blocking the caller.
+//!
+//! ``` ignore
+//! use lifo::logger::Logger;
+//! use log::LevelFilter;
+//!
+//! static LOGGER: Logger<80> = Logger::new(LevelFilter::Info);
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     static mut MEMORY: [u8; 1024] = [0; 1024];
+//!
+//!     LOGGER.grow(MEMORY);
+//!     let mut drain = LOGGER.init().unwrap();
+//!
+//!     loop {
+//!         if let Some(line) = drain.recv() {
+//!             writeln!(uart, "{} {}", line.level(), line.as_str()).ok();
+//!             // the memory block returns to the pool when `line` is dropped
+//!         }
+//!     }
+//! }
+//!
+//! #[interrupt]
+//! fn EXTI0() {
+//!     log::info!("button pressed");
+//! }
+//! ```
+
+use core::{
+    fmt::{self, Write as _},
+    ops::Deref,
+    str,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::{
+    mpsc::{Consumer, Mpsc},
+    Box, Pool,
+};
+
+/// A log record formatted into at most `N` bytes; the type of the memory blocks of a `Logger`
+///
+/// Longer messages are truncated at a character boundary
+pub struct Line<const N: usize> {
+    level: Level,
+    len: usize,
+    buf: [u8; N],
+}
+
+impl<const N: usize> Line<N> {
+    /// Returns the level of the record
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Returns the formatted message of the record
+    pub fn as_str(&self) -> &str {
+        // NOTE(unsafe) `write_str` only copies whole characters
+        unsafe { str::from_utf8_unchecked(&self.buf[..self.len]) }
+    }
+}
+
+impl<const N: usize> fmt::Write for Line<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut n = (N - self.len).min(s.len());
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+
+        Ok(())
+    }
+}
+
+impl<const N: usize> fmt::Debug for Line<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Line")
+            .field("level", &self.level)
+            .field("message", &self.as_str())
+            .finish()
+    }
+}
+
+/// A `log::Log` implementation that defers the output of the records it accepts
+///
+/// Each record is formatted into a `Line<N>` claimed from the logger's pool
+pub struct Logger<const N: usize> {
+    level: LevelFilter,
+    pool: Pool<Line<N>>,
+    queue: Mpsc<Line<N>>,
+    dropped: AtomicUsize,
+}
+
+impl<const N: usize> Logger<N> {
+    /// Creates a new logger, with an empty pool, that accepts records up to `level`
+    pub const fn new(level: LevelFilter) -> Self {
+        Logger {
+            level,
+            pool: Pool::new(),
+            queue: Mpsc::new(),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Increases the capacity of the logger's pool
+    ///
+    /// See `Pool::grow`
+    pub fn grow(&self, memory: &'static mut [u8]) {
+        self.pool.grow(memory)
+    }
+
+    /// Returns the number of records that were discarded because the pool was exhausted
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<const N: usize> Logger<N>
+where
+    Pool<Line<N>>: Sync,
+{
+    /// Installs this logger as the global logger and sets the maximum log level
+    ///
+    /// Returns the endpoint that drains the logged records. This fails if a global logger has
+    /// already been installed.
+    pub fn init(&'static self) -> Result<Drain<N>, SetLoggerError> {
+        log::set_logger(self)?;
+        log::set_max_level(self.level);
+
+        Ok(Drain {
+            pool: &self.pool,
+            // NOTE(unsafe) `set_logger` succeeds at most once so this is the only consumer
+            consumer: unsafe { self.queue.consumer() },
+        })
+    }
+}
+
+impl<const N: usize> Log for Logger<N>
+where
+    Pool<Line<N>>: Sync,
+{
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let mut line = match self.pool.alloc() {
+            Some(line) => line.init(Line {
+                level: record.level(),
+                len: 0,
+                buf: [0; N],
+            }),
+            None => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
+
+        write!(line, "{}", record.args()).ok();
+        self.queue.producer().send(line);
+    }
+
+    fn flush(&self) {}
+}
+
+impl<const N: usize> fmt::Debug for Logger<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Logger")
+            .field("level", &self.level)
+            .field("dropped", &self.dropped())
+            .finish()
+    }
+}
+
+/// The endpoint that drains the records logged through a `Logger`
+pub struct Drain<const N: usize> {
+    pool: &'static Pool<Line<N>>,
+    consumer: Consumer<'static, Line<N>>,
+}
+
+impl<const N: usize> Drain<N> {
+    /// Receives the oldest logged record
+    ///
+    /// Returns `None` if the queue is observed as empty
+    pub fn recv(&mut self) -> Option<Entry<N>> {
+        Some(Entry {
+            pool: self.pool,
+            line: Some(self.consumer.recv()?),
+        })
+    }
+
+    /// Returns `true` if there are no logged records to receive
+    pub fn is_empty(&self) -> bool {
+        self.consumer.is_empty()
+    }
+}
+
+impl<const N: usize> fmt::Debug for Drain<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Drain").finish()
+    }
+}
+
+/// A logged record
+///
+/// Dropping the entry returns its memory block to the logger's pool
+pub struct Entry<const N: usize> {
+    pool: &'static Pool<Line<N>>,
+    // always `Some` until dropped
+    line: Option<Box<Line<N>>>,
+}
+
+impl<const N: usize> Deref for Entry<N> {
+    type Target = Line<N>;
+
+    fn deref(&self) -> &Line<N> {
+        // NOTE(unwrap) only `None` in the destructor
+        self.line.as_ref().unwrap()
+    }
+}
+
+impl<const N: usize> Drop for Entry<N> {
+    fn drop(&mut self) {
+        if let Some(line) = self.line.take() {
+            self.pool.free(line)
+        }
+    }
+}
+
+impl<const N: usize> fmt::Debug for Entry<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <Line<N> as fmt::Debug>::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::{Level, LevelFilter};
+
+    use super::{Line, Logger};
+    use crate::tests::memory;
+
+    #[test]
+    fn logger() {
+        static LOGGER: Logger<8> = Logger::new(LevelFilter::Info);
+
+        LOGGER.grow(memory::<Line<8>>(2));
+        let mut drain = LOGGER.init().unwrap();
+        assert!(LOGGER.init().is_err());
+
+        log::debug!("filtered out");
+        assert!(drain.is_empty());
+
+        log::info!("{}+{}={}", 1, 2, 3);
+        // truncated at a character boundary
+        log::warn!("aünïcödé");
+        // the pool is exhausted
+        log::error!("dropped");
+        assert_eq!(LOGGER.dropped(), 1);
+
+        let line = drain.recv().unwrap();
+        assert_eq!(line.level(), Level::Info);
+        assert_eq!(line.as_str(), "1+2=3");
+        drop(line);
+
+        let line = drain.recv().unwrap();
+        assert_eq!(line.level(), Level::Warn);
+        assert_eq!(line.as_str(), "aünïc");
+        assert!(drain.recv().is_none());
+
+        // the memory blocks are back in the pool
+        log::error!("a");
+        drop(line);
+        log::error!("b");
+        assert_eq!(drain.recv().unwrap().as_str(), "a");
+        assert_eq!(drain.recv().unwrap().as_str(), "b");
+        assert_eq!(LOGGER.dropped(), 1);
+    }
+}
@@ -0,0 +1,91 @@
+//! A payload bundled with a header
+
+use core::fmt;
+
+/// A payload of type `T` stored next to a small header of type `H`
+///
+/// Storing a `Message` in a pool keeps routing metadata, like a timestamp, a source id or the
+/// length of the payload, in the same memory block as the payload so it doesn't need a second
+/// memory block or a side table indexed by the address of the payload.
+///
+/// ```
+/// use lifo::{Message, Pool};
+///
+/// #[derive(Clone, Copy)]
+/// struct Header {
+///     timestamp: u32,
+///     source: u8,
+///     len: u8,
+/// }
+///
+/// let pool = Pool::<Message<Header, [u8; 32]>>::new();
+/// pool.grow(Box::leak(Box::new([0; 256])));
+///
+/// let header = Header { timestamp: 42, source: 1, len: 3 };
+/// let mut payload = [0; 32];
+/// payload[..3].copy_from_slice(b"abc");
+///
+/// let mut message = pool.alloc().unwrap().init(Message::new(header, payload));
+/// message.header_mut().timestamp += 1;
+///
+/// assert_eq!(message.header().timestamp, 43);
+/// assert_eq!(&message.payload()[..usize::from(message.header().len)], b"abc");
+///
+/// pool.free(message);
+/// ```
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Message<H, T> {
+    header: H,
+    payload: T,
+}
+
+impl<H, T> Message<H, T> {
+    /// Creates a message from its header and its payload
+    pub const fn new(header: H, payload: T) -> Self {
+        Message { header, payload }
+    }
+
+    /// Returns a reference to the header
+    pub fn header(&self) -> &H {
+        &self.header
+    }
+
+    /// Returns a mutable reference to the header
+    pub fn header_mut(&mut self) -> &mut H {
+        &mut self.header
+    }
+
+    /// Returns a reference to the payload
+    pub fn payload(&self) -> &T {
+        &self.payload
+    }
+
+    /// Returns a mutable reference to the payload
+    pub fn payload_mut(&mut self) -> &mut T {
+        &mut self.payload
+    }
+
+    /// Returns mutable references to both the header and the payload
+    pub fn split_mut(&mut self) -> (&mut H, &mut T) {
+        (&mut self.header, &mut self.payload)
+    }
+
+    /// Splits the message into its header and its payload
+    pub fn into_parts(self) -> (H, T) {
+        (self.header, self.payload)
+    }
+}
+
+impl<H, T> fmt::Debug for Message<H, T>
+where
+    H: fmt::Debug,
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Message")
+            .field("header", &self.header)
+            .field("payload", &self.payload)
+            .finish()
+    }
+}
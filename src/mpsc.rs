@@ -0,0 +1,255 @@
+//! Intrusive multi-producer single-consumer queue of memory blocks
+//!
+//! The `next` pointer that links free memory blocks in the free list of a pool is unused while the
+//! memory block is allocated. `Mpsc` reuses it to link the `Box`es that have been sent through the
+//! queue, so sending a message needs no storage besides its own memory block. This makes it a good
+//! fit for passing data from interrupt handlers to a task.
+//!
+//! ``` ignore
+//! use lifo::{mpsc::{Consumer, Mpsc, Producer}, Box, Pool};
+//!
+//! static SAMPLES: Pool<[u16; 32]> = Pool::new();
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     static mut MEMORY: [u8; 1024] = [0; 1024];
+//!     static mut QUEUE: Mpsc<[u16; 32]> = Mpsc::new();
+//!
+//!     SAMPLES.grow(MEMORY);
+//!
+//!     let (producer, mut consumer) = QUEUE.split();
+//!     // move `producer` into the ADC interrupt handler, which sends boxes with `producer.send`
+//!
+//!     loop {
+//!         if let Some(samples) = consumer.recv() {
+//!             process(&samples);
+//!             SAMPLES.free(samples);
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Producers link the boxes into a lock-free stack; the consumer takes the whole stack at once and
+//! reverses it so boxes are received in the order they were sent.
+
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use crate::{Box, Node};
+
+/// An intrusive, lock-free multi-producer single-consumer queue of `Box<T>`es
+///
+/// Boxes still in the queue when it's dropped are leaked
+pub struct Mpsc<T> {
+    // boxes sent, but not yet taken by the consumer, newest first
+    sent: AtomicPtr<Node<T>>,
+    // boxes taken by the consumer, oldest first; only accessed by the consumer
+    taken: UnsafeCell<*mut Node<T>>,
+}
+
+unsafe impl<T> Sync for Mpsc<T> where T: Send {}
+
+unsafe impl<T> Send for Mpsc<T> where T: Send {}
+
+impl<T> Mpsc<T> {
+    /// Creates a new empty queue
+    pub const fn new() -> Self {
+        Mpsc {
+            sent: AtomicPtr::new(ptr::null_mut()),
+            taken: UnsafeCell::new(ptr::null_mut()),
+        }
+    }
+
+    /// Splits the queue into its producer and consumer endpoints
+    pub fn split(&mut self) -> (Producer<'_, T>, Consumer<'_, T>) {
+        (
+            Producer { queue: self },
+            Consumer {
+                queue: self,
+                _not_sync: PhantomData,
+            },
+        )
+    }
+
+    // Returns a producer endpoint of a shared queue
+    #[cfg(any(
+        all(
+            feature = "defmt-logger",
+            any(armv7m, test, feature = "x86-sync-hazard")
+        ),
+        feature = "logger",
+        feature = "work-queue"
+    ))]
+    pub(crate) fn producer(&self) -> Producer<'_, T> {
+        Producer { queue: self }
+    }
+
+    // Returns the consumer endpoint of a shared queue
+    //
+    // # Safety
+    //
+    // There must be at most one consumer of the queue at any time
+    #[cfg(any(
+        all(
+            feature = "defmt-logger",
+            any(armv7m, test, feature = "x86-sync-hazard")
+        ),
+        feature = "logger",
+        feature = "work-queue"
+    ))]
+    pub(crate) unsafe fn consumer(&self) -> Consumer<'_, T> {
+        Consumer {
+            queue: self,
+            _not_sync: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for Mpsc<T> {
+    fn default() -> Self {
+        Mpsc::new()
+    }
+}
+
+impl<T> fmt::Debug for Mpsc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Mpsc").finish()
+    }
+}
+
+/// The sending endpoint of a `Mpsc` queue
+///
+/// Producers can be copied so several contexts can send boxes through the same queue
+pub struct Producer<'a, T> {
+    queue: &'a Mpsc<T>,
+}
+
+impl<T> Producer<'_, T> {
+    /// Sends `value` through the queue
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    pub fn send(&self, value: Box<T>) {
+        let node = value.into_node();
+
+        let mut head = self.queue.sent.load(Ordering::Relaxed);
+        loop {
+            // NOTE(unsafe) the memory block is allocated so its `next` field is not in use
+            unsafe { (*node.as_ptr()).next = head }
+
+            match self.queue.sent.compare_exchange_weak(
+                head,
+                node.as_ptr(),
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                // queue was changed by some interrupt handler
+                Err(new_head) => head = new_head,
+            }
+        }
+    }
+}
+
+impl<T> Clone for Producer<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Producer<'_, T> {}
+
+impl<T> fmt::Debug for Producer<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Producer").finish()
+    }
+}
+
+/// The receiving endpoint of a `Mpsc` queue
+pub struct Consumer<'a, T> {
+    queue: &'a Mpsc<T>,
+    // `taken` is not synchronized
+    _not_sync: PhantomData<*const ()>,
+}
+
+unsafe impl<T> Send for Consumer<'_, T> where T: Send {}
+
+impl<T> Consumer<'_, T> {
+    /// Receives the oldest box in the queue
+    ///
+    /// Returns `None` when the queue is observed as empty
+    pub fn recv(&mut self) -> Option<Box<T>> {
+        let taken = unsafe { &mut *self.queue.taken.get() };
+
+        if taken.is_null() {
+            // take all the boxes sent so far and put them in sending order
+            let mut sent = self.queue.sent.swap(ptr::null_mut(), Ordering::Acquire);
+            while let Some(node) = NonNull::new(sent) {
+                unsafe {
+                    sent = (*node.as_ptr()).next;
+                    (*node.as_ptr()).next = *taken;
+                }
+                *taken = node.as_ptr();
+            }
+        }
+
+        let node = NonNull::new(*taken)?;
+        *taken = unsafe { (*node.as_ptr()).next };
+
+        Some(Box {
+            _state: PhantomData,
+            node,
+        })
+    }
+
+    /// Returns `true` if the queue is observed as empty
+    pub fn is_empty(&self) -> bool {
+        let taken = unsafe { *self.queue.taken.get() };
+
+        taken.is_null() && self.queue.sent.load(Ordering::Relaxed).is_null()
+    }
+}
+
+impl<T> fmt::Debug for Consumer<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Consumer").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mpsc;
+    use crate::{tests::memory, Pool};
+
+    #[test]
+    fn mpsc() {
+        static POOL: Pool<u8> = Pool::new();
+
+        POOL.grow(memory::<u8>(3));
+
+        let mut queue = Mpsc::new();
+        let (producer, mut consumer) = queue.split();
+        assert!(consumer.is_empty());
+
+        producer.send(POOL.alloc_init(1).ok().unwrap());
+        producer.send(POOL.alloc_init(2).ok().unwrap());
+        let x = consumer.recv().unwrap();
+        assert_eq!(*x, 1);
+        POOL.free(x);
+
+        // boxes sent while the consumer holds taken boxes come after them
+        producer.send(POOL.alloc_init(3).ok().unwrap());
+        assert!(!consumer.is_empty());
+
+        for i in 2..=3 {
+            let x = consumer.recv().unwrap();
+            assert_eq!(*x, i);
+            POOL.free(x);
+        }
+        assert!(consumer.recv().is_none());
+    }
+}
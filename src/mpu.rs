@@ -0,0 +1,260 @@
+//! Trapping use-after-free with the Memory Protection Unit (ARMv7-M)
+//!
+//! Once `enable`d, every memory block that enters the quarantine of a pool (see the `quarantine`
+//! feature) is covered by an MPU region with no access permissions, and the region is removed
+//! when the memory block leaves the quarantine. Dereferencing a stale pointer to a quarantined
+//! memory block then raises a MemManage fault on the spot instead of silently corrupting the value
+//! that will later be stored in the memory block.
+//!
+//! ``` ignore
+//! use lifo::mpu;
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     let mut p = cortex_m::Peripherals::take().unwrap();
+//!
+//!     // report violations as MemManage faults rather than HardFaults
+//!     p.SCB.enable(Exception::MemoryManagement);
+//!
+//!     // NOTE(unsafe) MPU regions 4 to 7 are not used by anything else
+//!     unsafe { mpu::enable(&mut p.MPU, 4..8) }
+//!
+//!     // ..
+//! }
+//! ```
+//!
+//! MPU regions have power-of-two sizes and must be aligned to their size, so only the largest
+//! part of a memory block that can be covered by a single region, using its eight subregions, is
+//! protected; small memory blocks may not be protected at all. There are usually far fewer MPU
+//! regions than quarantined memory blocks so, when all the regions given to `enable` are in use,
+//! newly quarantined memory blocks are not protected. The MPU background region is enabled for
+//! privileged code so memory not covered by a region keeps its default permissions. This is
+//! meant for debug builds: programming a region adds a few dozen cycles to `free`.
+
+use core::{
+    ops::Range,
+    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+};
+
+use cortex_m::{interrupt, peripheral::MPU};
+
+// CTRL
+const ENABLE: u32 = 1;
+const PRIVDEFENA: u32 = 1 << 2;
+
+// RBAR
+const VALID: u32 = 1 << 4;
+
+// RASR: no access, never execute, normal write-back memory
+const RASR_ENABLE: u32 = 1;
+const XN: u32 = 1 << 28;
+const C: u32 = 1 << 17;
+const B: u32 = 1 << 16;
+
+// ARMv7-M MPUs have up to 16 regions
+const MAX_REGIONS: usize = 16;
+
+// the MPU regions handed to `enable`; none while `FIRST == END`
+static FIRST: AtomicU8 = AtomicU8::new(0);
+static END: AtomicU8 = AtomicU8::new(0);
+
+// the memory block covered by each MPU region; `0` means the region is not in use
+#[allow(clippy::declare_interior_mutable_const)]
+const UNUSED: AtomicUsize = AtomicUsize::new(0);
+static GUARDED: [AtomicUsize; MAX_REGIONS] = [UNUSED; MAX_REGIONS];
+
+/// Starts protecting quarantined memory blocks using the MPU regions `regions`
+///
+/// This also enables the MPU, and its background region for privileged code, if it was disabled
+///
+/// # Safety
+///
+/// The MPU regions `regions` must not be used, or reconfigured, by any other code until `disable`
+/// is called
+pub unsafe fn enable(mpu: &mut MPU, regions: Range<u8>) {
+    let end = if regions.end as usize > MAX_REGIONS {
+        MAX_REGIONS as u8
+    } else {
+        regions.end
+    };
+
+    mpu.ctrl.modify(|ctrl| ctrl | ENABLE | PRIVDEFENA);
+    barrier();
+
+    FIRST.store(regions.start, Ordering::Relaxed);
+    END.store(end, Ordering::Release);
+}
+
+/// Stops protecting quarantined memory blocks and disables the MPU regions that were in use
+///
+/// The MPU itself is left enabled
+pub fn disable(_mpu: &mut MPU) {
+    let first = FIRST.load(Ordering::Relaxed);
+    let end = END.swap(0, Ordering::Acquire);
+
+    for region in first..end {
+        if GUARDED[usize::from(region)].swap(0, Ordering::Relaxed) != 0 {
+            program(region, 0, 0);
+        }
+    }
+}
+
+// Covers the memory block that spans `len` bytes from `start` with an MPU region, if one is free
+pub(crate) fn protect(start: usize, len: usize) {
+    let end = END.load(Ordering::Acquire);
+    if end == 0 {
+        return;
+    }
+
+    let fit = match Fit::new(start, len) {
+        Some(fit) => fit,
+        None => return,
+    };
+
+    for region in FIRST.load(Ordering::Relaxed)..end {
+        if GUARDED[usize::from(region)]
+            .compare_exchange(0, start, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+        {
+            program(
+                region,
+                fit.base,
+                XN | C | B | u32::from(fit.srd) << 8 | u32::from(fit.log2 - 1) << 1 | RASR_ENABLE,
+            );
+
+            return;
+        }
+    }
+}
+
+// Removes the MPU region that covers the memory block that starts at `start`, if any
+pub(crate) fn unprotect(start: usize) {
+    let end = END.load(Ordering::Acquire);
+
+    for region in FIRST.load(Ordering::Relaxed)..end {
+        if GUARDED[usize::from(region)]
+            .compare_exchange(start, 0, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+        {
+            program(region, 0, 0);
+
+            return;
+        }
+    }
+}
+
+// Writes the base address and the attributes of MPU region `region`; `rasr == 0` disables it
+fn program(region: u8, base: usize, rasr: u32) {
+    // NOTE(unsafe) the region has been reserved for this module (see `enable`); the critical
+    // section keeps the RBAR / RASR pair from being split by another update
+    interrupt::free(|_| unsafe {
+        let mpu = &*MPU::PTR;
+
+        // VALID makes RBAR also select the region
+        mpu.rbar.write(base as u32 | VALID | u32::from(region));
+        mpu.rasr.write(rasr);
+    });
+
+    barrier();
+}
+
+fn barrier() {
+    // the new permissions must be in effect before the memory block is handed out again, or
+    // before the stale pointer is dereferenced
+    #[cfg(armv7m)]
+    {
+        cortex_m::asm::dsb();
+        cortex_m::asm::isb();
+    }
+}
+
+// An MPU region that only covers bytes of a memory block
+#[derive(Debug, PartialEq)]
+struct Fit {
+    base: usize,
+    // the region spans `1 << log2` bytes
+    log2: u8,
+    // subregion disable mask
+    srd: u8,
+}
+
+impl Fit {
+    // Finds the MPU region that covers the most bytes of `start..start + len` without covering
+    // any byte outside of it
+    fn new(start: usize, len: usize) -> Option<Self> {
+        let end = start.checked_add(len)?;
+
+        let mut best: Option<(usize, Fit)> = None;
+        for log2 in 5..(8 * core::mem::size_of::<usize>() as u8 - 1) {
+            let size = 1usize << log2;
+            // the smallest part of a region that can be enabled on its own
+            let unit = if log2 < 8 { size } else { size / 8 };
+            if unit > len {
+                break;
+            }
+
+            // a memory block overlaps at most two size-aligned windows that are not fully covered
+            let first = start & !(size - 1);
+            for base in [first, first.wrapping_add(size)].iter().copied() {
+                let (covered, srd) = if log2 < 8 {
+                    // regions smaller than 256 bytes have no subregions
+                    if base >= start && base + size <= end {
+                        (size, 0)
+                    } else {
+                        (0, 0)
+                    }
+                } else {
+                    let sub = size / 8;
+                    let mut covered = 0;
+                    let mut srd = 0;
+                    for i in 0..8 {
+                        let s = base + i * sub;
+                        if s >= start && s + sub <= end {
+                            covered += sub;
+                        } else {
+                            srd |= 1 << i;
+                        }
+                    }
+                    (covered, srd)
+                };
+
+                if covered != 0 && best.as_ref().map(|(c, _)| covered > *c).unwrap_or(true) {
+                    best = Some((covered, Fit { base, log2, srd }));
+                }
+            }
+        }
+
+        best.map(|(_, fit)| fit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fit;
+
+    #[test]
+    fn fit() {
+        // 7 of the 8 subregions of a 256-byte region
+        assert_eq!(
+            Fit::new(0x2000_0010, 0x100),
+            Some(Fit {
+                base: 0x2000_0000,
+                log2: 8,
+                srd: 0b0000_0001,
+            })
+        );
+
+        // a whole 64-byte region
+        assert_eq!(
+            Fit::new(0x2000_0040, 0x50),
+            Some(Fit {
+                base: 0x2000_0040,
+                log2: 6,
+                srd: 0,
+            })
+        );
+
+        // too small to hold an aligned 32-byte region
+        assert_eq!(Fit::new(0x2000_0008, 0x30), None);
+    }
+}
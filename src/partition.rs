@@ -0,0 +1,99 @@
+//! Splitting one buffer among several pools; see `partition!`
+
+use core::mem::{self, MaybeUninit};
+
+use crate::{singleton::Pool, Node};
+
+/// A buffer that's about to be split among several pools
+///
+/// Implementation detail of `partition!`
+#[doc(hidden)]
+pub struct Partition<const N: usize> {
+    memory: &'static mut [MaybeUninit<u8>; N],
+}
+
+impl<const N: usize> Partition<N> {
+    #[doc(hidden)]
+    pub fn new(memory: &'static mut [MaybeUninit<u8>; N]) -> Self {
+        Partition { memory }
+    }
+
+    // This fails to compile if the buffer is smaller than `SIZE` bytes
+    #[doc(hidden)]
+    pub fn check<const SIZE: usize>(self) -> Rest {
+        let () = Fits::<N, SIZE>::ASSERT;
+
+        Rest {
+            memory: self.memory,
+        }
+    }
+}
+
+struct Fits<const N: usize, const SIZE: usize>;
+
+impl<const N: usize, const SIZE: usize> Fits<N, SIZE> {
+    // evaluated, and checked, when `Partition::<N>::check::<SIZE>` is instantiated
+    const ASSERT: () = assert!(
+        SIZE <= N,
+        "the pools given to `partition!` don't fit in the buffer"
+    );
+}
+
+/// The part of the buffer that has not been handed to any pool yet
+///
+/// Implementation detail of `partition!`
+#[doc(hidden)]
+pub struct Rest {
+    memory: &'static mut [MaybeUninit<u8>],
+}
+
+impl Rest {
+    // Grows the pool `P` with the smallest sub-region, at the start of the remaining buffer, that
+    // holds `capacity` claimable memory blocks
+    //
+    // `partition!` has checked that the buffer has room for `__memory_size::<P::Data>(capacity)`
+    // bytes, which covers the worst case alignment padding
+    #[doc(hidden)]
+    pub fn grow<P>(&mut self, capacity: usize)
+    where
+        P: Pool,
+    {
+        let align = mem::align_of::<Node<P::Data>>();
+        let padding = (align - self.memory.as_ptr() as usize % align) % align;
+        let len = padding + crate::__block_count(capacity) * mem::size_of::<Node<P::Data>>();
+
+        let (head, tail) = mem::take(&mut self.memory).split_at_mut(len);
+        self.memory = tail;
+
+        P::grow_uninit(head)
+    }
+
+    #[doc(hidden)]
+    pub fn into_inner(self) -> &'static mut [MaybeUninit<u8>] {
+        self.memory
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem::MaybeUninit;
+
+    use crate::singleton::Pool;
+
+    #[test]
+    fn partition() {
+        crate::pool!(PartA: u8, PartB: [u32; 3]);
+
+        const SIZE: usize = crate::__memory_size::<u8>(2) + crate::__memory_size::<[u32; 3]>(3);
+
+        let memory = Box::leak(Box::new([MaybeUninit::uninit(); SIZE + 1]));
+        let rest = crate::partition!(memory => PartA: 2, PartB: 3);
+
+        // whatever alignment padding was not needed is handed back
+        assert!(!rest.is_empty());
+
+        let a = (0..3).filter_map(|_| PartA::alloc()).collect::<Vec<_>>();
+        let b = (0..4).filter_map(|_| PartB::alloc()).collect::<Vec<_>>();
+        assert_eq!((a.len(), b.len()), (2, 3));
+    }
+}
@@ -0,0 +1,200 @@
+//! Per-core pools backed by a shared reserve
+//!
+//! `PerCorePool` gives each core its own local pool and keeps the memory given to `grow` in a
+//! reserve shared by all the cores. A core claims memory blocks from, and returns them to, its
+//! local pool; only when the local pool is empty does it steal a batch of memory blocks from the
+//! reserve, and only when the local pool holds too many free memory blocks does it hand a batch
+//! back. The CAS loops on the hot path then run on memory that the other cores don't touch.
+//!
+//! ``` ignore
+//! use lifo::per_core::PerCorePool;
+//!
+//! // 2 cores; memory blocks move between the local pools and the reserve 8 at a time
+//! static BUFFERS: PerCorePool<[u8; 64], 2> = PerCorePool::new(8);
+//!
+//! fn core_id() -> usize {
+//!     // e.g. read the SIO CPUID register
+//! }
+//!
+//! fn on_either_core() {
+//!     let buffer = BUFFERS.alloc(core_id()).unwrap().init([0; 64]);
+//!     // ..
+//!     BUFFERS.free(core_id(), buffer);
+//! }
+//! ```
+//!
+//! **NOTE** Like the rest of this crate the pools have not been revised for multi-core soundness
+//! (see the crate level documentation); this module only provides the layering.
+
+use core::{
+    fmt,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{Box, Pool, Uninit};
+
+struct Local<T> {
+    pool: Pool<T>,
+    // number of free memory blocks in `pool`
+    len: AtomicUsize,
+}
+
+/// A pool made of `CORES` local pools, one per core, backed by a shared reserve
+pub struct PerCorePool<T, const CORES: usize> {
+    reserve: Pool<T>,
+    locals: [Local<T>; CORES],
+    batch: usize,
+}
+
+impl<T, const CORES: usize> PerCorePool<T, CORES> {
+    // only used to initialize `PerCorePool.locals`
+    #[allow(clippy::declare_interior_mutable_const)]
+    const LOCAL: Local<T> = Local {
+        pool: Pool::new(),
+        // the first memory block pushed onto a `fifo` pool becomes its sentinel and can't be
+        // claimed; starting at -1 keeps `len` exact
+        len: AtomicUsize::new(if cfg!(feature = "fifo") {
+            usize::MAX
+        } else {
+            0
+        }),
+    };
+
+    /// Creates a new pool with an empty reserve
+    ///
+    /// Memory blocks move between the local pools and the reserve `batch` at a time. A local pool
+    /// hands a batch back to the reserve once it holds `2 * batch` free memory blocks.
+    pub const fn new(batch: usize) -> Self {
+        PerCorePool {
+            reserve: Pool::new(),
+            locals: [Self::LOCAL; CORES],
+            batch: if batch == 0 { 1 } else { batch },
+        }
+    }
+
+    /// Increases the capacity of the shared reserve
+    ///
+    /// See `Pool::grow`
+    pub fn grow(&self, memory: &'static mut [u8]) {
+        self.reserve.grow(memory)
+    }
+
+    /// Increases the capacity of the shared reserve using memory that has not been initialized
+    ///
+    /// See `Pool::grow_uninit`
+    pub fn grow_uninit(&self, memory: &'static mut [MaybeUninit<u8>]) {
+        self.reserve.grow_uninit(memory)
+    }
+
+    /// Claims a memory block from the local pool of `core`
+    ///
+    /// If the local pool is observed as empty a batch of memory blocks is first moved into it from
+    /// the reserve. Returns `None` when both are observed as exhausted. Memory blocks held by the
+    /// local pools of other cores are not considered.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `core` is not less than `CORES`
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains CAS loops
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc(&self, core: usize) -> Option<Box<T, Uninit>> {
+        let local = &self.locals[core];
+
+        let block = match local.pool.alloc() {
+            Some(block) => block,
+            None => {
+                // steal a batch from the reserve
+                for _ in 0..self.batch {
+                    match self.reserve.claim() {
+                        Some(node) => {
+                            local.pool.push(node);
+                            local.len.fetch_add(1, Ordering::Relaxed);
+                        }
+                        None => break,
+                    }
+                }
+
+                local.pool.alloc()?
+            }
+        };
+
+        local.len.fetch_sub(1, Ordering::Relaxed);
+
+        Some(block)
+    }
+
+    /// Returns a memory block to the local pool of `core`
+    ///
+    /// The memory block doesn't need to have been claimed by the same core. If the local pool then
+    /// holds `2 * batch` free memory blocks, `batch` of them are moved to the reserve.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `core` is not less than `CORES`
+    pub fn free<S>(&self, core: usize, value: Box<T, S>)
+    where
+        S: 'static,
+    {
+        let local = &self.locals[core];
+
+        local.pool.free(value);
+
+        if local.len.fetch_add(1, Ordering::Relaxed).wrapping_add(1) >= 2 * self.batch {
+            for _ in 0..self.batch {
+                match local.pool.claim() {
+                    Some(node) => {
+                        local.len.fetch_sub(1, Ordering::Relaxed);
+                        self.reserve.push(node);
+                    }
+                    None => break,
+                }
+            }
+        }
+    }
+}
+
+impl<T, const CORES: usize> fmt::Debug for PerCorePool<T, CORES> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PerCorePool")
+            .field("cores", &CORES)
+            .field("batch", &self.batch)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PerCorePool;
+    use crate::tests::{memory, RESERVED};
+
+    #[test]
+    fn per_core() {
+        static POOL: PerCorePool<u32, 2> = PerCorePool::new(2);
+
+        // the reserve and each local pool hold on to a sentinel when `fifo` is enabled
+        POOL.grow(memory::<u32>(4 + RESERVED));
+
+        let mut blocks = vec![];
+        while let Some(block) = POOL.alloc(0) {
+            blocks.push(block.init(0));
+        }
+        assert_eq!(blocks.len(), 4);
+        assert!(POOL.alloc(1).is_none());
+
+        // the fourth free hands a batch back to the reserve
+        for block in blocks {
+            POOL.free(0, block);
+        }
+
+        let a = POOL.alloc(1).unwrap();
+        let b = POOL.alloc(0).unwrap();
+        let c = POOL.alloc(0).unwrap();
+        assert!(POOL.alloc(0).is_none());
+
+        POOL.free(1, a);
+        POOL.free(0, b);
+        POOL.free(0, c);
+    }
+}
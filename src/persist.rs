@@ -0,0 +1,433 @@
+//! Pools whose memory blocks survive a warm reboot
+//!
+//! A `PersistentPool` manages a single memory region, e.g. battery-backed SRAM or RAM that the
+//! startup code doesn't zero. Next to the memory blocks, the region stores a header, tagged with a
+//! magic number and a checksum, and the state of every memory block. After a soft reset,
+//! `recover` validates the region and rebuilds the free list from it, handing back the memory
+//! blocks that were in use; if the region doesn't pass validation it's discarded and formatted from
+//! scratch.
+//!
+//! ``` ignore
+//! use core::mem::MaybeUninit;
+//!
+//! use lifo::persist::PersistentPool;
+//!
+//! static LOG: PersistentPool<[u8; 64]> = PersistentPool::new();
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     #[link_section = ".uninit.LOG"]
+//!     static mut MEMORY: [MaybeUninit<u8>; 1024] = [MaybeUninit::uninit(); 1024];
+//!
+//!     let mut records = heapless::Vec::<_, 16>::new();
+//!     // NOTE(unsafe) `MEMORY` is only ever handed to `LOG` and `[u8; 64]` is valid for any bytes
+//!     let recovered = unsafe { LOG.recover(MEMORY, |record| records.push(record).unwrap()) };
+//!     if !recovered {
+//!         // cold boot, or the region was corrupted; all the memory blocks are free
+//!     }
+//!
+//!     // these memory blocks outlive a soft reset
+//!     let record = LOG.alloc_init([0; 64]).ok().unwrap();
+//!
+//!     // ..
+//! }
+//! ```
+//!
+//! *NOTE:* The state of a memory block is recorded *after* the value has been moved into it, and
+//! *before* the value is dropped. Writes done through a `PersistentBox` are not tracked so a reset
+//! can leave a value half written.
+
+use core::{
+    fmt,
+    mem::{self, MaybeUninit},
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+    sync::atomic::{self, AtomicPtr, AtomicU8, Ordering},
+};
+
+use crate::{Node, Pool};
+
+// "lifo" in ASCII
+const MAGIC: usize = 0x6c69_666f;
+
+// states of a memory block; any other value means the region is corrupted
+const FREE: u8 = 0x5a;
+const USED: u8 = 0xa5;
+
+// Stored at the start of the region, followed by the state of each memory block and then by the
+// memory blocks themselves
+#[repr(C)]
+struct Header {
+    magic: usize,
+    // number of memory blocks in the region
+    len: usize,
+    node_size: usize,
+    // covers the other fields and the address of the region
+    checksum: usize,
+}
+
+impl Header {
+    // `at` is the address of the header in the region
+    fn checksum(&self, at: *const Header) -> usize {
+        // FNV-1a, folded over words
+        let mut sum = 0x811c_9dc5_usize;
+        for word in [self.magic, self.len, self.node_size, at as usize].iter() {
+            sum = (sum ^ word).wrapping_mul(0x0100_0193);
+        }
+        sum
+    }
+}
+
+// Where the parts of the region are
+struct Layout<T> {
+    header: *mut Header,
+    states: *mut AtomicU8,
+    nodes: *mut Node<T>,
+    len: usize,
+}
+
+impl<T> Layout<T> {
+    // Fits as many memory blocks as possible in `memory`
+    fn new(memory: &mut [MaybeUninit<u8>]) -> Option<Self> {
+        let start = memory.as_mut_ptr() as usize;
+        let end = start + memory.len();
+
+        let header = align_up(start, mem::align_of::<Header>());
+        let states = header + mem::size_of::<Header>();
+        if states > end {
+            return None;
+        }
+
+        let nodes = |len| align_up(states + len, mem::align_of::<Node<T>>());
+        let node_size = mem::size_of::<Node<T>>();
+
+        let mut len = (end - states) / (node_size + 1);
+        while len > 0 && nodes(len) + len * node_size > end {
+            len -= 1;
+        }
+
+        Some(Layout {
+            header: header as *mut Header,
+            states: states as *mut AtomicU8,
+            nodes: nodes(len) as *mut Node<T>,
+            len,
+        })
+    }
+
+    // Reads back the layout of a region managed by a pool
+    unsafe fn from_header(header: *mut Header) -> Self {
+        let len = (*header).len;
+        let states = header.add(1) as *mut AtomicU8;
+
+        Layout {
+            header,
+            states,
+            nodes: align_up(states as usize + len, mem::align_of::<Node<T>>()) as *mut Node<T>,
+            len,
+        }
+    }
+
+    unsafe fn state(&self, index: usize) -> &AtomicU8 {
+        &*self.states.add(index)
+    }
+
+    unsafe fn node(&self, index: usize) -> NonNull<Node<T>> {
+        NonNull::new_unchecked(self.nodes.add(index))
+    }
+}
+
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+/// A pool of `T` values whose memory blocks survive a warm reboot
+///
+/// See the [module-level documentation](index.html)
+pub struct PersistentPool<T> {
+    pool: Pool<T>,
+    header: AtomicPtr<Header>,
+}
+
+impl<T> PersistentPool<T> {
+    /// Creates a new empty pool
+    pub const fn new() -> Self {
+        PersistentPool {
+            pool: Pool::new(),
+            header: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Formats `memory` and gives all its memory blocks to the pool
+    ///
+    /// Anything the region held before is discarded. Returns the number of memory blocks that fit
+    /// in `memory`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the pool already manages a region
+    pub fn grow(&self, memory: &'static mut [MaybeUninit<u8>]) -> usize {
+        match Layout::new(memory) {
+            Some(layout) => self.format(layout),
+            None => 0,
+        }
+    }
+
+    /// Recovers the memory blocks of `memory`, a region that was formatted by `grow` before a reset
+    ///
+    /// `f` is called with each memory block that was in use before the reset. Returns `false` if
+    /// `memory` doesn't hold a valid region, in which case it's formatted, like `grow` does, and
+    /// all its memory blocks are free.
+    ///
+    /// # Safety
+    ///
+    /// `memory` must not have been handed to another pool, or used in any other way, since it was
+    /// last formatted, and `T` must be valid for any bytes a reset may have left in a memory block
+    /// (e.g. byte arrays)
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the pool already manages a region
+    pub unsafe fn recover<'a, F>(&'a self, memory: &'static mut [MaybeUninit<u8>], mut f: F) -> bool
+    where
+        F: FnMut(PersistentBox<'a, T>),
+    {
+        let layout = match Layout::new(memory) {
+            Some(layout) => layout,
+            None => return false,
+        };
+
+        // NOTE volatile reads because the compiler considers the region uninitialized
+        let header = ptr::read_volatile(layout.header);
+        let valid = header.magic == MAGIC
+            && header.len == layout.len
+            && header.node_size == mem::size_of::<Node<T>>()
+            && header.checksum == header.checksum(layout.header)
+            && (0..layout.len).all(|index| {
+                let state = ptr::read_volatile(layout.states.add(index) as *const u8);
+                state == FREE || state == USED
+            });
+
+        if !valid {
+            self.format(layout);
+
+            return false;
+        }
+
+        self.set_region(layout.header);
+
+        for index in 0..layout.len {
+            let node = layout.node(index);
+
+            if layout.state(index).load(Ordering::Relaxed) == USED {
+                self.pool.adopt(node);
+
+                f(PersistentBox { pool: self, node });
+            } else {
+                self.pool.manage(node);
+            }
+        }
+
+        true
+    }
+
+    // Writes a new header, marks all the memory blocks as free and gives them to the pool
+    fn format(&self, layout: Layout<T>) -> usize {
+        unsafe {
+            for index in 0..layout.len {
+                layout.states.add(index).write(AtomicU8::new(FREE));
+            }
+
+            // NOTE the checksum goes last so a reset halfway through leaves an invalid header
+            let header = layout.header;
+            header.write(Header {
+                magic: MAGIC,
+                len: layout.len,
+                node_size: mem::size_of::<Node<T>>(),
+                checksum: 0,
+            });
+            atomic::compiler_fence(Ordering::SeqCst);
+            (*header).checksum = (*header).checksum(header);
+        }
+
+        self.set_region(layout.header);
+
+        for index in 0..layout.len {
+            self.pool.manage(unsafe { layout.node(index) });
+        }
+
+        layout.len
+    }
+
+    fn set_region(&self, header: *mut Header) {
+        assert!(
+            self.header
+                .compare_exchange(
+                    ptr::null_mut(),
+                    header,
+                    Ordering::Release,
+                    Ordering::Relaxed
+                )
+                .is_ok(),
+            "the pool already manages a region"
+        );
+    }
+
+    fn state(&self, node: NonNull<Node<T>>) -> &AtomicU8 {
+        // NOTE(unsafe) the node belongs to the region, which was set before the pool handed out
+        // any memory block
+        unsafe {
+            let layout = Layout::<T>::from_header(self.header.load(Ordering::Acquire));
+            let index =
+                (node.as_ptr() as usize - layout.nodes as usize) / mem::size_of::<Node<T>>();
+
+            &*layout.states.add(index)
+        }
+    }
+
+    /// Claims a memory block from the pool and moves `val` into it
+    ///
+    /// Returns `val` back when the pool is observed as exhausted
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_init(&self, val: T) -> Result<PersistentBox<'_, T>, T> {
+        match self.pool.alloc() {
+            Some(block) => {
+                let node = block.init(val).into_node();
+                self.state(node).store(USED, Ordering::Release);
+
+                Ok(PersistentBox { pool: self, node })
+            }
+            None => Err(val),
+        }
+    }
+}
+
+impl<T> Default for PersistentPool<T> {
+    fn default() -> Self {
+        PersistentPool::new()
+    }
+}
+
+impl<T> fmt::Debug for PersistentPool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PersistentPool")
+            .field("region", &self.header.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// A value stored in a memory block of a `PersistentPool`
+///
+/// Dropping this box drops the value and returns the memory block to its pool
+pub struct PersistentBox<'a, T> {
+    pool: &'a PersistentPool<T>,
+    node: NonNull<Node<T>>,
+}
+
+impl<T> PersistentBox<'_, T> {
+    /// Moves the value out of the memory block and returns the memory block to its pool
+    pub fn into_inner(self) -> T {
+        let val = unsafe { self.node.as_ref().data.get().read() };
+        self.free();
+        mem::forget(self);
+        val
+    }
+
+    fn free(&self) {
+        self.pool.state(self.node).store(FREE, Ordering::Release);
+        self.pool.pool.release(self.node)
+    }
+}
+
+// NOTE(unsafe) the box owns its value
+unsafe impl<T> Send for PersistentBox<'_, T> where T: Send {}
+
+unsafe impl<T> Sync for PersistentBox<'_, T> where T: Sync {}
+
+impl<T> Deref for PersistentBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.node.as_ref().data.get() }
+    }
+}
+
+impl<T> DerefMut for PersistentBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.node.as_ref().data.get() }
+    }
+}
+
+impl<T> Drop for PersistentBox<'_, T> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.node.as_ref().data.get()) }
+
+        self.free()
+    }
+}
+
+impl<T> fmt::Debug for PersistentBox<'_, T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <T as fmt::Debug>::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem::{self, MaybeUninit};
+
+    use super::PersistentPool;
+    use crate::{tests::RESERVED, Node};
+
+    #[test]
+    fn recover() {
+        const SIZE: usize = 128 + (3 + RESERVED) * mem::size_of::<Node<u32>>();
+
+        // each pool stands for the pool as seen by the program before and after a reset
+        static A: PersistentPool<u32> = PersistentPool::new();
+        static B: PersistentPool<u32> = PersistentPool::new();
+        static C: PersistentPool<u32> = PersistentPool::new();
+
+        let memory: &'static mut [MaybeUninit<u8>] =
+            Box::leak(Box::new([MaybeUninit::new(0); SIZE]));
+        let p = memory as *mut [MaybeUninit<u8>];
+
+        let len = A.grow(unsafe { &mut *p });
+        assert!(len >= 3);
+
+        let x = A.alloc_init(1).ok().unwrap();
+        let y = A.alloc_init(2).ok().unwrap();
+        assert_eq!(y.into_inner(), 2);
+        mem::forget(x);
+
+        // reset
+        let mut recovered = vec![];
+        assert!(unsafe { B.recover(&mut *p, |x| recovered.push(x)) });
+        assert_eq!(recovered.iter().map(|x| **x).collect::<Vec<_>>(), [1]);
+
+        let mut boxes = vec![];
+        while let Ok(x) = B.alloc_init(3) {
+            boxes.push(x);
+        }
+        assert_eq!(boxes.len(), len - 1 - RESERVED);
+        drop(boxes);
+        drop(recovered);
+
+        // a corrupted region is discarded
+        let memory = unsafe { &mut *p };
+        for byte in &mut memory[..2 * mem::size_of::<usize>()] {
+            *byte = MaybeUninit::new(0xff);
+        }
+        assert!(!unsafe { C.recover(&mut *p, |_| panic!()) });
+        let mut n = 0;
+        while let Ok(x) = C.alloc_init(4) {
+            mem::forget(x);
+            n += 1;
+        }
+        assert_eq!(n, len - RESERVED);
+    }
+}
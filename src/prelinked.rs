@@ -0,0 +1,69 @@
+//! Free lists built at compile time; see `static_pool!`
+
+#[cfg(feature = "track-callers")]
+use core::sync::atomic::AtomicPtr;
+#[cfg(feature = "handles")]
+use core::sync::atomic::AtomicUsize;
+use core::{cell::UnsafeCell, mem::MaybeUninit, ptr};
+
+use crate::Node;
+#[cfg(feature = "poison")]
+use crate::{Pool, POISON};
+
+/// Statically allocated memory that's modified at runtime but that, unlike a `static mut`, can be
+/// referred to from the initializer of other statics
+///
+/// Implementation detail of `static_pool!`
+#[doc(hidden)]
+#[repr(transparent)]
+pub struct Memory<A>(UnsafeCell<A>);
+
+// NOTE(unsafe) only the pool that owns the memory accesses it
+unsafe impl<A> Sync for Memory<A> {}
+
+impl<A> Memory<A> {
+    #[doc(hidden)]
+    pub const fn new(array: A) -> Self {
+        Memory(UnsafeCell::new(array))
+    }
+
+    #[doc(hidden)]
+    pub const fn get(&self) -> *mut A {
+        self.0.get()
+    }
+}
+
+/// Creates a free memory block that links to `next`
+///
+/// Implementation detail of `static_pool!`
+// NOTE `next` is stored in the node, not dereferenced
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[doc(hidden)]
+pub const fn node<T>(next: *mut Node<T>) -> MaybeUninit<Node<T>> {
+    let mut node = MaybeUninit::<Node<T>>::uninit();
+    let p = node.as_mut_ptr();
+
+    // NOTE(unsafe) all the fields, other than `data`, are written before the node is handed out
+    unsafe {
+        ptr::addr_of_mut!((*p).next).write(next);
+
+        #[cfg(feature = "track-callers")]
+        {
+            ptr::addr_of_mut!((*p).location).write(AtomicPtr::new(ptr::null_mut()));
+            // the list of managed nodes follows the free list
+            ptr::addr_of_mut!((*p).managed_next).write(next);
+        }
+
+        #[cfg(feature = "handles")]
+        ptr::addr_of_mut!((*p).generation).write(AtomicUsize::new(0));
+
+        #[cfg(feature = "poison")]
+        {
+            let (offset, len) = Pool::<T>::poison_range();
+            let data = ptr::addr_of_mut!((*p).data) as *mut u8;
+            ptr::write_bytes(data.add(offset), POISON, len);
+        }
+    }
+
+    node
+}
@@ -0,0 +1,83 @@
+//! Quarantine for freed memory blocks
+
+#[cfg(feature = "mpu")]
+use core::mem;
+use core::{
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
+};
+
+#[cfg(feature = "mpu")]
+use crate::mpu;
+use crate::Node;
+
+/// Maximum number of memory blocks a quarantine can hold
+pub(crate) const CAPACITY: usize = 16;
+
+// A ring of recently freed memory blocks. Pushing a block into a full ring evicts the oldest one
+pub(crate) struct Quarantine<T> {
+    slots: [AtomicPtr<Node<T>>; CAPACITY],
+    index: AtomicUsize,
+    depth: AtomicUsize,
+}
+
+impl<T> Quarantine<T> {
+    // only used to initialize `slots`
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: AtomicPtr<Node<T>> = AtomicPtr::new(ptr::null_mut());
+
+    pub(crate) const fn new() -> Self {
+        Quarantine {
+            slots: [Self::EMPTY; CAPACITY],
+            index: AtomicUsize::new(0),
+            depth: AtomicUsize::new(CAPACITY),
+        }
+    }
+
+    // Quarantines `node`; returns the memory block that has been in quarantine the longest if the
+    // ring is full
+    pub(crate) fn push(&self, node: NonNull<Node<T>>) -> Option<NonNull<Node<T>>> {
+        let depth = self.depth.load(Ordering::Relaxed);
+        if depth == 0 {
+            return Some(node);
+        }
+
+        // NOTE protect the memory block before it's visible to `drain`, which unprotects it
+        #[cfg(feature = "mpu")]
+        mpu::protect(Self::addr(node), mem::size_of::<T>());
+
+        let i = self.index.fetch_add(1, Ordering::Relaxed) % depth;
+        let evicted = NonNull::new(self.slots[i].swap(node.as_ptr(), Ordering::Relaxed));
+
+        #[cfg(feature = "mpu")]
+        if let Some(evicted) = evicted {
+            mpu::unprotect(Self::addr(evicted));
+        }
+
+        evicted
+    }
+
+    // Empties the quarantine passing each memory block to `f`
+    pub(crate) fn drain(&self, mut f: impl FnMut(NonNull<Node<T>>)) {
+        for slot in self.slots.iter() {
+            if let Some(node) = NonNull::new(slot.swap(ptr::null_mut(), Ordering::Relaxed)) {
+                #[cfg(feature = "mpu")]
+                mpu::unprotect(Self::addr(node));
+
+                f(node)
+            }
+        }
+    }
+
+    // Address of the value stored in `node`
+    #[cfg(feature = "mpu")]
+    fn addr(node: NonNull<Node<T>>) -> usize {
+        unsafe { node.as_ref().data.get() as usize }
+    }
+
+    pub(crate) fn set_depth(&self, depth: usize, f: impl FnMut(NonNull<Node<T>>)) {
+        self.depth.store(0, Ordering::Relaxed);
+        self.drain(f);
+        self.depth.store(depth.min(CAPACITY), Ordering::Relaxed);
+    }
+}
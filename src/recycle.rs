@@ -0,0 +1,246 @@
+//! Pools of objects that are kept constructed between uses
+//!
+//! A `RecyclePool` constructs an object in each of its memory blocks when it's grown. Claiming a
+//! memory block hands out that object as is and freeing it calls `Reset::reset` instead of
+//! dropping the object, so expensive constructors, e.g. the ones of parser state machines or
+//! codecs with large internal buffers, only run once per memory block.
+//!
+//! ``` ignore
+//! use lifo::recycle::{RecyclePool, Reset};
+//!
+//! static PARSERS: RecyclePool<Parser> = RecyclePool::new();
+//!
+//! impl Reset for Parser {
+//!     fn reset(&mut self) {
+//!         self.state = State::Idle;
+//!         self.len = 0;
+//!     }
+//! }
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     static mut MEMORY: [u8; 4096] = [0; 4096];
+//!
+//!     PARSERS.grow(MEMORY, Parser::new);
+//!
+//!     let mut parser = PARSERS.alloc().unwrap();
+//!     parser.feed(b"..");
+//!
+//!     // `parser` is reset, not dropped, and its memory block is returned to the pool
+//!     drop(parser);
+//!
+//!     // ..
+//! }
+//! ```
+//!
+//! Free objects stay in their memory blocks so a `RecyclePool` doesn't poison, quarantine or
+//! track its memory blocks, even when the corresponding Cargo features are enabled.
+
+use core::{
+    fmt,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
+
+use crate::{Node, Pool};
+
+/// Objects that can be returned to their initial state
+pub trait Reset {
+    /// Returns this object to its initial state
+    ///
+    /// Called when the object is returned to its `RecyclePool`
+    fn reset(&mut self);
+}
+
+/// A lock-free pool whose free memory blocks hold constructed objects
+pub struct RecyclePool<T> {
+    // NOTE only the free list of this pool is used
+    inner: Pool<T>,
+    // unlike the ones of `Pool`, free memory blocks hold `T` values; opt out of the `Send` and
+    // `Sync` implementations of `Pool` which don't care about `T`
+    _not_send_or_sync: PhantomData<*const ()>,
+}
+
+// NOTE(unsafe) the objects in the free memory blocks are handed to whichever context calls `alloc`
+// so `T` must be `Send`. `Pool` is only `Sync` on some targets; see its `Sync` implementation
+#[cfg(any(armv7m, test, feature = "x86-sync-hazard"))]
+unsafe impl<T> Sync for RecyclePool<T> where T: Send {}
+
+unsafe impl<T> Send for RecyclePool<T> where T: Send {}
+
+impl<T> RecyclePool<T> {
+    /// Creates a new empty pool
+    pub const fn new() -> Self {
+        RecyclePool {
+            inner: Pool::new(),
+            _not_send_or_sync: PhantomData,
+        }
+    }
+}
+
+impl<T> RecyclePool<T>
+where
+    T: Reset,
+{
+    /// Claims an object from the pool
+    ///
+    /// Returns `None` when the pool is observed as exhausted
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    pub fn alloc(&'static self) -> Option<Recycled<T>> {
+        self.inner.pop().map(|node| Recycled { pool: self, node })
+    }
+
+    /// Increases the capacity of the pool, constructing an object with `f` in each new memory
+    /// block
+    ///
+    /// This method might *not* fully utilize the given memory block due to alignment requirements
+    pub fn grow<F>(&self, memory: &'static mut [u8], f: F)
+    where
+        F: FnMut() -> T,
+    {
+        // NOTE(unsafe) see `Pool::grow`
+        self.grow_uninit(
+            unsafe { &mut *(memory as *mut [u8] as *mut [MaybeUninit<u8>]) },
+            f,
+        )
+    }
+
+    /// Increases the capacity of the pool using memory that has not been initialized,
+    /// constructing an object with `f` in each new memory block
+    ///
+    /// This method might *not* fully utilize the given memory block due to alignment requirements
+    pub fn grow_uninit<F>(&self, memory: &'static mut [MaybeUninit<u8>], mut f: F)
+    where
+        F: FnMut() -> T,
+    {
+        Pool::<T>::split(memory, |node| {
+            unsafe { node.as_ref().data.get().write(f()) }
+
+            self.inner.push(node)
+        });
+    }
+}
+
+impl<T> Default for RecyclePool<T> {
+    fn default() -> Self {
+        RecyclePool::new()
+    }
+}
+
+/// An object claimed from a `RecyclePool`
+///
+/// Dropping this handle resets the object and returns it to its pool
+pub struct Recycled<T>
+where
+    T: Reset + 'static,
+{
+    pool: &'static RecyclePool<T>,
+    node: NonNull<Node<T>>,
+}
+
+impl<T> Deref for Recycled<T>
+where
+    T: Reset,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.node.as_ref().data.get() }
+    }
+}
+
+impl<T> DerefMut for Recycled<T>
+where
+    T: Reset,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.node.as_ref().data.get() }
+    }
+}
+
+impl<T> Drop for Recycled<T>
+where
+    T: Reset,
+{
+    fn drop(&mut self) {
+        self.reset();
+
+        self.pool.inner.push(self.node)
+    }
+}
+
+unsafe impl<T> Send for Recycled<T> where T: Reset + Send {}
+
+unsafe impl<T> Sync for Recycled<T> where T: Reset + Sync {}
+
+impl<T> fmt::Debug for Recycled<T>
+where
+    T: Reset + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <T as fmt::Debug>::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::{RecyclePool, Reset};
+    use crate::tests::{memory, RESERVED};
+
+    #[test]
+    fn recycle() {
+        static NEW: AtomicUsize = AtomicUsize::new(0);
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+        struct Parser {
+            len: usize,
+        }
+
+        impl Reset for Parser {
+            fn reset(&mut self) {
+                self.len = 0;
+            }
+        }
+
+        impl Drop for Parser {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        static PARSERS: RecyclePool<Parser> = RecyclePool::new();
+
+        PARSERS.grow(memory::<Parser>(1), || {
+            NEW.fetch_add(1, Ordering::Relaxed);
+            Parser { len: 0 }
+        });
+        assert_eq!(NEW.load(Ordering::Relaxed), 1 + RESERVED);
+
+        let mut x = PARSERS.alloc().unwrap();
+        assert!(PARSERS.alloc().is_none());
+        x.len = 3;
+        drop(x);
+
+        // the object was reset, not dropped
+        let x = PARSERS.alloc().unwrap();
+        assert_eq!(x.len, 0);
+        assert_eq!(NEW.load(Ordering::Relaxed), 1 + RESERVED);
+        assert_eq!(DROPS.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn send_sync() {
+        fn is_send_sync<T>()
+        where
+            T: Send + Sync,
+        {
+        }
+
+        is_send_sync::<RecyclePool<[u8; 4]>>();
+    }
+}
@@ -0,0 +1,218 @@
+//! RTIC integration
+//!
+//! Singleton pools are global so they don't need to be declared as RTIC resources. What RTIC apps
+//! need is a guarantee that a pool has been grown before any task tries to claim memory blocks
+//! from it. `Initialized` provides that guarantee at compile time: it can only be created from a
+//! critical section token, like the `cs` field of the `init::Context`, so it's created while
+//! interrupts are still disabled, and tasks can only claim memory blocks through it.
+//!
+//! # RTIC 1.x
+//!
+//! ``` ignore
+//! #[rtic::app(device = lm3s6965)]
+//! mod app {
+//!     use lifo::{pool, rtic::Initialized};
+//!
+//!     pool!(A: [u8; 128]);
+//!
+//!     #[shared]
+//!     struct Shared {}
+//!
+//!     #[local]
+//!     struct Local {
+//!         a: Initialized<A>,
+//!     }
+//!
+//!     #[init(local = [memory: [u8; 1024] = [0; 1024]])]
+//!     fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+//!         // `cx.cs` is a `bare_metal::CriticalSection`
+//!         let a = Initialized::<A>::grow(cx.cs, cx.local.memory);
+//!
+//!         (Shared {}, Local { a }, init::Monotonics())
+//!     }
+//!
+//!     #[task(binds = UART0, local = [a])]
+//!     fn uart0(cx: uart0::Context) {
+//!         let buffer = cx.local.a.alloc().unwrap().init([0; 128]);
+//!
+//!         // ..
+//!     }
+//! }
+//! ```
+//!
+//! # RTIC 2.x
+//!
+//! ``` ignore
+//! #[rtic::app(device = lm3s6965)]
+//! mod app {
+//!     use lifo::{pool, rtic::Initialized};
+//!
+//!     // `capacity` declares a static buffer; `A::init` grows the pool with it
+//!     pool!(A: [u8; 128]; capacity = 8);
+//!
+//!     #[shared]
+//!     struct Shared {
+//!         a: Initialized<A>,
+//!     }
+//!
+//!     #[local]
+//!     struct Local {}
+//!
+//!     #[init]
+//!     fn init(cx: init::Context) -> (Shared, Local) {
+//!         // `cx.cs` is a `critical_section::CriticalSection`
+//!         let a = Initialized::<A>::init_with(cx.cs, A::init);
+//!
+//!         (Shared { a }, Local {})
+//!     }
+//!
+//!     #[task(binds = UART0, shared = [a])]
+//!     fn uart0(mut cx: uart0::Context) {
+//!         // `Initialized` is `Copy`; locking is only needed to read it
+//!         let a = cx.shared.a.lock(|a| *a);
+//!         let buffer = a.alloc().unwrap().init([0; 128]);
+//!
+//!         // ..
+//!     }
+//! }
+//! ```
+
+use core::{fmt, marker::PhantomData, mem::MaybeUninit};
+
+use crate::{
+    singleton::{Box, Pool},
+    Uninit,
+};
+
+/// Proof that interrupts are disabled
+///
+/// Implemented for the critical section tokens that RTIC hands to `init`:
+/// `bare_metal::CriticalSection` (RTIC 1.x) and `critical_section::CriticalSection` (RTIC 2.x)
+pub trait CriticalSection: sealed::Sealed {}
+
+impl<'cs> CriticalSection for bare_metal::CriticalSection<'cs> {}
+
+impl<'cs> CriticalSection for critical_section::CriticalSection<'cs> {}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl<'cs> Sealed for bare_metal::CriticalSection<'cs> {}
+
+    impl<'cs> Sealed for critical_section::CriticalSection<'cs> {}
+}
+
+/// Proof that the singleton pool `POOL` has been grown during initialization
+///
+/// See the module documentation for examples
+pub struct Initialized<POOL>
+where
+    POOL: Pool,
+{
+    _pool: PhantomData<POOL>,
+}
+
+impl<P> Initialized<P>
+where
+    P: Pool,
+{
+    /// Grows the pool with the given memory
+    ///
+    /// See `Pool::grow`
+    pub fn grow<CS>(_cs: CS, memory: &'static mut [u8]) -> Self
+    where
+        CS: CriticalSection,
+    {
+        P::grow(memory);
+
+        Initialized { _pool: PhantomData }
+    }
+
+    /// Grows the pool with the given, possibly uninitialized, memory
+    ///
+    /// See `Pool::grow_uninit`
+    pub fn grow_uninit<CS>(_cs: CS, memory: &'static mut [MaybeUninit<u8>]) -> Self
+    where
+        CS: CriticalSection,
+    {
+        P::grow_uninit(memory);
+
+        Initialized { _pool: PhantomData }
+    }
+
+    /// Grows the pool using `f`, e.g. the `init` function declared by the `capacity` option of
+    /// `pool!`
+    pub fn init_with<CS, F>(_cs: CS, f: F) -> Self
+    where
+        CS: CriticalSection,
+        F: FnOnce(),
+    {
+        f();
+
+        Initialized { _pool: PhantomData }
+    }
+
+    /// Claims a memory block from the pool
+    ///
+    /// See `Pool::alloc`
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc(&self) -> Option<Box<P, Uninit>> {
+        P::alloc()
+    }
+
+    /// Claims a memory block from the pool and initializes it with `val`
+    ///
+    /// See `Pool::alloc_init`
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_init(&self, val: P::Data) -> Result<Box<P>, P::Data> {
+        P::alloc_init(val)
+    }
+}
+
+impl<P> Clone for Initialized<P>
+where
+    P: Pool,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<P> Copy for Initialized<P> where P: Pool {}
+
+// NOTE(unsafe) `Initialized` is a zero-sized token and the pool is interrupt-safe
+unsafe impl<P> Send for Initialized<P> where P: Pool {}
+
+unsafe impl<P> Sync for Initialized<P> where P: Pool {}
+
+impl<P> fmt::Debug for Initialized<P>
+where
+    P: Pool,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Initialized")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Initialized;
+    use crate::tests::memory;
+
+    #[test]
+    fn initialized() {
+        crate::pool!(A: u8, B: u8; capacity = 1);
+
+        let cs = unsafe { critical_section::CriticalSection::new() };
+        let a = Initialized::<A>::grow(cs, memory::<u8>(1));
+
+        let cs = unsafe { bare_metal::CriticalSection::new() };
+        let b = Initialized::<B>::init_with(cs, B::init);
+
+        let x = a.alloc_init(1).unwrap();
+        assert_eq!(a.alloc_init(2).err(), Some(2));
+        assert_eq!(*x, 1);
+
+        assert!(b.alloc().is_some());
+    }
+}
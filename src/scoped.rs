@@ -0,0 +1,180 @@
+//! Pools backed by memory that is not `'static`
+//!
+//! `Pool::grow` requires `'static` memory because the boxes handed out by a `Pool` can live for as
+//! long as the program runs. `scope` lifts that requirement for pools that are only needed for a
+//! bounded amount of time: it creates a pool backed by borrowed memory, e.g. a stack buffer, and
+//! hands it to a closure. The boxes claimed from the pool can't escape the closure so the memory
+//! can be reused once `scope` returns. This is mainly useful in unit tests and in subsystems that
+//! run for a bounded amount of time, as it removes the need for `static mut` buffers.
+//!
+//! ```
+//! use lifo::scoped;
+//!
+//! let mut memory = [0; 256];
+//!
+//! let sum = scoped::scope(&mut memory, |pool| {
+//!     let a = pool.alloc_init(1).ok().unwrap();
+//!     let b = pool.alloc_init(2).ok().unwrap();
+//!
+//!     *a + *b
+//! });
+//!
+//! assert_eq!(sum, 3);
+//! ```
+
+use core::{
+    fmt,
+    marker::PhantomData,
+    mem::{self, MaybeUninit},
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+};
+
+use crate::{Node, Pool};
+
+/// Creates a pool of `T` values backed by `memory` and passes it to `f`
+///
+/// The pool, and all the boxes claimed from it, are gone by the time this function returns
+pub fn scope<'env, T, F, R>(memory: &'env mut [u8], f: F) -> R
+where
+    F: for<'scope> FnOnce(&'scope ScopedPool<'scope, 'env, T>) -> R,
+{
+    let pool = ScopedPool {
+        _scope: PhantomData,
+        _env: PhantomData,
+        pool: Pool::new(),
+    };
+    pool.grow(memory);
+
+    f(&pool)
+}
+
+/// A pool of `T` values backed by memory that outlives `'env`
+///
+/// See `scope`
+pub struct ScopedPool<'scope, 'env: 'scope, T> {
+    // NOTE invariant lifetimes so they can't be shortened or extended
+    _scope: PhantomData<&'scope mut &'scope ()>,
+    _env: PhantomData<&'env mut &'env ()>,
+    pool: Pool<T>,
+}
+
+impl<'scope, 'env, T> ScopedPool<'scope, 'env, T> {
+    /// Claims a memory block from the pool and moves `val` into it
+    ///
+    /// Returns `val` back when the pool is observed as exhausted
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_init(&'scope self, val: T) -> Result<ScopedBox<'scope, T>, T> {
+        match self.pool.alloc() {
+            Some(block) => Ok(ScopedBox {
+                pool: &self.pool,
+                node: block.init(val).into_node(),
+            }),
+            None => Err(val),
+        }
+    }
+
+    /// Increases the capacity of the pool
+    ///
+    /// This method might *not* fully utilize the given memory block due to alignment requirements
+    pub fn grow(&self, memory: &'env mut [u8]) {
+        // NOTE(unsafe) the boxes claimed from the pool can't outlive `'scope`, which ends before
+        // `'env` does, so the memory is not accessed after it's been given back to the caller. See
+        // `Pool::grow` for the cast.
+        self.pool
+            .grow_uninit(unsafe { &mut *(memory as *mut [u8] as *mut [MaybeUninit<u8>]) })
+    }
+}
+
+impl<T> fmt::Debug for ScopedPool<'_, '_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ScopedPool").finish()
+    }
+}
+
+/// A value stored in a memory block of a `ScopedPool`
+///
+/// Dropping this box drops the value and returns the memory block to its pool
+pub struct ScopedBox<'scope, T> {
+    pool: &'scope Pool<T>,
+    node: NonNull<Node<T>>,
+}
+
+impl<T> ScopedBox<'_, T> {
+    /// Moves the value out of the memory block and returns the memory block to its pool
+    pub fn into_inner(self) -> T {
+        let val = unsafe { self.node.as_ref().data.get().read() };
+        self.pool.release(self.node);
+        mem::forget(self);
+        val
+    }
+}
+
+// NOTE(unsafe) the box owns its value
+unsafe impl<T> Send for ScopedBox<'_, T> where T: Send {}
+
+unsafe impl<T> Sync for ScopedBox<'_, T> where T: Sync {}
+
+impl<T> Deref for ScopedBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.node.as_ref().data.get() }
+    }
+}
+
+impl<T> DerefMut for ScopedBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.node.as_ref().data.get() }
+    }
+}
+
+impl<T> Drop for ScopedBox<'_, T> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.node.as_ref().data.get()) }
+
+        self.pool.release(self.node)
+    }
+}
+
+impl<T> fmt::Debug for ScopedBox<'_, T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <T as fmt::Debug>::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem;
+
+    use super::scope;
+    use crate::{tests::RESERVED, Node};
+
+    #[test]
+    fn scoped_pool() {
+        #[repr(align(8))]
+        struct Memory([u8; (2 + RESERVED) * mem::size_of::<Node<u32>>()]);
+
+        let mut memory = Memory([0; (2 + RESERVED) * mem::size_of::<Node<u32>>()]);
+
+        // the memory can be used again once the scope is over
+        for _ in 0..2 {
+            scope(&mut memory.0, |pool| {
+                let mut a = pool.alloc_init(1).unwrap();
+                let b = pool.alloc_init(2).unwrap();
+                assert_eq!(pool.alloc_init(3).err(), Some(3));
+
+                *a += 10;
+                assert_eq!((*a, *b), (11, 2));
+
+                assert_eq!(b.into_inner(), 2);
+                assert_eq!(*pool.alloc_init(4u32).unwrap(), 4);
+            })
+        }
+    }
+}
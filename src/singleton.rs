@@ -1,29 +1,119 @@
 //! `Pool` as a global singleton
 
-#[cfg(feature = "maybe-uninit")]
-use core::mem::MaybeUninit;
+#[cfg(any(feature = "registry", feature = "track-callers"))]
+use core::panic::Location;
 use core::{
-    any::TypeId,
+    any::{self, Any, TypeId},
+    borrow::{Borrow, BorrowMut},
+    cmp, fmt,
+    hash::{Hash, Hasher},
     marker::PhantomData,
-    mem,
+    mem::{self, ManuallyDrop, MaybeUninit},
     ops::{Deref, DerefMut},
-    ptr,
+    pin::Pin,
+    ptr::{self, NonNull},
 };
 
 use as_slice::{AsMutSlice, AsSlice};
 
-#[cfg(feature = "maybe-uninit")]
-use crate::Node;
-use crate::{Init, Uninit};
+use crate::{ByteArray, Init, Node, Uninit};
+
+pub use self::class::{ClassBox, ClassPool, Classes};
+pub use self::dynamic::{AnyBox, AnyPool};
+#[cfg(feature = "global-alloc")]
+pub use self::global::PoolAlloc;
+#[cfg(feature = "handles")]
+pub use self::handle::Handle;
+pub use self::job::Job;
+pub use self::payload::Payload;
+pub use self::ping_pong::PingPong;
+pub use self::waker::AllocFuture;
+#[doc(hidden)]
+pub use self::waker::WakerSlot;
+
+pub mod arc;
+#[cfg(feature = "broadcast")]
+pub mod broadcast;
+#[cfg(feature = "collections")]
+pub mod chain;
+mod class;
+mod dynamic;
+#[cfg(feature = "global-alloc")]
+mod global;
+#[cfg(feature = "handles")]
+mod handle;
+mod job;
+#[cfg(feature = "mailbox")]
+pub mod mailbox;
+mod payload;
+#[cfg(feature = "smoltcp")]
+pub mod phy;
+mod ping_pong;
+pub mod rc;
+#[cfg(feature = "registry")]
+pub mod registry;
+#[cfg(feature = "dma-ring")]
+pub mod ring;
+#[cfg(feature = "collections")]
+pub mod string;
+#[cfg(feature = "task-pool")]
+pub mod task;
+#[cfg(feature = "usb-device")]
+pub mod usb;
+#[cfg(feature = "collections")]
+pub mod vec;
+mod waker;
+#[cfg(feature = "work-queue")]
+pub mod work;
 
 /// Instantiates a pool as a global singleton
-#[cfg(any(armv7m, test))]
+///
+/// The pool declaration accepts options after the data type:
+///
+/// - `pool!(A: [u8; 128]; capacity = 16)` also declares a static buffer big enough to hold 16
+///   memory blocks and an `A::init()` function that grows the pool with it
+/// - `pool!(A: [u8; 128]; capacity = 16; link_section = ".ccmram")` places the static buffer
+///   declared by the `capacity` option in the given linker section, e.g. CCM or DTCM RAM. The
+///   section must not be initialized by the runtime as the buffer starts uninitialized
+/// - `pool!(A: [u8; 128]; wakers = 4)` embeds a table of 4 waker slots so up to 4 tasks can wait
+///   on `A::alloc_async()` at the same time
+///
+/// Options can be combined, e.g. `pool!(A: [u8; 128]; capacity = 16; wakers = 4)`.
+///
+/// `pool!(A: [u8; 128] as ABox)` also declares the type alias `ABox<S = Init>` for `Box<A, S>`
+/// so function signatures can use `ABox` and `ABox<Uninit>`. The alias takes the visibility and
+/// the attributes of the pool type.
+///
+/// The pool type takes the given visibility, e.g. `pool!(pub A: [u8; 128])`, and is private if
+/// none is given. Attributes, like doc comments or `#[cfg]`, placed before the visibility are
+/// applied to the pool type and to its trait implementations.
+///
+/// Several pools can be declared in a single invocation by separating them with commas, e.g.
+/// `pool!(RX: [u8; 1514], TX: [u8; 1514]; capacity = 4)`.
+#[cfg(any(armv7m, test, feature = "x86-sync-hazard"))]
 #[macro_export]
 macro_rules! pool {
-    ($ident:ident: $ty:ty) => {
-        pub struct $ident;
+    ($(
+        $(#[$attr:meta])* $vis:vis $ident:ident: $ty:ty $(as $alias:ident)?
+            $(; $option:ident = $value:expr)*
+    ),+ $(,)?) => {
+        $(
+            $crate::pool!(
+                @pool [$(#[$attr])*] $vis $ident: $ty, [$($alias)?], [$(; $option = $value)*]
+                    $(; $option = $value)*
+            );
+        )+
+    };
 
-        impl $crate::singleton::Pool for $ident {
+    (
+        @pool $attrs:tt $vis:vis $ident:ident: $ty:ty, $alias:tt, $options:tt
+            $(; $option:ident = $value:expr)*
+    ) => {
+        $crate::pool!(@attrs $attrs $vis struct $ident;);
+
+        $crate::pool!(@alias $attrs $vis $ident $alias);
+
+        $crate::pool!(@attrs $attrs impl $crate::singleton::Pool for $ident {
             type Data = $ty;
 
             fn ptr() -> &'static $crate::Pool<$ty> {
@@ -31,6 +121,465 @@ macro_rules! pool {
 
                 &POOL
             }
+
+            fn wakers() -> &'static [$crate::singleton::WakerSlot] {
+                $crate::pool!(@wakers $(; $option = $value)*)
+            }
+
+            $crate::__pool_registry_entry!($ident: $ty);
+        });
+
+        $(
+            $crate::pool!(@option $attrs $ident: $ty; $option = $value; $options);
+        )*
+    };
+
+    (@attrs [$(#[$attr:meta])*] $($item:tt)*) => {
+        $(#[$attr])*
+        $($item)*
+    };
+
+    (@alias $attrs:tt $vis:vis $ident:ident []) => {};
+
+    (@alias $attrs:tt $vis:vis $ident:ident [$alias:ident]) => {
+        $crate::pool!(
+            @attrs $attrs
+            $vis type $alias<S = $crate::Init> = $crate::singleton::Box<$ident, S>;
+        );
+    };
+
+    (
+        @option $attrs:tt $ident:ident: $ty:ty; capacity = $capacity:expr;
+            [$($options:tt)*]
+    ) => {
+        $crate::pool!(@attrs $attrs impl $ident {
+            /// Grows the pool with its statically allocated memory
+            ///
+            /// Only the first call has an effect
+            #[allow(unknown_lints, static_mut_refs)]
+            pub fn init() {
+                use core::{
+                    mem::MaybeUninit,
+                    sync::atomic::{AtomicBool, Ordering},
+                };
+
+                static ONCE: AtomicBool = AtomicBool::new(false);
+                $crate::pool!(@memory $ty, $capacity $($options)*);
+
+                if !ONCE.swap(true, Ordering::AcqRel) {
+                    // NOTE(unsafe) `ONCE` guarantees this is the only reference to `MEMORY`
+                    <$ident as $crate::singleton::Pool>::grow_uninit(unsafe { &mut MEMORY })
+                }
+            }
+        });
+    };
+
+    // NOTE handled by `@memory`
+    (
+        @option $attrs:tt $ident:ident: $ty:ty; link_section = $section:expr;
+            [$($options:tt)*]
+    ) => {
+        $crate::pool!(@needs_capacity link_section $($options)*);
+    };
+
+    // NOTE handled by `@wakers`
+    (@option $attrs:tt $ident:ident: $ty:ty; wakers = $n:expr; $options:tt) => {};
+
+    (@option $attrs:tt $ident:ident: $ty:ty; $option:ident = $value:expr; $options:tt) => {
+        compile_error!(concat!("unknown `pool!` option: `", stringify!($option), "`"));
+    };
+
+    (@memory $ty:ty, $capacity:expr) => {
+        static mut MEMORY: [MaybeUninit<u8>; $crate::__memory_size::<$ty>($capacity)] =
+            [MaybeUninit::uninit(); $crate::__memory_size::<$ty>($capacity)];
+    };
+
+    (
+        @memory $ty:ty, $capacity:expr; link_section = $section:expr
+            $(; $option:ident = $value:expr)*
+    ) => {
+        #[link_section = $section]
+        static mut MEMORY: [MaybeUninit<u8>; $crate::__memory_size::<$ty>($capacity)] =
+            [MaybeUninit::uninit(); $crate::__memory_size::<$ty>($capacity)];
+    };
+
+    (
+        @memory $ty:ty, $capacity:expr; $other:ident = $ignored:expr
+            $(; $option:ident = $value:expr)*
+    ) => {
+        $crate::pool!(@memory $ty, $capacity $(; $option = $value)*);
+    };
+
+    (@needs_capacity $option:ident) => {
+        compile_error!(concat!(
+            "the `",
+            stringify!($option),
+            "` option of `pool!` requires the `capacity` option"
+        ));
+    };
+
+    (
+        @needs_capacity $option:ident; capacity = $capacity:expr
+            $(; $other:ident = $value:expr)*
+    ) => {};
+
+    (
+        @needs_capacity $option:ident; $other:ident = $ignored:expr
+            $(; $rest:ident = $value:expr)*
+    ) => {
+        $crate::pool!(@needs_capacity $option $(; $rest = $value)*);
+    };
+
+    (@wakers) => {
+        &[]
+    };
+
+    (@wakers; wakers = $n:expr $(; $option:ident = $value:expr)*) => {{
+        #[allow(clippy::declare_interior_mutable_const)]
+        const EMPTY: $crate::singleton::WakerSlot = $crate::singleton::WakerSlot::new();
+        static WAKERS: [$crate::singleton::WakerSlot; $n] = [EMPTY; $n];
+
+        &WAKERS
+    }};
+
+    (@wakers; $other:ident = $ignored:expr $(; $option:ident = $value:expr)*) => {
+        $crate::pool!(@wakers $(; $option = $value)*)
+    };
+}
+
+#[cfg(all(any(armv7m, test, feature = "x86-sync-hazard"), feature = "registry"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pool_registry_entry {
+    ($ident:ident: $ty:ty) => {
+        fn registry_entry() -> &'static $crate::singleton::registry::Entry {
+            static ENTRY: $crate::singleton::registry::Entry =
+                $crate::singleton::registry::Entry::new::<$ty>(
+                    stringify!($ident),
+                    <$ident as $crate::singleton::Pool>::stats,
+                    <$ident as $crate::singleton::Pool>::live_sites,
+                );
+
+            &ENTRY
+        }
+    };
+}
+
+#[cfg(all(
+    any(armv7m, test, feature = "x86-sync-hazard"),
+    not(feature = "registry")
+))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pool_registry_entry {
+    ($ident:ident: $ty:ty) => {};
+}
+
+/// Instantiates a pool as a global singleton whose free list is built at compile time
+///
+/// ``` ignore
+/// static_pool!(pub A: [u8; 128]; capacity = 16);
+///
+/// // no need to `grow` the pool; it's ready before `main` runs
+/// let x = A::alloc().unwrap();
+/// ```
+///
+/// The memory blocks are linked into a free list during const evaluation and the resulting pool
+/// is placed in `.data`, so the pool is fully usable from the very first instruction: there's no
+/// `init` function to call, no `static mut` buffer, and interrupt handlers that run before `main`
+/// can't observe an empty pool. The trade-off is that the memory blocks are stored in the flash
+/// image, as part of the initial value of `.data`, rather than in `.bss`.
+///
+/// The `capacity` option is mandatory. Otherwise `static_pool!` accepts the syntax of `pool!`,
+/// including the `as ABox` alias and the `wakers` option; the `link_section` option is not
+/// supported. The resulting pool can still be grown at runtime with `grow` and friends.
+#[cfg(all(
+    feature = "static-pool",
+    any(armv7m, test, feature = "x86-sync-hazard")
+))]
+#[macro_export]
+macro_rules! static_pool {
+    ($(
+        $(#[$attr:meta])* $vis:vis $ident:ident: $ty:ty $(as $alias:ident)?;
+            capacity = $capacity:expr $(; $option:ident = $value:expr)*
+    ),+ $(,)?) => {
+        $(
+            $crate::pool!(@attrs [$(#[$attr])*] $vis struct $ident;);
+
+            $crate::pool!(@alias [$(#[$attr])*] $vis $ident [$($alias)?]);
+
+            $crate::pool!(@attrs [$(#[$attr])*] impl $crate::singleton::Pool for $ident {
+                type Data = $ty;
+
+                fn ptr() -> &'static $crate::Pool<$ty> {
+                    $crate::static_pool!(@pool $ty, $capacity)
+                }
+
+                fn wakers() -> &'static [$crate::singleton::WakerSlot] {
+                    $crate::pool!(@wakers $(; $option = $value)*)
+                }
+
+                $crate::__pool_registry_entry!($ident: $ty);
+            });
+
+            $(
+                $crate::static_pool!(@option $option);
+            )*
+        )+
+    };
+
+    (@pool $ty:ty, $capacity:expr) => {{
+        use core::mem::MaybeUninit;
+
+        type Node = $crate::Node<$ty>;
+
+        const LEN: usize = $crate::__block_count($capacity);
+        #[allow(clippy::declare_interior_mutable_const)]
+        const UNINIT: MaybeUninit<Node> = MaybeUninit::uninit();
+
+        static MEMORY: $crate::__StaticMemory<[MaybeUninit<Node>; LEN]> =
+            $crate::__StaticMemory::new({
+                let first = MEMORY.get() as *mut Node;
+                let mut nodes = [UNINIT; LEN];
+                let mut i = 0;
+                while i < LEN {
+                    let next = if i + 1 < LEN {
+                        unsafe { first.add(i + 1) }
+                    } else {
+                        core::ptr::null_mut()
+                    };
+                    nodes[i] = $crate::__static_node(next);
+                    i += 1;
+                }
+                nodes
+            });
+
+        static POOL: $crate::Pool<$ty> = unsafe {
+            let first = MEMORY.get() as *mut Node;
+            $crate::Pool::__prelinked(first, first.wrapping_add(LEN.saturating_sub(1)), LEN)
+        };
+
+        &POOL
+    }};
+
+    (@option wakers) => {};
+
+    (@option $option:ident) => {
+        compile_error!(concat!("unsupported `static_pool!` option: `", stringify!($option), "`"));
+    };
+}
+
+/// Grows a set of singleton pools with interrupts disabled
+///
+/// Pools declared with the `capacity` option are grown with their statically allocated memory
+/// (see `init`); other pools are grown with the memory given after `=`.
+///
+/// ``` ignore
+/// pool!(A: [u8; 128]; capacity = 8);
+/// pool!(B: [u8; 512]);
+///
+/// #[entry]
+/// fn main() -> ! {
+///     static mut MEMORY: [u8; 2048] = [0; 2048];
+///
+///     init_pools!(A, B = MEMORY);
+///
+///     // .. enable interrupts ..
+/// }
+/// ```
+#[cfg(all(
+    feature = "cortex-m-rt",
+    any(armv7m, test, feature = "x86-sync-hazard")
+))]
+#[macro_export]
+macro_rules! init_pools {
+    ($($pool:ident $(= $memory:expr)?),+ $(,)?) => {
+        $crate::__cortex_m::interrupt::free(move |_| {
+            $(
+                $crate::init_pools!(@init $pool $(= $memory)?);
+            )+
+        })
+    };
+
+    (@init $pool:ident) => {
+        $pool::init()
+    };
+
+    (@init $pool:ident = $memory:expr) => {
+        <$pool as $crate::singleton::Pool>::grow($memory)
+    };
+}
+
+/// Splits a statically allocated buffer among several singleton pools
+///
+/// ``` ignore
+/// pool!(A: [u8; 128]);
+/// pool!(B: [u32; 16]);
+///
+/// #[entry]
+/// fn main() -> ! {
+///     #[link_section = ".uninit"]
+///     static mut MEMORY: [MaybeUninit<u8>; 4096] = [MaybeUninit::uninit(); 4096];
+///
+///     // `A` gets 16 memory blocks and `B` gets 8
+///     let rest = partition!(MEMORY => A: 16, B: 8);
+/// }
+/// ```
+///
+/// The buffer must be a `&'static mut [MaybeUninit<u8>; N]`. Each pool is grown with a sub-region
+/// that's just large enough to hold the requested number of memory blocks once aligned. Whether
+/// the buffer is large enough for all the pools, whatever its address, is checked at compile time
+/// so the capacities must be constant expressions. The macro evaluates to the part of the buffer
+/// that was not handed to any pool, as a `&'static mut [MaybeUninit<u8>]`.
+#[cfg(all(feature = "partition", any(armv7m, test, feature = "x86-sync-hazard")))]
+#[macro_export]
+macro_rules! partition {
+    ($memory:expr => $($pool:path: $capacity:expr),+ $(,)?) => {{
+        const SIZE: usize = 0 $(
+            + $crate::__memory_size::<<$pool as $crate::singleton::Pool>::Data>($capacity)
+        )+;
+
+        let mut rest = $crate::__Partition::new($memory).check::<SIZE>();
+        $(
+            rest.grow::<$pool>($capacity);
+        )+
+        rest.into_inner()
+    }};
+}
+
+/// Declares a family of pools of byte buffers of increasing size (size classes) and a packet type
+/// that picks the smallest class that fits
+///
+/// ``` ignore
+/// pool_set!(pub Packet {
+///     Small: [u8; 32]; capacity = 16,
+///     Medium: [u8; 128]; capacity = 8,
+///     Large: [u8; 1536]; capacity = 2,
+/// });
+/// ```
+///
+/// Each class is declared using `pool!`, with the same syntax and options. The classes must hold
+/// byte arrays, see `ByteArray`, and must be listed from smallest to largest. `Packet` is an enum
+/// with one variant per class, named after the pool, that holds a `Payload` of that pool.
+/// `Packet::from_slice` and `Packet::with_capacity` claim a memory block from the smallest class
+/// that can hold the requested number of bytes, falling back to larger classes when that pool is
+/// exhausted.
+///
+/// The classes take the visibility given to the packet type. Attributes are only applied to the
+/// packet type.
+#[cfg(any(armv7m, test, feature = "x86-sync-hazard"))]
+#[macro_export]
+macro_rules! pool_set {
+    (
+        $(#[$attr:meta])* $vis:vis $set:ident {
+            $($pool:ident: $ty:ty $(; $option:ident = $value:expr)*),+ $(,)?
+        }
+    ) => {
+        $(
+            $crate::pool!($vis $pool: $ty $(; $option = $value)*);
+        )+
+
+        // the memory blocks are zeroed and handed out as initialized
+        const _: fn() = || {
+            fn byte_array<T: $crate::ByteArray>() {}
+
+            $(
+                byte_array::<$ty>();
+            )+
+        };
+
+        $(#[$attr])*
+        #[derive(Debug)]
+        $vis enum $set {
+            $(
+                #[allow(missing_docs)]
+                $pool($crate::singleton::Payload<$pool>),
+            )+
+        }
+
+        #[allow(dead_code)]
+        impl $set {
+            /// Claims a memory block from the smallest class that can hold `data` and copies
+            /// `data` into it
+            ///
+            /// Larger classes are tried when that class is exhausted
+            pub fn from_slice(data: &[u8]) -> Result<Self, $crate::singleton::FromSliceError> {
+                Self::alloc(data.len(), data)
+            }
+
+            /// Claims an empty packet from the smallest class that can hold `capacity` bytes
+            ///
+            /// Larger classes are tried when that class is exhausted
+            pub fn with_capacity(
+                capacity: usize,
+            ) -> Result<Self, $crate::singleton::FromSliceError> {
+                Self::alloc(capacity, &[])
+            }
+
+            fn alloc(
+                capacity: usize,
+                data: &[u8],
+            ) -> Result<Self, $crate::singleton::FromSliceError> {
+                let mut error = $crate::singleton::FromSliceError::TooLong;
+
+                $(
+                    if capacity <= core::mem::size_of::<$ty>() {
+                        match $crate::singleton::Payload::<$pool>::from_slice(data) {
+                            Ok(payload) => return Ok($set::$pool(payload)),
+                            Err(e) => error = e,
+                        }
+                    }
+                )+
+
+                Err(error)
+            }
+
+            /// Returns the number of valid bytes in the packet
+            pub fn len(&self) -> usize {
+                match self {
+                    $($set::$pool(payload) => payload.len(),)+
+                }
+            }
+
+            /// Returns `true` if the packet contains no bytes
+            pub fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            /// Returns the maximum number of bytes the packet can hold
+            pub fn capacity(&self) -> usize {
+                match self {
+                    $($set::$pool(payload) => payload.capacity(),)+
+                }
+            }
+
+            /// Appends `data` to the packet
+            ///
+            /// See `Payload::extend_from_slice`
+            pub fn extend_from_slice(
+                &mut self,
+                data: &[u8],
+            ) -> Result<(), $crate::singleton::FromSliceError> {
+                match self {
+                    $($set::$pool(payload) => payload.extend_from_slice(data),)+
+                }
+            }
+        }
+
+        impl AsRef<[u8]> for $set {
+            fn as_ref(&self) -> &[u8] {
+                match self {
+                    $($set::$pool(payload) => payload.as_ref(),)+
+                }
+            }
+        }
+
+        impl AsMut<[u8]> for $set {
+            fn as_mut(&mut self) -> &mut [u8] {
+                match self {
+                    $($set::$pool(payload) => payload.as_mut(),)+
+                }
+            }
         }
     };
 }
@@ -43,26 +592,270 @@ pub trait Pool {
     #[doc(hidden)]
     fn ptr() -> &'static super::Pool<Self::Data>;
 
+    #[doc(hidden)]
+    fn wakers() -> &'static [WakerSlot] {
+        &[]
+    }
+
     /// Claims a memory block from the pool
     ///
     /// Returns `None` when the pool is observed as exhausted
     ///
     /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    #[cfg_attr(feature = "track-callers", track_caller)]
     fn alloc() -> Option<Box<Self, Uninit>>
     where
         Self: Sized,
     {
         Self::ptr().alloc().map(|inner| Box {
             _pool: PhantomData,
-            inner,
+            inner: ManuallyDrop::new(inner),
         })
     }
 
+    /// Claims a memory block from the pool, waiting for one to be freed if the pool is exhausted
+    ///
+    /// The returned future registers the waker of the task in the pool's waker table (see the
+    /// `wakers` option of `pool!`) and is woken up when a memory block is returned to the pool or
+    /// the pool grows. If the table is full, or the pool has none, the future asks to be polled
+    /// again right away. Dropping a future that has been woken but not polled since hands the
+    /// wakeup over to another waiting task.
+    fn alloc_async() -> AllocFuture<Self>
+    where
+        Self: Sized,
+    {
+        AllocFuture::new()
+    }
+
+    /// Claims a memory block from the pool
+    ///
+    /// # Panics
+    ///
+    /// This method panics when the pool is observed as exhausted. The panic message includes the
+    /// location of the caller and the name of the pool.
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    #[track_caller]
+    fn alloc_or_panic() -> Box<Self, Uninit>
+    where
+        Self: Sized,
+    {
+        match Self::alloc() {
+            Some(block) => block,
+            None => panic!("pool `{}` is exhausted", any::type_name::<Self>()),
+        }
+    }
+
+    /// Claims memory blocks from the pool until `vec` is full or the pool is observed as exhausted
+    ///
+    /// Returns the number of memory blocks pushed onto `vec`
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains CAS loops
+    #[cfg(feature = "heapless")]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn alloc_many_into<const N: usize>(vec: &mut ::heapless::Vec<Box<Self, Uninit>, N>) -> usize
+    where
+        Self: Sized,
+    {
+        let mut n = 0;
+        while !vec.is_full() {
+            match Self::alloc() {
+                Some(block) => {
+                    // NOTE(unsafe) `vec` is not full
+                    unsafe { vec.push_unchecked(block) }
+                    n += 1;
+                }
+                None => break,
+            }
+        }
+        n
+    }
+
+    /// Claims a memory block from the pool and initializes it with `val`
+    ///
+    /// Returns `val` back when the pool is observed as exhausted
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn alloc_init(val: Self::Data) -> Result<Box<Self>, Self::Data>
+    where
+        Self: Sized,
+    {
+        match Self::alloc() {
+            Some(block) => Ok(block.init(val)),
+            None => Err(val),
+        }
+    }
+
+    /// Claims a memory block from the pool and initializes it in place with the value returned by
+    /// `f`
+    ///
+    /// Returns `None`, without calling `f`, when the pool is observed as exhausted. See
+    /// `Box::init_with`.
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn alloc_with<F>(f: F) -> Option<Box<Self>>
+    where
+        Self: Sized,
+        F: FnOnce() -> Self::Data,
+    {
+        Self::alloc().map(|block| block.init_with(f))
+    }
+
+    /// Claims a memory block from the pool, initializes it with `val` and pins it
+    ///
+    /// Returns `None` when the pool is observed as exhausted. See `Box::into_pin` for the
+    /// guarantees the pool provides for pinned values.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn alloc_pinned(val: Self::Data) -> Option<Pin<Box<Self>>>
+    where
+        Self: Sized,
+    {
+        Self::alloc().map(|block| block.init(val).into_pin())
+    }
+
+    /// Claims a memory block from the pool and copies `data` into it
+    ///
+    /// Returns the initialized memory block and the number of bytes copied, i.e. `data.len()`. The
+    /// rest of the memory block is zeroed.
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn from_slice(data: &[u8]) -> Result<(Box<Self>, usize), FromSliceError>
+    where
+        Self: Sized,
+        Self::Data: ByteArray,
+    {
+        if data.len() > mem::size_of::<Self::Data>() {
+            return Err(FromSliceError::TooLong);
+        }
+
+        let mut block = Self::alloc().ok_or(FromSliceError::Exhausted)?.zeroed();
+        block.as_mut_slice()[..data.len()].copy_from_slice(data);
+
+        Ok((block, data.len()))
+    }
+
+    /// Claims a memory block from the pool and decodes the postcard message at the start of
+    /// `bytes` into it
+    ///
+    /// See `lifo::Pool::decode_postcard`
+    #[cfg(feature = "postcard")]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn decode_postcard<'de>(bytes: &'de [u8]) -> Result<(Box<Self>, usize), crate::PostcardError>
+    where
+        Self: Sized,
+        Self::Data: serde::Deserialize<'de>,
+    {
+        let block = Self::alloc().ok_or(crate::PostcardError::Exhausted)?;
+
+        // NOTE if decoding fails `block` is dropped and returned to the pool
+        let (val, rest) = postcard::take_from_bytes(bytes)?;
+
+        Ok((block.init(val), bytes.len() - rest.len()))
+    }
+
+    /// Claims a memory block from the pool and encodes `value` into it as a postcard message
+    ///
+    /// See `lifo::Pool::encode_postcard`
+    #[cfg(feature = "postcard")]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn encode_postcard<V>(value: &V) -> Result<(Box<Self>, usize), crate::PostcardError>
+    where
+        Self: Sized,
+        Self::Data: ByteArray,
+        V: serde::Serialize + ?Sized,
+    {
+        let mut block = Self::alloc()
+            .ok_or(crate::PostcardError::Exhausted)?
+            .zeroed();
+
+        // NOTE if encoding fails `block` is dropped and returned to the pool
+        let len = postcard::to_slice(value, block.as_mut_slice())?.len();
+
+        Ok((block, len))
+    }
+
+    /// Calls `f` on every memory block that's currently allocated
+    ///
+    /// See `Pool::dump_live` for more details
+    #[cfg(feature = "track-callers")]
+    fn dump_live<F>(f: F)
+    where
+        F: FnMut(&'static Location<'static>, *const Self::Data),
+    {
+        Self::ptr().dump_live(f)
+    }
+
+    /// Sets the number of freed memory blocks that are kept in quarantine
+    ///
+    /// See `Pool::set_quarantine_depth` for more details
+    #[cfg(feature = "quarantine")]
+    fn set_quarantine_depth(depth: usize) {
+        Self::ptr().set_quarantine_depth(depth)
+    }
+
+    /// Returns a snapshot of the statistics of this pool
+    #[cfg(feature = "stats")]
+    fn stats() -> crate::Stats {
+        Self::ptr().stats()
+    }
+
+    /// Returns a snapshot of the health of this pool
+    ///
+    /// See `Pool::snapshot` for more details
+    #[cfg(feature = "stats")]
+    fn snapshot() -> crate::Snapshot {
+        Self::ptr().snapshot()
+    }
+
+    /// Resets the low watermark to the current number of free memory blocks
+    #[cfg(feature = "stats")]
+    fn reset_watermarks() {
+        Self::ptr().reset_watermarks()
+    }
+
+    #[cfg(feature = "registry")]
+    #[doc(hidden)]
+    fn registry_entry() -> &'static registry::Entry;
+
+    // Calls `f` with the location of the code that claimed each allocated memory block; does
+    // nothing unless the `track-callers` feature is enabled
+    #[cfg(feature = "registry")]
+    #[doc(hidden)]
+    fn live_sites(f: &mut dyn FnMut(&'static Location<'static>)) {
+        #[cfg(feature = "track-callers")]
+        Self::ptr().dump_live(|location, _| f(location));
+        #[cfg(not(feature = "track-callers"))]
+        let _ = f;
+    }
+
+    /// Adds this pool to the registry so it's listed by `registry::pools`
+    ///
+    /// Only the first call has an effect
+    #[cfg(feature = "registry")]
+    fn register()
+    where
+        Self: Sized,
+    {
+        registry::register(Self::registry_entry())
+    }
+
     /// Increases the capacity of the pool
     ///
     /// This method might *not* fully utilize the given memory block due to alignment requirements
     fn grow(memory: &'static mut [u8]) {
-        Self::ptr().grow(memory)
+        Self::ptr().grow(memory);
+        waker::wake_all(Self::wakers())
+    }
+
+    /// Increases the capacity of the pool using memory that has not been initialized
+    ///
+    /// This method might *not* fully utilize the given memory block due to alignment requirements
+    fn grow_uninit(memory: &'static mut [MaybeUninit<u8>]) {
+        Self::ptr().grow_uninit(memory);
+        waker::wake_all(Self::wakers())
     }
 
     /// Increases the capacity of the pool
@@ -71,10 +864,20 @@ pub trait Pool {
     where
         A: AsMutSlice<Element = Node<Self::Data>>,
     {
-        Self::ptr().grow_exact(memory)
+        Self::ptr().grow_exact(memory);
+        waker::wake_all(Self::wakers())
     }
 }
 
+/// Error returned by `Pool::from_slice`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FromSliceError {
+    /// The pool is exhausted
+    Exhausted,
+    /// The slice doesn't fit in a memory block
+    TooLong,
+}
+
 /// A memory block that belongs to the global memory pool, `POOL`
 pub struct Box<POOL, STATE = Init>
 where
@@ -82,7 +885,8 @@ where
     STATE: 'static,
 {
     _pool: PhantomData<POOL>,
-    inner: super::Box<POOL::Data, STATE>,
+    // NOTE `ManuallyDrop` because `Drop` returns the memory block to the pool
+    inner: ManuallyDrop<super::Box<POOL::Data, STATE>>,
 }
 
 impl<P> Box<P, Uninit>
@@ -100,39 +904,421 @@ where
         }
 
         Box {
-            inner: super::Box {
+            inner: ManuallyDrop::new(super::Box {
                 node,
                 _state: PhantomData,
-            },
+            }),
             _pool: PhantomData,
         }
     }
-}
 
-impl<P> Deref for Box<P>
-where
-    P: Pool,
-{
-    type Target = P::Data;
+    /// Initializes this memory block with the value returned by `f`
+    ///
+    /// Unlike `init`, the value is not passed by value so the compiler has a chance to construct it
+    /// directly in the memory block, avoiding a copy of large values. If `f` panics the memory
+    /// block is returned to the pool.
+    #[inline(always)]
+    pub fn init_with<F>(self, f: F) -> Box<P, Init>
+    where
+        F: FnOnce() -> P::Data,
+    {
+        let node = self.inner.node;
 
-    fn deref(&self) -> &P::Data {
-        self.inner.deref()
-    }
-}
+        unsafe {
+            ptr::write(node.as_ref().data.get(), f());
+        }
 
-impl<P> DerefMut for Box<P>
-where
-    P: Pool,
-{
-    fn deref_mut(&mut self) -> &mut P::Data {
-        self.inner.deref_mut()
-    }
-}
+        mem::forget(self);
 
-impl<P, S> Drop for Box<P, S>
-where
-    P: Pool,
-    S: 'static,
+        Box {
+            inner: ManuallyDrop::new(super::Box {
+                node,
+                _state: PhantomData,
+            }),
+            _pool: PhantomData,
+        }
+    }
+
+    /// Tries to initialize this memory block in place using `f`
+    ///
+    /// If `f` returns an error the memory block is handed back, still uninitialized, together with
+    /// the error
+    ///
+    /// # Safety
+    ///
+    /// `f` must fully initialize the `MaybeUninit` it receives whenever it returns `Ok`
+    pub unsafe fn try_init<E, F>(mut self, f: F) -> Result<Box<P, Init>, (Box<P, Uninit>, E)>
+    where
+        F: FnOnce(&mut MaybeUninit<P::Data>) -> Result<(), E>,
+    {
+        match f(self.as_uninit_mut()) {
+            Ok(()) => Ok(self.assume_init()),
+            Err(e) => Err((self, e)),
+        }
+    }
+
+    /// Marks this memory block as initialized without writing to it
+    ///
+    /// This is meant for memory blocks that were filled in by other means, e.g. a DMA transfer,
+    /// `write` or `as_uninit_mut`
+    ///
+    /// # Safety
+    ///
+    /// The contents of the memory block must be a valid, initialized value of type `P::Data`
+    pub unsafe fn assume_init(self) -> Box<P, Init> {
+        let node = self.inner.node;
+
+        mem::forget(self);
+
+        Box {
+            inner: ManuallyDrop::new(super::Box {
+                node,
+                _state: PhantomData,
+            }),
+            _pool: PhantomData,
+        }
+    }
+
+    /// Initializes this memory block with `val`, a value of a type other than `P::Data`, and erases
+    /// its type
+    ///
+    /// `f` performs the unsizing coercion, usually from `&mut T` to `&mut dyn Trait`, and must
+    /// return a reference into `val`. This lets a single pool store heterogeneous values as trait
+    /// objects.
+    ///
+    /// Returns `val` back (and the memory block to the pool) if `val` doesn't fit in the memory
+    /// block, either because it's too big or because the memory block is not sufficiently aligned
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the reference returned by `f` doesn't point to the start of `val`
+    pub fn coerce<T, U, F>(self, val: T, f: F) -> Result<DynBox<P, U>, T>
+    where
+        U: ?Sized,
+        F: for<'a> FnOnce(&'a mut T) -> &'a mut U,
+    {
+        let node = self.inner.node;
+        let p = unsafe { node.as_ref().data.get() as *mut T };
+
+        if mem::size_of::<T>() > mem::size_of::<P::Data>()
+            || p.align_offset(mem::align_of::<T>()) != 0
+        {
+            return Err(val);
+        }
+
+        mem::forget(self);
+
+        let dst = unsafe {
+            ptr::write(p, val);
+            NonNull::from(f(&mut *p))
+        };
+
+        if !ptr::eq(dst.as_ptr() as *const u8, p as *const u8) {
+            // NOTE `dst` may point anywhere; drop the value through `p`
+            unsafe { ptr::drop_in_place(p) }
+
+            P::ptr().release(node);
+            waker::wake_one(P::wakers());
+
+            panic!("`coerce` closure must return a reference to its argument");
+        }
+
+        Ok(DynBox {
+            node,
+            ptr: dst,
+            _pool: PhantomData,
+        })
+    }
+
+    /// Returns a reference to the contents of this memory block
+    pub fn as_uninit(&self) -> &MaybeUninit<P::Data> {
+        self.inner.as_uninit()
+    }
+
+    /// Returns a mutable reference to the contents of this memory block
+    ///
+    /// This can be used to initialize the memory block in place, e.g. field by field
+    pub fn as_uninit_mut(&mut self) -> &mut MaybeUninit<P::Data> {
+        self.inner.as_uninit_mut()
+    }
+
+    /// Returns a raw pointer to the contents of this memory block
+    ///
+    /// The pointer stays valid for as long as the memory block is allocated, e.g. it can be used
+    /// to program a DMA peripheral
+    pub fn as_ptr(&self) -> *const P::Data {
+        self.inner.as_ptr()
+    }
+
+    /// Returns a raw pointer to the contents of this memory block
+    ///
+    /// See `as_ptr`
+    pub fn as_mut_ptr(&mut self) -> *mut P::Data {
+        self.inner.as_mut_ptr()
+    }
+
+    /// Writes `val` into this memory block and returns a mutable reference to it
+    ///
+    /// This overwrites any previous value without dropping it. The memory block is *not* marked as
+    /// initialized, so `val` won't be dropped when the memory block is freed; use `init` for that.
+    pub fn write(&mut self, val: P::Data) -> &mut P::Data {
+        self.inner.write(val)
+    }
+}
+
+impl<P> Box<P, Uninit>
+where
+    P: Pool,
+    P::Data: ByteArray,
+{
+    /// Zeroes this memory block and marks it as initialized
+    fn zeroed(self) -> Box<P, Init> {
+        let node = self.inner.node;
+
+        mem::forget(self);
+
+        Box {
+            inner: ManuallyDrop::new(
+                super::Box::<P::Data, Uninit> {
+                    node,
+                    _state: PhantomData,
+                }
+                .zeroed(),
+            ),
+            _pool: PhantomData,
+        }
+    }
+}
+
+impl<P> Box<P, Init>
+where
+    P: Pool,
+{
+    /// Moves the value out of this memory block
+    ///
+    /// Returns the value and the, now uninitialized, memory block so the latter can be reused.
+    /// Dropping the uninitialized memory block returns it to the pool.
+    pub fn into_inner(self) -> (P::Data, Box<P, Uninit>) {
+        let node = self.inner.node;
+
+        mem::forget(self);
+
+        // NOTE the memory block is not released so the handles must be invalidated here
+        #[cfg(feature = "handles")]
+        unsafe {
+            node.as_ref().retire();
+        }
+
+        let val = unsafe { ptr::read(node.as_ref().data.get()) };
+
+        (
+            val,
+            Box {
+                inner: ManuallyDrop::new(super::Box {
+                    node,
+                    _state: PhantomData,
+                }),
+                _pool: PhantomData,
+            },
+        )
+    }
+
+    /// Drops the value in this memory block and returns the, now uninitialized, memory block
+    ///
+    /// The memory block is *not* returned to the pool so it can be reused right away without
+    /// touching the free list
+    pub fn reinit(self) -> Box<P, Uninit> {
+        let node = self.inner.node;
+
+        mem::forget(self);
+
+        // NOTE the memory block is not released so the handles must be invalidated here
+        #[cfg(feature = "handles")]
+        unsafe {
+            node.as_ref().retire();
+        }
+
+        unsafe { ptr::drop_in_place(node.as_ref().data.get()) }
+
+        Box {
+            inner: ManuallyDrop::new(super::Box {
+                node,
+                _state: PhantomData,
+            }),
+            _pool: PhantomData,
+        }
+    }
+
+    /// Consumes the box and returns a mutable reference to its value
+    ///
+    /// The memory block is never returned to the pool; this is meant for one-shot allocations
+    /// that must live for the rest of the program, like buffers handed to peripherals for good.
+    pub fn leak(self) -> &'static mut P::Data {
+        let node = self.inner.node;
+
+        mem::forget(self);
+
+        // NOTE(unsafe) memory blocks are never handed back to the system
+        unsafe { &mut *node.as_ref().data.get() }
+    }
+
+    /// Returns this memory block to the pool *without* running the destructor of its value
+    ///
+    /// Like `mem::forget`, but the memory block is not leaked. Useful when the value has already
+    /// been moved out, or its resources released, by other means, e.g. with `ptr::read`.
+    pub fn free_uninit(self) {
+        let node = self.inner.node;
+
+        mem::forget(self);
+
+        P::ptr().release(node);
+
+        waker::wake_one(P::wakers())
+    }
+
+    /// Pins the value in this memory block
+    ///
+    /// Memory blocks never move while they are allocated so pinning is free. A pinned value stays
+    /// at the same address until the box is dropped, at which point the value's destructor runs
+    /// *before* the memory block is returned to the pool, as required by the `Pin` drop guarantee.
+    pub fn into_pin(self) -> Pin<Self> {
+        // NOTE(unsafe) see above
+        unsafe { Pin::new_unchecked(self) }
+    }
+
+    /// Returns a weak handle to the value in this memory block
+    ///
+    /// See `Handle`
+    #[cfg(feature = "handles")]
+    pub fn handle(&self) -> Handle<P> {
+        Handle::new(self.inner.node)
+    }
+
+    /// Clones the value into a new memory block claimed from the same pool
+    ///
+    /// Returns `None` when the pool is observed as exhausted
+    pub fn try_clone(&self) -> Option<Self>
+    where
+        P::Data: Clone,
+    {
+        P::alloc().map(|block| block.init_with(|| self.deref().clone()))
+    }
+}
+
+impl<P, S> Box<P, S>
+where
+    P: Pool,
+    S: 'static,
+{
+    /// Returns `true` if both boxes refer to the same memory block
+    pub fn ptr_eq(&self, other: &Box<P, S>) -> bool {
+        self.inner.ptr_eq(&other.inner)
+    }
+
+    /// Converts this memory block into a plain `lifo::Box`
+    ///
+    /// This lets the memory block be passed to code written against `lifo::Box`. The returned box
+    /// doesn't know which pool it belongs to so dropping it leaks the memory block; use
+    /// `from_box` to turn it back into a singleton box.
+    pub fn into_box(self) -> super::Box<P::Data, S> {
+        let inner = unsafe { ptr::read(&self.inner) };
+
+        mem::forget(self);
+
+        ManuallyDrop::into_inner(inner)
+    }
+
+    /// Converts a plain `lifo::Box` back into a memory block of the pool `P`
+    ///
+    /// # Safety
+    ///
+    /// `inner` must have been claimed from the pool `P`, e.g. it was obtained from `into_box`
+    pub unsafe fn from_box(inner: super::Box<P::Data, S>) -> Self {
+        Box {
+            _pool: PhantomData,
+            inner: ManuallyDrop::new(inner),
+        }
+    }
+
+    /// Returns an opaque identifier of the memory block
+    ///
+    /// See `lifo::Box::id`
+    pub fn id(&self) -> usize {
+        self.inner.id()
+    }
+
+    /// Cleans the data cache lines that cover the value in the memory block
+    ///
+    /// See `lifo::Box::clean_dcache`
+    #[cfg(feature = "cortex-m")]
+    pub fn clean_dcache(&self, scb: &mut cortex_m::peripheral::SCB) {
+        self.inner.clean_dcache(scb)
+    }
+
+    /// Cleans and then invalidates the data cache lines that cover the value in the memory block
+    ///
+    /// See `lifo::Box::clean_invalidate_dcache`
+    #[cfg(feature = "cortex-m")]
+    pub fn clean_invalidate_dcache(&mut self, scb: &mut cortex_m::peripheral::SCB) {
+        self.inner.clean_invalidate_dcache(scb)
+    }
+
+    /// Invalidates the data cache lines that cover the value in the memory block
+    ///
+    /// # Safety
+    ///
+    /// See `lifo::Box::invalidate_dcache`
+    #[cfg(feature = "cortex-m")]
+    pub unsafe fn invalidate_dcache(&mut self, scb: &mut cortex_m::peripheral::SCB) {
+        self.inner.invalidate_dcache(scb)
+    }
+}
+
+impl<P> Deref for Box<P>
+where
+    P: Pool,
+{
+    type Target = P::Data;
+
+    fn deref(&self) -> &P::Data {
+        self.inner.deref()
+    }
+}
+
+impl<P> DerefMut for Box<P>
+where
+    P: Pool,
+{
+    fn deref_mut(&mut self) -> &mut P::Data {
+        self.inner.deref_mut()
+    }
+}
+
+impl<P> AsRef<P::Data> for Box<P>
+where
+    P: Pool,
+{
+    fn as_ref(&self) -> &P::Data {
+        self.deref()
+    }
+}
+
+impl<P> AsMut<P::Data> for Box<P>
+where
+    P: Pool,
+{
+    fn as_mut(&mut self) -> &mut P::Data {
+        self.deref_mut()
+    }
+}
+
+// NOTE `Borrow<P::Data>` can't be implemented: it overlaps with core's `impl<T> Borrow<T> for T`
+// because the compiler can't rule out `P::Data` being `Box<P>`. Use `AsRef` / `AsMut` instead
+
+impl<P, S> Drop for Box<P, S>
+where
+    P: Pool,
+    S: 'static,
 {
     fn drop(&mut self) {
         if TypeId::of::<S>() == TypeId::of::<Init>() {
@@ -141,55 +1327,561 @@ where
             }
         }
 
-        P::ptr().push(self.inner.node)
+        P::ptr().release(self.inner.node);
+
+        waker::wake_one(P::wakers())
+    }
+}
+
+unsafe impl<P, S> Send for Box<P, S>
+where
+    P: Pool,
+    P::Data: Send,
+{
+}
+
+unsafe impl<P, S> Sync for Box<P, S>
+where
+    P: Pool,
+    P::Data: Sync,
+{
+}
+
+impl<P, T> AsSlice for Box<P>
+where
+    P: Pool,
+    P::Data: AsSlice<Element = T>,
+{
+    type Element = T;
+
+    fn as_slice(&self) -> &[T] {
+        self.deref().as_slice()
+    }
+}
+
+impl<P, T> AsMutSlice for Box<P>
+where
+    P: Pool,
+    P::Data: AsMutSlice<Element = T>,
+{
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        self.deref_mut().as_mut_slice()
+    }
+}
+
+impl<P> fmt::Debug for Box<P>
+where
+    P: Pool,
+    P::Data: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <P::Data as fmt::Debug>::fmt(self, f)
+    }
+}
+
+impl<P> fmt::Display for Box<P>
+where
+    P: Pool,
+    P::Data: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <P::Data as fmt::Display>::fmt(self, f)
+    }
+}
+
+impl<P> PartialEq for Box<P>
+where
+    P: Pool,
+    P::Data: PartialEq,
+{
+    fn eq(&self, rhs: &Box<P>) -> bool {
+        <P::Data as PartialEq>::eq(self, rhs)
+    }
+}
+
+impl<P> Eq for Box<P>
+where
+    P: Pool,
+    P::Data: Eq,
+{
+}
+
+impl<P> PartialOrd for Box<P>
+where
+    P: Pool,
+    P::Data: PartialOrd,
+{
+    fn partial_cmp(&self, rhs: &Box<P>) -> Option<cmp::Ordering> {
+        <P::Data as PartialOrd>::partial_cmp(self, rhs)
+    }
+}
+
+impl<P> Ord for Box<P>
+where
+    P: Pool,
+    P::Data: Ord,
+{
+    fn cmp(&self, rhs: &Box<P>) -> cmp::Ordering {
+        <P::Data as Ord>::cmp(self, rhs)
+    }
+}
+
+impl<P> Hash for Box<P>
+where
+    P: Pool,
+    P::Data: Hash,
+{
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        <P::Data as Hash>::hash(self, state)
+    }
+}
+
+impl<P, S> fmt::Pointer for Box<P, S>
+where
+    P: Pool,
+    S: 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Pointer::fmt(&*self.inner, f)
+    }
+}
+
+/// A memory block, that belongs to the global memory pool `POOL`, containing a value of an
+/// unsized type `T`, usually a trait object
+///
+/// See `Box::coerce`
+pub struct DynBox<POOL, T>
+where
+    POOL: Pool,
+    T: ?Sized,
+{
+    _pool: PhantomData<POOL>,
+    node: NonNull<Node<POOL::Data>>,
+    ptr: NonNull<T>,
+}
+
+impl<P> DynBox<P, dyn Any>
+where
+    P: Pool,
+{
+    /// Attempts to downcast the trait object to a concrete type
+    ///
+    /// Returns the original box if the value is not of type `T`
+    pub fn downcast<T>(self) -> Result<DynBox<P, T>, Self>
+    where
+        T: Any,
+    {
+        if self.is::<T>() {
+            let node = self.node;
+            let ptr = self.ptr.cast();
+
+            mem::forget(self);
+
+            Ok(DynBox {
+                _pool: PhantomData,
+                node,
+                ptr,
+            })
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<P> DynBox<P, dyn Any + Send>
+where
+    P: Pool,
+{
+    /// Attempts to downcast the trait object to a concrete type
+    ///
+    /// Returns the original box if the value is not of type `T`
+    pub fn downcast<T>(self) -> Result<DynBox<P, T>, Self>
+    where
+        T: Any,
+    {
+        if self.is::<T>() {
+            let node = self.node;
+            let ptr = self.ptr.cast();
+
+            mem::forget(self);
+
+            Ok(DynBox {
+                _pool: PhantomData,
+                node,
+                ptr,
+            })
+        } else {
+            Err(self)
+        }
+    }
+}
+
+#[cfg(feature = "bytemuck")]
+impl<P> Box<P>
+where
+    P: Pool,
+    P::Data: bytemuck::Pod,
+{
+    /// Reinterprets the contents of this memory block as a value of type `T`
+    ///
+    /// Returns the original box if `T` doesn't have the same size as `P::Data` or if the memory
+    /// block is not sufficiently aligned for `T`
+    pub fn try_cast<T>(self) -> Result<DynBox<P, T>, Self>
+    where
+        T: bytemuck::Pod,
+    {
+        let node = self.inner.node;
+        let p = unsafe { node.as_ref().data.get() as *mut T };
+
+        if mem::size_of::<T>() != mem::size_of::<P::Data>()
+            || p.align_offset(mem::align_of::<T>()) != 0
+        {
+            return Err(self);
+        }
+
+        mem::forget(self);
+
+        Ok(DynBox {
+            _pool: PhantomData,
+            node,
+            ptr: unsafe { NonNull::new_unchecked(p) },
+        })
+    }
+}
+
+#[cfg(feature = "bytemuck")]
+impl<P, T> DynBox<P, T>
+where
+    P: Pool,
+    P::Data: bytemuck::Pod,
+    T: bytemuck::Pod,
+{
+    /// Reinterprets the contents of this memory block as a value of type `P::Data`
+    ///
+    /// This is the inverse of `Box::try_cast`. Returns the original box if `T` doesn't have the
+    /// same size as `P::Data`.
+    pub fn try_into_box(self) -> Result<Box<P>, Self> {
+        // NOTE the value starts at the beginning of the memory block (see `coerce`) so alignment
+        // is not a concern
+        if mem::size_of::<T>() != mem::size_of::<P::Data>() {
+            return Err(self);
+        }
+
+        let node = self.node;
+
+        mem::forget(self);
+
+        Ok(Box {
+            _pool: PhantomData,
+            inner: ManuallyDrop::new(super::Box {
+                node,
+                _state: PhantomData,
+            }),
+        })
+    }
+}
+
+impl<P, T> DynBox<P, T>
+where
+    P: Pool,
+    T: ?Sized,
+{
+    /// Returns `true` if both boxes refer to the same memory block
+    pub fn ptr_eq(&self, other: &DynBox<P, T>) -> bool {
+        self.node == other.node
+    }
+
+    /// Returns an opaque identifier of the memory block
+    ///
+    /// See `lifo::Box::id`
+    pub fn id(&self) -> usize {
+        self.node.as_ptr() as usize
+    }
+}
+
+impl<P, T> Deref for DynBox<P, T>
+where
+    P: Pool,
+    T: ?Sized,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<P, T> DerefMut for DynBox<P, T>
+where
+    P: Pool,
+    T: ?Sized,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<P, T> AsRef<T> for DynBox<P, T>
+where
+    P: Pool,
+    T: ?Sized,
+{
+    fn as_ref(&self) -> &T {
+        self.deref()
+    }
+}
+
+impl<P, T> AsMut<T> for DynBox<P, T>
+where
+    P: Pool,
+    T: ?Sized,
+{
+    fn as_mut(&mut self) -> &mut T {
+        self.deref_mut()
+    }
+}
+
+impl<P, T> Borrow<T> for DynBox<P, T>
+where
+    P: Pool,
+    T: ?Sized,
+{
+    fn borrow(&self) -> &T {
+        self.deref()
+    }
+}
+
+impl<P, T> BorrowMut<T> for DynBox<P, T>
+where
+    P: Pool,
+    T: ?Sized,
+{
+    fn borrow_mut(&mut self) -> &mut T {
+        self.deref_mut()
+    }
+}
+
+impl<P, T> Drop for DynBox<P, T>
+where
+    P: Pool,
+    T: ?Sized,
+{
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.ptr.as_ptr()) }
+
+        P::ptr().release(self.node);
+
+        waker::wake_one(P::wakers())
+    }
+}
+
+impl<P, T> fmt::Debug for DynBox<P, T>
+where
+    P: Pool,
+    T: fmt::Debug + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <T as fmt::Debug>::fmt(self, f)
+    }
+}
+
+impl<P, T> fmt::Display for DynBox<P, T>
+where
+    P: Pool,
+    T: fmt::Display + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <T as fmt::Display>::fmt(self, f)
+    }
+}
+
+impl<P, T> PartialEq for DynBox<P, T>
+where
+    P: Pool,
+    T: PartialEq + ?Sized,
+{
+    fn eq(&self, rhs: &DynBox<P, T>) -> bool {
+        <T as PartialEq>::eq(self, rhs)
+    }
+}
+
+impl<P, T> Eq for DynBox<P, T>
+where
+    P: Pool,
+    T: Eq + ?Sized,
+{
+}
+
+impl<P, T> PartialOrd for DynBox<P, T>
+where
+    P: Pool,
+    T: PartialOrd + ?Sized,
+{
+    fn partial_cmp(&self, rhs: &DynBox<P, T>) -> Option<cmp::Ordering> {
+        <T as PartialOrd>::partial_cmp(self, rhs)
+    }
+}
+
+impl<P, T> Ord for DynBox<P, T>
+where
+    P: Pool,
+    T: Ord + ?Sized,
+{
+    fn cmp(&self, rhs: &DynBox<P, T>) -> cmp::Ordering {
+        <T as Ord>::cmp(self, rhs)
+    }
+}
+
+impl<P, T> Hash for DynBox<P, T>
+where
+    P: Pool,
+    T: Hash + ?Sized,
+{
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        <T as Hash>::hash(self, state)
+    }
+}
+
+impl<P, T> fmt::Pointer for DynBox<P, T>
+where
+    P: Pool,
+    T: ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Pointer::fmt(&self.node.as_ptr(), f)
+    }
+}
+
+unsafe impl<P, T> Send for DynBox<P, T>
+where
+    P: Pool,
+    T: Send + ?Sized,
+{
+}
+
+unsafe impl<P, T> Sync for DynBox<P, T>
+where
+    P: Pool,
+    T: Sync + ?Sized,
+{
+}
+
+#[cfg(feature = "stable_deref_trait")]
+unsafe impl<P> stable_deref_trait::StableDeref for Box<P> where P: Pool {}
+
+#[cfg(feature = "stable_deref_trait")]
+unsafe impl<P, T> stable_deref_trait::StableDeref for DynBox<P, T>
+where
+    P: Pool,
+    T: ?Sized,
+{
+}
+
+#[cfg(feature = "dma")]
+unsafe impl<P> embedded_dma::WriteBuffer for Box<P, Uninit>
+where
+    P: Pool,
+    P::Data: embedded_dma::WriteTarget,
+{
+    type Word = <P::Data as embedded_dma::WriteTarget>::Word;
+
+    unsafe fn write_buffer(&mut self) -> (*mut Self::Word, usize) {
+        self.inner.write_buffer()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<P> defmt::Format for Box<P>
+where
+    P: Pool,
+    P::Data: defmt::Format,
+{
+    fn format(&self, f: defmt::Formatter) {
+        <P::Data as defmt::Format>::format(self, f)
     }
 }
 
-unsafe impl<P, S> Send for Box<P, S>
+#[cfg(feature = "defmt")]
+impl<P, T> defmt::Format for DynBox<P, T>
 where
     P: Pool,
-    P::Data: Send,
+    T: defmt::Format + ?Sized,
 {
+    fn format(&self, f: defmt::Formatter) {
+        <T as defmt::Format>::format(self, f)
+    }
 }
 
-unsafe impl<P, S> Sync for Box<P, S>
+#[cfg(feature = "serde")]
+impl<P> serde::Serialize for Box<P>
 where
     P: Pool,
-    P::Data: Sync,
+    P::Data: serde::Serialize,
 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        <P::Data as serde::Serialize>::serialize(self, serializer)
+    }
 }
 
-impl<P, T> AsSlice for Box<P>
+#[cfg(feature = "serde")]
+impl<P, T> serde::Serialize for DynBox<P, T>
 where
     P: Pool,
-    P::Data: AsSlice<Element = T>,
+    T: serde::Serialize + ?Sized,
 {
-    type Element = T;
-
-    fn as_slice(&self) -> &[T] {
-        self.deref().as_slice()
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        <T as serde::Serialize>::serialize(self, serializer)
     }
 }
 
-impl<P, T> AsMutSlice for Box<P>
+/// Deserializes a value into a memory block claimed from the pool `P`
+///
+/// Deserialization fails if the pool is observed as exhausted
+#[cfg(feature = "serde")]
+impl<'de, P> serde::Deserialize<'de> for Box<P>
 where
     P: Pool,
-    P::Data: AsMutSlice<Element = T>,
+    P::Data: serde::Deserialize<'de>,
 {
-    fn as_mut_slice(&mut self) -> &mut [T] {
-        self.deref_mut().as_mut_slice()
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let block = P::alloc()
+            .ok_or_else(|| <D::Error as serde::de::Error>::custom("memory pool is exhausted"))?;
+
+        // NOTE if deserialization fails `block` is dropped and returned to the pool
+        let val = P::Data::deserialize(deserializer)?;
+
+        Ok(block.init(val))
     }
 }
 
+#[allow(unknown_lints, static_mut_refs)]
 #[cfg(test)]
 mod tests {
     use core::{
-        mem,
+        any::Any,
         sync::atomic::{AtomicUsize, Ordering},
     };
+    use std::panic;
 
     use super::Pool;
+    use crate::tests::memory;
 
+    #[cfg(not(any(feature = "fifo", feature = "track-callers")))]
     #[test]
     fn sanity() {
         #[cfg(not(feature = "union"))]
@@ -216,6 +1908,562 @@ mod tests {
         assert_eq!(*A::alloc().unwrap().init(1), 1);
     }
 
+    #[test]
+    fn attributes() {
+        pool!(
+            /// A documented pool
+            pub(crate) A: u8
+        );
+
+        pool!(
+            #[cfg(any())]
+            B: u8;
+            capacity = 1
+        );
+
+        assert!(A::alloc().is_none());
+    }
+
+    #[test]
+    fn alias() {
+        use crate::Uninit;
+
+        pool!(pub(crate) A: u8 as ABox; capacity = 1);
+
+        fn init(x: ABox<Uninit>) -> ABox {
+            x.init(1)
+        }
+
+        A::init();
+
+        let x: ABox = init(A::alloc().unwrap());
+        assert_eq!(*x, 1);
+    }
+
+    #[cfg(feature = "pooled")]
+    #[test]
+    fn pooled() {
+        #[crate::pooled(capacity = 2)]
+        struct Msg {
+            id: u8,
+        }
+
+        #[crate::pooled(pool = Other)]
+        struct Empty;
+
+        assert_eq!(Msg::BLOCK_SIZE, core::mem::size_of::<crate::Node<Msg>>());
+        assert_eq!(Msg::CAPACITY, 2);
+        assert_eq!(Msg::MEMORY_SIZE, crate::__memory_size::<Msg>(2));
+
+        Msg::init();
+
+        let x: super::Box<MsgPool> = Msg::alloc().unwrap().init(Msg { id: 1 });
+        assert_eq!(x.id, 1);
+
+        assert!(Empty::alloc().is_none());
+        assert!(Other::alloc().is_none());
+    }
+
+    #[test]
+    fn pool_set() {
+        pool_set!(Packet {
+            Small: [u8; 4]; capacity = 1,
+            Large: [u8; 16]; capacity = 1,
+        });
+
+        Small::init();
+        Large::init();
+
+        let mut a = Packet::from_slice(&[1, 2]).unwrap();
+        assert!(matches!(a, Packet::Small(_)));
+        assert_eq!(a.capacity(), 4);
+        a.extend_from_slice(&[3]).unwrap();
+        assert_eq!(a.as_ref(), &[1, 2, 3]);
+
+        // `Small` is exhausted so the next packet comes from `Large`
+        let b = Packet::with_capacity(2).unwrap();
+        assert!(matches!(b, Packet::Large(_)));
+        assert!(b.is_empty());
+
+        assert_eq!(
+            Packet::from_slice(&[0; 2]).err(),
+            Some(super::FromSliceError::Exhausted)
+        );
+        assert_eq!(
+            Packet::with_capacity(32).err(),
+            Some(super::FromSliceError::TooLong)
+        );
+
+        drop(a);
+        assert!(matches!(Packet::with_capacity(0), Ok(Packet::Small(_))));
+    }
+
+    #[test]
+    fn link_section() {
+        pool!(A: u8; link_section = ".data.lifo"; capacity = 1);
+
+        A::init();
+
+        assert!(A::alloc().is_some());
+    }
+
+    #[cfg(feature = "static-pool")]
+    #[test]
+    fn static_pool() {
+        crate::static_pool!(A: [u8; 3] as ABox; capacity = 2, B: u8; capacity = 0);
+
+        // no `init` needed
+        let x: ABox = A::alloc_init([1, 2, 3]).unwrap();
+        let y = A::alloc().unwrap().init([4, 5, 6]);
+        assert!(A::alloc().is_none());
+        assert_eq!(*x, [1, 2, 3]);
+        assert_eq!(*y, [4, 5, 6]);
+
+        drop(x);
+        assert_eq!(*A::alloc_init([7, 8, 9]).unwrap(), [7, 8, 9]);
+
+        assert!(B::alloc().is_none());
+    }
+
+    #[test]
+    fn multiple() {
+        pool!(
+            Rx: [u8; 4],
+            Tx: [u8; 4]; capacity = 1,
+            Ctrl: u32,
+        );
+
+        Tx::init();
+
+        assert!(Rx::alloc().is_none());
+        assert!(Tx::alloc().is_some());
+        assert!(Ctrl::alloc().is_none());
+    }
+
+    #[test]
+    fn capacity() {
+        pool!(A: [u8; 3]; capacity = 4);
+
+        A::init();
+        // no effect
+        A::init();
+
+        let blocks = (0..4).map(|_| A::alloc().unwrap()).collect::<Vec<_>>();
+        assert!(A::alloc().is_none());
+
+        drop(blocks);
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats() {
+        pool!(A: u8; capacity = 2);
+
+        A::init();
+
+        let x = A::alloc().unwrap();
+        let stats = A::stats();
+        assert_eq!(stats.free, stats.capacity - 1);
+
+        drop(x);
+        A::reset_watermarks();
+        assert_eq!(A::stats().low_watermark, A::stats().capacity);
+    }
+
+    #[test]
+    fn into_box() {
+        pool!(A: u8);
+
+        A::grow(memory::<u8>(1));
+
+        fn bump(x: &mut crate::Box<u8>) {
+            **x += 1;
+        }
+
+        let mut inner = A::alloc().unwrap().init(1).into_box();
+        bump(&mut inner);
+
+        let x = unsafe { super::Box::<A>::from_box(inner) };
+        assert_eq!(*x, 2);
+
+        // the memory block is returned to the pool
+        drop(x);
+        assert!(A::alloc().is_some());
+    }
+
+    #[test]
+    fn leak() {
+        pool!(A: [u8; 4]);
+
+        A::grow(memory::<[u8; 4]>(2));
+
+        let x: &'static mut [u8; 4] = A::alloc().unwrap().init([1; 4]).leak();
+        x[0] = 0;
+        assert_eq!(*x, [0, 1, 1, 1]);
+
+        // the leaked memory block is not returned to the pool
+        let y = A::alloc().unwrap();
+        assert!(A::alloc().is_none());
+        drop(y);
+    }
+
+    #[test]
+    fn free_uninit() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        struct X;
+
+        impl Drop for X {
+            fn drop(&mut self) {
+                COUNT.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        pool!(A: X);
+
+        A::grow(memory::<X>(1));
+
+        let x = A::alloc().unwrap().init(X);
+        let moved = unsafe { core::ptr::read(&*x) };
+        x.free_uninit();
+        assert_eq!(COUNT.load(Ordering::Relaxed), 0);
+
+        // the memory block was returned to the pool
+        assert!(A::alloc().is_some());
+
+        drop(moved);
+        assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn alloc_init() {
+        pool!(A: u8);
+
+        A::grow(memory::<u8>(1));
+
+        let x = A::alloc_init(1).unwrap();
+        assert_eq!(A::alloc_init(2).err(), Some(2));
+        assert!(A::alloc_with(|| unreachable!()).is_none());
+
+        drop(x);
+        assert_eq!(*A::alloc_with(|| 3).unwrap(), 3);
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn alloc_many_into() {
+        pool!(A: u8);
+
+        A::grow(memory::<u8>(3));
+
+        let mut vec = heapless::Vec::<_, 4>::new();
+        assert_eq!(A::alloc_many_into(&mut vec), 3);
+        assert!(A::alloc().is_none());
+
+        vec.clear();
+        assert!(A::alloc().is_some());
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn postcard() {
+        pool!(A: [u8; 4]);
+        pool!(B: (u16, bool));
+
+        A::grow(memory::<[u8; 4]>(1));
+        B::grow(memory::<(u16, bool)>(1));
+
+        let (message, len) = A::encode_postcard(&(300u16, true)).unwrap();
+        assert_eq!(len, 3);
+
+        let (value, n) = B::decode_postcard(&message[..]).unwrap();
+        assert_eq!(*value, (300, true));
+        assert_eq!(n, len);
+        assert_eq!(
+            B::decode_postcard(&message[..]).err(),
+            Some(crate::PostcardError::Exhausted)
+        );
+    }
+
+    #[test]
+    fn init_with() {
+        pool!(A: [u8; 256]);
+
+        A::grow(memory::<[u8; 256]>(1));
+
+        let x = A::alloc().unwrap().init_with(|| [1; 256]);
+        assert!(x.iter().all(|byte| *byte == 1));
+    }
+
+    #[test]
+    fn into_inner() {
+        pool!(A: [u8; 4]);
+
+        A::grow(memory::<[u8; 4]>(1));
+
+        let (val, x) = A::alloc().unwrap().init([1, 2, 3, 4]).into_inner();
+        assert_eq!(val, [1, 2, 3, 4]);
+
+        // the memory block can be reused
+        let x = x.init([5, 6, 7, 8]);
+        assert_eq!(*x, [5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn try_clone() {
+        pool!(A: [u8; 4]);
+
+        A::grow(memory::<[u8; 4]>(2));
+
+        let x = A::alloc().unwrap().init([1, 2, 3, 4]);
+        let y = x.try_clone().unwrap();
+        assert_eq!(*y, [1, 2, 3, 4]);
+
+        // pool exhausted
+        assert!(y.try_clone().is_none());
+    }
+
+    #[test]
+    fn pinned() {
+        use core::marker::PhantomPinned;
+
+        pub struct X {
+            val: u8,
+            _pinned: PhantomPinned,
+        }
+
+        pool!(A: X);
+
+        A::grow(memory::<X>(1));
+
+        let x = A::alloc_pinned(X {
+            val: 1,
+            _pinned: PhantomPinned,
+        })
+        .unwrap();
+        assert_eq!(x.val, 1);
+        assert!(A::alloc_pinned(X {
+            val: 2,
+            _pinned: PhantomPinned,
+        })
+        .is_none());
+
+        // dropping the pinned box returns the memory block to the pool
+        drop(x);
+        assert!(A::alloc().is_some());
+    }
+
+    #[test]
+    fn coerce() {
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+        pub trait Command {
+            fn run(&self) -> u32;
+        }
+
+        pub struct Blink(u32);
+
+        impl Command for Blink {
+            fn run(&self) -> u32 {
+                self.0
+            }
+        }
+
+        impl Drop for Blink {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        pub struct Reset;
+
+        impl Command for Reset {
+            fn run(&self) -> u32 {
+                0
+            }
+        }
+
+        pool!(A: [u32; 4]);
+
+        A::grow(memory::<[u32; 4]>(2));
+
+        let x = A::alloc()
+            .unwrap()
+            .coerce(Blink(1), |x| x as &mut dyn Command)
+            .ok()
+            .unwrap();
+        let y = A::alloc()
+            .unwrap()
+            .coerce(Reset, |x| x as &mut dyn Command)
+            .ok()
+            .unwrap();
+        assert_eq!(x.run() + y.run(), 1);
+
+        drop(x);
+        assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+
+        // too big
+        assert!(A::alloc().unwrap().coerce([0u64; 4], |x| x).is_err());
+
+        // the closure doesn't return a reference to its argument; the value is dropped and the
+        // memory block returned to the pool before panicking
+        let other: &'static mut Blink = Box::leak(Box::new(Blink(2)));
+        let res = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            A::alloc()
+                .unwrap()
+                .coerce(Blink(3), move |_| other as &mut dyn Command)
+        }));
+        assert!(res.is_err());
+        assert_eq!(DROPS.load(Ordering::Relaxed), 2);
+        assert!(A::alloc().is_some());
+    }
+
+    #[test]
+    fn downcast() {
+        pool!(A: u64);
+
+        A::grow(memory::<u64>(2));
+
+        let x = A::alloc()
+            .unwrap()
+            .coerce(1u32, |x| x as &mut dyn Any)
+            .ok()
+            .unwrap();
+        let y = A::alloc()
+            .unwrap()
+            .coerce(2u64, |x| x as &mut dyn Any)
+            .ok()
+            .unwrap();
+
+        let x = x.downcast::<u64>().unwrap_err();
+        assert_eq!(*x.downcast::<u32>().ok().unwrap(), 1);
+        assert_eq!(*y.downcast::<u64>().ok().unwrap(), 2);
+    }
+
+    #[cfg(feature = "bytemuck")]
+    #[test]
+    fn cast() {
+        #[derive(Clone, Copy)]
+        #[repr(C)]
+        struct Header {
+            id: u16,
+            len: u16,
+        }
+
+        unsafe impl bytemuck::Zeroable for Header {}
+        unsafe impl bytemuck::Pod for Header {}
+
+        pool!(A: [u8; 4]);
+
+        A::grow(memory::<[u8; 4]>(1));
+
+        let x = A::alloc().unwrap().init([1, 0, 2, 0]);
+
+        // size mismatch
+        let x = x.try_cast::<u64>().err().unwrap();
+
+        let mut x = x.try_cast::<Header>().ok().unwrap();
+        assert_eq!((x.id, x.len), (u16::from_le(1), u16::from_le(2)));
+        x.len = 0;
+
+        assert_eq!(*x.try_into_box().ok().unwrap(), [1, 0, 0, 0]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize() {
+        use serde::{
+            de::{value, IntoDeserializer},
+            Deserialize,
+        };
+
+        pool!(A: u32);
+
+        A::grow(memory::<u32>(1));
+
+        let de: value::U32Deserializer<value::Error> = 42u32.into_deserializer();
+        let x = super::Box::<A>::deserialize(de).unwrap();
+        assert_eq!(*x, 42);
+
+        // pool exhausted
+        let de: value::U32Deserializer<value::Error> = 0u32.into_deserializer();
+        assert!(super::Box::<A>::deserialize(de).is_err());
+    }
+
+    #[test]
+    fn from_slice() {
+        use super::FromSliceError;
+
+        pool!(A: [u8; 4]);
+
+        A::grow(memory::<[u8; 4]>(1));
+
+        assert_eq!(A::from_slice(&[1; 5]).err(), Some(FromSliceError::TooLong));
+
+        let (x, len) = A::from_slice(&[1, 2]).unwrap();
+        assert_eq!(*x, [1, 2, 0, 0]);
+        assert_eq!(len, 2);
+
+        assert_eq!(A::from_slice(&[]).err(), Some(FromSliceError::Exhausted));
+        // the length is checked before claiming a memory block
+        assert_eq!(A::from_slice(&[1; 5]).err(), Some(FromSliceError::TooLong));
+    }
+
+    // the singleton boxes forward the same standard traits as `lifo::Box`
+    #[test]
+    fn traits() {
+        use core::{
+            borrow::BorrowMut,
+            fmt::{Debug, Display, Pointer},
+            hash::Hash,
+        };
+
+        use super::{Box, DynBox};
+
+        fn forwards<B, T>()
+        where
+            B: AsRef<T> + AsMut<T> + Debug + Display + Ord + Hash + Pointer,
+            T: ?Sized,
+        {
+        }
+
+        fn borrows<B, T>()
+        where
+            B: BorrowMut<T>,
+            T: ?Sized,
+        {
+        }
+
+        pool!(A: u32);
+
+        forwards::<crate::Box<u32>, u32>();
+        borrows::<crate::Box<u32>, u32>();
+        forwards::<Box<A>, u32>();
+        forwards::<DynBox<A, str>, str>();
+        borrows::<DynBox<A, str>, str>();
+    }
+
+    #[test]
+    fn debug() {
+        pool!(A: [u8; 2]);
+
+        A::grow(memory::<[u8; 2]>(1));
+
+        let x = A::alloc().unwrap().init([1, 2]);
+        assert_eq!(format!("{:?}", x), "[1, 2]");
+    }
+
+    #[test]
+    #[should_panic(expected = "is exhausted")]
+    fn alloc_or_panic() {
+        pool!(A: u8);
+
+        A::alloc_or_panic();
+    }
+
+    #[cfg(not(any(feature = "fifo", feature = "handles", feature = "track-callers")))]
     #[test]
     fn destructors() {
         static COUNT: AtomicUsize = AtomicUsize::new(0);
@@ -251,7 +2499,7 @@ mod tests {
         assert_eq!(COUNT.load(Ordering::Relaxed), 1);
 
         // this leaks memory
-        mem::forget(y);
+        core::mem::forget(y);
 
         assert_eq!(COUNT.load(Ordering::Relaxed), 1);
     }
@@ -0,0 +1,534 @@
+//! Reference-counted memory blocks
+//!
+//! A pool of `ArcInner<T>` values hands out `Arc`s: shared pointers to a `T` value that return
+//! their memory block to the pool when the last clone is dropped. This lets a single value, e.g. a
+//! received packet, be handed to several consumers without copying it.
+//!
+//! ``` ignore
+//! use lifo::{pool, singleton::arc::{Arc, ArcInner}};
+//!
+//! pool!(P: ArcInner<[u8; 128]>);
+//!
+//! let packet = Arc::<P>::new([0; 128]).ok().unwrap();
+//! let copy = packet.clone();
+//!
+//! drop(packet);
+//! // the memory block is returned to `P` here
+//! drop(copy);
+//! ```
+//!
+//! `ArcBytes` builds on `Arc` to hand out shared views of different parts of a pooled byte buffer:
+//!
+//! ``` ignore
+//! use lifo::{pool, singleton::arc::{Arc, ArcBytes, ArcInner}};
+//!
+//! pool!(F: ArcInner<[u8; 1536]>);
+//!
+//! let frame = ArcBytes::new(receive_frame()).slice(..len);
+//! let header = frame.slice(..14);
+//! let payload = frame.slice(14..);
+//! // the memory block is returned to `F` once `frame`, `header` and `payload` have been dropped
+//! ```
+
+use core::{
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    mem,
+    ops::{Bound, Deref, RangeBounds},
+    ptr::NonNull,
+    sync::atomic::{self, AtomicUsize, Ordering},
+};
+
+use as_slice::AsSlice;
+
+use super::{Box, Pool};
+use crate::{Init, Node};
+
+// Same limit as `alloc::sync::Arc`; going over it means clones are being leaked
+const MAX_REFCOUNT: usize = isize::MAX as usize;
+
+/// The data of a pool of `Arc`s: a `T` value plus its reference count
+pub struct ArcInner<T> {
+    strong: AtomicUsize,
+    data: T,
+}
+
+/// A singleton pool of `ArcInner` values
+///
+/// Implemented for every singleton pool whose data type is `ArcInner<T>`
+pub trait ArcPool: Pool<Data = ArcInner<<Self as ArcPool>::Value>> {
+    /// The type of the reference-counted values
+    type Value;
+}
+
+impl<P, T> ArcPool for P
+where
+    P: Pool<Data = ArcInner<T>>,
+{
+    type Value = T;
+}
+
+/// A reference-counted pointer to a value stored in a memory block of the pool `POOL`
+pub struct Arc<POOL>
+where
+    POOL: ArcPool,
+{
+    _pool: PhantomData<POOL>,
+    node: NonNull<Node<POOL::Data>>,
+}
+
+impl<P> Arc<P>
+where
+    P: ArcPool,
+{
+    /// Claims a memory block from the pool `P` and moves `val` into it
+    ///
+    /// Returns `val` back when the pool is observed as exhausted
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn new(val: P::Value) -> Result<Self, P::Value> {
+        match P::alloc() {
+            Some(block) => Ok(Arc::from_block(block.init(ArcInner {
+                strong: AtomicUsize::new(1),
+                data: val,
+            }))),
+            None => Err(val),
+        }
+    }
+
+    fn from_block(block: Box<P>) -> Self {
+        let node = block.inner.node;
+
+        // NOTE the memory block is now owned by the `Arc`
+        mem::forget(block);
+
+        Arc {
+            _pool: PhantomData,
+            node,
+        }
+    }
+
+    fn inner(&self) -> &ArcInner<P::Value> {
+        unsafe { &*self.node.as_ref().data.get() }
+    }
+
+    /// Returns the number of `Arc`s that point to the same value
+    pub fn strong_count(this: &Self) -> usize {
+        this.inner().strong.load(Ordering::Acquire)
+    }
+
+    /// Returns `true` if both `Arc`s point to the same value
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.node == other.node
+    }
+
+    /// Returns a mutable reference to the value if there are no other `Arc`s pointing to it
+    pub fn get_mut(this: &mut Self) -> Option<&mut P::Value> {
+        if Self::strong_count(this) == 1 {
+            // NOTE(unsafe) no other `Arc` can observe the value
+            Some(unsafe { &mut (*this.node.as_ref().data.get()).data })
+        } else {
+            None
+        }
+    }
+}
+
+impl<P> Clone for Arc<P>
+where
+    P: ArcPool,
+{
+    fn clone(&self) -> Self {
+        // NOTE a new reference can only be created from an existing one so `Relaxed` is enough
+        let old = self.inner().strong.fetch_add(1, Ordering::Relaxed);
+
+        if old > MAX_REFCOUNT {
+            panic!("reference count overflow");
+        }
+
+        Arc {
+            _pool: PhantomData,
+            node: self.node,
+        }
+    }
+}
+
+impl<P> Deref for Arc<P>
+where
+    P: ArcPool,
+{
+    type Target = P::Value;
+
+    fn deref(&self) -> &P::Value {
+        &self.inner().data
+    }
+}
+
+impl<P> AsRef<P::Value> for Arc<P>
+where
+    P: ArcPool,
+{
+    fn as_ref(&self) -> &P::Value {
+        self.deref()
+    }
+}
+
+impl<P> Drop for Arc<P>
+where
+    P: ArcPool,
+{
+    fn drop(&mut self) {
+        if self.inner().strong.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+
+        // synchronize with the other `Arc`s that were dropped before this one
+        atomic::fence(Ordering::Acquire);
+
+        // NOTE(unsafe) this was the last reference; dropping the box drops the value and returns
+        // the memory block to the pool
+        drop(unsafe {
+            Box::<P, Init>::from_box(crate::Box {
+                _state: PhantomData,
+                node: self.node,
+            })
+        })
+    }
+}
+
+unsafe impl<P> Send for Arc<P>
+where
+    P: ArcPool,
+    P::Value: Send + Sync,
+{
+}
+
+unsafe impl<P> Sync for Arc<P>
+where
+    P: ArcPool,
+    P::Value: Send + Sync,
+{
+}
+
+impl<P> fmt::Debug for Arc<P>
+where
+    P: ArcPool,
+    P::Value: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <P::Value as fmt::Debug>::fmt(self, f)
+    }
+}
+
+impl<P> fmt::Display for Arc<P>
+where
+    P: ArcPool,
+    P::Value: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <P::Value as fmt::Display>::fmt(self, f)
+    }
+}
+
+impl<P> PartialEq for Arc<P>
+where
+    P: ArcPool,
+    P::Value: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<P> Eq for Arc<P>
+where
+    P: ArcPool,
+    P::Value: Eq,
+{
+}
+
+impl<P> Hash for Arc<P>
+where
+    P: ArcPool,
+    P::Value: Hash,
+{
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        (**self).hash(state)
+    }
+}
+
+/// A shared view of part of a byte buffer stored in a memory block of the pool `POOL`
+///
+/// This is the pooled counterpart of `bytes::Bytes`: cloning and slicing only bump the reference
+/// count of the underlying `Arc`, so several protocol layers can hold on to different parts of the
+/// same received frame without copying it. `POOL::Value` is expected to be a plain byte buffer,
+/// like `[u8; N]`.
+pub struct ArcBytes<POOL>
+where
+    POOL: ArcPool,
+{
+    arc: Arc<POOL>,
+    // the view is `arc[start..end]`
+    start: usize,
+    end: usize,
+}
+
+impl<P> ArcBytes<P>
+where
+    P: ArcPool,
+    P::Value: AsSlice<Element = u8>,
+{
+    /// Creates a view of the whole buffer
+    pub fn new(arc: Arc<P>) -> Self {
+        let end = arc.as_slice().len();
+
+        ArcBytes { arc, start: 0, end }
+    }
+
+    /// Returns the number of bytes in the view
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Returns `true` if the view contains no bytes
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Returns a view of `range`, relative to this view, that shares the same buffer
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `range` is out of bounds
+    pub fn slice<R>(&self, range: R) -> Self
+    where
+        R: RangeBounds<usize>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n + 1,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.len(),
+        };
+
+        assert!(
+            start <= end && end <= self.len(),
+            "range {}..{} out of bounds of a view of length {}",
+            start,
+            end,
+            self.len()
+        );
+
+        ArcBytes {
+            arc: self.arc.clone(),
+            start: self.start + start,
+            end: self.start + end,
+        }
+    }
+
+    /// Splits the view in two at `at`
+    ///
+    /// Afterwards `self` contains the bytes `[at, len)` and the returned view contains the bytes
+    /// `[0, at)`
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `at > len`
+    pub fn split_to(&mut self, at: usize) -> Self {
+        let head = self.slice(..at);
+        self.start += at;
+        head
+    }
+
+    /// Splits the view in two at `at`
+    ///
+    /// Afterwards `self` contains the bytes `[0, at)` and the returned view contains the bytes
+    /// `[at, len)`
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `at > len`
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let tail = self.slice(at..);
+        self.end = self.start + at;
+        tail
+    }
+
+    /// Shortens the view to `len` bytes
+    ///
+    /// This has no effect if `len` is greater than the view's current length
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            self.end = self.start + len;
+        }
+    }
+
+    /// Returns the `Arc` that holds the whole buffer
+    pub fn arc(&self) -> &Arc<P> {
+        &self.arc
+    }
+}
+
+impl<P> Clone for ArcBytes<P>
+where
+    P: ArcPool,
+{
+    fn clone(&self) -> Self {
+        ArcBytes {
+            arc: self.arc.clone(),
+            start: self.start,
+            end: self.end,
+        }
+    }
+}
+
+impl<P> From<Arc<P>> for ArcBytes<P>
+where
+    P: ArcPool,
+    P::Value: AsSlice<Element = u8>,
+{
+    fn from(arc: Arc<P>) -> Self {
+        ArcBytes::new(arc)
+    }
+}
+
+impl<P> Deref for ArcBytes<P>
+where
+    P: ArcPool,
+    P::Value: AsSlice<Element = u8>,
+{
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.arc.as_slice()[self.start..self.end]
+    }
+}
+
+impl<P> AsRef<[u8]> for ArcBytes<P>
+where
+    P: ArcPool,
+    P::Value: AsSlice<Element = u8>,
+{
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<P> fmt::Debug for ArcBytes<P>
+where
+    P: ArcPool,
+    P::Value: AsSlice<Element = u8>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <[u8] as fmt::Debug>::fmt(self, f)
+    }
+}
+
+impl<P> PartialEq for ArcBytes<P>
+where
+    P: ArcPool,
+    P::Value: AsSlice<Element = u8>,
+{
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<P> PartialEq<[u8]> for ArcBytes<P>
+where
+    P: ArcPool,
+    P::Value: AsSlice<Element = u8>,
+{
+    fn eq(&self, other: &[u8]) -> bool {
+        **self == *other
+    }
+}
+
+impl<P> Eq for ArcBytes<P>
+where
+    P: ArcPool,
+    P::Value: AsSlice<Element = u8>,
+{
+}
+
+impl<P> Hash for ArcBytes<P>
+where
+    P: ArcPool,
+    P::Value: AsSlice<Element = u8>,
+{
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        (**self).hash(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Arc, ArcBytes, ArcInner};
+    use crate::{singleton::Pool, tests::memory};
+
+    #[test]
+    fn arc() {
+        crate::pool!(A: ArcInner<[u8; 4]>);
+
+        A::grow(memory::<ArcInner<[u8; 4]>>(1));
+
+        let mut x = Arc::<A>::new([1; 4]).unwrap();
+        assert_eq!(Arc::get_mut(&mut x), Some(&mut [1; 4]));
+        assert_eq!(Arc::<A>::new([2; 4]).err(), Some([2; 4]));
+
+        let y = x.clone();
+        assert_eq!(Arc::strong_count(&x), 2);
+        assert!(Arc::ptr_eq(&x, &y));
+        assert_eq!(*y, [1; 4]);
+        assert!(Arc::get_mut(&mut x).is_none());
+
+        // the memory block is only returned to the pool when the last `Arc` is dropped
+        drop(x);
+        assert!(A::alloc().is_none());
+        drop(y);
+        assert!(Arc::<A>::new([3; 4]).is_ok());
+    }
+
+    #[test]
+    fn arc_bytes() {
+        crate::pool!(A: ArcInner<[u8; 8]>);
+
+        A::grow(memory::<ArcInner<[u8; 8]>>(1));
+
+        let frame = ArcBytes::new(Arc::<A>::new([0, 1, 2, 3, 4, 5, 6, 7]).unwrap()).slice(..6);
+        assert_eq!(frame.len(), 6);
+
+        let mut rest = frame.slice(1..=4);
+        assert_eq!(&*rest, &[1, 2, 3, 4]);
+        assert_eq!(Arc::strong_count(rest.arc()), 2);
+
+        let head = rest.split_to(1);
+        assert_eq!(&*head, &[1]);
+        assert_eq!(&*rest, &[2, 3, 4]);
+
+        let tail = rest.split_off(2);
+        assert_eq!(&*rest, &[2, 3]);
+        assert_eq!(&*tail, &[4]);
+        assert!(tail.slice(1..).is_empty());
+
+        rest.truncate(1);
+        assert_eq!(rest, *frame.slice(2..3));
+
+        // the memory block is only returned to the pool when the last view is dropped
+        drop((frame, head, tail));
+        assert!(A::alloc().is_none());
+        drop(rest);
+        assert!(A::alloc().is_some());
+    }
+}
@@ -0,0 +1,375 @@
+//! Publish/subscribe broadcast of reference-counted messages
+//!
+//! A `Broadcast<POOL, S, D>` delivers each message published by its single `Publisher` to up to
+//! `S` `Subscriber`s. Messages are `Arc`s so every subscriber receives a clone of the same
+//! pointer, and the memory block is returned to `POOL` once all subscribers are done with it.
+//! Each subscriber has its own queue that holds up to `D` messages.
+//!
+//! ``` ignore
+//! use lifo::{pool, singleton::{arc::{Arc, ArcInner}, broadcast::Broadcast}};
+//!
+//! pool!(P: ArcInner<Reading>);
+//!
+//! static READINGS: Broadcast<P, 4, 2> = Broadcast::new();
+//!
+//! // sensor task
+//! let mut publisher = READINGS.publisher().unwrap();
+//! publisher.publish(Arc::new(sensor.read()).ok().unwrap());
+//!
+//! // display and logger tasks
+//! let mut subscriber = READINGS.subscribe().unwrap();
+//! if let Some(reading) = subscriber.recv() {
+//!     display.show(&reading);
+//! }
+//! ```
+//!
+//! Publishing never blocks: subscribers whose queue is full miss the message.
+
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use super::arc::{Arc, ArcPool};
+
+struct Slot<P>
+where
+    P: ArcPool,
+{
+    arc: UnsafeCell<MaybeUninit<Arc<P>>>,
+}
+
+impl<P> Slot<P>
+where
+    P: ArcPool,
+{
+    // only used to initialize `Queue.ring`
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: Slot<P> = Slot {
+        arc: UnsafeCell::new(MaybeUninit::uninit()),
+    };
+}
+
+// Single-producer single-consumer queue of `Arc`s; the producer is the publisher
+struct Queue<P, const D: usize>
+where
+    P: ArcPool,
+{
+    // whether a `Subscriber` owns the consumer side of the queue
+    subscribed: AtomicBool,
+    // `head` and `tail` count modulo `2 * D` so a full queue can be told apart from an empty one
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    ring: [Slot<P>; D],
+}
+
+impl<P, const D: usize> Queue<P, D>
+where
+    P: ArcPool,
+{
+    // only used to initialize `Broadcast.queues`
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: Queue<P, D> = Queue {
+        subscribed: AtomicBool::new(false),
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        ring: [Slot::EMPTY; D],
+    };
+
+    fn is_full(&self) -> bool {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Relaxed);
+
+        (if tail >= head {
+            tail - head
+        } else {
+            tail + 2 * D - head
+        }) == D
+    }
+
+    // NOTE(unsafe) must only be called by the publisher and when the queue is not full
+    unsafe fn push(&self, arc: Arc<P>) {
+        let tail = self.tail.load(Ordering::Relaxed);
+
+        (*self.ring[tail % D].arc.get()) = MaybeUninit::new(arc);
+        self.tail.store(Self::next(tail), Ordering::Release);
+    }
+
+    // NOTE(unsafe) must only be called by the owner of the consumer side
+    unsafe fn pop(&self) -> Option<Arc<P>> {
+        let head = self.head.load(Ordering::Relaxed);
+
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let arc = (*self.ring[head % D].arc.get()).as_ptr().read();
+        self.head.store(Self::next(head), Ordering::Release);
+
+        Some(arc)
+    }
+
+    fn next(index: usize) -> usize {
+        if index + 1 == 2 * D {
+            0
+        } else {
+            index + 1
+        }
+    }
+}
+
+/// Broadcasts `Arc`s of the pool `POOL` to up to `S` subscribers, each with a queue of depth `D`
+pub struct Broadcast<POOL, const S: usize, const D: usize>
+where
+    POOL: ArcPool,
+{
+    published: AtomicBool,
+    queues: [Queue<POOL, D>; S],
+}
+
+unsafe impl<P, const S: usize, const D: usize> Sync for Broadcast<P, S, D>
+where
+    P: ArcPool,
+    P::Value: Send + Sync,
+{
+}
+
+impl<P, const S: usize, const D: usize> Broadcast<P, S, D>
+where
+    P: ArcPool,
+{
+    /// Creates a new broadcast without publisher nor subscribers
+    pub const fn new() -> Self {
+        Broadcast {
+            published: AtomicBool::new(false),
+            queues: [Queue::EMPTY; S],
+        }
+    }
+
+    /// Returns the publisher of this broadcast
+    ///
+    /// Returns `None` if a `Publisher` already exists
+    pub fn publisher(&self) -> Option<Publisher<'_, P, S, D>> {
+        if self.published.swap(true, Ordering::Acquire) {
+            None
+        } else {
+            Some(Publisher { broadcast: self })
+        }
+    }
+
+    /// Subscribes to this broadcast
+    ///
+    /// Returns `None` if there are already `S` subscribers
+    ///
+    /// The subscriber only receives messages published after this call returns
+    pub fn subscribe(&self) -> Option<Subscriber<'_, P, D>> {
+        for queue in &self.queues {
+            if !queue.subscribed.load(Ordering::Relaxed)
+                && !queue.subscribed.swap(true, Ordering::Acquire)
+            {
+                // drop the messages that the publisher sent to the previous subscriber while it
+                // was being dropped
+                while let Some(arc) = unsafe { queue.pop() } {
+                    drop(arc);
+                }
+
+                return Some(Subscriber { queue });
+            }
+        }
+
+        None
+    }
+}
+
+impl<P, const S: usize, const D: usize> Default for Broadcast<P, S, D>
+where
+    P: ArcPool,
+{
+    fn default() -> Self {
+        Broadcast::new()
+    }
+}
+
+impl<P, const S: usize, const D: usize> Drop for Broadcast<P, S, D>
+where
+    P: ArcPool,
+{
+    fn drop(&mut self) {
+        for queue in &self.queues {
+            while let Some(arc) = unsafe { queue.pop() } {
+                drop(arc);
+            }
+        }
+    }
+}
+
+impl<P, const S: usize, const D: usize> fmt::Debug for Broadcast<P, S, D>
+where
+    P: ArcPool,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Broadcast")
+            .field("subscribers", &S)
+            .field("depth", &D)
+            .finish()
+    }
+}
+
+/// The publishing side of a `Broadcast`
+///
+/// Dropping the publisher lets `Broadcast::publisher` hand out a new one
+pub struct Publisher<'a, P, const S: usize, const D: usize>
+where
+    P: ArcPool,
+{
+    broadcast: &'a Broadcast<P, S, D>,
+}
+
+impl<P, const S: usize, const D: usize> Publisher<'_, P, S, D>
+where
+    P: ArcPool,
+{
+    /// Sends a clone of `message` to every subscriber whose queue is not full
+    ///
+    /// Returns the number of subscribers the message was sent to. If it's zero `message` is
+    /// dropped, which returns its memory block to the pool if there are no other clones.
+    pub fn publish(&mut self, message: Arc<P>) -> usize {
+        let mut sent = 0;
+
+        for queue in &self.broadcast.queues {
+            if queue.subscribed.load(Ordering::Acquire) && !queue.is_full() {
+                // NOTE(unsafe) there's a single publisher and the queue is not full
+                unsafe { queue.push(message.clone()) }
+                sent += 1;
+            }
+        }
+
+        sent
+    }
+}
+
+impl<P, const S: usize, const D: usize> Drop for Publisher<'_, P, S, D>
+where
+    P: ArcPool,
+{
+    fn drop(&mut self) {
+        self.broadcast.published.store(false, Ordering::Release)
+    }
+}
+
+impl<P, const S: usize, const D: usize> fmt::Debug for Publisher<'_, P, S, D>
+where
+    P: ArcPool,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Publisher").finish()
+    }
+}
+
+/// The receiving side of a `Broadcast`
+///
+/// Dropping the subscriber drops the messages still in its queue and frees its place
+pub struct Subscriber<'a, P, const D: usize>
+where
+    P: ArcPool,
+{
+    queue: &'a Queue<P, D>,
+}
+
+// NOTE(unsafe) a subscriber can be moved to another context along with the `Arc`s in its queue
+unsafe impl<P, const D: usize> Send for Subscriber<'_, P, D>
+where
+    P: ArcPool,
+    P::Value: Send + Sync,
+{
+}
+
+impl<P, const D: usize> Subscriber<'_, P, D>
+where
+    P: ArcPool,
+{
+    /// Receives the oldest message in the queue
+    ///
+    /// Returns `None` when the queue is observed as empty
+    pub fn recv(&mut self) -> Option<Arc<P>> {
+        // NOTE(unsafe) this subscriber owns the consumer side of the queue
+        unsafe { self.queue.pop() }
+    }
+
+    /// Returns `true` if the queue is observed as empty
+    pub fn is_empty(&self) -> bool {
+        self.queue.head.load(Ordering::Relaxed) == self.queue.tail.load(Ordering::Relaxed)
+    }
+}
+
+impl<P, const D: usize> Drop for Subscriber<'_, P, D>
+where
+    P: ArcPool,
+{
+    fn drop(&mut self) {
+        while let Some(arc) = self.recv() {
+            drop(arc);
+        }
+
+        self.queue.subscribed.store(false, Ordering::Release)
+    }
+}
+
+impl<P, const D: usize> fmt::Debug for Subscriber<'_, P, D>
+where
+    P: ArcPool,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Subscriber").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Broadcast;
+    use crate::{
+        singleton::{
+            arc::{Arc, ArcInner},
+            Pool,
+        },
+        tests::memory,
+    };
+
+    #[test]
+    fn broadcast() {
+        crate::pool!(A: ArcInner<u8>);
+
+        A::grow(memory::<ArcInner<u8>>(2));
+
+        let broadcast = Broadcast::<A, 2, 1>::new();
+        let mut publisher = broadcast.publisher().unwrap();
+        assert!(broadcast.publisher().is_none());
+
+        let mut a = broadcast.subscribe().unwrap();
+        let mut b = broadcast.subscribe().unwrap();
+        assert!(broadcast.subscribe().is_none());
+
+        assert_eq!(publisher.publish(Arc::new(1).unwrap()), 2);
+        // both queues are full; the message is dropped right away
+        assert_eq!(publisher.publish(Arc::new(2).unwrap()), 0);
+
+        let x = a.recv().unwrap();
+        assert_eq!(*x, 1);
+        assert_eq!(Arc::strong_count(&x), 2);
+        assert_eq!(publisher.publish(Arc::new(3).unwrap()), 1);
+
+        assert_eq!(*b.recv().unwrap(), 1);
+        assert!(b.is_empty());
+        drop(x);
+
+        // the place of a dropped subscriber can be taken by a new one
+        drop(b);
+        let c = broadcast.subscribe().unwrap();
+        assert!(c.is_empty());
+
+        assert_eq!(*a.recv().unwrap(), 3);
+        assert!(a.recv().is_none());
+    }
+}
@@ -0,0 +1,566 @@
+//! Chained byte buffers for packet assembly
+//!
+//! A `Chain` links several memory blocks, i.e. segments, into a single logical packet, like BSD's
+//! `mbuf`s or Linux's `sk_buff`s. Headers can be prepended into the free space at the front of
+//! the first segment, and payloads appended to the back of the last one, without moving the rest
+//! of the packet. Segments are linked through the `next` pointer of their memory block.
+//!
+//! ``` ignore
+//! use lifo::{pool, singleton::chain::{Chain, Segment}};
+//!
+//! pool!(Segments: Segment<[u8; 128]>);
+//!
+//! // leave room for the UDP, IPv4 and Ethernet headers in the first segment
+//! let mut packet = Chain::<Segments>::with_headroom(8 + 20 + 14);
+//! packet.append(payload)?;
+//! packet.prepend(&udp_header(packet.len()))?;
+//! packet.prepend(&ipv4_header(packet.len()))?;
+//! packet.prepend(&ethernet_header())?;
+//!
+//! for segment in packet.segments() {
+//!     dma.enqueue(segment);
+//! }
+//! ```
+
+use core::{cmp, fmt, marker::PhantomData, mem, ptr, slice};
+
+use as_slice::AsMutSlice;
+
+use super::{waker, FromSliceError, Pool};
+use crate::Node;
+
+/// The data of a pool of chain segments: a byte buffer plus the range of it that's in use
+#[repr(C)]
+pub struct Segment<A> {
+    // NOTE `buf` must be the first field; see `buf`
+    buf: A,
+    start: usize,
+    end: usize,
+}
+
+/// A singleton pool of `Segment`s
+///
+/// Implemented for every singleton pool whose data type is `Segment<A>`, where `A` is a plain byte
+/// buffer like `[u8; N]`
+pub trait SegmentPool: Pool<Data = Segment<<Self as SegmentPool>::Buffer>> {
+    /// The type of the byte buffer of each segment
+    type Buffer: AsMutSlice<Element = u8> + 'static;
+}
+
+impl<P, A> SegmentPool for P
+where
+    P: Pool<Data = Segment<A>>,
+    A: AsMutSlice<Element = u8> + 'static,
+{
+    type Buffer = A;
+}
+
+/// A packet made of one or more segments of the pool `POOL`
+pub struct Chain<POOL>
+where
+    POOL: SegmentPool,
+{
+    head: *mut Node<POOL::Data>,
+    tail: *mut Node<POOL::Data>,
+    len: usize,
+    // free space left at the front of the first segment
+    headroom: usize,
+    _pool: PhantomData<POOL>,
+}
+
+impl<P> Chain<P>
+where
+    P: SegmentPool,
+{
+    // Size of the buffer of each segment
+    const CAPACITY: usize = mem::size_of::<P::Buffer>();
+
+    /// Creates a new empty chain
+    ///
+    /// This doesn't claim any memory block
+    pub fn new() -> Self {
+        Self::with_headroom(0)
+    }
+
+    /// Creates a new empty chain that leaves `headroom` bytes of free space at the front of its
+    /// first segment, so that many bytes of headers can later be prepended without claiming
+    /// another segment
+    ///
+    /// This doesn't claim any memory block
+    pub fn with_headroom(headroom: usize) -> Self {
+        Chain {
+            head: ptr::null_mut(),
+            tail: ptr::null_mut(),
+            len: 0,
+            headroom: cmp::min(headroom, Self::CAPACITY),
+            _pool: PhantomData,
+        }
+    }
+
+    /// Returns the length of the packet, in bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the packet contains no bytes
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Appends `data` to the back of the packet, claiming new segments as needed
+    ///
+    /// If the pool is observed as exhausted the packet is left unchanged
+    pub fn append(&mut self, mut data: &[u8]) -> Result<(), FromSliceError> {
+        let old_len = self.len;
+        let old_tail = self.tail;
+        let old_end = if old_tail.is_null() {
+            None
+        } else {
+            Some(unsafe { segment::<P>(old_tail).end })
+        };
+
+        while !data.is_empty() {
+            let spare = if self.tail.is_null() {
+                0
+            } else {
+                Self::CAPACITY - unsafe { segment::<P>(self.tail).end }
+            };
+
+            if spare == 0 {
+                let start = if self.head.is_null() {
+                    self.headroom
+                } else {
+                    0
+                };
+                let node = match Self::claim(start) {
+                    Some(node) => node,
+                    None => {
+                        // undo the changes made by this call
+                        let first = match old_end {
+                            Some(end) => unsafe {
+                                segment::<P>(old_tail).end = end;
+                                mem::replace(&mut (*old_tail).next, ptr::null_mut())
+                            },
+                            None => mem::replace(&mut self.head, ptr::null_mut()),
+                        };
+                        free_from::<P>(first, ptr::null_mut());
+                        self.tail = old_tail;
+                        self.len = old_len;
+
+                        return Err(FromSliceError::Exhausted);
+                    }
+                };
+
+                if self.tail.is_null() {
+                    self.head = node;
+                } else {
+                    unsafe { (*self.tail).next = node }
+                }
+                self.tail = node;
+
+                continue;
+            }
+
+            let n = cmp::min(spare, data.len());
+            unsafe {
+                let seg = segment::<P>(self.tail);
+                ptr::copy_nonoverlapping(data.as_ptr(), buf::<P>(self.tail).add(seg.end), n);
+                seg.end += n;
+            }
+            self.len += n;
+            data = &data[n..];
+        }
+
+        Ok(())
+    }
+
+    /// Prepends `data` to the front of the packet, claiming new segments as needed
+    ///
+    /// New segments are filled from the back so the space left at their front can be used by
+    /// later calls to `prepend`. If the pool is observed as exhausted the packet is left unchanged
+    pub fn prepend(&mut self, mut data: &[u8]) -> Result<(), FromSliceError> {
+        let old_len = self.len;
+        let old_head = self.head;
+        let old_start = if old_head.is_null() {
+            None
+        } else {
+            Some(unsafe { segment::<P>(old_head).start })
+        };
+
+        while !data.is_empty() {
+            let room = if self.head.is_null() {
+                0
+            } else {
+                unsafe { segment::<P>(self.head).start }
+            };
+
+            if room == 0 {
+                let node = match Self::claim(Self::CAPACITY) {
+                    Some(node) => node,
+                    None => {
+                        // undo the changes made by this call
+                        free_from::<P>(self.head, old_head);
+                        self.head = old_head;
+                        match old_start {
+                            Some(start) => unsafe { segment::<P>(old_head).start = start },
+                            None => self.tail = ptr::null_mut(),
+                        }
+                        self.len = old_len;
+
+                        return Err(FromSliceError::Exhausted);
+                    }
+                };
+
+                unsafe { (*node).next = self.head }
+                if self.tail.is_null() {
+                    self.tail = node;
+                }
+                self.head = node;
+
+                continue;
+            }
+
+            let n = cmp::min(room, data.len());
+            let (rest, last) = data.split_at(data.len() - n);
+            unsafe {
+                let seg = segment::<P>(self.head);
+                seg.start -= n;
+                ptr::copy_nonoverlapping(last.as_ptr(), buf::<P>(self.head).add(seg.start), n);
+            }
+            self.len += n;
+            data = rest;
+        }
+
+        Ok(())
+    }
+
+    /// Appends the segments of `other` to the back of this packet
+    ///
+    /// This doesn't copy any data
+    pub fn concat(&mut self, mut other: Chain<P>) {
+        if other.head.is_null() {
+            return;
+        }
+
+        if self.tail.is_null() {
+            self.head = other.head;
+        } else {
+            unsafe { (*self.tail).next = other.head }
+        }
+        self.tail = other.tail;
+        self.len += other.len;
+
+        // NOTE the segments are now owned by this chain
+        other.head = ptr::null_mut();
+        other.tail = ptr::null_mut();
+    }
+
+    /// Removes the first `n` bytes of the packet, e.g. a header that has been parsed, returning
+    /// the segments that become empty to the pool
+    ///
+    /// The whole packet is removed if `n` is greater than its length
+    pub fn trim_front(&mut self, n: usize) {
+        let mut n = cmp::min(n, self.len);
+        self.len -= n;
+
+        while n != 0 {
+            let seg = unsafe { segment::<P>(self.head) };
+            let available = seg.end - seg.start;
+
+            if n < available {
+                seg.start += n;
+                return;
+            }
+
+            n -= available;
+            let head = self.head;
+            self.head = unsafe { (*head).next };
+            free_from::<P>(head, self.head);
+        }
+
+        if self.head.is_null() {
+            self.tail = ptr::null_mut();
+        }
+    }
+
+    /// Shortens the packet to `len` bytes, returning the segments that are no longer needed to
+    /// the pool
+    ///
+    /// This has no effect if `len` is greater than the packet's current length
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+
+        self.len = len;
+
+        if len == 0 {
+            free_from::<P>(
+                mem::replace(&mut self.head, ptr::null_mut()),
+                ptr::null_mut(),
+            );
+            self.tail = ptr::null_mut();
+            return;
+        }
+
+        let mut remaining = len;
+        let mut node = self.head;
+        loop {
+            let seg = unsafe { segment::<P>(node) };
+            let available = seg.end - seg.start;
+
+            if remaining <= available {
+                seg.end = seg.start + remaining;
+                break;
+            }
+
+            remaining -= available;
+            node = unsafe { (*node).next };
+        }
+
+        let next = unsafe { mem::replace(&mut (*node).next, ptr::null_mut()) };
+        free_from::<P>(next, ptr::null_mut());
+        self.tail = node;
+    }
+
+    /// Copies bytes of the packet, starting at `offset`, into `buf`
+    ///
+    /// Returns the number of bytes copied, which is less than `buf.len()` if the end of the packet
+    /// is reached first
+    pub fn copy_out(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let mut copied = 0;
+        self.walk(offset, |segment| {
+            let n = cmp::min(segment.len(), buf.len() - copied);
+            buf[copied..copied + n].copy_from_slice(&segment[..n]);
+            copied += n;
+            copied < buf.len()
+        });
+        copied
+    }
+
+    /// Overwrites bytes of the packet, starting at `offset`, with `data`
+    ///
+    /// Returns the number of bytes copied, which is less than `data.len()` if the end of the
+    /// packet is reached first; this method never grows the packet
+    pub fn copy_in(&mut self, offset: usize, data: &[u8]) -> usize {
+        let mut copied = 0;
+        self.walk(offset, |segment| {
+            let n = cmp::min(segment.len(), data.len() - copied);
+            segment[..n].copy_from_slice(&data[copied..copied + n]);
+            copied += n;
+            copied < data.len()
+        });
+        copied
+    }
+
+    /// Returns an iterator over the segments of the packet, as byte slices
+    pub fn segments(&self) -> Segments<'_, P> {
+        Segments {
+            node: self.head,
+            _chain: PhantomData,
+        }
+    }
+
+    // Calls `f` on the contents of the segments, starting at `offset` bytes into the packet, until
+    // it returns `false`
+    fn walk<F>(&self, mut offset: usize, mut f: F)
+    where
+        F: FnMut(&mut [u8]) -> bool,
+    {
+        let mut node = self.head;
+        while !node.is_null() {
+            unsafe {
+                let seg = segment::<P>(node);
+                let available = seg.end - seg.start;
+
+                if offset < available {
+                    let contents = slice::from_raw_parts_mut(
+                        buf::<P>(node).add(seg.start + offset),
+                        available - offset,
+                    );
+                    if !f(contents) {
+                        return;
+                    }
+                    offset = 0;
+                } else {
+                    offset -= available;
+                }
+
+                node = (*node).next;
+            }
+        }
+    }
+
+    fn claim(start: usize) -> Option<*mut Node<P::Data>> {
+        let block = P::alloc()?;
+        let node = block.inner.node.as_ptr();
+
+        // NOTE the memory block is now owned by the chain
+        mem::forget(block);
+
+        unsafe {
+            (*node).next = ptr::null_mut();
+
+            // NOTE(unsafe) only the range bookkeeping is initialized; the bytes of the buffer are
+            // only read after they have been written
+            let seg = (*node).data.get();
+            (*seg).start = start;
+            (*seg).end = start;
+        }
+
+        Some(node)
+    }
+}
+
+// NOTE(unsafe) `node` must be a segment owned by a chain
+unsafe fn segment<'a, P>(node: *mut Node<P::Data>) -> &'a mut Segment<P::Buffer>
+where
+    P: SegmentPool,
+{
+    &mut *(*node).data.get()
+}
+
+// Returns a pointer to the start of the buffer of the segment `node`
+unsafe fn buf<P>(node: *mut Node<P::Data>) -> *mut u8
+where
+    P: SegmentPool,
+{
+    // NOTE `Segment` is `repr(C)` and `buf` is its first field
+    (*node).data.get() as *mut u8
+}
+
+// Returns the segments in `[node, end)` to the pool
+fn free_from<P>(mut node: *mut Node<P::Data>, end: *mut Node<P::Data>)
+where
+    P: SegmentPool,
+{
+    while node != end {
+        let next = unsafe { (*node).next };
+
+        // NOTE(unsafe) `node` is not null because `end` is reachable from it
+        P::ptr().release(unsafe { ptr::NonNull::new_unchecked(node) });
+        waker::wake_one(P::wakers());
+
+        node = next;
+    }
+}
+
+impl<P> Default for Chain<P>
+where
+    P: SegmentPool,
+{
+    fn default() -> Self {
+        Chain::new()
+    }
+}
+
+impl<P> Drop for Chain<P>
+where
+    P: SegmentPool,
+{
+    fn drop(&mut self) {
+        free_from::<P>(self.head, ptr::null_mut())
+    }
+}
+
+unsafe impl<P> Send for Chain<P> where P: SegmentPool {}
+
+unsafe impl<P> Sync for Chain<P> where P: SegmentPool {}
+
+impl<P> fmt::Debug for Chain<P>
+where
+    P: SegmentPool,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.segments()).finish()
+    }
+}
+
+/// An iterator over the segments of a `Chain`
+pub struct Segments<'a, P>
+where
+    P: SegmentPool,
+{
+    node: *mut Node<P::Data>,
+    _chain: PhantomData<&'a Chain<P>>,
+}
+
+impl<'a, P> Iterator for Segments<'a, P>
+where
+    P: SegmentPool,
+{
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        if self.node.is_null() {
+            return None;
+        }
+
+        unsafe {
+            let seg = segment::<P>(self.node);
+            let contents =
+                slice::from_raw_parts(buf::<P>(self.node).add(seg.start), seg.end - seg.start);
+            self.node = (*self.node).next;
+
+            Some(contents)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Chain, Segment};
+    use crate::{
+        singleton::{FromSliceError, Pool},
+        tests::memory,
+    };
+
+    #[test]
+    fn chain() {
+        crate::pool!(A: Segment<[u8; 4]>);
+
+        A::grow(memory::<Segment<[u8; 4]>>(3));
+
+        let mut packet = Chain::<A>::with_headroom(2);
+        packet.append(b"cdefgh").unwrap();
+        packet.prepend(b"ab").unwrap();
+        assert_eq!(
+            packet.segments().collect::<Vec<_>>(),
+            [&b"abcd"[..], b"efgh"]
+        );
+
+        // the packet is left unchanged when the pool runs out of segments
+        assert_eq!(packet.prepend(b"0123456"), Err(FromSliceError::Exhausted));
+        assert_eq!(packet.append(b"ijklm"), Err(FromSliceError::Exhausted));
+        assert_eq!(packet.len(), 8);
+        assert!(A::alloc().is_some());
+
+        packet.prepend(b"_").unwrap();
+        assert_eq!(packet.len(), 9);
+
+        let mut buf = [0; 4];
+        assert_eq!(packet.copy_out(3, &mut buf), 4);
+        assert_eq!(&buf, b"cdef");
+        assert_eq!(packet.copy_in(7, b"GHI"), 2);
+        assert_eq!(packet.copy_out(6, &mut buf), 3);
+        assert_eq!(&buf[..3], b"fGH");
+
+        // the first segment is returned to the pool once it's empty
+        packet.trim_front(4);
+        assert_eq!(packet.segments().collect::<Vec<_>>(), [&b"d"[..], b"efGH"]);
+        packet.truncate(3);
+        assert_eq!(packet.segments().collect::<Vec<_>>(), [&b"d"[..], b"ef"]);
+
+        let mut other = Chain::<A>::new();
+        other.append(b"xy").unwrap();
+        packet.concat(other);
+        assert_eq!(packet.len(), 5);
+        assert_eq!(
+            format!("{:?}", packet),
+            format!("{:?}", [&b"d"[..], b"ef", b"xy"])
+        );
+
+        drop(packet);
+        assert!(A::alloc().is_some());
+    }
+}
@@ -0,0 +1,375 @@
+//! Size-class allocator over several pools of byte buffers
+
+use core::{
+    fmt,
+    marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    slice,
+};
+
+use as_slice::AsMutSlice;
+
+use super::{Box, FromSliceError, Pool};
+use crate::{ByteArray, Init, Node};
+
+/// A list of global singleton pools of byte buffers, i.e. size classes, ordered from smallest to
+/// largest
+///
+/// This trait is implemented for tuples of up to 8 pools whose data type is a byte array, see
+/// `ByteArray`, e.g. `(Small, Medium, Large)`
+pub trait Classes {
+    /// Number of size classes
+    const COUNT: usize;
+
+    #[doc(hidden)]
+    fn size(class: usize) -> usize;
+
+    // Claims a memory block from `class` and copies `data` into it; returns the node and data
+    // pointers of the memory block
+    #[doc(hidden)]
+    fn alloc(class: usize, data: &[u8]) -> Result<(NonNull<u8>, NonNull<u8>), FromSliceError>;
+
+    // NOTE(unsafe) `node` must have been returned by `alloc` for the same `class`
+    #[doc(hidden)]
+    unsafe fn free(class: usize, node: NonNull<u8>);
+
+    #[cfg(feature = "stats")]
+    #[doc(hidden)]
+    fn stats(class: usize) -> crate::Stats;
+
+    // Offset of the data of a memory block of `class` from the start of the memory block
+    #[cfg(feature = "global-alloc")]
+    #[doc(hidden)]
+    fn data_offset(class: usize) -> usize;
+}
+
+fn alloc<P>(data: &[u8]) -> Result<(NonNull<u8>, NonNull<u8>), FromSliceError>
+where
+    P: Pool,
+    P::Data: ByteArray,
+{
+    let (mut block, _) = P::from_slice(data)?;
+    let data = NonNull::from(block.as_mut_slice()).cast();
+    let node = block.inner.node.cast();
+
+    // NOTE the memory block is now owned by the `ClassBox`
+    mem::forget(block);
+
+    Ok((node, data))
+}
+
+#[cfg(feature = "global-alloc")]
+fn data_offset<P>() -> usize
+where
+    P: Pool,
+{
+    let node = mem::MaybeUninit::<Node<P::Data>>::uninit();
+    let base = node.as_ptr();
+
+    // NOTE(unsafe) no reference to the uninitialized node is created
+    unsafe { core::ptr::addr_of!((*base).data) as usize - base as usize }
+}
+
+unsafe fn free<P>(node: NonNull<u8>)
+where
+    P: Pool,
+{
+    drop(Box::<P, Init>::from_box(crate::Box {
+        _state: PhantomData,
+        node: node.cast::<Node<P::Data>>(),
+    }))
+}
+
+macro_rules! classes {
+    ($($P:ident $i:tt),+) => {
+        impl<$($P),+> Classes for ($($P,)+)
+        where
+            $(
+                $P: Pool,
+                $P::Data: ByteArray,
+            )+
+        {
+            const COUNT: usize = [$($i),+].len();
+
+            fn size(class: usize) -> usize {
+                match class {
+                    $($i => mem::size_of::<$P::Data>(),)+
+                    _ => 0,
+                }
+            }
+
+            fn alloc(
+                class: usize,
+                data: &[u8],
+            ) -> Result<(NonNull<u8>, NonNull<u8>), FromSliceError> {
+                match class {
+                    $($i => alloc::<$P>(data),)+
+                    _ => Err(FromSliceError::TooLong),
+                }
+            }
+
+            unsafe fn free(class: usize, node: NonNull<u8>) {
+                match class {
+                    $($i => free::<$P>(node),)+
+                    _ => unreachable!(),
+                }
+            }
+
+            #[cfg(feature = "stats")]
+            fn stats(class: usize) -> crate::Stats {
+                match class {
+                    $($i => $P::stats(),)+
+                    _ => unreachable!(),
+                }
+            }
+
+            #[cfg(feature = "global-alloc")]
+            fn data_offset(class: usize) -> usize {
+                match class {
+                    $($i => data_offset::<$P>(),)+
+                    _ => unreachable!(),
+                }
+            }
+        }
+    };
+}
+
+classes!(A 0);
+classes!(A 0, B 1);
+classes!(A 0, B 1, C 2);
+classes!(A 0, B 1, C 2, D 3);
+classes!(A 0, B 1, C 2, D 3, E 4);
+classes!(A 0, B 1, C 2, D 3, E 4, F 5);
+classes!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+classes!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+/// A size-class allocator: a facade over the pools `C` that hands out byte buffers from the
+/// smallest class that fits
+///
+/// ``` ignore
+/// pool!(Small: [u8; 32]; capacity = 16);
+/// pool!(Medium: [u8; 128]; capacity = 8);
+/// pool!(Large: [u8; 1024]; capacity = 2);
+///
+/// type Payloads = ClassPool<(Small, Medium, Large)>;
+///
+/// // comes from `Medium`, or from `Large` if `Medium` is exhausted
+/// let payload = Payloads::from_slice(&[0; 100]).unwrap();
+/// assert_eq!(payload.len(), 100);
+/// assert_eq!(payload.capacity(), 128);
+/// ```
+pub struct ClassPool<C>
+where
+    C: Classes,
+{
+    _classes: PhantomData<C>,
+}
+
+impl<C> ClassPool<C>
+where
+    C: Classes,
+{
+    /// Claims a zeroed buffer of `len` bytes from the smallest class that can hold it
+    ///
+    /// Larger classes are tried when that class is exhausted
+    pub fn alloc(len: usize) -> Result<ClassBox<C>, FromSliceError> {
+        Self::claim(len, &[])
+    }
+
+    /// Claims a buffer from the smallest class that can hold `data` and copies `data` into it
+    ///
+    /// Larger classes are tried when that class is exhausted
+    pub fn from_slice(data: &[u8]) -> Result<ClassBox<C>, FromSliceError> {
+        Self::claim(data.len(), data)
+    }
+
+    fn claim(len: usize, data: &[u8]) -> Result<ClassBox<C>, FromSliceError> {
+        let mut error = FromSliceError::TooLong;
+
+        for class in 0..C::COUNT {
+            if len <= C::size(class) {
+                match C::alloc(class, data) {
+                    Ok((node, data)) => {
+                        return Ok(ClassBox {
+                            _classes: PhantomData,
+                            class,
+                            node,
+                            data,
+                            len,
+                        })
+                    }
+                    Err(e) => error = e,
+                }
+            }
+        }
+
+        Err(error)
+    }
+
+    /// Returns the size, in bytes, of the buffers of `class`
+    ///
+    /// Returns `None` if there's no such class
+    pub fn class_size(class: usize) -> Option<usize> {
+        if class < C::COUNT {
+            Some(C::size(class))
+        } else {
+            None
+        }
+    }
+
+    /// Returns a snapshot of the statistics of the pool of `class`
+    ///
+    /// Returns `None` if there's no such class
+    #[cfg(feature = "stats")]
+    pub fn stats(class: usize) -> Option<crate::Stats> {
+        if class < C::COUNT {
+            Some(C::stats(class))
+        } else {
+            None
+        }
+    }
+}
+
+/// A byte buffer claimed from a `ClassPool`
+///
+/// Dropping the buffer returns it to the pool of its class
+pub struct ClassBox<C>
+where
+    C: Classes,
+{
+    _classes: PhantomData<C>,
+    class: usize,
+    node: NonNull<u8>,
+    data: NonNull<u8>,
+    len: usize,
+}
+
+impl<C> ClassBox<C>
+where
+    C: Classes,
+{
+    /// Returns the size class the buffer was claimed from
+    pub fn class(&self) -> usize {
+        self.class
+    }
+
+    /// Returns the maximum number of bytes the buffer can hold, i.e. the size of its class
+    pub fn capacity(&self) -> usize {
+        C::size(self.class)
+    }
+
+    /// Changes the length of the buffer
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `len` is greater than the capacity of the buffer
+    pub fn set_len(&mut self, len: usize) {
+        assert!(len <= self.capacity(), "length exceeds the buffer capacity");
+
+        self.len = len;
+    }
+}
+
+impl<C> Deref for ClassBox<C>
+where
+    C: Classes,
+{
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // NOTE(unsafe) the whole memory block was initialized by `alloc`
+        unsafe { slice::from_raw_parts(self.data.as_ptr(), self.len) }
+    }
+}
+
+impl<C> DerefMut for ClassBox<C>
+where
+    C: Classes,
+{
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.data.as_ptr(), self.len) }
+    }
+}
+
+impl<C> AsRef<[u8]> for ClassBox<C>
+where
+    C: Classes,
+{
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<C> AsMut<[u8]> for ClassBox<C>
+where
+    C: Classes,
+{
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+impl<C> Drop for ClassBox<C>
+where
+    C: Classes,
+{
+    fn drop(&mut self) {
+        unsafe { C::free(self.class, self.node) }
+    }
+}
+
+unsafe impl<C> Send for ClassBox<C> where C: Classes {}
+
+unsafe impl<C> Sync for ClassBox<C> where C: Classes {}
+
+impl<C> fmt::Debug for ClassBox<C>
+where
+    C: Classes,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <[u8] as fmt::Debug>::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClassPool, FromSliceError};
+    #[cfg(feature = "stats")]
+    use crate::singleton::Pool;
+
+    #[test]
+    fn class_pool() {
+        crate::pool!(Small: [u8; 4]; capacity = 1, Large: [u8; 16]; capacity = 1);
+
+        type Payloads = ClassPool<(Small, Large)>;
+
+        Small::init();
+        Large::init();
+
+        assert_eq!(Payloads::class_size(1), Some(16));
+        assert_eq!(Payloads::class_size(2), None);
+
+        let a = Payloads::from_slice(&[1, 2, 3]).unwrap();
+        assert_eq!(a.class(), 0);
+        assert_eq!(&*a, &[1, 2, 3]);
+
+        // `Small` is exhausted so the next buffer comes from `Large`
+        let mut b = Payloads::alloc(2).unwrap();
+        assert_eq!(b.class(), 1);
+        assert_eq!(b.capacity(), 16);
+        b.set_len(5);
+        assert_eq!(&*b, &[0; 5]);
+
+        assert_eq!(Payloads::alloc(1).err(), Some(FromSliceError::Exhausted));
+        assert_eq!(Payloads::alloc(17).err(), Some(FromSliceError::TooLong));
+
+        #[cfg(feature = "stats")]
+        assert_eq!(Payloads::stats(0), Some(Small::stats()));
+
+        // the buffer is returned to its class
+        drop(a);
+        assert_eq!(Payloads::alloc(4).unwrap().class(), 0);
+    }
+}
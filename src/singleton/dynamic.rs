@@ -0,0 +1,221 @@
+//! Type-erased handles to global singleton pools
+
+use core::{
+    any::TypeId,
+    fmt,
+    mem::{self, ManuallyDrop, MaybeUninit},
+    ops::{Deref, DerefMut},
+    ptr,
+};
+
+use super::{waker, Box, Pool, WakerSlot};
+use crate::{Init, Uninit};
+
+/// A handle to *some* global singleton pool of `T` values
+///
+/// Unlike the `Pool` trait, which is implemented by the pool types declared with `pool!`, this
+/// handle is a value so it can be picked at runtime, stored in a driver struct and passed around
+/// without making the code generic over the pool type.
+pub struct AnyPool<T>
+where
+    T: 'static,
+{
+    pool: &'static crate::Pool<T>,
+    wakers: &'static [WakerSlot],
+}
+
+impl<T> AnyPool<T> {
+    /// Returns a handle to the pool `P`
+    pub fn of<P>() -> Self
+    where
+        P: Pool<Data = T>,
+    {
+        AnyPool {
+            pool: P::ptr(),
+            wakers: P::wakers(),
+        }
+    }
+
+    /// Claims a memory block from the pool
+    ///
+    /// Returns `None` when the pool is observed as exhausted
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc(&self) -> Option<AnyBox<T, Uninit>> {
+        let pool = *self;
+
+        self.pool.alloc().map(|inner| AnyBox {
+            pool,
+            inner: ManuallyDrop::new(inner),
+        })
+    }
+
+    /// Returns a snapshot of the statistics of the pool
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> crate::Stats {
+        self.pool.stats()
+    }
+}
+
+impl<T> Clone for AnyPool<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for AnyPool<T> {}
+
+impl<T> fmt::Debug for AnyPool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AnyPool")
+            .field("pool", &(self.pool as *const crate::Pool<T>))
+            .finish()
+    }
+}
+
+/// A memory block that belongs to the global singleton pool referred to by an `AnyPool` handle
+///
+/// Like `Box`, dropping an `AnyBox` returns the memory block to its pool
+pub struct AnyBox<T, STATE = Init>
+where
+    T: 'static,
+    STATE: 'static,
+{
+    pool: AnyPool<T>,
+    // NOTE `ManuallyDrop` because `Drop` returns the memory block to the pool
+    inner: ManuallyDrop<crate::Box<T, STATE>>,
+}
+
+impl<T, S> AnyBox<T, S> {
+    /// Returns the handle of the pool this memory block belongs to
+    pub fn pool(&self) -> AnyPool<T> {
+        self.pool
+    }
+
+    fn into_parts(self) -> (AnyPool<T>, crate::Box<T, S>) {
+        let pool = self.pool;
+        let inner = unsafe { ptr::read(&self.inner) };
+
+        mem::forget(self);
+
+        (pool, ManuallyDrop::into_inner(inner))
+    }
+}
+
+impl<T> AnyBox<T, Uninit> {
+    /// Initializes this memory block
+    pub fn init(self, val: T) -> AnyBox<T, Init> {
+        let (pool, inner) = self.into_parts();
+
+        AnyBox {
+            pool,
+            inner: ManuallyDrop::new(inner.init(val)),
+        }
+    }
+
+    /// Returns a reference to the uninitialized memory block
+    pub fn as_uninit(&self) -> &MaybeUninit<T> {
+        self.inner.as_uninit()
+    }
+
+    /// Returns a mutable reference to the uninitialized memory block
+    pub fn as_uninit_mut(&mut self) -> &mut MaybeUninit<T> {
+        self.inner.as_uninit_mut()
+    }
+}
+
+impl<P, S> From<Box<P, S>> for AnyBox<P::Data, S>
+where
+    P: Pool,
+{
+    fn from(block: Box<P, S>) -> Self {
+        AnyBox {
+            pool: AnyPool::of::<P>(),
+            inner: ManuallyDrop::new(block.into_box()),
+        }
+    }
+}
+
+impl<T> Deref for AnyBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.inner.deref()
+    }
+}
+
+impl<T> DerefMut for AnyBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.inner.deref_mut()
+    }
+}
+
+impl<T, S> Drop for AnyBox<T, S> {
+    fn drop(&mut self) {
+        if TypeId::of::<S>() == TypeId::of::<Init>() {
+            unsafe {
+                ptr::drop_in_place(self.inner.node.as_ref().data.get());
+            }
+        }
+
+        self.pool.pool.release(self.inner.node);
+
+        waker::wake_one(self.pool.wakers)
+    }
+}
+
+unsafe impl<T, S> Send for AnyBox<T, S> where T: Send {}
+
+unsafe impl<T, S> Sync for AnyBox<T, S> where T: Sync {}
+
+impl<T> fmt::Debug for AnyBox<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <T as fmt::Debug>::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnyBox, AnyPool};
+    use crate::{singleton::Pool, tests::memory};
+
+    struct Driver {
+        buffers: AnyPool<[u8; 4]>,
+    }
+
+    impl Driver {
+        fn rx(&self, byte: u8) -> Option<AnyBox<[u8; 4]>> {
+            self.buffers.alloc().map(|block| block.init([byte; 4]))
+        }
+    }
+
+    #[test]
+    fn any() {
+        crate::pool!(A: [u8; 4], B: [u8; 4]);
+
+        A::grow(memory::<[u8; 4]>(1));
+
+        let a = Driver {
+            buffers: AnyPool::of::<A>(),
+        };
+        let b = Driver {
+            buffers: AnyPool::of::<B>(),
+        };
+
+        let x = a.rx(1).unwrap();
+        assert_eq!(*x, [1; 4]);
+        assert!(a.rx(2).is_none());
+        assert!(b.rx(3).is_none());
+
+        // the memory block is returned to `A`
+        drop(x);
+        assert!(A::alloc().is_some());
+
+        let y: AnyBox<[u8; 4]> = A::alloc().unwrap().init([4; 4]).into();
+        assert_eq!(*y, [4; 4]);
+    }
+}
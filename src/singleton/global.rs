@@ -0,0 +1,169 @@
+//! `GlobalAlloc` on top of size classes
+
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cmp,
+    marker::PhantomData,
+    ptr::{self, NonNull},
+};
+
+use super::Classes;
+
+/// A global allocator that serves allocations from the pools `C`, i.e. from size classes
+///
+/// An allocation is served by the smallest class that can hold it. Unlike `ClassPool`, larger
+/// classes are *not* tried when that class is exhausted; the allocation fails instead. This keeps
+/// the behavior deterministic: the class, and thus the amount of memory, used by an allocation
+/// only depends on its size.
+///
+/// Allocations are also rejected when the memory blocks of their class are not sufficiently
+/// aligned. The data of a memory block is always aligned to, at least, the alignment of its data
+/// type so pools of byte buffers are only guaranteed to serve byte-aligned allocations; in
+/// practice memory blocks are usually pointer-aligned.
+///
+/// ``` ignore
+/// pool!(Small: [u8; 32]; capacity = 64);
+/// pool!(Medium: [u8; 256]; capacity = 16);
+/// pool!(Large: [u8; 2048]; capacity = 2);
+///
+/// #[global_allocator]
+/// static A: PoolAlloc<(Small, Medium, Large)> = PoolAlloc::new();
+///
+/// #[entry]
+/// fn main() -> ! {
+///     Small::init();
+///     Medium::init();
+///     Large::init();
+///
+///     // served by `Medium`
+///     let v: Vec<u8> = Vec::with_capacity(100);
+///
+///     // ..
+/// }
+/// ```
+pub struct PoolAlloc<C> {
+    _classes: PhantomData<C>,
+}
+
+impl<C> PoolAlloc<C> {
+    /// Creates a new allocator
+    pub const fn new() -> Self {
+        PoolAlloc {
+            _classes: PhantomData,
+        }
+    }
+}
+
+impl<C> PoolAlloc<C>
+where
+    C: Classes,
+{
+    // Returns the class that serves allocations of `size` bytes
+    fn class(size: usize) -> Option<usize> {
+        (0..C::COUNT).find(|class| size <= C::size(*class))
+    }
+}
+
+unsafe impl<C> GlobalAlloc for PoolAlloc<C>
+where
+    C: Classes,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let class = match Self::class(layout.size()) {
+            Some(class) => class,
+            None => return ptr::null_mut(),
+        };
+
+        match C::alloc(class, &[]) {
+            Ok((node, data)) => {
+                if data.as_ptr() as usize & (layout.align() - 1) == 0 {
+                    data.as_ptr()
+                } else {
+                    C::free(class, node);
+
+                    ptr::null_mut()
+                }
+            }
+            Err(_) => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        // NOTE `Classes::alloc` already zeroes the memory block
+        self.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // NOTE(unwrap) `ptr` was allocated with the same `layout` so it has a class
+        let class = Self::class(layout.size()).unwrap();
+        let node = ptr.sub(C::data_offset(class));
+
+        C::free(class, NonNull::new_unchecked(node))
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // the memory block can be reused if the new size maps to the same class
+        if Self::class(layout.size()) == Self::class(new_size) {
+            return ptr;
+        }
+
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let new_ptr = self.alloc(new_layout);
+        if !new_ptr.is_null() {
+            ptr::copy_nonoverlapping(ptr, new_ptr, cmp::min(layout.size(), new_size));
+            self.dealloc(ptr, layout);
+        }
+        new_ptr
+    }
+}
+
+impl<C> Default for PoolAlloc<C> {
+    fn default() -> Self {
+        PoolAlloc::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::{GlobalAlloc, Layout};
+
+    use super::PoolAlloc;
+
+    #[test]
+    fn pool_alloc() {
+        crate::pool!(Small: [u8; 4]; capacity = 1, Large: [u8; 16]; capacity = 1);
+
+        static A: PoolAlloc<(Small, Large)> = PoolAlloc::new();
+
+        Small::init();
+        Large::init();
+
+        unsafe {
+            let small = Layout::from_size_align(3, 1).unwrap();
+            let x = A.alloc(small);
+            assert!(!x.is_null());
+            x.write(42);
+
+            // `Small` is exhausted and allocations don't spill into `Large`
+            assert!(A.alloc(small).is_null());
+            assert!(A.alloc(Layout::from_size_align(17, 1).unwrap()).is_null());
+
+            // growing past the class moves the data to `Large`
+            let y = A.realloc(x, small, 10);
+            assert!(!y.is_null());
+            assert_ne!(x, y);
+            assert_eq!(y.read(), 42);
+
+            // ..and returns the old memory block to `Small`
+            let x = A.alloc_zeroed(small);
+            assert!(!x.is_null());
+            assert_eq!(x.read(), 0);
+
+            // resizing within the class keeps the memory block
+            assert_eq!(A.realloc(y, Layout::from_size_align(10, 1).unwrap(), 16), y);
+
+            A.dealloc(x, small);
+            A.dealloc(y, Layout::from_size_align(16, 1).unwrap());
+        }
+    }
+}
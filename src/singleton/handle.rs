@@ -0,0 +1,170 @@
+//! Generation-counted weak handles
+
+use core::{marker::PhantomData, ptr::NonNull};
+
+use super::Pool;
+use crate::Node;
+
+/// A weak reference to a value stored in the global memory pool `POOL`
+///
+/// A handle doesn't keep the value alive. Every memory block carries a generation counter that's
+/// bumped whenever its value is dropped or moved out so a handle can tell whether the value it
+/// was created from is still around, even if the memory block has been reused for a new value.
+///
+/// Created with `Box::handle`
+pub struct Handle<POOL>
+where
+    POOL: Pool,
+{
+    _pool: PhantomData<POOL>,
+    node: NonNull<Node<POOL::Data>>,
+    generation: usize,
+}
+
+impl<P> Handle<P>
+where
+    P: Pool,
+{
+    pub(crate) fn new(node: NonNull<Node<P::Data>>) -> Self {
+        Handle {
+            _pool: PhantomData,
+            node,
+            generation: unsafe { node.as_ref().generation() },
+        }
+    }
+
+    /// Returns `true` if the value this handle was created from has not been freed yet
+    pub fn is_valid(&self) -> bool {
+        // NOTE(unsafe) memory blocks are never handed back to the system so `node` is always
+        // valid memory, even after the value has been freed
+        unsafe { self.node.as_ref().generation() == self.generation }
+    }
+
+    /// Returns a reference to the value, or `None` if the value has been freed
+    ///
+    /// # Safety
+    ///
+    /// The value must not be freed, moved out of its box or mutated while the returned reference
+    /// is in use. This holds, for example, if the box is only ever accessed from the same
+    /// execution context as the handle.
+    pub unsafe fn get(&self) -> Option<&P::Data> {
+        if self.is_valid() {
+            Some(&*self.node.as_ref().data.get())
+        } else {
+            None
+        }
+    }
+}
+
+impl<P> Clone for Handle<P>
+where
+    P: Pool,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<P> Copy for Handle<P> where P: Pool {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{singleton::Pool, tests::memory};
+
+    #[test]
+    fn handle() {
+        crate::pool!(A: u32);
+
+        A::grow(memory::<u32>(1));
+
+        let x = A::alloc().unwrap().init(1);
+        let h = x.handle();
+        assert_eq!(unsafe { h.get() }, Some(&1));
+
+        drop(x);
+        assert!(!h.is_valid());
+
+        // the memory block is reused but the handle stays invalid
+        let y = A::alloc().unwrap().init(2);
+        assert!(unsafe { h.get() }.is_none());
+
+        // moving the value out also invalidates the handle
+        let h = y.handle();
+        let (_, _y) = y.into_inner();
+        assert!(!h.is_valid());
+    }
+
+    #[test]
+    fn reinit() {
+        crate::pool!(A: u32);
+
+        A::grow(memory::<u32>(1));
+
+        let x = A::alloc().unwrap().init(1);
+        let h = x.handle();
+
+        // the memory block is kept but its value is dropped
+        let x = x.reinit().init(2);
+        assert!(!h.is_valid());
+
+        let h = x.handle();
+        assert_eq!(unsafe { h.get() }, Some(&2));
+    }
+
+    #[cfg(feature = "bytemuck")]
+    #[test]
+    fn dyn_box() {
+        crate::pool!(A: u32);
+
+        A::grow(memory::<u32>(1));
+
+        let x = A::alloc().unwrap().init(1);
+        let h = x.handle();
+
+        // the value stays in place when its type is erased
+        let x = x.try_cast::<[u8; 4]>().ok().unwrap();
+        assert!(h.is_valid());
+
+        // freeing the memory block through the `DynBox` invalidates the handle
+        drop(x);
+        assert!(!h.is_valid());
+    }
+
+    #[test]
+    fn generation() {
+        crate::pool!(A: u32);
+
+        A::grow(memory::<u32>(1));
+
+        let generation = |x: &crate::singleton::Box<A>| {
+            let node = x.inner.node;
+            (node, unsafe { node.as_ref().generation() })
+        };
+
+        // dropping a value bumps the generation of its memory block exactly once
+        let x = A::alloc().unwrap().init(1);
+        let (node, start) = generation(&x);
+        drop(x);
+        assert_eq!(unsafe { node.as_ref().generation() }, start + 1);
+
+        // ... even if the memory block is kept around
+        let x = A::alloc().unwrap().init(1);
+        let (node, start) = generation(&x);
+        let x = x.reinit();
+        assert_eq!(unsafe { node.as_ref().generation() }, start + 1);
+        drop(x);
+
+        let x = A::alloc().unwrap().init(1);
+        let (node, start) = generation(&x);
+        x.free_uninit();
+        assert_eq!(unsafe { node.as_ref().generation() }, start + 1);
+
+        #[cfg(feature = "bytemuck")]
+        {
+            let x = A::alloc().unwrap().init(1);
+            let (node, start) = generation(&x);
+            drop(x.try_cast::<[u8; 4]>().ok().unwrap());
+            assert_eq!(unsafe { node.as_ref().generation() }, start + 1);
+        }
+    }
+}
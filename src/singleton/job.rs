@@ -0,0 +1,163 @@
+//! Closures stored in memory blocks of a pool
+
+use core::{fmt, marker::PhantomData, mem::ManuallyDrop, ptr};
+
+use super::{Box, DynBox, Pool};
+
+// A `dyn FnOnce` can't be called in place, so the closure is called through a `&mut` that it's
+// moved out of
+trait Call {
+    // # Safety
+    //
+    // `self` must not be used, or dropped, afterwards
+    unsafe fn call_once(&mut self);
+}
+
+impl<F> Call for F
+where
+    F: FnOnce(),
+{
+    unsafe fn call_once(&mut self) {
+        ptr::read(self)()
+    }
+}
+
+/// A type-erased `FnOnce()` closure stored in a memory block of the pool `POOL`
+///
+/// This is the pool counterpart of `Box<dyn FnOnce() + Send>`: an interrupt handler can capture a
+/// small environment in a closure and hand it to thread mode, which later runs it, without a heap.
+/// The closure, including its captured environment, must fit in a memory block of the pool.
+///
+/// Dropping the job without running it drops the closure and returns the memory block to the pool
+///
+/// ``` ignore
+/// use lifo::{pool, singleton::Job};
+///
+/// // closures that capture up to 32 bytes
+/// pool!(J: [usize; 32 / core::mem::size_of::<usize>()]);
+///
+/// #[interrupt]
+/// fn USART1() {
+///     let byte = uart.read();
+///     if let Ok(job) = Job::<J>::new(move || handle_byte(byte)) {
+///         QUEUE.enqueue(job).ok();
+///     }
+/// }
+///
+/// #[entry]
+/// fn main() -> ! {
+///     // ..
+///     loop {
+///         if let Some(job) = QUEUE.dequeue() {
+///             job.run();
+///         }
+///     }
+/// }
+/// ```
+pub struct Job<POOL>
+where
+    POOL: Pool,
+{
+    f: DynBox<POOL, dyn Call + Send>,
+}
+
+impl<P> Job<P>
+where
+    P: Pool,
+{
+    /// Claims a memory block from the pool `P` and moves `f` into it
+    ///
+    /// Returns `f` back when the pool is observed as exhausted, or if `f` doesn't fit in a memory
+    /// block, either because it's too big or because it needs a stricter alignment (see
+    /// `Box::coerce`)
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    pub fn new<F>(f: F) -> Result<Self, F>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let block = match P::alloc() {
+            Some(block) => block,
+            None => return Err(f),
+        };
+
+        let f = block.coerce(f, |f| f as &mut (dyn Call + Send))?;
+
+        Ok(Job { f })
+    }
+
+    /// Runs the closure and returns the memory block to the pool
+    pub fn run(self) {
+        let f = ManuallyDrop::new(self.f);
+
+        // NOTE(unsafe) the closure is moved out of the memory block, which is then returned to the
+        // pool without running the destructor of its contents
+        unsafe { (*f.ptr.as_ptr()).call_once() }
+
+        Box::<P> {
+            _pool: PhantomData,
+            inner: ManuallyDrop::new(crate::Box {
+                _state: PhantomData,
+                node: f.node,
+            }),
+        }
+        .free_uninit()
+    }
+}
+
+impl<P> fmt::Debug for Job<P>
+where
+    P: Pool,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Job").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::Job;
+    use crate::{singleton::Pool, tests::memory};
+
+    #[test]
+    fn job() {
+        static SUM: AtomicUsize = AtomicUsize::new(0);
+
+        crate::pool!(J: [usize; 2]);
+
+        J::grow(memory::<[usize; 2]>(1));
+
+        let (a, b) = (1usize, 2usize);
+        let job = Job::<J>::new(move || {
+            SUM.fetch_add(a + b, Ordering::Relaxed);
+        })
+        .ok()
+        .unwrap();
+
+        // the pool is exhausted
+        assert!(Job::<J>::new(|| {}).is_err());
+
+        job.run();
+        assert_eq!(SUM.load(Ordering::Relaxed), 3);
+
+        // the memory block is back in the pool
+        let job = Job::<J>::new(|| {}).ok().unwrap();
+        drop(job);
+
+        // the environment doesn't fit in a memory block
+        let big = [0usize; 3];
+        assert!(Job::<J>::new(move || {
+            SUM.fetch_add(big.len(), Ordering::Relaxed);
+        })
+        .is_err());
+
+        // a dropped job drops its environment and returns its memory block
+        let arc = std::sync::Arc::new(());
+        let c = arc.clone();
+        drop(Job::<J>::new(move || drop(c)));
+        assert_eq!(std::sync::Arc::strong_count(&arc), 1);
+        assert!(J::alloc().is_some());
+    }
+}
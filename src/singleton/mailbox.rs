@@ -0,0 +1,263 @@
+//! Inter-core mailboxes that transfer the ownership of memory blocks
+//!
+//! On dual-core microcontrollers both cores can share a global singleton pool, as long as the pool
+//! lives in memory that both cores can access. A `Mailbox` moves a `Box` from one core to the other
+//! through a hardware FIFO of words, like the inter-processor FIFOs of the RP2040's SIO block, or
+//! through a `SharedSlot` in shared memory. Only the address of the memory block crosses the FIFO;
+//! the `Box` is re-materialized on the receiving core.
+//!
+//! ``` ignore
+//! use lifo::{pool, singleton::mailbox::{Fifo, Mailbox}};
+//!
+//! pool!(P: [u8; 256]);
+//!
+//! // the FIFOs between the two cores
+//! struct Ipc { /* .. */ }
+//!
+//! unsafe impl Fifo for Ipc {
+//!     fn try_write(&mut self, word: usize) -> Result<(), usize> {
+//!         // write `word` to the FIFO that goes to the other core, unless it's full
+//!     }
+//!
+//!     fn try_read(&mut self) -> Option<usize> {
+//!         // read a word from the FIFO that comes from the other core, if any
+//!     }
+//! }
+//!
+//! // core 0
+//! let mut mailbox = unsafe { Mailbox::<P, _>::new(Ipc::core0()) };
+//! let mut frame = P::alloc().unwrap().init([0; 256]);
+//! fill(&mut frame);
+//! mailbox.send(frame).ok().unwrap();
+//!
+//! // core 1
+//! let mut mailbox = unsafe { Mailbox::<P, _>::new(Ipc::core1()) };
+//! if let Some(frame) = mailbox.recv() {
+//!     process(&frame);
+//!     // returns the memory block to the pool shared by both cores
+//!     drop(frame);
+//! }
+//! ```
+//!
+//! *NOTE:* The cores of the RP2040 implement ARMv6-M, which this crate doesn't support yet (see
+//! the [Portability](../../index.html#portability) section), so on the RP2040 this module can only
+//! be used once that support lands. Dual-core ARMv7-M and ARMv8-M devices are supported.
+
+use core::{
+    fmt,
+    marker::PhantomData,
+    mem::{self, ManuallyDrop},
+    ptr::NonNull,
+    sync::atomic::{self, AtomicUsize, Ordering},
+};
+
+use super::{Box, Pool};
+
+/// One endpoint of a bidirectional channel of words between two cores
+///
+/// # Safety
+///
+/// Words written with `try_write` on one core must be returned, unmodified and in order, by
+/// `try_read` on the other core, and each word must only be returned once. `0` is never written.
+pub unsafe trait Fifo {
+    /// Sends `word` to the other core
+    ///
+    /// Returns `word` back if the channel is full
+    fn try_write(&mut self, word: usize) -> Result<(), usize>;
+
+    /// Receives a word sent by the other core
+    ///
+    /// Returns `None` if there are no words to receive
+    fn try_read(&mut self) -> Option<usize>;
+}
+
+/// Transfers `Box`es of the pool `POOL` to and from another core through the FIFO `F`
+pub struct Mailbox<POOL, F>
+where
+    POOL: Pool,
+    F: Fifo,
+{
+    _pool: PhantomData<POOL>,
+    fifo: F,
+}
+
+impl<P, F> Mailbox<P, F>
+where
+    P: Pool,
+    F: Fifo,
+{
+    /// Creates a mailbox on top of `fifo`
+    ///
+    /// # Safety
+    ///
+    /// The words that come out of `fifo` must only have been sent by a `Mailbox<P, _>` on the
+    /// other core, and the pool `P` must be shared by both cores
+    pub unsafe fn new(fifo: F) -> Self {
+        Mailbox {
+            _pool: PhantomData,
+            fifo,
+        }
+    }
+
+    /// Sends `value` to the other core
+    ///
+    /// Returns `value` back if the FIFO is full
+    pub fn send(&mut self, value: Box<P>) -> Result<(), Box<P>> {
+        let node = value.inner.node;
+
+        // make the contents of the memory block visible to the other core before it can observe
+        // the address of the memory block
+        atomic::fence(Ordering::Release);
+
+        match self.fifo.try_write(node.as_ptr() as usize) {
+            Ok(()) => {
+                // NOTE the memory block is now owned by the other core
+                mem::forget(value);
+
+                Ok(())
+            }
+            Err(_) => Err(value),
+        }
+    }
+
+    /// Receives a box sent by the other core
+    ///
+    /// Returns `None` if the FIFO is empty
+    pub fn recv(&mut self) -> Option<Box<P>> {
+        let word = self.fifo.try_read()?;
+
+        // pairs with the fence in `send`
+        atomic::fence(Ordering::Acquire);
+
+        // NOTE(unsafe) the word is the address of a memory block sent by the other core; see `new`
+        let node = unsafe { NonNull::new_unchecked(word as *mut _) };
+
+        Some(Box {
+            _pool: PhantomData,
+            inner: ManuallyDrop::new(crate::Box {
+                _state: PhantomData,
+                node,
+            }),
+        })
+    }
+
+    /// Returns the underlying FIFO
+    pub fn free(self) -> F {
+        self.fifo
+    }
+}
+
+impl<P, F> fmt::Debug for Mailbox<P, F>
+where
+    P: Pool,
+    F: Fifo,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Mailbox").finish()
+    }
+}
+
+/// A one-word channel in shared memory, for devices whose cores don't share a hardware FIFO
+///
+/// Each direction needs its own slot; see `SharedSlot::endpoint`
+pub struct SharedSlot {
+    // `0` means empty
+    word: AtomicUsize,
+}
+
+impl SharedSlot {
+    /// Creates a new empty slot
+    pub const fn new() -> Self {
+        SharedSlot {
+            word: AtomicUsize::new(0),
+        }
+    }
+
+    /// Creates the endpoint of a core that sends words through `tx` and receives them from `rx`
+    ///
+    /// The other core must use the same slots with the roles swapped
+    pub fn endpoint(tx: &'static SharedSlot, rx: &'static SharedSlot) -> Endpoint {
+        Endpoint { tx, rx }
+    }
+}
+
+impl Default for SharedSlot {
+    fn default() -> Self {
+        SharedSlot::new()
+    }
+}
+
+impl fmt::Debug for SharedSlot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedSlot").finish()
+    }
+}
+
+/// The endpoint of a core in a channel made of two `SharedSlot`s
+///
+/// See `SharedSlot::endpoint`
+#[derive(Debug)]
+pub struct Endpoint {
+    tx: &'static SharedSlot,
+    rx: &'static SharedSlot,
+}
+
+// NOTE(unsafe) each slot has a single writer of non-zero words, the sending endpoint, and a single
+// writer of `0`, the receiving endpoint, which only writes after the sending endpoint has written
+unsafe impl Fifo for Endpoint {
+    fn try_write(&mut self, word: usize) -> Result<(), usize> {
+        if self.tx.word.load(Ordering::Acquire) != 0 {
+            return Err(word);
+        }
+
+        self.tx.word.store(word, Ordering::Release);
+
+        Ok(())
+    }
+
+    fn try_read(&mut self) -> Option<usize> {
+        let word = self.rx.word.load(Ordering::Acquire);
+        if word == 0 {
+            return None;
+        }
+
+        self.rx.word.store(0, Ordering::Release);
+
+        Some(word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Mailbox, SharedSlot};
+    use crate::{singleton::Pool, tests::memory};
+
+    #[test]
+    fn mailbox() {
+        crate::pool!(A: u32);
+        static A_TO_B: SharedSlot = SharedSlot::new();
+        static B_TO_A: SharedSlot = SharedSlot::new();
+
+        A::grow(memory::<u32>(2));
+
+        let mut a = unsafe { Mailbox::<A, _>::new(SharedSlot::endpoint(&A_TO_B, &B_TO_A)) };
+        let mut b = unsafe { Mailbox::<A, _>::new(SharedSlot::endpoint(&B_TO_A, &A_TO_B)) };
+
+        let x = A::alloc().unwrap().init(1);
+        a.send(x).ok().unwrap();
+        // the slot is full
+        let y = a.send(A::alloc().unwrap().init(2)).err().unwrap();
+        assert!(a.recv().is_none());
+
+        let x = b.recv().unwrap();
+        assert_eq!(*x, 1);
+        assert!(b.recv().is_none());
+
+        // the box can travel back and be freed on the other side
+        b.send(x).ok().unwrap();
+        a.send(y).ok().unwrap();
+        assert_eq!(*a.recv().unwrap(), 1);
+        assert_eq!(*b.recv().unwrap(), 2);
+        assert!(A::alloc().is_some());
+    }
+}
@@ -0,0 +1,275 @@
+//! Pooled byte buffers with a length
+
+use core::{fmt, ops::Range};
+
+use as_slice::{AsMutSlice, AsSlice};
+
+use super::{Box, FromSliceError, Pool};
+use crate::ByteArray;
+
+/// A byte buffer, backed by a memory block of the global memory pool `POOL`, that keeps track of
+/// which of its bytes are valid
+///
+/// `POOL::Data` is expected to be a plain byte buffer, like `[u8; N]`
+pub struct Payload<POOL>
+where
+    POOL: Pool,
+{
+    block: Box<POOL>,
+    // the valid bytes are `block[start..end]`
+    start: usize,
+    end: usize,
+}
+
+impl<P> Payload<P>
+where
+    P: Pool,
+    P::Data: AsMutSlice<Element = u8>,
+{
+    /// Creates an empty payload backed by `block`
+    pub fn new(block: Box<P>) -> Self {
+        Payload {
+            block,
+            start: 0,
+            end: 0,
+        }
+    }
+
+    /// Claims a memory block from the pool and copies `data` into it
+    ///
+    /// See `Pool::from_slice`
+    pub fn from_slice(data: &[u8]) -> Result<Self, FromSliceError>
+    where
+        P::Data: ByteArray,
+    {
+        P::from_slice(data).map(|(block, len)| Payload {
+            block,
+            start: 0,
+            end: len,
+        })
+    }
+
+    /// Returns the number of valid bytes in the payload
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Returns `true` if the payload contains no bytes
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Returns the maximum number of bytes the payload can hold
+    pub fn capacity(&self) -> usize {
+        self.block.as_slice().len()
+    }
+
+    /// Returns the number of bytes that can still be appended to the payload
+    ///
+    /// This can be less than `capacity() - len()` after bytes have been consumed from the front of
+    /// the payload, e.g. through `bytes::Buf`; `clear` reclaims that space.
+    pub fn spare_capacity(&self) -> usize {
+        self.capacity() - self.end
+    }
+
+    /// Shortens the payload to `len` bytes
+    ///
+    /// This has no effect if `len` is greater than the payload's current length
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            self.end = self.start + len;
+        }
+    }
+
+    /// Clears the payload
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.end = 0;
+    }
+
+    /// Appends `data` to the payload
+    ///
+    /// Returns an error, and leaves the payload unchanged, if `data` doesn't fit in the remaining
+    /// capacity
+    pub fn extend_from_slice(&mut self, data: &[u8]) -> Result<(), FromSliceError> {
+        if data.len() > self.spare_capacity() {
+            return Err(FromSliceError::TooLong);
+        }
+
+        let end = self.end + data.len();
+        self.block.as_mut_slice()[self.end..end].copy_from_slice(data);
+        self.end = end;
+
+        Ok(())
+    }
+
+    /// Consumes the payload returning the memory block and the range of valid bytes in it
+    pub fn into_inner(self) -> (Box<P>, Range<usize>) {
+        (self.block, self.start..self.end)
+    }
+}
+
+impl<P> AsRef<[u8]> for Payload<P>
+where
+    P: Pool,
+    P::Data: AsMutSlice<Element = u8>,
+{
+    fn as_ref(&self) -> &[u8] {
+        &self.block.as_slice()[self.start..self.end]
+    }
+}
+
+impl<P> AsMut<[u8]> for Payload<P>
+where
+    P: Pool,
+    P::Data: AsMutSlice<Element = u8>,
+{
+    fn as_mut(&mut self) -> &mut [u8] {
+        let (start, end) = (self.start, self.end);
+        &mut self.block.as_mut_slice()[start..end]
+    }
+}
+
+/// Appends the formatted string to the payload
+///
+/// `write!` returns an error if the string doesn't fit; in that case the payload contains the
+/// pieces that were written before running out of space. Use `len` to get the length of the
+/// formatted output.
+impl<P> fmt::Write for Payload<P>
+where
+    P: Pool,
+    P::Data: AsMutSlice<Element = u8>,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.extend_from_slice(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl<P> bytes::Buf for Payload<P>
+where
+    P: Pool,
+    P::Data: AsMutSlice<Element = u8>,
+{
+    fn remaining(&self) -> usize {
+        self.len()
+    }
+
+    fn chunk(&self) -> &[u8] {
+        self.as_ref()
+    }
+
+    fn advance(&mut self, cnt: usize) {
+        assert!(
+            cnt <= self.len(),
+            "cannot advance past the end of the payload"
+        );
+
+        self.start += cnt;
+    }
+}
+
+#[cfg(feature = "bytes")]
+unsafe impl<P> bytes::BufMut for Payload<P>
+where
+    P: Pool,
+    P::Data: AsMutSlice<Element = u8>,
+{
+    fn remaining_mut(&self) -> usize {
+        self.spare_capacity()
+    }
+
+    unsafe fn advance_mut(&mut self, cnt: usize) {
+        assert!(
+            cnt <= self.spare_capacity(),
+            "cannot advance past the end of the memory block"
+        );
+
+        self.end += cnt;
+    }
+
+    fn chunk_mut(&mut self) -> &mut bytes::buf::UninitSlice {
+        let end = self.end;
+        bytes::buf::UninitSlice::new(&mut self.block.as_mut_slice()[end..])
+    }
+}
+
+impl<P> fmt::Debug for Payload<P>
+where
+    P: Pool,
+    P::Data: AsMutSlice<Element = u8>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <[u8] as fmt::Debug>::fmt(self.as_ref(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Payload;
+    use crate::{singleton::Pool, tests::memory};
+
+    #[test]
+    fn payload() {
+        crate::pool!(A: [u8; 4]);
+
+        A::grow(memory::<[u8; 4]>(1));
+
+        let mut x = Payload::<A>::from_slice(&[1]).unwrap();
+        assert_eq!(x.len(), 1);
+        assert_eq!(x.capacity(), 4);
+
+        x.extend_from_slice(&[2, 3]).unwrap();
+        assert!(x.extend_from_slice(&[4, 5]).is_err());
+        assert_eq!(x.as_ref(), &[1, 2, 3]);
+
+        x.truncate(1);
+        x.as_mut()[0] = 0;
+        assert_eq!(format!("{:?}", x), "[0]");
+
+        // the memory block is returned to the pool when the payload is dropped
+        drop(x);
+        assert!(A::alloc().is_some());
+    }
+
+    #[test]
+    fn write() {
+        use core::fmt::Write;
+
+        crate::pool!(A: [u8; 8]);
+
+        A::grow(memory::<[u8; 8]>(1));
+
+        let mut x = Payload::<A>::from_slice(b"AT+").unwrap();
+        let (cmd, arg) = ("X", 12);
+        write!(x, "{}={}", cmd, arg).unwrap();
+        assert_eq!(x.as_ref(), b"AT+X=12");
+
+        assert!(write!(x, "{}", arg).is_err());
+        assert_eq!(x.len(), 7);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn bytes() {
+        use bytes::{Buf, BufMut};
+
+        crate::pool!(A: [u8; 8]);
+
+        A::grow(memory::<[u8; 8]>(1));
+
+        let mut x = Payload::new(A::alloc().unwrap().init([0; 8]));
+        x.put_u16(0x1234);
+        x.put_slice(b"abc");
+        assert_eq!(x.remaining_mut(), 3);
+
+        assert_eq!(x.get_u16(), 0x1234);
+        assert_eq!(x.get_u8(), b'a');
+        assert_eq!(x.chunk(), b"bc");
+
+        // consumed bytes are not reclaimed until the payload is cleared
+        assert_eq!(x.spare_capacity(), 3);
+        x.clear();
+        assert_eq!(x.spare_capacity(), 8);
+    }
+}
@@ -0,0 +1,315 @@
+//! smoltcp devices whose frames are stored in memory blocks of a pool
+//!
+//! `PoolDevice` implements smoltcp's `phy::Device` on top of a `Driver`, a network interface that
+//! exchanges frames as `Box`es of a global singleton pool of `[u8; N]` buffers. Received frames
+//! land directly in memory blocks of the pool and are returned to it as soon as smoltcp has
+//! processed them; frames to transmit are claimed from the same pool and handed to the driver,
+//! which frees them once the hardware is done with them. No static slices of frame buffers are
+//! needed.
+//!
+//! ``` ignore
+//! use lifo::{pool, singleton::{phy::{Driver, PoolDevice}, Box}};
+//!
+//! pool!(F: [u8; 1536]);
+//!
+//! struct Eth { /* .. */ }
+//!
+//! impl Driver for Eth {
+//!     type Pool = F;
+//!
+//!     fn receive(&mut self) -> Option<(Box<F>, usize)> {
+//!         // take a frame from the RX descriptor ring and hand a fresh buffer to the DMA
+//!     }
+//!
+//!     fn can_transmit(&self) -> bool {
+//!         // is there a free TX descriptor?
+//!     }
+//!
+//!     fn transmit(&mut self, frame: Box<F>, len: usize) {
+//!         // hand the frame to a TX descriptor; free it once it's been sent
+//!     }
+//!
+//!     fn capabilities(&self) -> DeviceCapabilities {
+//!         // ..
+//!     }
+//! }
+//!
+//! let mut device = PoolDevice::new(Eth::new());
+//! let mut iface = Interface::new(config, &mut device, now());
+//! ```
+
+use core::fmt;
+
+use smoltcp::{
+    phy::{self, DeviceCapabilities},
+    time::Instant,
+};
+
+use super::{Box, Pool};
+
+/// A network interface that exchanges frames as `Box`es of the pool `Self::Pool`
+pub trait Driver {
+    /// The pool that holds the frames
+    type Pool: Pool;
+
+    /// Returns the oldest received frame, and its length, if any
+    fn receive(&mut self) -> Option<(Box<Self::Pool>, usize)>;
+
+    /// Returns `true` if the driver can accept a frame to transmit
+    fn can_transmit(&self) -> bool;
+
+    /// Transmits the first `len` bytes of `frame`
+    ///
+    /// Only called after `can_transmit` returned `true`
+    fn transmit(&mut self, frame: Box<Self::Pool>, len: usize);
+
+    /// Returns the capabilities of the network interface
+    ///
+    /// `PoolDevice` caps the maximum transmission unit to the size of a memory block
+    fn capabilities(&self) -> DeviceCapabilities;
+}
+
+/// A smoltcp device backed by the driver `D`
+pub struct PoolDevice<D, const N: usize>
+where
+    D: Driver,
+    D::Pool: Pool<Data = [u8; N]>,
+{
+    driver: D,
+    // a received frame that couldn't be handed to smoltcp because the pool was exhausted
+    pending: Option<(Box<D::Pool>, usize)>,
+}
+
+impl<D, const N: usize> PoolDevice<D, N>
+where
+    D: Driver,
+    D::Pool: Pool<Data = [u8; N]>,
+{
+    /// Creates a device on top of `driver`
+    pub fn new(driver: D) -> Self {
+        PoolDevice {
+            driver,
+            pending: None,
+        }
+    }
+
+    /// Returns a reference to the driver
+    pub fn driver(&mut self) -> &mut D {
+        &mut self.driver
+    }
+
+    /// Releases the driver
+    ///
+    /// A frame that was received but not yet processed is dropped
+    pub fn free(self) -> D {
+        self.driver
+    }
+
+    fn tx_buffer() -> Option<Box<D::Pool>> {
+        Some(D::Pool::alloc()?.init([0; N]))
+    }
+}
+
+impl<D, const N: usize> fmt::Debug for PoolDevice<D, N>
+where
+    D: Driver + fmt::Debug,
+    D::Pool: Pool<Data = [u8; N]>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PoolDevice")
+            .field("driver", &self.driver)
+            .finish()
+    }
+}
+
+impl<D, const N: usize> phy::Device for PoolDevice<D, N>
+where
+    D: Driver,
+    D::Pool: Pool<Data = [u8; N]>,
+{
+    type RxToken<'a>
+        = RxToken<D::Pool>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = TxToken<'a, D>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, _: Instant) -> Option<(RxToken<D::Pool>, TxToken<'_, D>)> {
+        let (frame, len) = match self.pending.take() {
+            Some(pending) => pending,
+            None => self.driver.receive()?,
+        };
+
+        // smoltcp may answer the frame right away so a transmit buffer must be claimed up front
+        match Self::tx_buffer() {
+            Some(buffer) => Some((
+                RxToken { frame, len },
+                TxToken {
+                    driver: &mut self.driver,
+                    buffer,
+                },
+            )),
+            None => {
+                // keep the frame until memory blocks are freed
+                self.pending = Some((frame, len));
+                None
+            }
+        }
+    }
+
+    fn transmit(&mut self, _: Instant) -> Option<TxToken<'_, D>> {
+        if !self.driver.can_transmit() {
+            return None;
+        }
+
+        Some(TxToken {
+            buffer: Self::tx_buffer()?,
+            driver: &mut self.driver,
+        })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = self.driver.capabilities();
+        if caps.max_transmission_unit > N {
+            caps.max_transmission_unit = N;
+        }
+        caps
+    }
+}
+
+/// A received frame
+///
+/// The frame is returned to its pool once consumed
+pub struct RxToken<P>
+where
+    P: Pool,
+{
+    frame: Box<P>,
+    len: usize,
+}
+
+impl<P, const N: usize> phy::RxToken for RxToken<P>
+where
+    P: Pool<Data = [u8; N]>,
+{
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(&self.frame[..self.len])
+    }
+}
+
+impl<P> fmt::Debug for RxToken<P>
+where
+    P: Pool,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RxToken").field("len", &self.len).finish()
+    }
+}
+
+/// A frame to transmit
+///
+/// Dropping the token without consuming it returns the frame to its pool
+pub struct TxToken<'a, D>
+where
+    D: Driver,
+{
+    driver: &'a mut D,
+    buffer: Box<D::Pool>,
+}
+
+impl<D, const N: usize> phy::TxToken for TxToken<'_, D>
+where
+    D: Driver,
+    D::Pool: Pool<Data = [u8; N]>,
+{
+    fn consume<R, F>(mut self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let r = f(&mut self.buffer[..len]);
+        self.driver.transmit(self.buffer, len);
+        r
+    }
+}
+
+impl<D> fmt::Debug for TxToken<'_, D>
+where
+    D: Driver,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TxToken").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use smoltcp::{
+        phy::{Device, DeviceCapabilities, RxToken, TxToken},
+        time::Instant,
+    };
+
+    use super::{Driver, PoolDevice};
+    use crate::{
+        singleton::{Box, Pool},
+        tests::memory,
+    };
+
+    crate::pool!(F: [u8; 64]);
+
+    #[derive(Default)]
+    struct Loopback {
+        frames: Vec<(Box<F>, usize)>,
+    }
+
+    impl Driver for Loopback {
+        type Pool = F;
+
+        fn receive(&mut self) -> Option<(Box<F>, usize)> {
+            self.frames.pop()
+        }
+
+        fn can_transmit(&self) -> bool {
+            true
+        }
+
+        fn transmit(&mut self, frame: Box<F>, len: usize) {
+            self.frames.push((frame, len))
+        }
+
+        fn capabilities(&self) -> DeviceCapabilities {
+            let mut caps = DeviceCapabilities::default();
+            caps.max_transmission_unit = 1500;
+            caps
+        }
+    }
+
+    #[test]
+    fn pool_device() {
+        F::grow(memory::<[u8; 64]>(2));
+
+        let mut device = PoolDevice::new(Loopback::default());
+        assert_eq!(device.capabilities().max_transmission_unit, 64);
+        assert!(device.receive(Instant::ZERO).is_none());
+
+        device
+            .transmit(Instant::ZERO)
+            .unwrap()
+            .consume(3, |buf| buf.copy_from_slice(b"abc"));
+
+        // one memory block holds the frame, the other one the transmit buffer
+        let (rx, tx) = device.receive(Instant::ZERO).unwrap();
+        assert!(F::alloc().is_none());
+        drop(tx);
+        assert_eq!(rx.consume(|frame| frame.to_vec()), b"abc");
+
+        // the frame has been returned to the pool
+        let a = F::alloc().unwrap();
+        let b = F::alloc().unwrap();
+        drop((a, b));
+    }
+}
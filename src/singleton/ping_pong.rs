@@ -0,0 +1,129 @@
+//! Double buffering
+
+use core::{fmt, mem};
+
+use super::{Box, Pool};
+
+/// Two memory blocks of the global memory pool `POOL` used as a double buffer
+///
+/// One of the buffers, the *active* one, is processed by the application while the other one, the
+/// *in-flight* one, is being filled in, or drained, by a peripheral, usually through DMA. Once the
+/// transfer completes `swap` exchanges the roles of the two buffers.
+///
+/// ``` ignore
+/// use lifo::{pool, singleton::{Pool, PingPong}};
+///
+/// pool!(A: [i16; 256]);
+///
+/// #[interrupt]
+/// fn DMA1_STREAM0() {
+///     static mut BUFFERS: Option<PingPong<A>> = None;
+///
+///     let buffers = BUFFERS.get_or_insert_with(|| {
+///         PingPong::new(
+///             A::alloc().unwrap().init([0; 256]),
+///             A::alloc().unwrap().init([0; 256]),
+///         )
+///     });
+///
+///     // the DMA has filled in the in-flight buffer; hand it the other one
+///     buffers.swap();
+///     dma.start(buffers.in_flight_mut());
+///
+///     process(buffers.active());
+/// }
+/// ```
+pub struct PingPong<POOL>
+where
+    POOL: Pool,
+{
+    active: Box<POOL>,
+    in_flight: Box<POOL>,
+}
+
+impl<P> PingPong<P>
+where
+    P: Pool,
+{
+    /// Creates a double buffer from two memory blocks
+    pub fn new(active: Box<P>, in_flight: Box<P>) -> Self {
+        PingPong { active, in_flight }
+    }
+
+    /// Returns the buffer that the application works on
+    pub fn active(&self) -> &P::Data {
+        &self.active
+    }
+
+    /// Returns a mutable reference to the buffer that the application works on
+    pub fn active_mut(&mut self) -> &mut P::Data {
+        &mut self.active
+    }
+
+    /// Returns the buffer that the peripheral works on
+    pub fn in_flight(&self) -> &P::Data {
+        &self.in_flight
+    }
+
+    /// Returns a mutable reference to the buffer that the peripheral works on
+    pub fn in_flight_mut(&mut self) -> &mut P::Data {
+        &mut self.in_flight
+    }
+
+    /// Exchanges the roles of the two buffers
+    ///
+    /// The in-flight buffer becomes the active one and vice versa. This doesn't move any data.
+    pub fn swap(&mut self) {
+        mem::swap(&mut self.active, &mut self.in_flight)
+    }
+
+    /// Splits the double buffer into its active and in-flight buffers, in that order
+    pub fn into_parts(self) -> (Box<P>, Box<P>) {
+        (self.active, self.in_flight)
+    }
+}
+
+impl<P> fmt::Debug for PingPong<P>
+where
+    P: Pool,
+    P::Data: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PingPong")
+            .field("active", self.active())
+            .field("in_flight", self.in_flight())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PingPong;
+    use crate::{singleton::Pool, tests::memory};
+
+    #[test]
+    fn ping_pong() {
+        crate::pool!(A: [u8; 4]);
+
+        A::grow(memory::<[u8; 4]>(2));
+
+        let mut buffers = PingPong::new(
+            A::alloc().unwrap().init([0; 4]),
+            A::alloc().unwrap().init([0; 4]),
+        );
+
+        // the "DMA" fills in the in-flight buffer
+        buffers.in_flight_mut().copy_from_slice(&[1, 2, 3, 4]);
+        buffers.swap();
+        assert_eq!(buffers.active(), &[1, 2, 3, 4]);
+        assert_eq!(buffers.in_flight(), &[0; 4]);
+
+        buffers.active_mut()[0] = 5;
+        buffers.swap();
+        assert_eq!(buffers.in_flight(), &[5, 2, 3, 4]);
+
+        let (active, in_flight) = buffers.into_parts();
+        assert_eq!(*active, [0; 4]);
+        assert_eq!(*in_flight, [5, 2, 3, 4]);
+    }
+}
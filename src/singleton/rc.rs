@@ -0,0 +1,263 @@
+//! Single-threaded reference-counted memory blocks
+//!
+//! Like `arc`, but the reference count is a plain `Cell` so cloning and dropping an `Rc` doesn't
+//! involve atomic read-modify-write operations. In exchange `Rc` is neither `Send` nor `Sync`: all
+//! the clones of an `Rc` must stay in the execution context (e.g. the task or the interrupt
+//! handler) that created it.
+//!
+//! ``` ignore
+//! use lifo::{pool, singleton::rc::{Rc, RcInner}};
+//!
+//! pool!(P: RcInner<[u8; 128]>);
+//!
+//! let packet = Rc::<P>::new([0; 128]).ok().unwrap();
+//! let copy = packet.clone();
+//!
+//! drop(packet);
+//! // the memory block is returned to `P` here
+//! drop(copy);
+//! ```
+
+use core::{
+    cell::Cell,
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    mem,
+    ops::Deref,
+    ptr::NonNull,
+};
+
+use super::{Box, Pool};
+use crate::{Init, Node};
+
+/// The data of a pool of `Rc`s: a `T` value plus its reference count
+pub struct RcInner<T> {
+    strong: Cell<usize>,
+    data: T,
+}
+
+/// A singleton pool of `RcInner` values
+///
+/// Implemented for every singleton pool whose data type is `RcInner<T>`
+pub trait RcPool: Pool<Data = RcInner<<Self as RcPool>::Value>> {
+    /// The type of the reference-counted values
+    type Value;
+}
+
+impl<P, T> RcPool for P
+where
+    P: Pool<Data = RcInner<T>>,
+{
+    type Value = T;
+}
+
+/// A reference-counted pointer to a value stored in a memory block of the pool `POOL`
+pub struct Rc<POOL>
+where
+    POOL: RcPool,
+{
+    _pool: PhantomData<POOL>,
+    node: NonNull<Node<POOL::Data>>,
+}
+
+impl<P> Rc<P>
+where
+    P: RcPool,
+{
+    /// Claims a memory block from the pool `P` and moves `val` into it
+    ///
+    /// Returns `val` back when the pool is observed as exhausted
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn new(val: P::Value) -> Result<Self, P::Value> {
+        match P::alloc() {
+            Some(block) => Ok(Rc::from_block(block.init(RcInner {
+                strong: Cell::new(1),
+                data: val,
+            }))),
+            None => Err(val),
+        }
+    }
+
+    fn from_block(block: Box<P>) -> Self {
+        let node = block.inner.node;
+
+        // NOTE the memory block is now owned by the `Rc`
+        mem::forget(block);
+
+        Rc {
+            _pool: PhantomData,
+            node,
+        }
+    }
+
+    fn inner(&self) -> &RcInner<P::Value> {
+        unsafe { &*self.node.as_ref().data.get() }
+    }
+
+    /// Returns the number of `Rc`s that point to the same value
+    pub fn strong_count(this: &Self) -> usize {
+        this.inner().strong.get()
+    }
+
+    /// Returns `true` if both `Rc`s point to the same value
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.node == other.node
+    }
+
+    /// Returns a mutable reference to the value if there are no other `Rc`s pointing to it
+    pub fn get_mut(this: &mut Self) -> Option<&mut P::Value> {
+        if Self::strong_count(this) == 1 {
+            // NOTE(unsafe) no other `Rc` can observe the value
+            Some(unsafe { &mut (*this.node.as_ref().data.get()).data })
+        } else {
+            None
+        }
+    }
+}
+
+impl<P> Clone for Rc<P>
+where
+    P: RcPool,
+{
+    fn clone(&self) -> Self {
+        let strong = &self.inner().strong;
+        strong.set(
+            strong
+                .get()
+                .checked_add(1)
+                .expect("reference count overflow"),
+        );
+
+        Rc {
+            _pool: PhantomData,
+            node: self.node,
+        }
+    }
+}
+
+impl<P> Deref for Rc<P>
+where
+    P: RcPool,
+{
+    type Target = P::Value;
+
+    fn deref(&self) -> &P::Value {
+        &self.inner().data
+    }
+}
+
+impl<P> AsRef<P::Value> for Rc<P>
+where
+    P: RcPool,
+{
+    fn as_ref(&self) -> &P::Value {
+        self.deref()
+    }
+}
+
+impl<P> Drop for Rc<P>
+where
+    P: RcPool,
+{
+    fn drop(&mut self) {
+        let strong = &self.inner().strong;
+        strong.set(strong.get() - 1);
+
+        if strong.get() != 0 {
+            return;
+        }
+
+        // NOTE(unsafe) this was the last reference; dropping the box drops the value and returns
+        // the memory block to the pool
+        drop(unsafe {
+            Box::<P, Init>::from_box(crate::Box {
+                _state: PhantomData,
+                node: self.node,
+            })
+        })
+    }
+}
+
+// NOTE `Rc` is neither `Send` nor `Sync` because of its `NonNull` field
+
+impl<P> fmt::Debug for Rc<P>
+where
+    P: RcPool,
+    P::Value: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <P::Value as fmt::Debug>::fmt(self, f)
+    }
+}
+
+impl<P> fmt::Display for Rc<P>
+where
+    P: RcPool,
+    P::Value: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <P::Value as fmt::Display>::fmt(self, f)
+    }
+}
+
+impl<P> PartialEq for Rc<P>
+where
+    P: RcPool,
+    P::Value: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<P> Eq for Rc<P>
+where
+    P: RcPool,
+    P::Value: Eq,
+{
+}
+
+impl<P> Hash for Rc<P>
+where
+    P: RcPool,
+    P::Value: Hash,
+{
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        (**self).hash(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rc, RcInner};
+    use crate::{singleton::Pool, tests::memory};
+
+    #[test]
+    fn rc() {
+        crate::pool!(A: RcInner<[u8; 4]>);
+
+        A::grow(memory::<RcInner<[u8; 4]>>(1));
+
+        let mut x = Rc::<A>::new([1; 4]).unwrap();
+        assert_eq!(Rc::get_mut(&mut x), Some(&mut [1; 4]));
+        assert_eq!(Rc::<A>::new([2; 4]).err(), Some([2; 4]));
+
+        let y = x.clone();
+        assert_eq!(Rc::strong_count(&x), 2);
+        assert!(Rc::ptr_eq(&x, &y));
+        assert_eq!(*y, [1; 4]);
+        assert!(Rc::get_mut(&mut x).is_none());
+
+        // the memory block is only returned to the pool when the last `Rc` is dropped
+        drop(x);
+        assert!(A::alloc().is_none());
+        drop(y);
+        assert!(Rc::<A>::new([3; 4]).is_ok());
+    }
+}
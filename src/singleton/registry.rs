@@ -0,0 +1,251 @@
+//! Registry of global singleton pools
+
+use core::{
+    fmt, mem,
+    panic::Location,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering},
+};
+
+use crate::{Node, Stats};
+
+// Head of the intrusive list of registered pools
+static HEAD: AtomicPtr<Entry> = AtomicPtr::new(ptr::null_mut());
+
+/// Entry in the registry
+///
+/// Implementation detail of `pool!`
+#[doc(hidden)]
+pub struct Entry {
+    name: &'static str,
+    block_size: usize,
+    stats: fn() -> Stats,
+    live: fn(&mut dyn FnMut(&'static Location<'static>)),
+    registered: AtomicBool,
+    next: AtomicPtr<Entry>,
+}
+
+impl Entry {
+    #[doc(hidden)]
+    pub const fn new<T>(
+        name: &'static str,
+        stats: fn() -> Stats,
+        live: fn(&mut dyn FnMut(&'static Location<'static>)),
+    ) -> Self {
+        Entry {
+            name,
+            block_size: mem::size_of::<Node<T>>(),
+            stats,
+            live,
+            registered: AtomicBool::new(false),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+}
+
+// Adds `entry` to the registry, unless it has already been added
+pub(crate) fn register(entry: &'static Entry) {
+    if entry.registered.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    // NOTE entries are never removed from the list so this push is not affected by the ABA problem
+    let new = entry as *const Entry as *mut Entry;
+    let mut head = HEAD.load(Ordering::Relaxed);
+    loop {
+        entry.next.store(head, Ordering::Relaxed);
+
+        match HEAD.compare_exchange_weak(head, new, Ordering::Release, Ordering::Relaxed) {
+            Ok(_) => break,
+            // head was changed by some interrupt handler
+            Err(new_head) => head = new_head,
+        }
+    }
+}
+
+/// Information about a registered pool
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolInfo {
+    /// Name of the pool type, as given to `pool!`
+    pub name: &'static str,
+
+    /// Number of bytes used by each memory block, including the pool's bookkeeping
+    pub block_size: usize,
+
+    /// Snapshot of the statistics of the pool
+    pub stats: Stats,
+}
+
+/// Returns an iterator over all the pools that have been registered with `Pool::register`
+///
+/// Pools are yielded in reverse registration order
+pub fn pools() -> Pools {
+    Pools {
+        next: HEAD.load(Ordering::Acquire),
+    }
+}
+
+/// Iterator over the registered pools
+///
+/// Created with `pools`
+pub struct Pools {
+    next: *const Entry,
+}
+
+impl Iterator for Pools {
+    type Item = PoolInfo;
+
+    fn next(&mut self) -> Option<PoolInfo> {
+        // NOTE(unsafe) all entries are `'static`
+        let entry = unsafe { self.next.as_ref()? };
+        self.next = entry.next.load(Ordering::Acquire);
+
+        Some(PoolInfo {
+            name: entry.name,
+            block_size: entry.block_size,
+            stats: (entry.stats)(),
+        })
+    }
+}
+
+/// Writes the state of every registered pool to `w`
+///
+/// Each pool gets one line with its name, its number of free memory blocks, its capacity, its low
+/// watermark and the size of its memory blocks; exhausted pools are flagged as such. With the
+/// `track-callers` feature the line is followed by the location of the code that claimed each of
+/// the pool's allocated memory blocks.
+///
+/// This is meant to be called from a panic handler (see `report_panics!`) so that post-mortem logs
+/// show whether running out of memory blocks led to the panic. It doesn't allocate nor take any
+/// lock but pools that are used while it runs may be reported inconsistently.
+pub fn panic_report<W>(w: &mut W) -> fmt::Result
+where
+    W: fmt::Write + ?Sized,
+{
+    let mut next = HEAD.load(Ordering::Acquire) as *const Entry;
+    // NOTE(unsafe) all entries are `'static`
+    while let Some(entry) = unsafe { next.as_ref() } {
+        next = entry.next.load(Ordering::Acquire);
+
+        let stats = (entry.stats)();
+        write!(
+            w,
+            "{}: {}/{} free, low watermark {}, {}-byte blocks",
+            entry.name, stats.free, stats.capacity, stats.low_watermark, entry.block_size
+        )?;
+
+        if stats.free == 0 {
+            w.write_str(" (exhausted)")?;
+        }
+        w.write_char('\n')?;
+
+        let mut res = Ok(());
+        (entry.live)(&mut |location| {
+            if res.is_ok() {
+                res = writeln!(w, "  allocated at {}", location);
+            }
+        });
+        res?;
+    }
+
+    Ok(())
+}
+
+/// Installs a panic handler that writes the panic message and a `panic_report` to a writer
+///
+/// ``` ignore
+/// lifo::report_panics!(Uart::steal());
+/// ```
+///
+/// The expression, which is evaluated once the program has panicked, must return a value that
+/// implements `core::fmt::Write`. Errors returned by the writer are ignored. After writing the
+/// report the panic handler spins forever.
+#[cfg(feature = "panic-report")]
+#[macro_export]
+macro_rules! report_panics {
+    ($writer:expr) => {
+        #[panic_handler]
+        fn __lifo_panic(info: &::core::panic::PanicInfo) -> ! {
+            let mut w = $writer;
+            let _ = ::core::fmt::Write::write_fmt(&mut w, format_args!("{}\n", info));
+            let _ = $crate::panic_report(&mut w);
+
+            loop {
+                ::core::sync::atomic::compiler_fence(::core::sync::atomic::Ordering::SeqCst);
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem;
+
+    use super::{panic_report, pools};
+    use crate::{singleton::Pool, Node};
+
+    #[test]
+    fn registry() {
+        crate::pool!(RegA: u8; capacity = 2, RegB: [u8; 32]);
+
+        RegA::init();
+        RegA::register();
+        RegB::register();
+        // registering a pool again has no effect
+        RegA::register();
+
+        let _x = RegA::alloc().unwrap();
+
+        // other tests may register their own pools
+        let a = pools()
+            .filter(|info| info.name == "RegA")
+            .collect::<Vec<_>>();
+        assert_eq!(a.len(), 1);
+        assert_eq!(a[0].block_size, mem::size_of::<Node<u8>>());
+        assert_eq!(a[0].stats, RegA::stats());
+        assert_eq!(a[0].stats.free, a[0].stats.capacity - 1);
+
+        let b = pools().find(|info| info.name == "RegB").unwrap();
+        assert_eq!(b.block_size, mem::size_of::<Node<[u8; 32]>>());
+        assert_eq!(b.stats.capacity, 0);
+    }
+
+    #[test]
+    fn report() {
+        crate::pool!(RepA: u16; capacity = 1);
+
+        RepA::init();
+        RepA::register();
+
+        let _x = RepA::alloc().unwrap();
+
+        let mut report = String::new();
+        panic_report(&mut report).unwrap();
+
+        // other tests may register their own pools
+        let mut lines = report.lines().skip_while(|line| !line.starts_with("RepA:"));
+        // the sentinel of a `fifo` pool is counted as a free memory block
+        let stats = RepA::stats();
+        assert_eq!(
+            lines.next().unwrap(),
+            format!(
+                "RepA: {}/{} free, low watermark {}, {}-byte blocks{}",
+                stats.free,
+                stats.capacity,
+                stats.low_watermark,
+                mem::size_of::<Node<u16>>(),
+                if cfg!(feature = "fifo") {
+                    ""
+                } else {
+                    " (exhausted)"
+                },
+            )
+        );
+
+        if cfg!(feature = "track-callers") {
+            let line = lines.next().unwrap();
+            assert!(line.starts_with("  allocated at src/singleton/registry.rs:"));
+        }
+    }
+}
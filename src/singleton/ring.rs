@@ -0,0 +1,280 @@
+//! DMA descriptor rings whose buffers are memory blocks of a pool
+//!
+//! Peripherals like Ethernet MACs and some USB controllers receive data into a ring of buffer
+//! descriptors: the driver hands each descriptor, with an empty buffer attached, to the DMA and the
+//! DMA hands it back once it has filled the buffer in. `DescriptorRing` keeps such a ring stocked
+//! with memory blocks claimed from a pool and hands the filled memory blocks over to the
+//! application as `Box`es, so received data doesn't need to be copied out of the ring.
+//!
+//! ``` ignore
+//! use lifo::{pool, singleton::ring::{Descriptor, DescriptorRing}};
+//!
+//! pool!(P: [u8; 1536]);
+//!
+//! // the hardware-specific layout of a receive descriptor
+//! #[repr(C)]
+//! struct RxDescriptor { /* .. */ }
+//!
+//! unsafe impl Descriptor for RxDescriptor {
+//!     type Word = u8;
+//!
+//!     fn arm(&mut self, buffer: *mut u8, len: usize) {
+//!         // write the buffer address and length, then set the OWN bit
+//!     }
+//!
+//!     fn completed(&mut self) -> Option<usize> {
+//!         // `None` while the OWN bit is set; otherwise, the length of the received frame
+//!     }
+//! }
+//!
+//! let mut ring = DescriptorRing::<P, RxDescriptor, 8>::new(DESCRIPTORS);
+//! ring.refill();
+//! // program the address of `DESCRIPTORS` into the peripheral and start the DMA
+//!
+//! // on each receive interrupt
+//! while let Some((frame, len)) = ring.poll() {
+//!     // NOTE(unsafe) any byte pattern is a valid `[u8; 1536]` and the memory was initialized
+//!     // when the pool was grown
+//!     let frame = unsafe { frame.assume_init() };
+//!     process(&frame[..len]);
+//! }
+//! ring.refill();
+//! ```
+
+use core::{
+    fmt, mem,
+    sync::atomic::{self, Ordering},
+};
+
+use embedded_dma::{WriteBuffer, WriteTarget};
+
+use super::{Box, Pool};
+use crate::Uninit;
+
+/// A hardware DMA descriptor
+///
+/// # Safety
+///
+/// `completed` must only return `Some` once the DMA will no longer access the buffer that was
+/// handed to it with `arm`
+pub unsafe trait Descriptor {
+    /// The unit of transfer of the DMA
+    type Word;
+
+    /// Attaches the buffer that starts at `buffer` and spans `len` words to this descriptor and
+    /// hands the descriptor to the DMA
+    fn arm(&mut self, buffer: *mut Self::Word, len: usize);
+
+    /// Returns `None` while the DMA owns this descriptor; otherwise, returns the number of words
+    /// that the DMA wrote into the buffer
+    fn completed(&mut self) -> Option<usize>;
+}
+
+/// A ring of `N` DMA descriptors whose buffers are memory blocks of the pool `POOL`
+///
+/// Memory blocks still handed to the DMA when the ring is dropped are leaked, as the DMA may still
+/// write to them
+pub struct DescriptorRing<POOL, D, const N: usize>
+where
+    POOL: Pool,
+    POOL::Data: WriteTarget<Word = D::Word>,
+    D: Descriptor + 'static,
+{
+    descriptors: &'static mut [D; N],
+    buffers: [Option<Box<POOL, Uninit>>; N],
+    // index of the oldest descriptor handed to the DMA
+    head: usize,
+    // index of the next descriptor to hand to the DMA
+    tail: usize,
+    // number of descriptors owned by the DMA
+    armed: usize,
+}
+
+impl<P, D, const N: usize> DescriptorRing<P, D, N>
+where
+    P: Pool,
+    P::Data: WriteTarget<Word = D::Word>,
+    D: Descriptor + 'static,
+{
+    // only used to initialize `DescriptorRing.buffers`
+    const NONE: Option<Box<P, Uninit>> = None;
+
+    /// Creates a ring out of `descriptors`
+    ///
+    /// No descriptor is handed to the DMA until `refill` is called
+    pub fn new(descriptors: &'static mut [D; N]) -> Self {
+        DescriptorRing {
+            descriptors,
+            buffers: [Self::NONE; N],
+            head: 0,
+            tail: 0,
+            armed: 0,
+        }
+    }
+
+    /// Returns the descriptors of the ring, e.g. to program their address into the peripheral
+    pub fn descriptors(&self) -> &[D; N] {
+        self.descriptors
+    }
+
+    /// Returns the number of descriptors that are currently owned by the DMA
+    pub fn armed(&self) -> usize {
+        self.armed
+    }
+
+    /// Hands descriptors to the DMA, in ring order, attaching a memory block claimed from the pool
+    /// to each one
+    ///
+    /// Stops when all the descriptors are owned by the DMA or the pool is observed as exhausted.
+    /// Returns the number of descriptors that were handed to the DMA.
+    pub fn refill(&mut self) -> usize {
+        let mut refilled = 0;
+
+        while self.armed < N {
+            let buffer = match P::alloc() {
+                Some(block) => self.buffers[self.tail].get_or_insert(block),
+                None => break,
+            };
+
+            // NOTE(unsafe) the memory block is not accessed again until the DMA is done with it
+            let (ptr, len) = unsafe { buffer.write_buffer() };
+
+            // make sure the DMA observes all previous writes to the memory block
+            atomic::fence(Ordering::Release);
+            self.descriptors[self.tail].arm(ptr, len);
+
+            self.tail = next::<N>(self.tail);
+            self.armed += 1;
+            refilled += 1;
+        }
+
+        refilled
+    }
+
+    /// Takes the memory block of the oldest descriptor if the DMA is done with it
+    ///
+    /// Returns the memory block along with the number of words that the DMA wrote into it. The
+    /// descriptor stays empty until `refill` is called.
+    pub fn poll(&mut self) -> Option<(Box<P, Uninit>, usize)> {
+        if self.armed == 0 {
+            return None;
+        }
+
+        let len = self.descriptors[self.head].completed()?;
+
+        // make sure the writes of the DMA are observed
+        atomic::fence(Ordering::Acquire);
+
+        let buffer = self.buffers[self.head].take()?;
+        self.head = next::<N>(self.head);
+        self.armed -= 1;
+
+        Some((buffer, len))
+    }
+}
+
+fn next<const N: usize>(index: usize) -> usize {
+    if index + 1 == N {
+        0
+    } else {
+        index + 1
+    }
+}
+
+impl<P, D, const N: usize> Drop for DescriptorRing<P, D, N>
+where
+    P: Pool,
+    P::Data: WriteTarget<Word = D::Word>,
+    D: Descriptor + 'static,
+{
+    fn drop(&mut self) {
+        for buffer in self.buffers.iter_mut() {
+            mem::forget(buffer.take());
+        }
+    }
+}
+
+impl<P, D, const N: usize> fmt::Debug for DescriptorRing<P, D, N>
+where
+    P: Pool,
+    P::Data: WriteTarget<Word = D::Word>,
+    D: Descriptor + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DescriptorRing")
+            .field("capacity", &N)
+            .field("armed", &self.armed)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{cell::Cell, ptr};
+
+    use super::{Descriptor, DescriptorRing};
+    use crate::{singleton::Pool, tests::memory};
+
+    // emulates a descriptor whose DMA transfer completes as soon as `written` is set
+    struct Mock {
+        buffer: Cell<*mut u8>,
+        written: Cell<Option<usize>>,
+    }
+
+    unsafe impl Descriptor for Mock {
+        type Word = u8;
+
+        fn arm(&mut self, buffer: *mut u8, len: usize) {
+            assert_eq!(len, 4);
+            self.buffer.set(buffer);
+            self.written.set(None);
+        }
+
+        fn completed(&mut self) -> Option<usize> {
+            self.written.get()
+        }
+    }
+
+    impl Mock {
+        fn receive(&self, data: &[u8]) {
+            unsafe { ptr::copy_nonoverlapping(data.as_ptr(), self.buffer.get(), data.len()) }
+            self.written.set(Some(data.len()));
+        }
+    }
+
+    #[test]
+    fn descriptor_ring() {
+        crate::pool!(A: [u8; 4]);
+
+        A::grow(memory::<[u8; 4]>(3));
+
+        let descriptors = Box::leak(Box::new([0, 1].map(|_| Mock {
+            buffer: Cell::new(ptr::null_mut()),
+            written: Cell::new(None),
+        })));
+        let mut ring = DescriptorRing::<A, Mock, 2>::new(descriptors);
+        assert!(ring.poll().is_none());
+
+        assert_eq!(ring.refill(), 2);
+        assert!(ring.poll().is_none());
+
+        ring.descriptors()[0].receive(&[1, 2]);
+        let (a, len) = ring.poll().unwrap();
+        assert_eq!(&unsafe { a.assume_init() }[..len], &[1, 2]);
+        // the next descriptor is still owned by the DMA
+        assert!(ring.poll().is_none());
+
+        // only the descriptor that was emptied needs a new memory block
+        assert_eq!(ring.refill(), 1);
+        assert_eq!(ring.armed(), 2);
+
+        // wraps around the ring
+        ring.descriptors()[1].receive(&[3]);
+        ring.descriptors()[0].receive(&[4, 5, 6]);
+        let (b, len) = ring.poll().unwrap();
+        assert_eq!(&unsafe { b.assume_init() }[..len], &[3]);
+        let (c, len) = ring.poll().unwrap();
+        assert_eq!(&unsafe { c.assume_init() }[..len], &[4, 5, 6]);
+        assert_eq!(ring.armed(), 0);
+    }
+}
@@ -0,0 +1,319 @@
+//! Growable strings backed by a chain of memory blocks
+
+use core::{
+    fmt::{self, Write},
+    iter::Copied,
+    str,
+};
+
+use as_slice::AsSlice;
+
+use super::{
+    vec::{Chunks, Iter, PoolVec},
+    Pool,
+};
+
+/// Error returned when a `PoolString` needs to claim a memory block but its pool is exhausted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Exhausted;
+
+/// Error returned by `PoolString::from_utf8` and `PoolString::push_utf8`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FromUtf8Error {
+    /// The pool is exhausted
+    Exhausted,
+    /// The bytes are not valid UTF-8
+    Utf8(str::Utf8Error),
+}
+
+/// A growable UTF-8 string whose storage is a chain of memory blocks of the pool `POOL`
+///
+/// `POOL::Data` is expected to be a plain byte buffer, like `[u8; N]`. A character can straddle
+/// two memory blocks so the string can't be viewed as a single `&str`; use its `Display`
+/// implementation, or `chunks`, to read it back.
+///
+/// ``` ignore
+/// pool!(Chunks: [u8; 64]);
+///
+/// let mut json = PoolString::<Chunks>::new();
+/// write!(json, "{{\"temperature\":{},\"humidity\":{}}}", t, h).unwrap();
+///
+/// for chunk in json.chunks() {
+///     uart.write_all(chunk);
+/// }
+/// ```
+pub struct PoolString<POOL>
+where
+    POOL: Pool,
+    POOL::Data: AsSlice<Element = u8>,
+{
+    vec: PoolVec<POOL>,
+}
+
+impl<P> PoolString<P>
+where
+    P: Pool,
+    P::Data: AsSlice<Element = u8>,
+{
+    /// Creates a new empty string
+    ///
+    /// This doesn't claim any memory block
+    pub fn new() -> Self {
+        PoolString {
+            vec: PoolVec::new(),
+        }
+    }
+
+    /// Creates a string from `bytes`, validating that they are UTF-8
+    pub fn from_utf8(bytes: &[u8]) -> Result<Self, FromUtf8Error> {
+        let mut s = PoolString::new();
+        s.push_utf8(bytes)?;
+        Ok(s)
+    }
+
+    /// Returns the length of the string, in bytes
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    /// Returns `true` if the string is empty
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    /// Appends `c` to the end of the string
+    ///
+    /// Returns `c` back when a memory block needs to be claimed but the pool is observed as
+    /// exhausted
+    pub fn push(&mut self, c: char) -> Result<(), char> {
+        self.push_str(c.encode_utf8(&mut [0; 4])).map_err(|_| c)
+    }
+
+    /// Appends `s` to the end of the string
+    ///
+    /// If a memory block needs to be claimed but the pool is observed as exhausted the string is
+    /// left unchanged
+    pub fn push_str(&mut self, s: &str) -> Result<(), Exhausted> {
+        let len = self.len();
+
+        for byte in s.bytes() {
+            if self.vec.push(byte).is_err() {
+                self.vec.truncate(len);
+
+                return Err(Exhausted);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Appends `bytes` to the end of the string, validating that they are UTF-8
+    ///
+    /// On error the string is left unchanged
+    pub fn push_utf8(&mut self, bytes: &[u8]) -> Result<(), FromUtf8Error> {
+        let s = str::from_utf8(bytes).map_err(FromUtf8Error::Utf8)?;
+
+        self.push_str(s).map_err(|_| FromUtf8Error::Exhausted)
+    }
+
+    /// Shortens the string to `len` bytes, returning the memory blocks that are no longer needed
+    /// to the pool
+    ///
+    /// This has no effect if `len` is greater than the string's current length
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `len` does not lie on a `char` boundary
+    pub fn truncate(&mut self, len: usize) {
+        if let Some(byte) = self.vec.get(len) {
+            assert!(
+                !is_continuation(*byte),
+                "new length does not lie on a char boundary"
+            );
+
+            self.vec.truncate(len)
+        }
+    }
+
+    /// Removes all the contents of the string and returns all its memory blocks to the pool
+    pub fn clear(&mut self) {
+        self.vec.clear()
+    }
+
+    /// Returns an iterator over the bytes of the string
+    pub fn bytes(&self) -> Copied<Iter<'_, P>> {
+        self.vec.iter().copied()
+    }
+
+    /// Returns an iterator over the contents of the memory blocks of the string, as byte slices
+    ///
+    /// A character can be split across two slices
+    pub fn chunks(&self) -> Chunks<'_, P> {
+        self.vec.chunks()
+    }
+
+    // Calls `f` on consecutive pieces of the string; unlike `chunks` no character is split across
+    // two pieces
+    fn for_each_str<F>(&self, mut f: F) -> fmt::Result
+    where
+        F: FnMut(&str) -> fmt::Result,
+    {
+        // a character split across memory blocks
+        let mut split = [0; 4];
+        let mut n = 0;
+
+        for mut chunk in self.chunks() {
+            if n != 0 {
+                let width = width(split[0]);
+                let take = (width - n).min(chunk.len());
+                split[n..n + take].copy_from_slice(&chunk[..take]);
+                n += take;
+                chunk = &chunk[take..];
+
+                if n != width {
+                    continue;
+                }
+
+                // NOTE(unsafe) the contents of the string were validated when they were pushed
+                f(unsafe { str::from_utf8_unchecked(&split[..n]) })?;
+            }
+
+            // find where the last, possibly incomplete, character of the chunk starts
+            let end = match chunk.iter().rposition(|byte| !is_continuation(*byte)) {
+                Some(start) if start + width(chunk[start]) > chunk.len() => start,
+                _ => chunk.len(),
+            };
+
+            f(unsafe { str::from_utf8_unchecked(&chunk[..end]) })?;
+
+            n = chunk.len() - end;
+            split[..n].copy_from_slice(&chunk[end..]);
+        }
+
+        Ok(())
+    }
+}
+
+// Returns the number of bytes of the UTF-8 sequence that starts with `byte`
+fn width(byte: u8) -> usize {
+    if byte < 0x80 {
+        1
+    } else if byte < 0xe0 {
+        2
+    } else if byte < 0xf0 {
+        3
+    } else {
+        4
+    }
+}
+
+fn is_continuation(byte: u8) -> bool {
+    byte & 0xc0 == 0x80
+}
+
+impl<P> Default for PoolString<P>
+where
+    P: Pool,
+    P::Data: AsSlice<Element = u8>,
+{
+    fn default() -> Self {
+        PoolString::new()
+    }
+}
+
+impl<P> Write for PoolString<P>
+where
+    P: Pool,
+    P::Data: AsSlice<Element = u8>,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s).map_err(|_| fmt::Error)
+    }
+}
+
+impl<P> fmt::Display for PoolString<P>
+where
+    P: Pool,
+    P::Data: AsSlice<Element = u8>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.for_each_str(|s| f.write_str(s))
+    }
+}
+
+impl<P> fmt::Debug for PoolString<P>
+where
+    P: Pool,
+    P::Data: AsSlice<Element = u8>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_char('"')?;
+        self.for_each_str(|s| write!(f, "{}", s.escape_debug()))?;
+        f.write_char('"')
+    }
+}
+
+impl<P> PartialEq<str> for PoolString<P>
+where
+    P: Pool,
+    P::Data: AsSlice<Element = u8>,
+{
+    fn eq(&self, other: &str) -> bool {
+        self.len() == other.len() && self.bytes().eq(other.bytes())
+    }
+}
+
+impl<P> PartialEq<&str> for PoolString<P>
+where
+    P: Pool,
+    P::Data: AsSlice<Element = u8>,
+{
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use super::{Exhausted, FromUtf8Error, PoolString};
+    use crate::{singleton::Pool, tests::memory};
+
+    #[test]
+    fn pool_string() {
+        crate::pool!(A: [u8; 3]);
+
+        A::grow(memory::<[u8; 3]>(3));
+
+        let mut s = PoolString::<A>::from_utf8(b"ab").unwrap();
+        // 'é' is split across the first two memory blocks
+        write!(s, "é{}", 1).unwrap();
+        s.push('ñ').unwrap();
+        assert_eq!(s.len(), 7);
+        assert_eq!(s, "abé1ñ");
+        assert_eq!(format!("{}", s), "abé1ñ");
+        assert_eq!(format!("{:?}", s), "\"abé1ñ\"");
+
+        // the string is left unchanged when the pool runs out of memory blocks
+        assert_eq!(s.push_str("xyz"), Err(Exhausted));
+        assert_eq!(s, "abé1ñ");
+
+        assert!(matches!(s.push_utf8(&[0xff]), Err(FromUtf8Error::Utf8(_))));
+
+        s.truncate(2);
+        assert_eq!(s, "ab");
+        assert_eq!(s.chunks().count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "char boundary")]
+    fn truncate_char_boundary() {
+        crate::pool!(A: [u8; 4]);
+
+        A::grow(memory::<[u8; 4]>(1));
+
+        let mut s = PoolString::<A>::from_utf8("é".as_bytes()).unwrap();
+        s.truncate(1);
+    }
+}
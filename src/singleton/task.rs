@@ -0,0 +1,348 @@
+//! Futures stored in memory blocks of a pool
+//!
+//! `Task` moves a future into a memory block of a global singleton pool, erases its type and pins
+//! it, so an executor can hold tasks of different types without a heap. `TaskPool` builds on it: it
+//! keeps a table of up to `N` spawned tasks, each with a waker that marks it as ready to be polled,
+//! which is all a minimal executor needs.
+//!
+//! ``` ignore
+//! use lifo::{pool, singleton::task::TaskPool};
+//!
+//! // tasks whose future is up to 256 bytes large
+//! pool!(T: [u64; 32]);
+//!
+//! static TASKS: TaskPool<T, 8> = TaskPool::new();
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     static mut MEMORY: [u8; 2048] = [0; 2048];
+//!
+//!     T::grow(MEMORY);
+//!     TASKS.spawn(blink()).ok().unwrap();
+//!
+//!     loop {
+//!         // polls the tasks that have been woken up since the last call
+//!         if TASKS.poll_ready() == 0 {
+//!             asm::wfi();
+//!         }
+//!     }
+//! }
+//!
+//! #[interrupt]
+//! fn USART1() {
+//!     // tasks can be spawned from any context
+//!     TASKS.spawn(handle_command()).ok();
+//! }
+//! ```
+
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+use super::{DynBox, Pool};
+
+/// A type-erased, pinned future stored in a memory block of the pool `POOL`
+///
+/// Dropping the task drops the future and returns the memory block to the pool
+pub struct Task<POOL>
+where
+    POOL: Pool,
+{
+    future: Pin<DynBox<POOL, dyn Future<Output = ()> + Send>>,
+}
+
+impl<P> Task<P>
+where
+    P: Pool,
+{
+    /// Claims a memory block from the pool `P` and moves `future` into it
+    ///
+    /// Returns `future` back when the pool is observed as exhausted, or if `future` doesn't fit in
+    /// a memory block (see `Box::coerce`)
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    pub fn new<F>(future: F) -> Result<Self, F>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let block = match P::alloc() {
+            Some(block) => block,
+            None => return Err(future),
+        };
+
+        let future = block.coerce(future, |f| f as &mut (dyn Future<Output = ()> + Send))?;
+
+        Ok(Task {
+            // NOTE(unsafe) the future stays in its memory block until it's dropped
+            future: unsafe { Pin::new_unchecked(future) },
+        })
+    }
+
+    /// Polls the future
+    pub fn poll(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        self.future.as_mut().poll(cx)
+    }
+}
+
+impl<P> fmt::Debug for Task<P>
+where
+    P: Pool,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Task").finish()
+    }
+}
+
+const FREE: u8 = 0;
+// the slot is being filled in, or its task is being polled
+const BUSY: u8 = 1;
+const OCCUPIED: u8 = 2;
+
+struct Slot<P>
+where
+    P: Pool,
+{
+    state: AtomicU8,
+    woken: AtomicBool,
+    task: UnsafeCell<Option<Task<P>>>,
+}
+
+impl<P> Slot<P>
+where
+    P: Pool,
+{
+    // only used to initialize `TaskPool.slots`
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: Slot<P> = Slot {
+        state: AtomicU8::new(FREE),
+        woken: AtomicBool::new(false),
+        task: UnsafeCell::new(None),
+    };
+}
+
+/// A table of up to `N` tasks whose futures are stored in memory blocks of the pool `POOL`
+///
+/// Tasks can be spawned from any context. Each task gets a waker that marks it as ready;
+/// `poll_ready` polls the tasks that are ready and drops the ones that complete.
+pub struct TaskPool<POOL, const N: usize>
+where
+    POOL: Pool,
+{
+    slots: [Slot<POOL>; N],
+}
+
+// NOTE(unsafe) tasks are `Send` and each one is polled from one context at a time
+unsafe impl<P, const N: usize> Sync for TaskPool<P, N> where P: Pool {}
+
+impl<P, const N: usize> TaskPool<P, N>
+where
+    P: Pool,
+{
+    /// Creates a new table without tasks
+    pub const fn new() -> Self {
+        TaskPool {
+            slots: [Slot::EMPTY; N],
+        }
+    }
+
+    /// Spawns a task that runs `future`
+    ///
+    /// Returns `future` back if the table is full, or if a `Task` can't be created for it (see
+    /// `Task::new`). The task is polled on the next call to `poll_ready`.
+    pub fn spawn<F>(&self, future: F) -> Result<(), F>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let slot = match self.slots.iter().find(|slot| {
+            slot.state
+                .compare_exchange(FREE, BUSY, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        }) {
+            Some(slot) => slot,
+            None => return Err(future),
+        };
+
+        match Task::new(future) {
+            Ok(task) => {
+                // NOTE(unsafe) the `BUSY` state grants exclusive access to the slot
+                unsafe { *slot.task.get() = Some(task) }
+                slot.woken.store(true, Ordering::Relaxed);
+                slot.state.store(OCCUPIED, Ordering::Release);
+
+                Ok(())
+            }
+            Err(future) => {
+                slot.state.store(FREE, Ordering::Relaxed);
+
+                Err(future)
+            }
+        }
+    }
+
+    /// Polls all the tasks that have been woken up since they were last polled
+    ///
+    /// Returns the number of tasks that were polled. Tasks whose future completes are dropped.
+    pub fn poll_ready(&'static self) -> usize {
+        let mut polled = 0;
+
+        for slot in &self.slots {
+            if !slot.woken.load(Ordering::Relaxed)
+                || slot
+                    .state
+                    .compare_exchange(OCCUPIED, BUSY, Ordering::Acquire, Ordering::Relaxed)
+                    .is_err()
+            {
+                continue;
+            }
+
+            slot.woken.store(false, Ordering::Relaxed);
+
+            // NOTE(unsafe) the waker only refers to the `woken` flag, which lives in `'static`
+            // memory
+            let waker = unsafe { Waker::from_raw(raw_waker(&slot.woken)) };
+            let mut cx = Context::from_waker(&waker);
+
+            // NOTE(unsafe) the `BUSY` state grants exclusive access to the slot
+            let task = unsafe { &mut *slot.task.get() };
+            let state = match task.as_mut().map(|task| task.poll(&mut cx)) {
+                Some(Poll::Pending) => OCCUPIED,
+                _ => {
+                    *task = None;
+                    FREE
+                }
+            };
+            slot.state.store(state, Ordering::Release);
+
+            polled += 1;
+        }
+
+        polled
+    }
+
+    /// Returns `true` if any task has been woken up since it was last polled
+    pub fn has_ready(&self) -> bool {
+        self.slots.iter().any(|slot| {
+            slot.woken.load(Ordering::Relaxed) && slot.state.load(Ordering::Relaxed) == OCCUPIED
+        })
+    }
+}
+
+impl<P, const N: usize> Default for TaskPool<P, N>
+where
+    P: Pool,
+{
+    fn default() -> Self {
+        TaskPool::new()
+    }
+}
+
+impl<P, const N: usize> fmt::Debug for TaskPool<P, N>
+where
+    P: Pool,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TaskPool").field("capacity", &N).finish()
+    }
+}
+
+// The waker of a task sets its `woken` flag
+static VTABLE: RawWakerVTable =
+    RawWakerVTable::new(waker_clone, waker_wake, waker_wake, waker_drop);
+
+fn raw_waker(woken: &'static AtomicBool) -> RawWaker {
+    RawWaker::new(woken as *const AtomicBool as *const (), &VTABLE)
+}
+
+unsafe fn waker_clone(woken: *const ()) -> RawWaker {
+    raw_waker(&*(woken as *const AtomicBool))
+}
+
+unsafe fn waker_wake(woken: *const ()) {
+    (*(woken as *const AtomicBool)).store(true, Ordering::Release)
+}
+
+unsafe fn waker_drop(_: *const ()) {}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        future::Future,
+        pin::Pin,
+        sync::atomic::{AtomicUsize, Ordering},
+        task::{Context, Poll, Waker},
+    };
+    use std::sync::Mutex;
+
+    use super::TaskPool;
+    use crate::{singleton::Pool, tests::memory};
+
+    static WAKER: Mutex<Option<Waker>> = Mutex::new(None);
+
+    // completes the second time it's polled
+    struct Wait(bool);
+
+    impl Future for Wait {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                *WAKER.lock().unwrap() = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn task_pool() {
+        crate::pool!(A: [usize; 4]);
+        static TASKS: TaskPool<A, 2> = TaskPool::new();
+        static DONE: AtomicUsize = AtomicUsize::new(0);
+
+        A::grow(memory::<[usize; 4]>(2));
+
+        // doesn't fit in a memory block
+        let big = [0u8; 64];
+        assert!(TASKS
+            .spawn(async move { assert_eq!(big.len(), 64) })
+            .is_err());
+
+        TASKS
+            .spawn(async {
+                Wait(false).await;
+                DONE.fetch_add(1, Ordering::Relaxed);
+            })
+            .ok()
+            .unwrap();
+        TASKS
+            .spawn(async {
+                DONE.fetch_add(10, Ordering::Relaxed);
+            })
+            .ok()
+            .unwrap();
+        // the table is full
+        assert!(TASKS.spawn(async {}).is_err());
+
+        assert!(TASKS.has_ready());
+        assert_eq!(TASKS.poll_ready(), 2);
+        assert_eq!(DONE.load(Ordering::Relaxed), 10);
+        // the first task hasn't been woken up
+        assert!(!TASKS.has_ready());
+        assert_eq!(TASKS.poll_ready(), 0);
+
+        WAKER.lock().unwrap().take().unwrap().wake();
+        assert_eq!(TASKS.poll_ready(), 1);
+        assert_eq!(DONE.load(Ordering::Relaxed), 11);
+
+        // the slots, and memory blocks, of the completed tasks are reused
+        assert!(TASKS.spawn(async {}).is_ok());
+        assert!(TASKS.spawn(async {}).is_ok());
+    }
+}
@@ -0,0 +1,376 @@
+//! Pooled packet buffers for `usb-device` endpoints
+//!
+//! USB classes usually copy every packet into, or out of, a fixed array that lives inside the
+//! class. `Packet` is a buffer sized and aligned for one USB packet that can be stored in a memory
+//! block of a global singleton pool, and `PacketQueue` keeps a queue of those boxes for a bulk
+//! endpoint: received packets are read straight into memory blocks of the pool and packets to
+//! send are written straight from them, so a class can hand packets to the rest of the
+//! application, or take them from it, without copying.
+//!
+//! ``` ignore
+//! use lifo::{pool, singleton::{usb::{FsPacket, PacketQueue}, Pool}};
+//!
+//! pool!(P: FsPacket);
+//!
+//! let mut rx = PacketQueue::<P, 4>::new();
+//! let mut tx = PacketQueue::<P, 4>::new();
+//!
+//! // on USB activity
+//! rx.read_from(&ep_out).ok();
+//! tx.write_to(&ep_in).ok();
+//!
+//! // in the application; echo every packet back to the host
+//! if let Some(packet) = rx.dequeue() {
+//!     tx.enqueue(packet).ok();
+//! }
+//! ```
+
+use core::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+use usb_device::{
+    bus::UsbBus,
+    endpoint::{EndpointIn, EndpointOut},
+    UsbError,
+};
+
+use super::{Box, Pool};
+
+/// A buffer for a full-speed bulk packet
+pub type FsPacket = Packet<64>;
+
+/// A buffer for a high-speed bulk packet
+pub type HsPacket = Packet<512>;
+
+/// A buffer that holds one USB packet of up to `N` bytes
+///
+/// The buffer is word aligned so USB peripherals can copy it to and from their packet memory a
+/// word at a time. It dereferences to the bytes of the packet.
+#[repr(C, align(4))]
+pub struct Packet<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Packet<N> {
+    /// Creates an empty packet
+    pub const fn new() -> Self {
+        Packet {
+            buf: [0; N],
+            len: 0,
+        }
+    }
+
+    /// Returns the maximum size of the packet
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Removes all the bytes from the packet
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Appends as many bytes of `data` as fit in the packet
+    ///
+    /// Returns the number of bytes that were appended
+    pub fn extend_from_slice(&mut self, data: &[u8]) -> usize {
+        let n = (N - self.len).min(data.len());
+        self.buf[self.len..self.len + n].copy_from_slice(&data[..n]);
+        self.len += n;
+        n
+    }
+
+    /// Replaces the contents of the packet with a packet read from `ep`
+    ///
+    /// See `EndpointOut::read`
+    pub fn read_from<B>(&mut self, ep: &EndpointOut<'_, B>) -> usb_device::Result<usize>
+    where
+        B: UsbBus,
+    {
+        let n = ep.read(&mut self.buf)?;
+        self.len = n;
+        Ok(n)
+    }
+
+    /// Writes the packet to `ep`
+    ///
+    /// See `EndpointIn::write`
+    pub fn write_to<B>(&self, ep: &EndpointIn<'_, B>) -> usb_device::Result<usize>
+    where
+        B: UsbBus,
+    {
+        ep.write(self)
+    }
+}
+
+impl<const N: usize> Default for Packet<N> {
+    fn default() -> Self {
+        Packet::new()
+    }
+}
+
+impl<const N: usize> Deref for Packet<N> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl<const N: usize> DerefMut for Packet<N> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buf[..self.len]
+    }
+}
+
+impl<const N: usize> fmt::Debug for Packet<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <[u8] as fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// A queue of up to `D` packets stored in memory blocks of the pool `POOL`
+pub struct PacketQueue<POOL, const D: usize>
+where
+    POOL: Pool,
+{
+    // index of the oldest packet
+    head: usize,
+    len: usize,
+    ring: [Option<Box<POOL>>; D],
+}
+
+impl<P, const D: usize> PacketQueue<P, D>
+where
+    P: Pool,
+{
+    // only used to initialize `PacketQueue.ring`
+    const NONE: Option<Box<P>> = None;
+
+    /// Creates an empty queue
+    pub const fn new() -> Self {
+        PacketQueue {
+            head: 0,
+            len: 0,
+            ring: [Self::NONE; D],
+        }
+    }
+
+    /// Returns the number of packets in the queue
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `true` if the queue is full
+    pub fn is_full(&self) -> bool {
+        self.len == D
+    }
+
+    /// Adds `packet` to the back of the queue
+    ///
+    /// Returns `packet` back if the queue is full
+    pub fn enqueue(&mut self, packet: Box<P>) -> Result<(), Box<P>> {
+        if self.is_full() {
+            return Err(packet);
+        }
+
+        self.ring[(self.head + self.len) % D] = Some(packet);
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// Removes the packet at the front of the queue
+    pub fn dequeue(&mut self) -> Option<Box<P>> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let packet = self.ring[self.head].take();
+        self.head = (self.head + 1) % D;
+        self.len -= 1;
+
+        packet
+    }
+}
+
+impl<P, const N: usize, const D: usize> PacketQueue<P, D>
+where
+    P: Pool<Data = Packet<N>>,
+{
+    /// Reads a packet from `ep` into a memory block claimed from the pool `P` and adds it to the
+    /// back of the queue
+    ///
+    /// Returns `WouldBlock`, and leaves the packet in the endpoint, if the queue is full or if the
+    /// pool is observed as exhausted. Otherwise see `EndpointOut::read`.
+    pub fn read_from<B>(&mut self, ep: &EndpointOut<'_, B>) -> usb_device::Result<usize>
+    where
+        B: UsbBus,
+    {
+        if self.is_full() {
+            return Err(UsbError::WouldBlock);
+        }
+
+        let mut packet = P::alloc().ok_or(UsbError::WouldBlock)?.init(Packet::new());
+        let n = packet.read_from(ep)?;
+        self.enqueue(packet).ok();
+
+        Ok(n)
+    }
+
+    /// Writes the packet at the front of the queue to `ep` and, if that succeeds, returns its
+    /// memory block to the pool `P`
+    ///
+    /// Returns `WouldBlock` if the queue is empty. Otherwise see `EndpointIn::write`.
+    pub fn write_to<B>(&mut self, ep: &EndpointIn<'_, B>) -> usb_device::Result<usize>
+    where
+        B: UsbBus,
+    {
+        let n = match self.ring[self.head].as_ref() {
+            Some(packet) if !self.is_empty() => packet.write_to(ep)?,
+            _ => return Err(UsbError::WouldBlock),
+        };
+        drop(self.dequeue());
+
+        Ok(n)
+    }
+}
+
+impl<P, const D: usize> Default for PacketQueue<P, D>
+where
+    P: Pool,
+{
+    fn default() -> Self {
+        PacketQueue::new()
+    }
+}
+
+impl<P, const D: usize> fmt::Debug for PacketQueue<P, D>
+where
+    P: Pool,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PacketQueue")
+            .field("len", &self.len)
+            .field("capacity", &D)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, sync::Mutex};
+
+    use usb_device::{
+        bus::{PollResult, UsbBus, UsbBusAllocator},
+        device::{UsbDeviceBuilder, UsbVidPid},
+        endpoint::{EndpointAddress, EndpointType},
+        UsbDirection, UsbError,
+    };
+
+    use super::{FsPacket, PacketQueue};
+    use crate::{singleton::Pool, tests::memory};
+
+    // loops the packets written to any IN endpoint back to the OUT endpoints
+    #[derive(Default)]
+    struct Loopback {
+        packets: Mutex<VecDeque<Vec<u8>>>,
+        next: Mutex<usize>,
+    }
+
+    impl UsbBus for Loopback {
+        fn alloc_ep(
+            &mut self,
+            dir: UsbDirection,
+            ep_addr: Option<EndpointAddress>,
+            _: EndpointType,
+            _: u16,
+            _: u8,
+        ) -> usb_device::Result<EndpointAddress> {
+            let mut next = self.next.lock().unwrap();
+            *next += 1;
+            Ok(ep_addr.unwrap_or_else(|| EndpointAddress::from_parts(*next, dir)))
+        }
+
+        fn enable(&mut self) {}
+
+        fn reset(&self) {}
+
+        fn set_device_address(&self, _: u8) {}
+
+        fn write(&self, _: EndpointAddress, buf: &[u8]) -> usb_device::Result<usize> {
+            self.packets.lock().unwrap().push_back(buf.to_vec());
+            Ok(buf.len())
+        }
+
+        fn read(&self, _: EndpointAddress, buf: &mut [u8]) -> usb_device::Result<usize> {
+            let packet = self
+                .packets
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or(UsbError::WouldBlock)?;
+            buf[..packet.len()].copy_from_slice(&packet);
+            Ok(packet.len())
+        }
+
+        fn set_stalled(&self, _: EndpointAddress, _: bool) {}
+
+        fn is_stalled(&self, _: EndpointAddress) -> bool {
+            false
+        }
+
+        fn suspend(&self) {}
+
+        fn resume(&self) {}
+
+        fn poll(&self) -> PollResult {
+            PollResult::None
+        }
+    }
+
+    #[test]
+    fn packet_queue() {
+        crate::pool!(P: FsPacket);
+
+        P::grow(memory::<FsPacket>(2));
+
+        let alloc = UsbBusAllocator::new(Loopback::default());
+        let ep_in = alloc.bulk(64);
+        let ep_out = alloc.bulk(64);
+        let _device = UsbDeviceBuilder::new(&alloc, UsbVidPid(0x1209, 0x0001)).build();
+
+        let mut tx = PacketQueue::<P, 2>::new();
+        let mut rx = PacketQueue::<P, 2>::new();
+        assert_eq!(tx.write_to(&ep_in), Err(UsbError::WouldBlock));
+
+        let mut packet = P::alloc().unwrap().init(FsPacket::new());
+        assert_eq!(packet.extend_from_slice(&[1; 100]), 64);
+        tx.enqueue(packet).ok().unwrap();
+        let mut packet = P::alloc().unwrap().init(FsPacket::new());
+        packet.extend_from_slice(b"abc");
+        tx.enqueue(packet).ok().unwrap();
+
+        // the sent packets are returned to the pool
+        assert_eq!(tx.write_to(&ep_in), Ok(64));
+        assert_eq!(tx.write_to(&ep_in), Ok(3));
+        assert!(tx.is_empty());
+
+        assert_eq!(rx.read_from(&ep_out), Ok(64));
+        assert_eq!(rx.read_from(&ep_out), Ok(3));
+        // the queue is full
+        assert_eq!(rx.read_from(&ep_out), Err(UsbError::WouldBlock));
+
+        assert_eq!(&rx.dequeue().unwrap()[..], &[1; 64][..]);
+        assert_eq!(&rx.dequeue().unwrap()[..], b"abc");
+        assert!(rx.dequeue().is_none());
+    }
+}
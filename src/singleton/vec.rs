@@ -0,0 +1,493 @@
+//! Growable vectors backed by a chain of memory blocks
+
+use core::{cmp, fmt, marker::PhantomData, mem, ptr, ptr::NonNull, slice};
+
+use as_slice::AsSlice;
+
+use super::{waker, Pool};
+use crate::Node;
+
+/// The type of the elements of a `PoolVec<P>`
+type Element<P> = <<P as Pool>::Data as AsSlice>::Element;
+
+/// A growable vector whose storage is a chain of memory blocks, i.e. chunks, of the pool `POOL`
+///
+/// `POOL::Data` is expected to be an array, like `[T; N]`; each memory block holds `N` elements.
+/// Memory blocks are claimed as the vector grows and returned to the pool as it shrinks. Chunks
+/// are linked through the `next` pointer of their memory block so no other storage is needed.
+///
+/// ``` ignore
+/// pool!(Chunks: [Sample; 16]);
+///
+/// let mut samples = PoolVec::<Chunks>::new();
+/// for sample in adc.samples() {
+///     // claims a new memory block every 16 samples
+///     samples.push(sample).ok().unwrap();
+/// }
+///
+/// let sum = samples.iter().map(|s| s.value).sum();
+/// ```
+pub struct PoolVec<POOL>
+where
+    POOL: Pool,
+    POOL::Data: AsSlice,
+{
+    head: *mut Node<POOL::Data>,
+    tail: *mut Node<POOL::Data>,
+    len: usize,
+    _pool: PhantomData<POOL>,
+}
+
+impl<P> PoolVec<P>
+where
+    P: Pool,
+    P::Data: AsSlice,
+{
+    // Number of elements in each memory block
+    const CHUNK: usize = mem::size_of::<P::Data>() / mem::size_of::<Element<P>>();
+
+    /// Creates a new empty vector
+    ///
+    /// This doesn't claim any memory block
+    pub fn new() -> Self {
+        PoolVec {
+            head: ptr::null_mut(),
+            tail: ptr::null_mut(),
+            len: 0,
+            _pool: PhantomData,
+        }
+    }
+
+    /// Returns the number of elements in the vector
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the vector contains no elements
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of elements the vector can hold without claiming more memory blocks
+    pub fn capacity(&self) -> usize {
+        Self::chunk_count(self.len) * Self::CHUNK
+    }
+
+    /// Appends `val` to the back of the vector
+    ///
+    /// Returns `val` back when a memory block needs to be claimed but the pool is observed as
+    /// exhausted
+    pub fn push(&mut self, val: Element<P>) -> Result<(), Element<P>> {
+        let offset = self.len % Self::CHUNK;
+
+        if offset == 0 {
+            let block = match P::alloc() {
+                Some(block) => block,
+                None => return Err(val),
+            };
+            let node = block.inner.node.as_ptr();
+
+            // NOTE the memory block is now owned by the vector
+            mem::forget(block);
+
+            unsafe {
+                (*node).next = ptr::null_mut();
+
+                if self.tail.is_null() {
+                    self.head = node;
+                } else {
+                    (*self.tail).next = node;
+                }
+            }
+            self.tail = node;
+        }
+
+        unsafe { elements::<P>(self.tail).add(offset).write(val) }
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// Removes the last element from the vector and returns it
+    ///
+    /// *NOTE:* When this empties the last memory block of the vector, this method walks the chain
+    /// of memory blocks to find its new last memory block
+    pub fn pop(&mut self) -> Option<Element<P>> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let offset = (self.len - 1) % Self::CHUNK;
+        let val = unsafe { elements::<P>(self.tail).add(offset).read() };
+        self.len -= 1;
+
+        if offset == 0 {
+            self.free_chunks();
+        }
+
+        Some(val)
+    }
+
+    /// Returns a reference to the element at `index`
+    ///
+    /// *NOTE:* This method walks the chain of memory blocks
+    pub fn get(&self, index: usize) -> Option<&Element<P>> {
+        if index < self.len {
+            Some(unsafe { &*self.element(index) })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the element at `index`
+    ///
+    /// *NOTE:* This method walks the chain of memory blocks
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Element<P>> {
+        if index < self.len {
+            Some(unsafe { &mut *self.element(index) })
+        } else {
+            None
+        }
+    }
+
+    /// Shortens the vector to `len` elements, dropping the rest and returning the memory blocks
+    /// that are no longer needed to the pool
+    ///
+    /// This has no effect if `len` is greater than the vector's current length
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+
+        let old_len = self.len;
+        // NOTE update the length first in case a destructor panics
+        self.len = len;
+
+        let mut node = self.head;
+        for index in 0..old_len {
+            let offset = index % Self::CHUNK;
+            if index != 0 && offset == 0 {
+                node = unsafe { (*node).next };
+            }
+
+            if index >= len {
+                unsafe { ptr::drop_in_place(elements::<P>(node).add(offset)) }
+            }
+        }
+
+        self.free_chunks();
+    }
+
+    /// Removes all the elements from the vector and returns all its memory blocks to the pool
+    pub fn clear(&mut self) {
+        self.truncate(0)
+    }
+
+    /// Returns an iterator over the elements of the vector
+    pub fn iter(&self) -> Iter<'_, P> {
+        Iter {
+            node: self.head,
+            index: 0,
+            len: self.len,
+            _vec: PhantomData,
+        }
+    }
+
+    /// Returns an iterator that allows modifying the elements of the vector
+    pub fn iter_mut(&mut self) -> IterMut<'_, P> {
+        IterMut {
+            node: self.head,
+            index: 0,
+            len: self.len,
+            _vec: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over the contents of the memory blocks of the vector, as slices
+    ///
+    /// All the slices but the last one are full memory blocks
+    pub fn chunks(&self) -> Chunks<'_, P> {
+        Chunks {
+            node: self.head,
+            len: self.len,
+            _vec: PhantomData,
+        }
+    }
+
+    // Number of memory blocks needed to hold `len` elements
+    fn chunk_count(len: usize) -> usize {
+        // NOTE only the last chunk can be partially filled
+        if len == 0 {
+            0
+        } else {
+            (len - 1) / Self::CHUNK + 1
+        }
+    }
+
+    fn element(&self, index: usize) -> *mut Element<P> {
+        let mut node = self.head;
+        for _ in 0..index / Self::CHUNK {
+            node = unsafe { (*node).next };
+        }
+
+        unsafe { elements::<P>(node).add(index % Self::CHUNK) }
+    }
+
+    // Returns the memory blocks past the ones needed to hold `len` elements to the pool
+    fn free_chunks(&mut self) {
+        let chunks = Self::chunk_count(self.len);
+
+        let mut link: *mut *mut Node<P::Data> = &mut self.head;
+        let mut tail = ptr::null_mut();
+        for _ in 0..chunks {
+            unsafe {
+                tail = *link;
+                link = &mut (*tail).next;
+            }
+        }
+
+        let mut node = unsafe { mem::replace(&mut *link, ptr::null_mut()) };
+        self.tail = tail;
+
+        while let Some(nn) = NonNull::new(node) {
+            unsafe { node = (*node).next }
+
+            P::ptr().release(nn);
+            waker::wake_one(P::wakers())
+        }
+    }
+}
+
+// Returns a pointer to the first element of the memory block `node`
+unsafe fn elements<P>(node: *mut Node<P::Data>) -> *mut Element<P>
+where
+    P: Pool,
+    P::Data: AsSlice,
+{
+    (*node).data.get() as *mut Element<P>
+}
+
+impl<P> Default for PoolVec<P>
+where
+    P: Pool,
+    P::Data: AsSlice,
+{
+    fn default() -> Self {
+        PoolVec::new()
+    }
+}
+
+impl<P> Drop for PoolVec<P>
+where
+    P: Pool,
+    P::Data: AsSlice,
+{
+    fn drop(&mut self) {
+        self.clear()
+    }
+}
+
+unsafe impl<P> Send for PoolVec<P>
+where
+    P: Pool,
+    P::Data: AsSlice,
+    Element<P>: Send,
+{
+}
+
+unsafe impl<P> Sync for PoolVec<P>
+where
+    P: Pool,
+    P::Data: AsSlice,
+    Element<P>: Sync,
+{
+}
+
+impl<P> fmt::Debug for PoolVec<P>
+where
+    P: Pool,
+    P::Data: AsSlice,
+    Element<P>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, P> IntoIterator for &'a PoolVec<P>
+where
+    P: Pool,
+    P::Data: AsSlice,
+{
+    type Item = &'a Element<P>;
+    type IntoIter = Iter<'a, P>;
+
+    fn into_iter(self) -> Iter<'a, P> {
+        self.iter()
+    }
+}
+
+impl<'a, P> IntoIterator for &'a mut PoolVec<P>
+where
+    P: Pool,
+    P::Data: AsSlice,
+{
+    type Item = &'a mut Element<P>;
+    type IntoIter = IterMut<'a, P>;
+
+    fn into_iter(self) -> IterMut<'a, P> {
+        self.iter_mut()
+    }
+}
+
+/// An iterator over the elements of a `PoolVec`
+pub struct Iter<'a, P>
+where
+    P: Pool,
+    P::Data: AsSlice,
+{
+    node: *mut Node<P::Data>,
+    index: usize,
+    len: usize,
+    _vec: PhantomData<&'a PoolVec<P>>,
+}
+
+impl<'a, P> Iterator for Iter<'a, P>
+where
+    P: Pool,
+    P::Data: AsSlice,
+{
+    type Item = &'a Element<P>;
+
+    fn next(&mut self) -> Option<&'a Element<P>> {
+        next::<P>(&mut self.node, &mut self.index, self.len).map(|elem| unsafe { &*elem })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.len - self.index;
+        (n, Some(n))
+    }
+}
+
+/// An iterator that allows modifying the elements of a `PoolVec`
+pub struct IterMut<'a, P>
+where
+    P: Pool,
+    P::Data: AsSlice,
+{
+    node: *mut Node<P::Data>,
+    index: usize,
+    len: usize,
+    _vec: PhantomData<&'a mut PoolVec<P>>,
+}
+
+impl<'a, P> Iterator for IterMut<'a, P>
+where
+    P: Pool,
+    P::Data: AsSlice,
+{
+    type Item = &'a mut Element<P>;
+
+    fn next(&mut self) -> Option<&'a mut Element<P>> {
+        next::<P>(&mut self.node, &mut self.index, self.len).map(|elem| unsafe { &mut *elem })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.len - self.index;
+        (n, Some(n))
+    }
+}
+
+/// An iterator over the contents of the memory blocks of a `PoolVec`
+pub struct Chunks<'a, P>
+where
+    P: Pool,
+    P::Data: AsSlice,
+{
+    node: *mut Node<P::Data>,
+    // number of elements not yet yielded
+    len: usize,
+    _vec: PhantomData<&'a PoolVec<P>>,
+}
+
+impl<'a, P> Iterator for Chunks<'a, P>
+where
+    P: Pool,
+    P::Data: AsSlice,
+{
+    type Item = &'a [Element<P>];
+
+    fn next(&mut self) -> Option<&'a [Element<P>]> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let len = cmp::min(self.len, PoolVec::<P>::CHUNK);
+        let chunk = unsafe { slice::from_raw_parts(elements::<P>(self.node), len) };
+
+        self.len -= len;
+        self.node = unsafe { (*self.node).next };
+
+        Some(chunk)
+    }
+}
+
+fn next<P>(node: &mut *mut Node<P::Data>, index: &mut usize, len: usize) -> Option<*mut Element<P>>
+where
+    P: Pool,
+    P::Data: AsSlice,
+{
+    if *index == len {
+        return None;
+    }
+
+    let offset = *index % PoolVec::<P>::CHUNK;
+    if *index != 0 && offset == 0 {
+        *node = unsafe { (**node).next };
+    }
+    *index += 1;
+
+    Some(unsafe { elements::<P>(*node).add(offset) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PoolVec;
+    use crate::{singleton::Pool, tests::memory};
+
+    #[test]
+    fn pool_vec() {
+        crate::pool!(A: [u16; 2]);
+
+        A::grow(memory::<[u16; 2]>(2));
+
+        let mut v = PoolVec::<A>::new();
+        assert_eq!(v.capacity(), 0);
+
+        for i in 0..4 {
+            v.push(i).unwrap();
+        }
+        // both memory blocks are in use
+        assert_eq!(v.push(4), Err(4));
+        assert!(A::alloc().is_none());
+
+        for x in &mut v {
+            *x *= 10;
+        }
+        assert_eq!(v.iter().copied().collect::<Vec<_>>(), [0, 10, 20, 30]);
+        assert_eq!(v.get(2), Some(&20));
+        assert_eq!(v.get(4), None);
+
+        assert_eq!(v.pop(), Some(30));
+        assert_eq!(v.capacity(), 4);
+
+        // the second memory block is returned to the pool
+        v.truncate(1);
+        assert_eq!(v.len(), 1);
+        assert_eq!(v.capacity(), 2);
+        assert!(A::alloc().is_some());
+
+        v.push(5).unwrap();
+        assert_eq!(format!("{:?}", v), "[0, 5]");
+    }
+}
@@ -0,0 +1,358 @@
+//! Asynchronous allocation
+
+use core::{
+    cell::UnsafeCell,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+};
+
+use super::{Box, Pool};
+use crate::Uninit;
+
+// The state of a slot is a tag (the 2 lowest bits) plus a generation counter (the remaining bits).
+// The generation is bumped every time the slot is emptied so a future can tell whether the slot it
+// registered its waker in has been reused by another future
+const EMPTY: usize = 0;
+const LOCKED: usize = 1;
+const FULL: usize = 2;
+const TAG: usize = 0b11;
+
+/// A slot in the waker table of a pool
+///
+/// Implementation detail of `pool!`; see the `wakers` option
+#[doc(hidden)]
+pub struct WakerSlot {
+    state: AtomicUsize,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+unsafe impl Sync for WakerSlot {}
+
+impl WakerSlot {
+    #[doc(hidden)]
+    pub const fn new() -> Self {
+        WakerSlot {
+            state: AtomicUsize::new(EMPTY),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    // Tries to store `waker` in this slot; returns the generation of the slot on success
+    fn claim(&self, waker: &Waker) -> Option<usize> {
+        let state = self.state.load(Ordering::Relaxed);
+        if state & TAG != EMPTY {
+            return None;
+        }
+
+        let generation = state & !TAG;
+        self.state
+            .compare_exchange(
+                state,
+                generation | LOCKED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .ok()?;
+
+        unsafe { *self.waker.get() = Some(waker.clone()) }
+        self.state.store(generation | FULL, Ordering::Release);
+
+        Some(generation)
+    }
+
+    // Replaces the waker previously stored with `claim`; returns `false` if the slot has been
+    // emptied since
+    fn update(&self, generation: usize, waker: &Waker) -> bool {
+        if !self.lock(generation) {
+            return false;
+        }
+
+        unsafe {
+            let slot = &mut *self.waker.get();
+            match slot {
+                Some(old) if old.will_wake(waker) => {}
+                _ => *slot = Some(waker.clone()),
+            }
+        }
+        self.state.store(generation | FULL, Ordering::Release);
+
+        true
+    }
+
+    // Removes the waker previously stored with `claim`, if it's still there; returns `false` if
+    // the task has already been woken
+    fn cancel(&self, generation: usize) -> bool {
+        if self.lock(generation) {
+            drop(self.take(generation));
+            true
+        } else {
+            false
+        }
+    }
+
+    // Wakes the task stored in this slot, if any; returns `true` if a task was woken
+    fn wake(&self) -> bool {
+        let state = self.state.load(Ordering::Relaxed);
+        if state & TAG != FULL {
+            return false;
+        }
+
+        let generation = state & !TAG;
+        if !self.lock(generation) {
+            return false;
+        }
+
+        match self.take(generation) {
+            Some(waker) => {
+                waker.wake();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn lock(&self, generation: usize) -> bool {
+        self.state
+            .compare_exchange(
+                generation | FULL,
+                generation | LOCKED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+
+    // NOTE the slot must be locked
+    fn take(&self, generation: usize) -> Option<Waker> {
+        let waker = unsafe { (*self.waker.get()).take() };
+        self.state
+            .store(generation.wrapping_add(TAG + 1) | EMPTY, Ordering::Release);
+        waker
+    }
+}
+
+// Wakes one of the tasks waiting for a memory block
+pub(crate) fn wake_one(slots: &[WakerSlot]) {
+    for slot in slots {
+        if slot.wake() {
+            break;
+        }
+    }
+}
+
+// Wakes all the tasks waiting for a memory block
+pub(crate) fn wake_all(slots: &[WakerSlot]) {
+    for slot in slots {
+        slot.wake();
+    }
+}
+
+/// Future returned by `Pool::alloc_async`
+pub struct AllocFuture<POOL>
+where
+    POOL: Pool,
+{
+    _pool: PhantomData<POOL>,
+    // index and generation of the slot that holds our waker
+    slot: Option<(usize, usize)>,
+}
+
+impl<P> AllocFuture<P>
+where
+    P: Pool,
+{
+    pub(crate) fn new() -> Self {
+        AllocFuture {
+            _pool: PhantomData,
+            slot: None,
+        }
+    }
+
+    fn register(&mut self, waker: &Waker) -> bool {
+        let slots = P::wakers();
+
+        if let Some((index, generation)) = self.slot {
+            if slots[index].update(generation, waker) {
+                return true;
+            }
+        }
+
+        self.slot = slots
+            .iter()
+            .enumerate()
+            .find_map(|(index, slot)| slot.claim(waker).map(|generation| (index, generation)));
+
+        self.slot.is_some()
+    }
+
+    // Returns `false` if the task was woken but has not claimed a memory block yet
+    fn cancel(&mut self) -> bool {
+        match self.slot.take() {
+            Some((index, generation)) => P::wakers()[index].cancel(generation),
+            None => true,
+        }
+    }
+}
+
+// NOTE the future holds no self-references
+impl<P> Unpin for AllocFuture<P> where P: Pool {}
+
+impl<P> Future for AllocFuture<P>
+where
+    P: Pool,
+{
+    type Output = Box<P, Uninit>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Box<P, Uninit>> {
+        let this = self.get_mut();
+
+        if let Some(block) = P::alloc() {
+            this.cancel();
+            return Poll::Ready(block);
+        }
+
+        if this.register(cx.waker()) {
+            // a memory block may have been freed before the waker was registered
+            if let Some(block) = P::alloc() {
+                this.cancel();
+                return Poll::Ready(block);
+            }
+        } else {
+            // no waker slot available; fall back to polling
+            cx.waker().wake_by_ref();
+        }
+
+        Poll::Pending
+    }
+}
+
+impl<P> Drop for AllocFuture<P>
+where
+    P: Pool,
+{
+    fn drop(&mut self) {
+        if !self.cancel() {
+            // the wakeup was meant for a memory block that this future will never claim; hand it
+            // over to the next waiting task so it doesn't get lost
+            wake_one(P::wakers())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::Future,
+        pin::Pin,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Context, Poll, Wake, Waker},
+    };
+
+    use crate::{singleton::Pool, tests::memory};
+
+    struct Counter(AtomicUsize);
+
+    impl Wake for Counter {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn alloc_async() {
+        crate::pool!(A: u8; capacity = 1; wakers = 1);
+
+        A::init();
+
+        let counter = Arc::new(Counter(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let x = A::alloc().unwrap();
+
+        let mut fut = A::alloc_async();
+        assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+
+        // the second future doesn't get a waker slot and keeps polling itself
+        let mut other = A::alloc_async();
+        assert!(Pin::new(&mut other).poll(&mut cx).is_pending());
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+        drop(other);
+
+        drop(x);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 2);
+
+        match Pin::new(&mut fut).poll(&mut cx) {
+            Poll::Ready(block) => drop(block),
+            Poll::Pending => panic!("memory block was not claimed"),
+        }
+    }
+
+    #[test]
+    fn grow_wakes() {
+        crate::pool!(B: u8; wakers = 2);
+
+        let counter = Arc::new(Counter(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut a = B::alloc_async();
+        let mut b = B::alloc_async();
+        assert!(Pin::new(&mut a).poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut b).poll(&mut cx).is_pending());
+        assert_eq!(counter.0.load(Ordering::Relaxed), 0);
+
+        B::grow(memory::<u8>(2));
+        assert_eq!(counter.0.load(Ordering::Relaxed), 2);
+
+        for fut in [&mut a, &mut b] {
+            match Pin::new(fut).poll(&mut cx) {
+                Poll::Ready(block) => drop(block),
+                Poll::Pending => panic!("memory block was not claimed"),
+            }
+        }
+    }
+
+    #[test]
+    fn drop_woken() {
+        crate::pool!(C: u8; capacity = 1; wakers = 2);
+
+        C::init();
+
+        let first = Arc::new(Counter(AtomicUsize::new(0)));
+        let first_waker = Waker::from(first.clone());
+        let second = Arc::new(Counter(AtomicUsize::new(0)));
+        let second_waker = Waker::from(second.clone());
+
+        let x = C::alloc().unwrap();
+
+        let mut a = C::alloc_async();
+        let mut b = C::alloc_async();
+        assert!(Pin::new(&mut a)
+            .poll(&mut Context::from_waker(&first_waker))
+            .is_pending());
+        assert!(Pin::new(&mut b)
+            .poll(&mut Context::from_waker(&second_waker))
+            .is_pending());
+
+        // only the first task is woken
+        drop(x);
+        assert_eq!(first.0.load(Ordering::Relaxed), 1);
+        assert_eq!(second.0.load(Ordering::Relaxed), 0);
+
+        // the first task gives up without claiming the memory block so the second task is woken
+        drop(a);
+        assert_eq!(second.0.load(Ordering::Relaxed), 1);
+
+        match Pin::new(&mut b).poll(&mut Context::from_waker(&second_waker)) {
+            Poll::Ready(block) => drop(block),
+            Poll::Pending => panic!("memory block was not claimed"),
+        }
+    }
+}
@@ -0,0 +1,283 @@
+//! Deferring closures to a lower priority context
+//!
+//! `WorkQueue` moves closures into memory blocks of a global singleton pool and links those memory
+//! blocks into an intrusive queue (see the `mpsc` module), so neither storing nor queuing a
+//! closure needs any memory besides its memory block. Interrupt handlers `spawn` closures and pend
+//! a low priority software interrupt, usually PendSV, whose handler runs them with `run_pending`.
+//!
+//! ``` ignore
+//! use cortex_m::peripheral::SCB;
+//! use lifo::{pool, singleton::work::WorkQueue};
+//!
+//! // closures whose captured environment is up to 24 bytes large
+//! pool!(W: [usize; 32 / core::mem::size_of::<usize>()]);
+//!
+//! static WORK: WorkQueue<W> = WorkQueue::new();
+//!
+//! #[interrupt]
+//! fn USART1() {
+//!     let byte = uart.read();
+//!
+//!     // keep the interrupt handler short; process the byte later
+//!     if WORK.spawn(move || handle_byte(byte)).is_ok() {
+//!         SCB::set_pendsv();
+//!     }
+//! }
+//!
+//! #[exception]
+//! fn PendSV() {
+//!     WORK.run_pending();
+//! }
+//! ```
+
+use core::{
+    fmt,
+    marker::PhantomData,
+    mem, ptr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::{Box, Pool};
+use crate::mpsc::Mpsc;
+
+// The contents of the memory block of a spawned closure. `run` is the first field so it can be
+// found without knowing the type of the closure
+#[repr(C)]
+struct Deferred<F> {
+    run: unsafe fn(*mut u8, bool),
+    f: F,
+}
+
+// Moves the closure out of `Deferred<F>` and calls it, or only drops it
+//
+// # Safety
+//
+// `deferred` must point to an initialized `Deferred<F>`, which must not be used afterwards
+unsafe fn run<F>(deferred: *mut u8, call: bool)
+where
+    F: FnOnce(),
+{
+    let f = ptr::read(&(*(deferred as *mut Deferred<F>)).f);
+
+    if call {
+        f()
+    }
+}
+
+/// A queue of `FnOnce()` closures stored in memory blocks of the pool `POOL`
+///
+/// Closures can be spawned from any context; `run_pending` runs them in the order they were
+/// spawned. Dropping the queue drops the closures that have not run yet.
+pub struct WorkQueue<POOL>
+where
+    POOL: Pool,
+{
+    queue: Mpsc<POOL::Data>,
+    // a context is in `run_pending`
+    running: AtomicBool,
+    _pool: PhantomData<POOL>,
+}
+
+// NOTE(unsafe) the memory blocks in the queue only ever hold `Send` closures, and `running`
+// ensures there's a single consumer at any time
+unsafe impl<P> Sync for WorkQueue<P> where P: Pool {}
+
+unsafe impl<P> Send for WorkQueue<P> where P: Pool {}
+
+impl<P> WorkQueue<P>
+where
+    P: Pool,
+{
+    /// Creates a new empty queue
+    pub const fn new() -> Self {
+        WorkQueue {
+            queue: Mpsc::new(),
+            running: AtomicBool::new(false),
+            _pool: PhantomData,
+        }
+    }
+
+    /// Moves `f` into a memory block of the pool `P` and appends it to the queue
+    ///
+    /// Returns `f` back when the pool is observed as exhausted, or if `f` doesn't fit in a memory
+    /// block along with a function pointer, either because it's too big or because it needs a
+    /// stricter alignment
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains CAS loops
+    pub fn spawn<F>(&self, f: F) -> Result<(), F>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let block = match P::alloc() {
+            Some(block) => block,
+            None => return Err(f),
+        };
+
+        let node = block.into_box().into_node();
+        let p = unsafe { node.as_ref().data.get() as *mut Deferred<F> };
+
+        if mem::size_of::<Deferred<F>>() > mem::size_of::<P::Data>()
+            || p.align_offset(mem::align_of::<Deferred<F>>()) != 0
+        {
+            free::<P>(node);
+            return Err(f);
+        }
+
+        unsafe { ptr::write(p, Deferred { run: run::<F>, f }) }
+
+        self.queue.producer().send(crate::Box {
+            _state: PhantomData,
+            node,
+        });
+
+        Ok(())
+    }
+
+    /// Runs the queued closures, including the ones spawned while this method runs, until the
+    /// queue is observed as empty
+    ///
+    /// Returns the number of closures that ran. A call made while another context is already in
+    /// this method, e.g. from one of the closures, returns `0` right away.
+    pub fn run_pending(&self) -> usize {
+        self.drain(true)
+    }
+
+    fn drain(&self, call: bool) -> usize {
+        if self.running.swap(true, Ordering::Acquire) {
+            return 0;
+        }
+
+        // NOTE(unsafe) `running` makes this the only consumer
+        let mut consumer = unsafe { self.queue.consumer() };
+
+        let mut n = 0;
+        while let Some(deferred) = consumer.recv() {
+            let node = deferred.into_node();
+
+            // NOTE(unsafe) the memory block holds a `Deferred` closure, which is moved out of it
+            // before the memory block is returned to the pool
+            unsafe {
+                let p = node.as_ref().data.get() as *mut u8;
+                (*(p as *const unsafe fn(*mut u8, bool)))(p, call);
+            }
+
+            free::<P>(node);
+            n += 1;
+        }
+
+        self.running.store(false, Ordering::Release);
+
+        n
+    }
+}
+
+// Returns a memory block to the pool `P` without running any destructor
+fn free<P>(node: ptr::NonNull<crate::Node<P::Data>>)
+where
+    P: Pool,
+{
+    // NOTE(unsafe) the memory block was claimed from the pool `P`
+    unsafe {
+        Box::<P>::from_box(crate::Box {
+            _state: PhantomData,
+            node,
+        })
+    }
+    .free_uninit()
+}
+
+impl<P> Default for WorkQueue<P>
+where
+    P: Pool,
+{
+    fn default() -> Self {
+        WorkQueue::new()
+    }
+}
+
+impl<P> Drop for WorkQueue<P>
+where
+    P: Pool,
+{
+    fn drop(&mut self) {
+        self.drain(false);
+    }
+}
+
+impl<P> fmt::Debug for WorkQueue<P>
+where
+    P: Pool,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WorkQueue").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        mem,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+    use std::sync::Arc;
+
+    use super::WorkQueue;
+    use crate::{singleton::Pool, tests::memory};
+
+    #[test]
+    fn work_queue() {
+        static LOG: AtomicUsize = AtomicUsize::new(0);
+        static WORK: WorkQueue<W> = WorkQueue::new();
+
+        crate::pool!(W: [usize; 3]);
+
+        W::grow(memory::<[usize; 3]>(2));
+
+        assert_eq!(WORK.run_pending(), 0);
+
+        for i in 1..=2usize {
+            WORK.spawn(move || {
+                LOG.store(LOG.load(Ordering::Relaxed) * 10 + i, Ordering::Relaxed);
+            })
+            .ok()
+            .unwrap();
+        }
+
+        // the pool is exhausted
+        assert!(WORK.spawn(|| {}).is_err());
+
+        // closures run in the order they were spawned
+        assert_eq!(WORK.run_pending(), 2);
+        assert_eq!(LOG.load(Ordering::Relaxed), 12);
+        assert_eq!(WORK.run_pending(), 0);
+
+        // the environment doesn't fit in a memory block along with the function pointer
+        let big = [0usize; 3];
+        assert!(WORK
+            .spawn(move || {
+                LOG.fetch_add(big.len(), Ordering::Relaxed);
+            })
+            .is_err());
+
+        // the memory blocks are back in the pool
+        assert!(W::alloc().is_some());
+        assert!(W::alloc().is_some());
+    }
+
+    #[test]
+    fn drop_pending() {
+        crate::pool!(W: [usize; 2]);
+
+        W::grow(memory::<[usize; 2]>(1));
+
+        let arc = Arc::new(());
+        let c = arc.clone();
+        let work = WorkQueue::<W>::new();
+        work.spawn(move || mem::drop(c)).ok().unwrap();
+
+        // the closure is dropped without running and its memory block is returned
+        mem::drop(work);
+        assert_eq!(Arc::strong_count(&arc), 1);
+        assert!(W::alloc().is_some());
+    }
+}
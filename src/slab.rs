@@ -0,0 +1,406 @@
+//! Index-based slab with small, generational keys
+//!
+//! `Slab<T, N>` stores up to `N` values inline and identifies each of them with a `Key`: a 16-bit
+//! integer made of the index of the value in the slab plus a generation counter. Keys are much
+//! cheaper to store in queues, registers or messages than pointers and they can be sent to other
+//! cores, or serialized, as plain integers. The generation counter makes stale keys, i.e. keys to
+//! values that have been removed, detectable even after their slot has been reused.
+//!
+//! ``` ignore
+//! use lifo::slab::{Key, Slab};
+//!
+//! static REQUESTS: Slab<Request, 64> = Slab::new();
+//!
+//! let key = REQUESTS.insert(Request::new()).ok().unwrap();
+//!
+//! // e.g. passed to the other core through a hardware mailbox
+//! let raw: u16 = key.into_raw();
+//!
+//! if let Some(mut request) = REQUESTS.get(Key::from_raw(raw)) {
+//!     request.complete();
+//! }
+//!
+//! let request = REQUESTS.remove(key).unwrap();
+//! // `key` is now stale
+//! assert!(REQUESTS.get(key).is_none());
+//! ```
+//!
+//! The free list of the slab is a stack of indices tagged with a modification counter so, unlike
+//! `Pool`, `Slab` doesn't rely on LL/SC semantics to avoid the ABA problem and it's `Sync` on all
+//! targets.
+
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    mem::{self, MaybeUninit},
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::atomic::{AtomicU16, AtomicU32, AtomicUsize, Ordering},
+};
+
+// Bits of the state of a slot; the remaining (upper) bits are the generation of the slot
+const OCCUPIED: u32 = 1 << 0;
+const BORROWED: u32 = 1 << 1;
+const GENERATION_SHIFT: u32 = 2;
+
+/// A key that identifies a value stored in a `Slab`
+///
+/// The lower bits of the key are the index of the value in the slab and the upper bits are a
+/// generation counter. The smaller the capacity of the slab, the more bits are left for the
+/// generation and the longer it takes for a stale key to become valid again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct Key(u16);
+
+impl Key {
+    /// Returns the integer representation of this key
+    pub fn into_raw(self) -> u16 {
+        self.0
+    }
+
+    /// Creates a key from its integer representation
+    ///
+    /// A key that doesn't refer to a value currently stored in the slab is rejected by the slab,
+    /// like any other stale key
+    pub fn from_raw(raw: u16) -> Self {
+        Key(raw)
+    }
+}
+
+struct Slot<T> {
+    state: AtomicU32,
+    // index + 1 of the next free slot; 0 marks the end of the free list
+    next: AtomicU16,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> Slot<T> {
+    // only used to initialize `Slab.slots`
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: Slot<T> = Slot {
+        state: AtomicU32::new(0),
+        next: AtomicU16::new(0),
+        value: UnsafeCell::new(MaybeUninit::uninit()),
+    };
+}
+
+/// A fixed-capacity, lock-free slab of `N` values of type `T`
+///
+/// `N` must be in the range `1..=65535`; other values are rejected at compile time. A key has room
+/// for `16 - ceil(log2(N))` generation bits, e.g. 10 bits for `N = 64`, so a stale key becomes
+/// valid again only after its slot has been reused that many times over. Slabs with more than
+/// 32768 slots have no generation bits left and can't detect stale keys.
+pub struct Slab<T, const N: usize> {
+    // free list: a modification counter (upper 16 bits) and the index + 1 of the first free slot
+    head: AtomicU32,
+    // number of slots that have ever been used; slots past this one are not in the free list
+    fresh: AtomicUsize,
+    slots: [Slot<T>; N],
+}
+
+unsafe impl<T, const N: usize> Send for Slab<T, N> where T: Send {}
+
+// NOTE(unsafe) values can be removed from any context so `T` must be `Send`; `get` hands out
+// exclusive references so `T` doesn't need to be `Sync`
+unsafe impl<T, const N: usize> Sync for Slab<T, N> where T: Send {}
+
+impl<T, const N: usize> Slab<T, N> {
+    // evaluated, and checked, when `new` is instantiated
+    const ASSERT: () = assert!(
+        N >= 1 && N < 1 << 16,
+        "`Slab` capacity must be in the range `1..=65535`"
+    );
+
+    // Number of bits of a key used for the index
+    const INDEX_BITS: u32 = (mem::size_of::<usize>() * 8) as u32 - (N - 1).leading_zeros();
+
+    /// Creates a new empty slab
+    pub const fn new() -> Self {
+        let () = Self::ASSERT;
+
+        Slab {
+            head: AtomicU32::new(0),
+            fresh: AtomicUsize::new(0),
+            slots: [Slot::EMPTY; N],
+        }
+    }
+
+    /// Returns the maximum number of values the slab can hold
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Moves `val` into the slab and returns its key
+    ///
+    /// Returns `val` back when the slab is observed as full
+    pub fn insert(&self, val: T) -> Result<Key, T> {
+        let index = match self.pop().or_else(|| self.claim_fresh()) {
+            Some(index) => index,
+            None => return Err(val),
+        };
+
+        let slot = &self.slots[index];
+        unsafe { slot.value.get().write(MaybeUninit::new(val)) }
+
+        // NOTE the slot is not reachable through any key, or the free list, at this point
+        let state = slot.state.load(Ordering::Relaxed) | OCCUPIED;
+        slot.state.store(state, Ordering::Release);
+
+        Ok(self.key(index, state))
+    }
+
+    /// Removes the value identified by `key` from the slab and returns it
+    ///
+    /// Returns `None` if `key` is stale or if the value is currently borrowed (see `get`)
+    pub fn remove(&self, key: Key) -> Option<T> {
+        let (slot, state) = self.slot(key)?;
+
+        // bump the generation; this invalidates `key` and all its copies
+        let next = (state & !(OCCUPIED | BORROWED)).wrapping_add(1 << GENERATION_SHIFT);
+        slot.state
+            .compare_exchange(state, next, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+
+        let val = unsafe { ptr::read(slot.value.get()).assume_init() };
+
+        self.push(self.index(key));
+
+        Some(val)
+    }
+
+    /// Returns exclusive access to the value identified by `key`
+    ///
+    /// Returns `None` if `key` is stale or if the value is already borrowed. The value can't be
+    /// removed while the returned guard is alive.
+    pub fn get(&self, key: Key) -> Option<SlabRef<'_, T>> {
+        let (slot, state) = self.slot(key)?;
+
+        slot.state
+            .compare_exchange(
+                state,
+                state | BORROWED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .ok()?;
+
+        Some(SlabRef {
+            slot: &slot.state,
+            value: unsafe { &mut *(*slot.value.get()).as_mut_ptr() },
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Returns `true` if `key` identifies a value currently stored in the slab
+    pub fn contains(&self, key: Key) -> bool {
+        self.slot(key).is_some()
+    }
+
+    fn index(&self, key: Key) -> usize {
+        usize::from(key.0) & ((1 << Self::INDEX_BITS) - 1)
+    }
+
+    fn key(&self, index: usize, state: u32) -> Key {
+        let generation = (state >> GENERATION_SHIFT) << Self::INDEX_BITS;
+
+        // NOTE the upper bits of the generation don't fit in the key
+        Key((generation as usize | index) as u16)
+    }
+
+    // Returns the slot identified by `key`, and its state, if `key` is not stale and the value is
+    // not borrowed
+    fn slot(&self, key: Key) -> Option<(&Slot<T>, u32)> {
+        let index = self.index(key);
+        let slot = self.slots.get(index)?;
+        let state = slot.state.load(Ordering::Acquire);
+
+        if state & (OCCUPIED | BORROWED) == OCCUPIED && self.key(index, state) == key {
+            Some((slot, state))
+        } else {
+            None
+        }
+    }
+
+    fn claim_fresh(&self) -> Option<usize> {
+        let mut fresh = self.fresh.load(Ordering::Relaxed);
+        loop {
+            if fresh >= N {
+                // slab is observed as full
+                return None;
+            }
+
+            match self.fresh.compare_exchange_weak(
+                fresh,
+                fresh + 1,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(fresh),
+                // counter was changed by some interrupt handler
+                Err(new_fresh) => fresh = new_fresh,
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<usize> {
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            let index = (head & 0xffff) as usize;
+            if index == 0 {
+                // free list is observed as empty
+                return None;
+            }
+
+            let next = u32::from(self.slots[index - 1].next.load(Ordering::Relaxed));
+            // NOTE the counter makes the CAS fail if the free list was modified, even if its first
+            // slot is the same
+            let new_head = (head & !0xffff).wrapping_add(1 << 16) | next;
+
+            match self.head.compare_exchange_weak(
+                head,
+                new_head,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Some(index - 1),
+                // free list was changed by some interrupt handler
+                Err(new) => head = new,
+            }
+        }
+    }
+
+    fn push(&self, index: usize) {
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            self.slots[index]
+                .next
+                .store((head & 0xffff) as u16, Ordering::Relaxed);
+            let new_head = (head & !0xffff).wrapping_add(1 << 16) | (index as u32 + 1);
+
+            match self.head.compare_exchange_weak(
+                head,
+                new_head,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                // free list was changed by some interrupt handler
+                Err(new) => head = new,
+            }
+        }
+    }
+}
+
+impl<T, const N: usize> Default for Slab<T, N> {
+    fn default() -> Self {
+        Slab::new()
+    }
+}
+
+impl<T, const N: usize> Drop for Slab<T, N> {
+    fn drop(&mut self) {
+        for slot in &mut self.slots[..*self.fresh.get_mut()] {
+            if *slot.state.get_mut() & OCCUPIED != 0 {
+                unsafe { ptr::drop_in_place((*slot.value.get()).as_mut_ptr()) }
+            }
+        }
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Slab<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Slab").field("capacity", &N).finish()
+    }
+}
+
+/// Exclusive access to a value stored in a `Slab`
+///
+/// Created with `Slab::get`
+pub struct SlabRef<'a, T> {
+    slot: &'a AtomicU32,
+    value: &'a mut T,
+    // the borrow must be released from the context that acquired it
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<T> Deref for SlabRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> DerefMut for SlabRef<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<T> Drop for SlabRef<'_, T> {
+    fn drop(&mut self) {
+        self.slot.fetch_and(!BORROWED, Ordering::Release);
+    }
+}
+
+impl<T> fmt::Debug for SlabRef<'_, T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <T as fmt::Debug>::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Key, Slab};
+
+    #[test]
+    fn slab() {
+        static SLAB: Slab<u32, 2> = Slab::new();
+
+        let a = SLAB.insert(1).unwrap();
+        let b = SLAB.insert(2).unwrap();
+        assert_eq!(SLAB.insert(3), Err(3));
+
+        {
+            let mut x = SLAB.get(a).unwrap();
+            *x += 10;
+
+            // borrowed values can't be borrowed again or removed
+            assert!(SLAB.get(a).is_none());
+            assert!(SLAB.remove(a).is_none());
+        }
+
+        assert_eq!(SLAB.remove(Key::from_raw(a.into_raw())), Some(11));
+
+        // `a` is stale, even after its slot has been reused
+        let c = SLAB.insert(4).unwrap();
+        assert_ne!(a, c);
+        assert!(!SLAB.contains(a));
+        assert!(SLAB.get(a).is_none());
+        assert!(SLAB.remove(a).is_none());
+
+        assert_eq!(*SLAB.get(b).unwrap(), 2);
+        assert_eq!(*SLAB.get(c).unwrap(), 4);
+    }
+
+    #[test]
+    fn max_capacity() {
+        static SLAB: Slab<u8, 65535> = Slab::new();
+
+        let keys = (0..65535)
+            .map(|_| SLAB.insert(0).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(SLAB.insert(0), Err(0));
+
+        // the last slot goes through the free list like any other
+        let last = keys[65534];
+        assert_eq!(SLAB.remove(last), Some(0));
+        let key = SLAB.insert(1).unwrap();
+        assert_eq!(*SLAB.get(key).unwrap(), 1);
+        assert_eq!(SLAB.insert(0), Err(0));
+    }
+}
@@ -0,0 +1,247 @@
+//! Pool statistics
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// A snapshot of the statistics of a pool
+///
+/// The counters are updated *after* the free list has been modified so, when `alloc` or `free`
+/// are preempted, a snapshot may be off by a few blocks
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stats {
+    /// Total number of memory blocks that have been handed to the pool via `grow` and friends
+    pub capacity: usize,
+
+    /// Number of memory blocks currently available for allocation
+    pub free: usize,
+
+    /// Lowest number of free memory blocks observed since the pool was created or since the last
+    /// call to `reset_watermarks`
+    pub low_watermark: usize,
+}
+
+// Maximum number of memory regions listed in a `Snapshot`
+pub(crate) const MAX_REGIONS: usize = 4;
+
+/// A memory region handed to a pool
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct Region {
+    /// Address of the first byte of the region
+    pub start: usize,
+
+    /// Size of the region in bytes
+    pub len: usize,
+}
+
+/// A plain-old-data snapshot of the health of a pool, meant to be sent to a host-side tool
+///
+/// Like `Stats`, a snapshot may be off by a few blocks if `alloc` or `free` are preempted while
+/// it's taken
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub struct Snapshot {
+    /// Size of the values stored in the pool, in bytes
+    pub block_size: usize,
+
+    /// Total number of memory blocks that have been handed to the pool via `grow` and friends
+    pub capacity: usize,
+
+    /// Number of memory blocks currently available for allocation
+    pub free: usize,
+
+    /// Lowest number of free memory blocks observed since the pool was created or since the last
+    /// call to `reset_watermarks`
+    pub low_watermark: usize,
+
+    /// Number of successful allocations since the pool was created; wraps around on overflow
+    pub allocs: usize,
+
+    /// Number of frees since the pool was created; wraps around on overflow
+    pub frees: usize,
+
+    /// Number of memory regions handed to the pool via `grow` and friends
+    pub region_count: usize,
+
+    /// The first four memory regions handed to the pool; only the first `region_count` entries
+    /// are meaningful
+    pub regions: [Region; MAX_REGIONS],
+}
+
+impl Snapshot {
+    /// Returns the memory regions listed in this snapshot
+    pub fn regions(&self) -> &[Region] {
+        let len = if self.region_count < MAX_REGIONS {
+            self.region_count
+        } else {
+            MAX_REGIONS
+        };
+
+        &self.regions[..len]
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Region {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Region", 2)?;
+        state.serialize_field("start", &self.start)?;
+        state.serialize_field("len", &self.len)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Snapshot {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Snapshot", 8)?;
+        state.serialize_field("block_size", &self.block_size)?;
+        state.serialize_field("capacity", &self.capacity)?;
+        state.serialize_field("free", &self.free)?;
+        state.serialize_field("low_watermark", &self.low_watermark)?;
+        state.serialize_field("allocs", &self.allocs)?;
+        state.serialize_field("frees", &self.frees)?;
+        state.serialize_field("region_count", &self.region_count)?;
+        state.serialize_field("regions", self.regions())?;
+        state.end()
+    }
+}
+
+pub(crate) struct Counters {
+    capacity: AtomicUsize,
+    // number of memory blocks currently allocated
+    used: AtomicUsize,
+    // highest value of `used` observed since the last reset
+    max_used: AtomicUsize,
+    allocs: AtomicUsize,
+    frees: AtomicUsize,
+    region_count: AtomicUsize,
+    regions: [RegionSlot; MAX_REGIONS],
+}
+
+struct RegionSlot {
+    start: AtomicUsize,
+    len: AtomicUsize,
+}
+
+impl RegionSlot {
+    const fn new() -> Self {
+        RegionSlot {
+            start: AtomicUsize::new(0),
+            len: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Counters {
+    pub(crate) const fn new() -> Self {
+        Counters::with_capacity(0)
+    }
+
+    pub(crate) const fn with_capacity(capacity: usize) -> Self {
+        Counters {
+            capacity: AtomicUsize::new(capacity),
+            used: AtomicUsize::new(0),
+            max_used: AtomicUsize::new(0),
+            allocs: AtomicUsize::new(0),
+            frees: AtomicUsize::new(0),
+            region_count: AtomicUsize::new(0),
+            regions: [
+                RegionSlot::new(),
+                RegionSlot::new(),
+                RegionSlot::new(),
+                RegionSlot::new(),
+            ],
+        }
+    }
+
+    pub(crate) fn on_region(&self, start: usize, len: usize) {
+        let index = self.region_count.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(slot) = self.regions.get(index) {
+            slot.start.store(start, Ordering::Relaxed);
+            slot.len.store(len, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn on_grow(&self, blocks: usize) {
+        self.capacity.fetch_add(blocks, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_alloc(&self) {
+        self.allocs.fetch_add(1, Ordering::Relaxed);
+
+        let used = self.used.fetch_add(1, Ordering::Relaxed) + 1;
+
+        let mut max = self.max_used.load(Ordering::Relaxed);
+        while used > max {
+            match self.max_used.compare_exchange_weak(
+                max,
+                used,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                // watermark was changed by some interrupt handler
+                Err(new_max) => max = new_max,
+            }
+        }
+    }
+
+    pub(crate) fn on_free(&self) {
+        self.frees.fetch_add(1, Ordering::Relaxed);
+        self.used.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn reset_watermarks(&self) {
+        self.max_used
+            .store(self.used.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> Stats {
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        let used = self.used.load(Ordering::Relaxed);
+        let max_used = self.max_used.load(Ordering::Relaxed);
+
+        Stats {
+            capacity,
+            free: capacity.saturating_sub(used),
+            low_watermark: capacity.saturating_sub(max_used),
+        }
+    }
+
+    pub(crate) fn telemetry(&self, block_size: usize) -> Snapshot {
+        let stats = self.snapshot();
+
+        let mut regions = [Region::default(); MAX_REGIONS];
+        for (region, slot) in regions.iter_mut().zip(&self.regions) {
+            *region = Region {
+                start: slot.start.load(Ordering::Relaxed),
+                len: slot.len.load(Ordering::Relaxed),
+            };
+        }
+
+        Snapshot {
+            block_size,
+            capacity: stats.capacity,
+            free: stats.free,
+            low_watermark: stats.low_watermark,
+            allocs: self.allocs.load(Ordering::Relaxed),
+            frees: self.frees.load(Ordering::Relaxed),
+            region_count: self.region_count.load(Ordering::Relaxed),
+            regions,
+        }
+    }
+}
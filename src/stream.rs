@@ -0,0 +1,307 @@
+//! Single-producer single-consumer byte stream backed by memory blocks
+//!
+//! `Stream<S, N>` moves bytes from a producer to a consumer in chunks of up to `S` bytes, each
+//! stored in a memory block of the stream's pool. The producer is granted a whole chunk, fills it,
+//! e.g. with a DMA transfer, and commits the number of bytes it wrote; the consumer then reads the
+//! committed bytes as a contiguous slice, and the chunk returns to the pool once it's done with
+//! it. Up to `N` committed chunks can be waiting for the consumer.
+//!
+//! Unlike a byte ring buffer, a grant never wraps around the end of the buffer so it can always be
+//! handed to a DMA transfer in one piece, and the memory of the stream can be spread over several
+//! regions given to `grow`.
+//!
+//! ``` ignore
+//! use lifo::stream::Stream;
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     static mut MEMORY: [u8; 2048] = [0; 2048];
+//!     static mut STREAM: Stream<256, 4> = Stream::new();
+//!
+//!     STREAM.grow(MEMORY);
+//!     let (producer, mut consumer) = STREAM.split();
+//!     // move `producer` into the UART interrupt handler, which does:
+//!     // let mut grant = producer.grant().unwrap();
+//!     // let n = uart.read(&mut grant);
+//!     // grant.commit(n);
+//!
+//!     loop {
+//!         if let Some(bytes) = consumer.read() {
+//!             process(&bytes);
+//!             // the chunk returns to the pool when `bytes` is dropped
+//!         }
+//!     }
+//! }
+//! ```
+
+use core::{
+    fmt,
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{
+    channel::{Channel, Receiver, Sender},
+    Box,
+};
+
+/// A chunk of a `Stream`; the type of the memory blocks of the stream's pool
+pub struct Chunk<const S: usize> {
+    len: usize,
+    buf: [u8; S],
+}
+
+/// A pool of chunks of up to `S` bytes bundled with a queue of up to `N` committed chunks
+///
+/// Chunks still in the queue when the stream is dropped are leaked
+pub struct Stream<const S: usize, const N: usize> {
+    channel: Channel<Chunk<S>, N>,
+}
+
+impl<const S: usize, const N: usize> Stream<S, N> {
+    /// Creates a new stream with an empty pool
+    pub const fn new() -> Self {
+        Stream {
+            channel: Channel::new(),
+        }
+    }
+
+    /// Increases the capacity of the stream's pool
+    ///
+    /// See `Pool::grow`
+    pub fn grow(&self, memory: &'static mut [u8]) {
+        self.channel.grow(memory)
+    }
+
+    /// Increases the capacity of the stream's pool using memory that has not been initialized
+    ///
+    /// See `Pool::grow_uninit`
+    pub fn grow_uninit(&self, memory: &'static mut [MaybeUninit<u8>]) {
+        self.channel.grow_uninit(memory)
+    }
+
+    /// Splits the stream into its producer and consumer endpoints
+    pub fn split(&mut self) -> (Producer<'_, S, N>, Consumer<'_, S, N>) {
+        let (sender, receiver) = self.channel.split();
+
+        (Producer { sender }, Consumer { receiver })
+    }
+}
+
+impl<const S: usize, const N: usize> Default for Stream<S, N> {
+    fn default() -> Self {
+        Stream::new()
+    }
+}
+
+impl<const S: usize, const N: usize> fmt::Debug for Stream<S, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Stream")
+            .field("chunk_size", &S)
+            .field("capacity", &N)
+            .finish()
+    }
+}
+
+/// The producing endpoint of a `Stream`
+pub struct Producer<'a, const S: usize, const N: usize> {
+    sender: Sender<'a, Chunk<S>, N>,
+}
+
+impl<'a, const S: usize, const N: usize> Producer<'a, S, N> {
+    /// Grants a chunk of `S` bytes to the producer
+    ///
+    /// Returns `None` if the queue is observed as full, or if the stream's pool is observed as
+    /// exhausted. The bytes of the chunk are zeroed.
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    pub fn grant(&mut self) -> Option<GrantW<'_, 'a, S, N>> {
+        if self.sender.is_full() {
+            return None;
+        }
+
+        let chunk = self.sender.alloc()?.init(Chunk {
+            len: 0,
+            buf: [0; S],
+        });
+
+        Some(GrantW {
+            producer: self,
+            chunk: Some(chunk),
+        })
+    }
+}
+
+impl<const S: usize, const N: usize> fmt::Debug for Producer<'_, S, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Producer").finish()
+    }
+}
+
+/// A chunk granted to the producer
+///
+/// Dropping the grant without committing it returns the chunk to the stream's pool
+pub struct GrantW<'p, 'a, const S: usize, const N: usize> {
+    producer: &'p mut Producer<'a, S, N>,
+    // `None` once committed
+    chunk: Option<Box<Chunk<S>>>,
+}
+
+impl<const S: usize, const N: usize> GrantW<'_, '_, S, N> {
+    /// Makes the first `len` bytes of the chunk available to the consumer
+    ///
+    /// `len` is capped to `S`. Committing zero bytes returns the chunk to the stream's pool.
+    pub fn commit(mut self, len: usize) {
+        if let Some(mut chunk) = self.chunk.take() {
+            chunk.len = if len < S { len } else { S };
+
+            if chunk.len == 0 {
+                self.producer.sender.free(chunk);
+            } else if let Err(chunk) = self.producer.sender.send(chunk) {
+                // unreachable: only the producer fills the queue and it was not full when the
+                // chunk was granted
+                self.producer.sender.free(chunk);
+            }
+        }
+    }
+}
+
+impl<const S: usize, const N: usize> Deref for GrantW<'_, '_, S, N> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self.chunk.as_ref() {
+            Some(chunk) => &chunk.buf,
+            None => &[],
+        }
+    }
+}
+
+impl<const S: usize, const N: usize> DerefMut for GrantW<'_, '_, S, N> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self.chunk.as_mut() {
+            Some(chunk) => &mut chunk.buf,
+            None => &mut [],
+        }
+    }
+}
+
+impl<const S: usize, const N: usize> Drop for GrantW<'_, '_, S, N> {
+    fn drop(&mut self) {
+        if let Some(chunk) = self.chunk.take() {
+            self.producer.sender.free(chunk)
+        }
+    }
+}
+
+impl<const S: usize, const N: usize> fmt::Debug for GrantW<'_, '_, S, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GrantW").field("len", &S).finish()
+    }
+}
+
+/// The consuming endpoint of a `Stream`
+pub struct Consumer<'a, const S: usize, const N: usize> {
+    receiver: Receiver<'a, Chunk<S>, N>,
+}
+
+impl<'a, const S: usize, const N: usize> Consumer<'a, S, N> {
+    /// Reads the oldest committed chunk
+    ///
+    /// Returns `None` if the queue is observed as empty
+    pub fn read(&mut self) -> Option<GrantR<'_, 'a, S, N>> {
+        let chunk = self.receiver.recv()?;
+
+        Some(GrantR {
+            consumer: self,
+            chunk: Some(chunk),
+        })
+    }
+
+    /// Returns `true` if there are no committed chunks to read
+    pub fn is_empty(&self) -> bool {
+        self.receiver.is_empty()
+    }
+}
+
+impl<const S: usize, const N: usize> fmt::Debug for Consumer<'_, S, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Consumer").finish()
+    }
+}
+
+/// The committed bytes of a chunk
+///
+/// Dropping the grant returns the chunk to the stream's pool
+pub struct GrantR<'c, 'a, const S: usize, const N: usize> {
+    consumer: &'c mut Consumer<'a, S, N>,
+    // always `Some` until dropped
+    chunk: Option<Box<Chunk<S>>>,
+}
+
+impl<const S: usize, const N: usize> Deref for GrantR<'_, '_, S, N> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self.chunk.as_ref() {
+            Some(chunk) => &chunk.buf[..chunk.len],
+            None => &[],
+        }
+    }
+}
+
+impl<const S: usize, const N: usize> Drop for GrantR<'_, '_, S, N> {
+    fn drop(&mut self) {
+        if let Some(chunk) = self.chunk.take() {
+            self.consumer.receiver.free(chunk)
+        }
+    }
+}
+
+impl<const S: usize, const N: usize> fmt::Debug for GrantR<'_, '_, S, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <[u8] as fmt::Debug>::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Chunk, Stream};
+    use crate::tests::memory;
+
+    #[test]
+    fn stream() {
+        let mut stream = Stream::<8, 2>::new();
+        stream.grow(memory::<Chunk<8>>(3));
+
+        let (mut producer, mut consumer) = stream.split();
+        assert!(consumer.read().is_none());
+
+        let mut grant = producer.grant().unwrap();
+        assert_eq!(grant.len(), 8);
+        grant[..3].copy_from_slice(b"abc");
+        grant.commit(3);
+
+        // dropped grants and empty commits don't reach the consumer
+        drop(producer.grant().unwrap());
+        producer.grant().unwrap().commit(0);
+
+        let mut grant = producer.grant().unwrap();
+        grant.copy_from_slice(b"defghijk");
+        grant.commit(100);
+
+        // the queue is full
+        assert!(producer.grant().is_none());
+
+        assert_eq!(&*consumer.read().unwrap(), b"abc");
+        assert_eq!(&*consumer.read().unwrap(), b"defghijk");
+        assert!(consumer.is_empty());
+
+        // all the chunks are back in the pool
+        producer.grant().unwrap().commit(1);
+        producer.grant().unwrap().commit(1);
+        let bytes = consumer.read().unwrap();
+        assert!(producer.grant().is_some());
+        drop(bytes);
+    }
+}
@@ -0,0 +1,312 @@
+//! Pools of small memory blocks carved out of the memory blocks of a parent pool
+//!
+//! A `SubPool<C, P>` claims memory blocks from a parent `Pool<P>` only when it needs them and
+//! splits each one into several smaller memory blocks, each able to hold a `C` value. A parent
+//! memory block is returned to the parent pool as soon as all the smaller memory blocks carved out
+//! of it are free again. This gives two block sizes without having to statically partition RAM
+//! between two pools.
+//!
+//! ``` ignore
+//! use lifo::{sub_pool::SubPool, Pool};
+//!
+//! static FRAMES: Pool<[u8; 512]> = Pool::new();
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     static mut MEMORY: [u8; 4096] = [0; 4096];
+//!
+//!     FRAMES.grow(MEMORY);
+//!
+//!     // small messages share the memory of the frame pool
+//!     let events = SubPool::<[u8; 32], [u8; 512]>::new(&FRAMES);
+//!
+//!     let event = events.alloc_init([0; 32]).ok().unwrap();
+//!     // the frame memory block that holds `event` is returned to `FRAMES` here
+//!     drop(event);
+//! }
+//! ```
+//!
+//! Each small memory block carries two pointers of bookkeeping, and each parent memory block a
+//! small header. A `SubPool` is meant to be used from a single context so, unlike `Pool`, it's not
+//! `Sync`.
+
+use core::{
+    cell::{Cell, UnsafeCell},
+    fmt,
+    mem::{self, MaybeUninit},
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+};
+
+use crate::{Node, Pool};
+
+// Bookkeeping stored at the start of each parent memory block
+struct Header<C, P> {
+    // the parent memory block, used to return it to the parent pool
+    node: NonNull<Node<P>>,
+    // free child memory blocks
+    free: *mut Child<C, P>,
+    // number of child memory blocks that are allocated
+    used: usize,
+    // doubly linked list of the parent memory blocks that have free child memory blocks
+    prev: *mut Header<C, P>,
+    next: *mut Header<C, P>,
+}
+
+struct Child<C, P> {
+    header: *mut Header<C, P>,
+    // only used while the memory block is free
+    next: *mut Child<C, P>,
+    value: UnsafeCell<MaybeUninit<C>>,
+}
+
+/// A pool of memory blocks of type `C` carved out of the memory blocks of a parent `Pool<P>`
+pub struct SubPool<C, P>
+where
+    P: 'static,
+{
+    parent: &'static Pool<P>,
+    // parent memory blocks with at least one free child memory block
+    partial: Cell<*mut Header<C, P>>,
+}
+
+impl<C, P> SubPool<C, P> {
+    /// Creates a new pool that carves its memory blocks out of the memory blocks of `parent`
+    pub const fn new(parent: &'static Pool<P>) -> Self {
+        SubPool {
+            parent,
+            partial: Cell::new(ptr::null_mut()),
+        }
+    }
+
+    /// Claims a memory block from the pool and moves `val` into it
+    ///
+    /// A new memory block is claimed from the parent pool if all the parent memory blocks that are
+    /// in use are full. Returns `val` back if the parent pool is observed as exhausted, or if a
+    /// parent memory block is too small to hold a single `C` value.
+    pub fn alloc_init(&self, val: C) -> Result<SubBox<'_, C, P>, C> {
+        let header = match NonNull::new(self.partial.get()).or_else(|| self.carve()) {
+            Some(header) => header.as_ptr(),
+            None => return Err(val),
+        };
+
+        unsafe {
+            let child = (*header).free;
+            (*header).free = (*child).next;
+            (*header).used += 1;
+
+            if (*header).free.is_null() {
+                self.unlink(header);
+            }
+
+            (*child).value.get().write(MaybeUninit::new(val));
+
+            Ok(SubBox {
+                pool: self,
+                child: NonNull::new_unchecked(child),
+            })
+        }
+    }
+
+    // Claims a memory block from the parent pool and splits it into child memory blocks
+    fn carve(&self) -> Option<NonNull<Header<C, P>>> {
+        let node = self.parent.alloc()?.into_node();
+
+        let start = unsafe { node.as_ref().data.get() as usize };
+        let end = start + mem::size_of::<P>();
+
+        let header = align_up(start, mem::align_of::<Header<C, P>>());
+        let mut child = align_up(
+            header + mem::size_of::<Header<C, P>>(),
+            mem::align_of::<Child<C, P>>(),
+        );
+
+        let header = header as *mut Header<C, P>;
+        let mut free = ptr::null_mut();
+        while child + mem::size_of::<Child<C, P>>() <= end {
+            let p = child as *mut Child<C, P>;
+            unsafe {
+                p.write(Child {
+                    header,
+                    next: free,
+                    value: UnsafeCell::new(MaybeUninit::uninit()),
+                })
+            }
+            free = p;
+
+            child += mem::size_of::<Child<C, P>>();
+        }
+
+        if free.is_null() {
+            // the parent memory block can't even hold the header and one child
+            self.parent.release(node);
+
+            return None;
+        }
+
+        unsafe {
+            header.write(Header {
+                node,
+                free,
+                used: 0,
+                prev: ptr::null_mut(),
+                next: ptr::null_mut(),
+            });
+        }
+        self.link(header);
+
+        NonNull::new(header)
+    }
+
+    // Returns the child memory block to its parent memory block
+    unsafe fn free(&self, child: NonNull<Child<C, P>>) {
+        let child = child.as_ptr();
+        let header = (*child).header;
+
+        let was_full = (*header).free.is_null();
+        (*child).next = (*header).free;
+        (*header).free = child;
+        (*header).used -= 1;
+
+        if (*header).used == 0 {
+            // all the children are free; return the parent memory block to the parent pool
+            if !was_full {
+                self.unlink(header);
+            }
+
+            self.parent.release((*header).node);
+        } else if was_full {
+            self.link(header);
+        }
+    }
+
+    // Adds `header` to the list of partially used parent memory blocks
+    fn link(&self, header: *mut Header<C, P>) {
+        let head = self.partial.get();
+
+        unsafe {
+            (*header).prev = ptr::null_mut();
+            (*header).next = head;
+
+            if !head.is_null() {
+                (*head).prev = header;
+            }
+        }
+
+        self.partial.set(header);
+    }
+
+    // Removes `header` from the list of partially used parent memory blocks
+    unsafe fn unlink(&self, header: *mut Header<C, P>) {
+        let prev = (*header).prev;
+        let next = (*header).next;
+
+        if prev.is_null() {
+            self.partial.set(next);
+        } else {
+            (*prev).next = next;
+        }
+
+        if !next.is_null() {
+            (*next).prev = prev;
+        }
+    }
+}
+
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+impl<C, P> fmt::Debug for SubPool<C, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SubPool")
+            .field("parent", &(self.parent as *const Pool<P>))
+            .finish()
+    }
+}
+
+/// A value stored in a memory block of a `SubPool`
+///
+/// Dropping this box drops the value and returns the memory block to its pool
+pub struct SubBox<'a, C, P>
+where
+    P: 'static,
+{
+    pool: &'a SubPool<C, P>,
+    child: NonNull<Child<C, P>>,
+}
+
+impl<C, P> Deref for SubBox<'_, C, P> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        unsafe { &*(*self.child.as_ref().value.get()).as_ptr() }
+    }
+}
+
+impl<C, P> DerefMut for SubBox<'_, C, P> {
+    fn deref_mut(&mut self) -> &mut C {
+        unsafe { &mut *(*self.child.as_ref().value.get()).as_mut_ptr() }
+    }
+}
+
+impl<C, P> Drop for SubBox<'_, C, P> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place((*self.child.as_ref().value.get()).as_mut_ptr());
+
+            self.pool.free(self.child)
+        }
+    }
+}
+
+impl<C, P> fmt::Debug for SubBox<'_, C, P>
+where
+    C: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <C as fmt::Debug>::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubPool;
+    use crate::{tests::memory, Pool};
+
+    #[test]
+    fn sub_pool() {
+        static PARENT: Pool<[u8; 128]> = Pool::new();
+
+        PARENT.grow(memory::<[u8; 128]>(2));
+
+        let children = SubPool::<u32, [u8; 128]>::new(&PARENT);
+
+        let mut boxes = vec![];
+        let mut i = 0;
+        while let Ok(x) = children.alloc_init(i) {
+            boxes.push(x);
+            i += 1;
+        }
+        // both parent memory blocks were carved
+        assert!(PARENT.alloc().is_none());
+        assert!(boxes.len() >= 2 && boxes.len() % 2 == 0);
+        assert!(boxes.iter().map(|x| **x).eq(0..i));
+
+        // the memory blocks of the second parent memory block are freed first
+        let per_parent = boxes.len() / 2;
+        boxes.truncate(per_parent);
+        let block = PARENT.alloc().unwrap();
+        PARENT.free(block);
+
+        // freed child memory blocks are reused
+        boxes.push(children.alloc_init(i).ok().unwrap());
+        assert_eq!(*boxes[per_parent], i);
+
+        boxes.clear();
+        let a = PARENT.alloc().unwrap();
+        let b = PARENT.alloc().unwrap();
+        PARENT.free(a);
+        PARENT.free(b);
+    }
+}
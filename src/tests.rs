@@ -1,10 +1,29 @@
 use core::{
-    mem,
+    mem::{self, ManuallyDrop, MaybeUninit},
+    slice,
     sync::atomic::{AtomicUsize, Ordering},
 };
 
-use crate::Pool;
+use crate::{Node, Pool};
 
+// Number of memory blocks, out of the ones handed to a pool, that can never be claimed
+pub(crate) const RESERVED: usize = if cfg!(feature = "fifo") { 1 } else { 0 };
+
+// Leaks a zeroed buffer from which a pool of `T` values can claim `capacity` memory blocks
+pub(crate) fn memory<T>(capacity: usize) -> &'static mut [u8] {
+    let len = capacity + RESERVED;
+
+    // NOTE a buffer of nodes, rather than bytes, so it's suitably aligned
+    let mut blocks = ManuallyDrop::new(Vec::<Node<T>>::with_capacity(len));
+    let start = blocks.as_mut_ptr();
+
+    unsafe {
+        start.write_bytes(0, len);
+        slice::from_raw_parts_mut(start as *mut u8, len * mem::size_of::<Node<T>>())
+    }
+}
+
+#[cfg(not(any(feature = "fifo", feature = "track-callers")))]
 #[test]
 fn grow() {
     static POOL: Pool<[u8; 128]> = Pool::new();
@@ -17,6 +36,215 @@ fn grow() {
     }
 }
 
+#[test]
+fn grow_uninit() {
+    static POOL: Pool<[u8; 128]> = Pool::new();
+
+    POOL.grow_uninit(Box::leak(Box::new(
+        [MaybeUninit::uninit(); (7 + RESERVED) * mem::size_of::<Node<[u8; 128]>>()],
+    )));
+
+    for _ in 0..7 {
+        assert!(POOL.alloc().is_some());
+    }
+}
+
+#[test]
+#[should_panic(expected = "is exhausted")]
+fn alloc_or_panic() {
+    static POOL: Pool<u8> = Pool::new();
+
+    POOL.alloc_or_panic();
+}
+
+#[test]
+fn alloc_init() {
+    static POOL: Pool<u8> = Pool::new();
+
+    POOL.grow(memory::<u8>(1));
+
+    let x = POOL.alloc_init(1).unwrap();
+    assert_eq!(POOL.alloc_init(2).err(), Some(2));
+    assert!(POOL.alloc_with(|| unreachable!()).is_none());
+
+    POOL.free(x);
+    assert_eq!(*POOL.alloc_with(|| 3).unwrap(), 3);
+}
+
+#[test]
+fn debug() {
+    static POOL: Pool<[u8; 2]> = Pool::new();
+
+    POOL.grow(memory::<[u8; 2]>(1));
+
+    let x = POOL.alloc().unwrap().init([1, 2]);
+    assert_eq!(format!("{:?}", x), "[1, 2]");
+    assert_eq!(format!("{:#?}", x), format!("{:#?}", [1, 2]));
+}
+
+#[test]
+fn display() {
+    static POOL: Pool<&'static str> = Pool::new();
+
+    POOL.grow(memory::<&'static str>(1));
+
+    let x = POOL.alloc().unwrap().init("hello");
+    assert_eq!(format!("{:>6}", x), " hello");
+}
+
+#[test]
+fn cmp() {
+    static POOL: Pool<u32> = Pool::new();
+
+    POOL.grow(memory::<u32>(2));
+
+    let x = POOL.alloc().unwrap().init(1);
+    let y = POOL.alloc().unwrap().init(2);
+
+    assert!(x != y);
+    assert!(x < y);
+    assert_eq!(x.cmp(&y), 1.cmp(&2));
+    assert_eq!(*x.max(y), 2);
+}
+
+#[test]
+fn borrow() {
+    use core::borrow::Borrow;
+
+    static POOL: Pool<u32> = Pool::new();
+
+    POOL.grow(memory::<u32>(1));
+
+    fn as_ref(x: impl AsRef<u32>) -> u32 {
+        *x.as_ref()
+    }
+
+    let mut x = POOL.alloc().unwrap().init(1);
+    *x.as_mut() += 1;
+
+    assert_eq!(*Borrow::<u32>::borrow(&x), 2);
+    assert_eq!(as_ref(x), 2);
+}
+
+#[test]
+fn pointer() {
+    static POOL: Pool<u8> = Pool::new();
+
+    POOL.grow(memory::<u8>(1));
+
+    let x = POOL.alloc().unwrap();
+    assert_eq!(format!("{:p}", x), format!("{:p}", x.node.as_ptr()));
+}
+
+#[test]
+fn ptr_eq() {
+    static POOL: Pool<u8> = Pool::new();
+
+    POOL.grow(memory::<u8>(2));
+
+    let x = POOL.alloc().unwrap().init(0);
+    let y = POOL.alloc().unwrap().init(0);
+
+    // same value but different memory blocks
+    assert_eq!(x, y);
+    assert!(!x.ptr_eq(&y));
+    assert_ne!(x.id(), y.id());
+
+    assert!(x.ptr_eq(&x));
+    assert_eq!(x.id(), x.id());
+}
+
+#[test]
+fn uninit() {
+    static POOL: Pool<[u8; 4]> = Pool::new();
+
+    POOL.grow(memory::<[u8; 4]>(1));
+
+    let mut x = POOL.alloc().unwrap();
+    assert_eq!(x.write([1, 2, 3, 4]), &[1, 2, 3, 4]);
+
+    unsafe { (*x.as_uninit_mut().as_mut_ptr())[0] = 0 }
+    assert_eq!(unsafe { *x.as_uninit().as_ptr() }, [0, 2, 3, 4]);
+
+    unsafe { (*x.as_mut_ptr())[1] = 0 }
+    assert_eq!(unsafe { *x.as_ptr() }, [0, 0, 3, 4]);
+}
+
+#[test]
+fn assume_init() {
+    static POOL: Pool<[u8; 4]> = Pool::new();
+
+    POOL.grow(memory::<[u8; 4]>(1));
+
+    let mut x = POOL.alloc().unwrap();
+    unsafe { x.as_uninit_mut().as_mut_ptr().write_bytes(1, 1) }
+
+    let x = unsafe { x.assume_init() };
+    assert_eq!(*x, [1; 4]);
+}
+
+#[test]
+fn try_init() {
+    static POOL: Pool<u32> = Pool::new();
+
+    POOL.grow(memory::<u32>(1));
+
+    let x = POOL.alloc().unwrap();
+    let (x, _) = unsafe {
+        x.try_init(|slot| "x".parse().map(|val| slot.as_mut_ptr().write(val)))
+            .unwrap_err()
+    };
+
+    let x = unsafe {
+        x.try_init(|slot| "42".parse::<u32>().map(|val| slot.as_mut_ptr().write(val)))
+            .ok()
+            .unwrap()
+    };
+    assert_eq!(*x, 42);
+}
+
+#[test]
+fn reinit() {
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    struct X;
+
+    impl Drop for X {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    static POOL: Pool<X> = Pool::new();
+
+    POOL.grow(memory::<X>(1));
+
+    let x = POOL.alloc().unwrap().init(X);
+    let node = x.node;
+
+    let x = x.reinit();
+    assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+
+    // same memory block
+    assert_eq!(x.node, node);
+    POOL.free(x.init(X));
+    assert_eq!(DROPS.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn try_clone() {
+    static A: Pool<[u8; 4]> = Pool::new();
+    static B: Pool<[u8; 4]> = Pool::new();
+
+    A.grow(memory::<[u8; 4]>(1));
+    B.grow(memory::<[u8; 4]>(1));
+
+    let x = A.alloc().unwrap().init([1, 2, 3, 4]);
+    assert!(x.try_clone(&A).is_none());
+    assert_eq!(*x.try_clone(&B).unwrap(), [1, 2, 3, 4]);
+}
+
+#[cfg(not(any(feature = "fifo", feature = "track-callers")))]
 #[test]
 fn sanity() {
     static POOL: Pool<u8> = Pool::new();
@@ -42,6 +270,7 @@ fn sanity() {
     assert_eq!(*POOL.alloc().unwrap().init(1), 1);
 }
 
+#[cfg(not(any(feature = "fifo", feature = "handles", feature = "track-callers")))]
 #[test]
 fn destructors() {
     static COUNT: AtomicUsize = AtomicUsize::new(0);
@@ -87,3 +316,424 @@ fn destructors() {
 
     assert_eq!(COUNT.load(Ordering::Relaxed), 2);
 }
+
+#[cfg(feature = "stats")]
+#[test]
+fn stats() {
+    static POOL: Pool<[u8; 128]> = Pool::new();
+
+    POOL.grow(Box::leak(Box::new([0; 1024])));
+
+    let capacity = POOL.stats().capacity;
+    assert_eq!(POOL.stats().free, capacity);
+    assert_eq!(POOL.stats().low_watermark, capacity);
+
+    let x = POOL.alloc().unwrap();
+    let y = POOL.alloc().unwrap();
+    POOL.free(x);
+
+    let stats = POOL.stats();
+    assert_eq!(stats.free, capacity - 1);
+    assert_eq!(stats.low_watermark, capacity - 2);
+
+    POOL.reset_watermarks();
+    assert_eq!(POOL.stats().low_watermark, capacity - 1);
+
+    POOL.free(y);
+    assert_eq!(POOL.stats().low_watermark, capacity - 1);
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn snapshot() {
+    static POOL: Pool<[u8; 128]> = Pool::new();
+
+    let a: &'static mut [u8] = Box::leak(Box::new([0; 1024]));
+    let b: &'static mut [u8] = Box::leak(Box::new([0; 512]));
+    let regions = [(a.as_ptr() as usize, 1024), (b.as_ptr() as usize, 512)];
+    POOL.grow(a);
+    POOL.grow(b);
+
+    let x = POOL.alloc().unwrap();
+    let y = POOL.alloc().unwrap();
+    POOL.free(x);
+
+    let snapshot = POOL.snapshot();
+    assert_eq!(snapshot.block_size, 128);
+    assert_eq!(snapshot.capacity, POOL.stats().capacity);
+    assert_eq!(snapshot.free, snapshot.capacity - 1);
+    assert_eq!((snapshot.allocs, snapshot.frees), (2, 1));
+    assert!(snapshot
+        .regions()
+        .iter()
+        .map(|region| (region.start, region.len))
+        .eq(regions.iter().copied()));
+
+    POOL.free(y);
+}
+
+#[cfg(not(any(feature = "track-callers", feature = "handles")))]
+#[test]
+fn contiguous() {
+    static POOL: Pool<[u8; 128]> = Pool::new();
+
+    POOL.grow(memory::<[u8; 128]>(7));
+
+    let x = POOL.alloc().unwrap();
+    let mut blocks = POOL.alloc_contiguous(3).unwrap();
+    assert_eq!(blocks.blocks(), 3);
+    assert_eq!(blocks.as_uninit_slice_mut().len(), blocks.len());
+    assert!(blocks.len() >= 3 * 128);
+
+    // the whole buffer is writable
+    for byte in blocks.iter_mut() {
+        *byte = MaybeUninit::new(1);
+    }
+
+    // not enough free blocks left
+    assert!(POOL.alloc_contiguous(4).is_none());
+
+    // the free list is still intact
+    let y = POOL.alloc().unwrap();
+    POOL.free(y);
+
+    POOL.free_contiguous(blocks);
+    POOL.free(x);
+
+    // all the memory blocks are back in the pool
+    for _ in 0..7 {
+        assert!(POOL.alloc().is_some());
+    }
+}
+
+#[cfg(feature = "track-callers")]
+#[test]
+fn dump_live() {
+    static POOL: Pool<u8> = Pool::new();
+
+    POOL.grow(memory::<u8>(2));
+
+    let x = POOL.alloc().unwrap();
+    let line = line!() - 1;
+    let y = POOL.alloc().unwrap();
+    POOL.free(y);
+
+    let mut live = 0;
+    POOL.dump_live(|location, _| {
+        live += 1;
+
+        assert_eq!(location.file(), file!());
+        assert_eq!(location.line(), line);
+    });
+    assert_eq!(live, 1);
+
+    POOL.free(x);
+}
+
+// `sanity` and `grow` don't account for the location stored in every memory block
+#[cfg(feature = "track-callers")]
+#[test]
+fn track_callers_sanity() {
+    static POOL: Pool<u8> = Pool::new();
+
+    POOL.grow(memory::<u8>(2));
+
+    let x = POOL.alloc().unwrap().init(0);
+    let y = POOL.alloc().unwrap().init(1);
+
+    // pool exhausted
+    assert!(POOL.alloc().is_none());
+
+    let mut live = 0;
+    POOL.dump_live(|_, _| live += 1);
+    assert_eq!(live, 2);
+
+    POOL.free(x);
+    POOL.free(y);
+
+    // freed memory blocks are not reported
+    POOL.dump_live(|_, _| unreachable!());
+
+    // should be possible to allocate again
+    assert_eq!(*POOL.alloc().unwrap().init(2), 2);
+}
+
+#[cfg(all(
+    feature = "fifo",
+    not(any(feature = "handles", feature = "track-callers"))
+))]
+#[test]
+fn fifo_contiguous() {
+    static POOL: Pool<[u8; 16]> = Pool::new();
+
+    POOL.grow(memory::<[u8; 16]>(3));
+
+    // the sentinel is not part of the buffer
+    assert!(POOL.alloc_contiguous(4).is_none());
+    let blocks = POOL.alloc_contiguous(3).unwrap();
+    assert!(POOL.alloc().is_none());
+
+    POOL.free_contiguous(blocks);
+
+    for _ in 0..3 {
+        assert!(POOL.alloc().is_some());
+    }
+}
+
+#[cfg(feature = "fifo")]
+#[test]
+fn fifo() {
+    static POOL: Pool<u8> = Pool::new();
+
+    POOL.grow(memory::<u8>(3));
+
+    let x = POOL.alloc().unwrap();
+    let node = x.node;
+    POOL.free(x);
+
+    // the memory block that was freed most recently is not reused while there are other free
+    // memory blocks
+    let blocks = (0..3).map(|_| POOL.alloc().unwrap()).collect::<Vec<_>>();
+    assert!(blocks.iter().all(|y| y.node != node));
+
+    // one memory block is always kept as the sentinel
+    assert!(POOL.alloc().is_none());
+}
+
+// `sanity` doesn't account for the sentinel
+#[cfg(feature = "fifo")]
+#[test]
+fn fifo_sanity() {
+    static POOL: Pool<u8> = Pool::new();
+
+    // empty pool
+    assert!(POOL.alloc().is_none());
+
+    POOL.grow(memory::<u8>(1));
+
+    let x = POOL.alloc().unwrap().init(0);
+    assert_eq!(*x, 0);
+
+    // pool exhausted; the other memory block is the sentinel
+    assert!(POOL.alloc().is_none());
+
+    POOL.free(x);
+
+    // should be possible to allocate again
+    assert_eq!(*POOL.alloc().unwrap().init(1), 1);
+}
+
+// NOTE in FIFO mode the stale memory block becomes the sentinel and is never claimed again
+#[cfg(all(feature = "poison", not(feature = "fifo")))]
+#[test]
+#[should_panic(expected = "use after free detected")]
+fn poison() {
+    static POOL: Pool<[u8; 16]> = Pool::new();
+
+    POOL.grow(memory::<[u8; 16]>(1));
+
+    let x = POOL.alloc().unwrap().init([0; 16]);
+    // `Node` isn't `repr(C)` so the data may not be at the start of the memory block
+    let stale = unsafe { x.node.as_ref().data.get() as *mut u8 };
+    POOL.free(x);
+
+    unsafe { stale.add(15).write(1) }
+
+    POOL.alloc();
+}
+
+#[cfg(all(
+    feature = "poison",
+    not(any(
+        feature = "fifo",
+        feature = "handles",
+        feature = "quarantine",
+        feature = "track-callers"
+    ))
+))]
+#[test]
+#[should_panic(expected = "use after free detected")]
+fn poison_contiguous() {
+    static POOL: Pool<[u8; 16]> = Pool::new();
+
+    POOL.grow(memory::<[u8; 16]>(2));
+
+    let x = POOL.alloc().unwrap().init([0; 16]);
+    let stale = unsafe { x.node.as_ref().data.get() as *mut u8 };
+    POOL.free(x);
+
+    unsafe { stale.add(15).write(1) }
+
+    POOL.alloc_contiguous(2);
+}
+
+#[cfg(all(feature = "quarantine", not(feature = "fifo")))]
+#[test]
+fn quarantine() {
+    static POOL: Pool<u8> = Pool::new();
+
+    POOL.grow(memory::<u8>(3));
+
+    let x = POOL.alloc().unwrap();
+    let node = x.node;
+    POOL.free(x);
+
+    // the freed memory block is in quarantine
+    let y = POOL.alloc().unwrap();
+    let z = POOL.alloc().unwrap();
+    assert_ne!(y.node, node);
+    assert_ne!(z.node, node);
+
+    // the quarantine is used as a fallback when the free list is empty
+    assert_eq!(POOL.alloc().unwrap().node, node);
+}
+
+#[cfg(feature = "stable_deref_trait")]
+#[test]
+fn stable_deref() {
+    use stable_deref_trait::StableDeref;
+
+    fn is_stable_deref<T>()
+    where
+        T: StableDeref,
+    {
+    }
+
+    is_stable_deref::<crate::Box<[u8; 128]>>();
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deserialize_seed() {
+    use serde::de::{value, DeserializeSeed, IntoDeserializer};
+
+    static POOL: Pool<u32> = Pool::new();
+
+    POOL.grow(memory::<u32>(1));
+
+    // bad input; the memory block is returned to the pool
+    let de: value::StrDeserializer<value::Error> = "x".into_deserializer();
+    assert!((&POOL).deserialize(de).is_err());
+
+    let de: value::U32Deserializer<value::Error> = 42u32.into_deserializer();
+    assert_eq!(*(&POOL).deserialize(de).unwrap(), 42);
+}
+
+#[cfg(feature = "postcard")]
+#[test]
+fn postcard() {
+    use crate::PostcardError;
+
+    static MESSAGES: Pool<[u8; 8]> = Pool::new();
+
+    MESSAGES.grow(memory::<[u8; 8]>(1));
+
+    let (message, len) = MESSAGES.encode_postcard(&(1u8, "hi")).unwrap();
+    assert_eq!(&message[..len], &[1, 2, b'h', b'i']);
+    assert_eq!(
+        MESSAGES.encode_postcard(&0u8),
+        Err(PostcardError::Exhausted)
+    );
+
+    // the decoded value borrows from the message
+    let pool = Pool::<(u8, &str)>::new();
+    pool.grow(memory::<(u8, &str)>(1));
+
+    // truncated message; the memory block is returned to the pool
+    assert!(matches!(
+        pool.decode_postcard(&message[..3]),
+        Err(PostcardError::Postcard(_))
+    ));
+
+    let (value, len) = pool.decode_postcard(&message[..]).unwrap();
+    assert_eq!(*value, (1, "hi"));
+    assert_eq!(len, 4);
+    pool.free(value);
+
+    // the value doesn't fit in a memory block; the memory block is returned to the pool
+    MESSAGES.free(message);
+    assert!(MESSAGES.encode_postcard(&[1000u32; 8]).is_err());
+    assert!(MESSAGES.alloc().is_some());
+}
+
+#[cfg(feature = "dma")]
+#[test]
+fn dma() {
+    use embedded_dma::{ReadBuffer, WriteBuffer};
+
+    static POOL: Pool<[u16; 8]> = Pool::new();
+
+    POOL.grow(memory::<[u16; 8]>(1));
+
+    let mut x = POOL.alloc().unwrap();
+    let (ptr, len) = unsafe { x.write_buffer() };
+    assert_eq!(ptr as usize, x.as_uninit().as_ptr() as usize);
+    assert_eq!(len, 8);
+
+    let x = x.init([0; 8]);
+    let (ptr, len) = unsafe { x.read_buffer() };
+    assert_eq!(ptr, x.as_ptr());
+    assert_eq!(len, 8);
+}
+
+#[cfg(feature = "heapless")]
+#[test]
+fn alloc_many_into() {
+    static POOL: Pool<u8> = Pool::new();
+
+    POOL.grow(memory::<u8>(3));
+
+    // stops when the vector is full
+    let mut vec = heapless::Vec::<_, 2>::new();
+    assert_eq!(POOL.alloc_many_into(&mut vec), 2);
+    assert_eq!(POOL.alloc_many_into(&mut vec), 0);
+
+    // stops when the pool is exhausted
+    let mut rest = heapless::Vec::<_, 4>::new();
+    assert_eq!(POOL.alloc_many_into(&mut rest), 1);
+
+    for block in vec.into_iter().chain(rest) {
+        POOL.free(block);
+    }
+}
+
+// The tests leak boxes on purpose, e.g. `destructors`, so the guard only fires on the threads
+// of the tests that arm it
+#[cfg(feature = "leak-guard")]
+thread_local! {
+    pub(crate) static LEAK_GUARD: core::cell::Cell<bool> = const { core::cell::Cell::new(false) };
+}
+
+#[cfg(feature = "leak-guard")]
+#[test]
+#[should_panic(expected = "leaked")]
+fn leak_guard() {
+    LEAK_GUARD.with(|armed| armed.set(true));
+
+    static POOL: Pool<u8> = Pool::new();
+
+    POOL.grow(memory::<u8>(2));
+
+    // returning the memory block to the pool is fine
+    let x = POOL.alloc().unwrap().init(0);
+    let (_, x) = x.into_inner();
+    POOL.free(x);
+
+    // leaking it is not
+    drop(POOL.alloc().unwrap());
+}
+
+#[cfg(feature = "leak-guard")]
+#[test]
+#[should_panic(expected = "unrelated")]
+fn leak_guard_unwinding() {
+    LEAK_GUARD.with(|armed| armed.set(true));
+
+    static POOL: Pool<u8> = Pool::new();
+
+    POOL.grow(memory::<u8>(1));
+
+    // the box is dropped while unwinding; the guard stays quiet instead of aborting the process
+    let _x = POOL.alloc().unwrap();
+    panic!("unrelated");
+}
@@ -0,0 +1,313 @@
+//! Intrusive timer queue of memory blocks
+//!
+//! A `TimerQueue` keeps `Box`es sorted by the deadline stored in them, linking them through the
+//! `next` pointer that is unused while a memory block is allocated (see the `mpsc` module). This
+//! lets software timers live entirely in pool memory.
+//!
+//! ``` ignore
+//! use lifo::{timer::{Deadline, TimerQueue}, Pool};
+//!
+//! struct Timeout {
+//!     at: u64,
+//!     request: u8,
+//! }
+//!
+//! impl Deadline for Timeout {
+//!     type Instant = u64;
+//!
+//!     fn deadline(&self) -> u64 {
+//!         self.at
+//!     }
+//! }
+//!
+//! static TIMEOUTS: Pool<Timeout> = Pool::new();
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     static mut QUEUE: TimerQueue<Timeout> = TimerQueue::new();
+//!
+//!     let (scheduler, mut dispatcher) = QUEUE.split();
+//!     // move `scheduler` into the interrupt handlers that start timeouts
+//!
+//!     loop {
+//!         while let Some(timeout) = dispatcher.pop_expired(now()) {
+//!             cancel(timeout.request);
+//!             TIMEOUTS.free(timeout);
+//!         }
+//!
+//!         if let Some(deadline) = dispatcher.next_deadline() {
+//!             set_alarm(deadline);
+//!         }
+//!
+//!         wfi();
+//!     }
+//! }
+//! ```
+//!
+//! Scheduling a timer pushes it onto a lock-free stack in constant time so it can be done from
+//! any interrupt handler. The dispatcher moves the scheduled timers into its sorted list the next
+//! time it's used; popping an expired timer from the head of that list takes constant time.
+
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use crate::{Box, Node};
+
+/// Values that have a deadline
+pub trait Deadline {
+    /// The type of the deadline
+    type Instant: Ord;
+
+    /// Returns the deadline of this value
+    fn deadline(&self) -> Self::Instant;
+}
+
+/// An intrusive timer queue of `Box<T>`es, sorted by deadline
+///
+/// Boxes still in the queue when it's dropped are leaked
+pub struct TimerQueue<T> {
+    // boxes scheduled, but not yet sorted by the dispatcher, newest first
+    scheduled: AtomicPtr<Node<T>>,
+    // boxes sorted by deadline; only accessed by the dispatcher
+    sorted: UnsafeCell<*mut Node<T>>,
+}
+
+unsafe impl<T> Sync for TimerQueue<T> where T: Send {}
+
+unsafe impl<T> Send for TimerQueue<T> where T: Send {}
+
+impl<T> TimerQueue<T> {
+    /// Creates a new empty timer queue
+    pub const fn new() -> Self {
+        TimerQueue {
+            scheduled: AtomicPtr::new(ptr::null_mut()),
+            sorted: UnsafeCell::new(ptr::null_mut()),
+        }
+    }
+
+    /// Splits the timer queue into its scheduling and dispatching endpoints
+    pub fn split(&mut self) -> (Scheduler<'_, T>, Dispatcher<'_, T>) {
+        (
+            Scheduler { queue: self },
+            Dispatcher {
+                queue: self,
+                _not_sync: PhantomData,
+            },
+        )
+    }
+}
+
+impl<T> Default for TimerQueue<T> {
+    fn default() -> Self {
+        TimerQueue::new()
+    }
+}
+
+impl<T> fmt::Debug for TimerQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TimerQueue").finish()
+    }
+}
+
+/// The scheduling endpoint of a `TimerQueue`
+///
+/// Schedulers can be copied so several contexts can schedule timers on the same queue
+pub struct Scheduler<'a, T> {
+    queue: &'a TimerQueue<T>,
+}
+
+impl<T> Scheduler<'_, T> {
+    /// Schedules `timer`; it expires once its deadline has been reached
+    ///
+    /// *NOTE:* This method does *not* have bounded execution time; i.e. it contains a CAS loop
+    pub fn schedule(&self, timer: Box<T>) {
+        let node = timer.into_node();
+
+        let mut head = self.queue.scheduled.load(Ordering::Relaxed);
+        loop {
+            // NOTE(unsafe) the memory block is allocated so its `next` field is not in use
+            unsafe { (*node.as_ptr()).next = head }
+
+            match self.queue.scheduled.compare_exchange_weak(
+                head,
+                node.as_ptr(),
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                // queue was changed by some interrupt handler
+                Err(new_head) => head = new_head,
+            }
+        }
+    }
+}
+
+impl<T> Clone for Scheduler<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Scheduler<'_, T> {}
+
+impl<T> fmt::Debug for Scheduler<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Scheduler").finish()
+    }
+}
+
+/// The dispatching endpoint of a `TimerQueue`
+pub struct Dispatcher<'a, T> {
+    queue: &'a TimerQueue<T>,
+    // `sorted` is not synchronized
+    _not_sync: PhantomData<*const ()>,
+}
+
+unsafe impl<T> Send for Dispatcher<'_, T> where T: Send {}
+
+impl<T> Dispatcher<'_, T>
+where
+    T: Deadline,
+{
+    /// Removes the timer with the earliest deadline from the queue if that deadline is not later
+    /// than `now`
+    ///
+    /// Timers with the same deadline expire in the order they were scheduled
+    pub fn pop_expired(&mut self, now: T::Instant) -> Option<Box<T>> {
+        self.sort();
+
+        let sorted = unsafe { &mut *self.queue.sorted.get() };
+        let node = NonNull::new(*sorted)?;
+
+        if deadline(node) > now {
+            return None;
+        }
+
+        *sorted = unsafe { (*node.as_ptr()).next };
+
+        Some(Box {
+            _state: PhantomData,
+            node,
+        })
+    }
+
+    /// Returns the earliest deadline in the queue
+    pub fn next_deadline(&mut self) -> Option<T::Instant> {
+        self.sort();
+
+        NonNull::new(unsafe { *self.queue.sorted.get() }).map(deadline)
+    }
+
+    /// Returns `true` if there are no timers in the queue
+    pub fn is_empty(&self) -> bool {
+        let sorted = unsafe { *self.queue.sorted.get() };
+
+        sorted.is_null() && self.queue.scheduled.load(Ordering::Relaxed).is_null()
+    }
+
+    // Moves the scheduled timers into the sorted list
+    fn sort(&mut self) {
+        // put the scheduled timers in scheduling order
+        let mut scheduled = self
+            .queue
+            .scheduled
+            .swap(ptr::null_mut(), Ordering::Acquire);
+        let mut fifo = ptr::null_mut::<Node<T>>();
+        while let Some(node) = NonNull::new(scheduled) {
+            unsafe {
+                scheduled = (*node.as_ptr()).next;
+                (*node.as_ptr()).next = fifo;
+            }
+            fifo = node.as_ptr();
+        }
+
+        while let Some(node) = NonNull::new(fifo) {
+            unsafe {
+                fifo = (*node.as_ptr()).next;
+
+                // insert the timer after all the timers with the same, or an earlier, deadline
+                let at = deadline(node);
+                let mut link: *mut *mut Node<T> = self.queue.sorted.get();
+                while let Some(next) = NonNull::new(*link) {
+                    if deadline(next) > at {
+                        break;
+                    }
+
+                    link = &mut (*next.as_ptr()).next;
+                }
+
+                (*node.as_ptr()).next = *link;
+                *link = node.as_ptr();
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for Dispatcher<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Dispatcher").finish()
+    }
+}
+
+fn deadline<T>(node: NonNull<Node<T>>) -> T::Instant
+where
+    T: Deadline,
+{
+    // NOTE(unsafe) boxes in the queue hold an initialized value
+    unsafe { (*node.as_ref().data.get()).deadline() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Deadline, TimerQueue};
+    use crate::{tests::memory, Pool};
+
+    #[test]
+    fn timer_queue() {
+        struct Timer {
+            at: u32,
+            id: u8,
+        }
+
+        impl Deadline for Timer {
+            type Instant = u32;
+
+            fn deadline(&self) -> u32 {
+                self.at
+            }
+        }
+
+        static POOL: Pool<Timer> = Pool::new();
+
+        POOL.grow(memory::<Timer>(4));
+
+        let mut queue = TimerQueue::new();
+        let (scheduler, mut dispatcher) = queue.split();
+        assert!(dispatcher.is_empty());
+        assert_eq!(dispatcher.next_deadline(), None);
+
+        for &(at, id) in &[(30, 0), (10, 1), (30, 2)] {
+            scheduler.schedule(POOL.alloc_init(Timer { at, id }).ok().unwrap());
+        }
+        assert_eq!(dispatcher.next_deadline(), Some(10));
+
+        // scheduled after the dispatcher sorted the first timers
+        scheduler.schedule(POOL.alloc_init(Timer { at: 20, id: 3 }).ok().unwrap());
+
+        assert!(dispatcher.pop_expired(5).is_none());
+
+        let mut expired = [0; 4];
+        for id in expired.iter_mut() {
+            let timer = dispatcher.pop_expired(30).unwrap();
+            *id = timer.id;
+            POOL.free(timer);
+        }
+        assert_eq!(expired, [1, 3, 0, 2]);
+        assert!(dispatcher.is_empty());
+    }
+}
@@ -0,0 +1,243 @@
+//! Pools of raw memory blocks that can hold values of different types
+//!
+//! An `UntypedPool<B>` manages memory blocks with the size and alignment of `B` but doesn't store
+//! `B` values in them. Instead, `alloc` moves a value of any type `T` that fits in a memory block
+//! into it. This lets a single memory region serve several small message types without having to
+//! create, and size, one pool per type.
+//!
+//! ``` ignore
+//! use lifo::untyped::UntypedPool;
+//!
+//! // memory blocks of 32 bytes aligned to 8 bytes
+//! static MESSAGES: UntypedPool<[u64; 4]> = UntypedPool::new();
+//!
+//! MESSAGES.grow(MEMORY);
+//!
+//! let ping = MESSAGES.alloc(Ping { seq: 0 }).ok().unwrap();
+//! let reading = MESSAGES.alloc(Reading { channel: 1, value: 2.5 }).ok().unwrap();
+//!
+//! // does not compile: `[u8; 64]` doesn't fit in a memory block
+//! // let frame = MESSAGES.alloc([0u8; 64]);
+//! ```
+//!
+//! Whether `T` fits in a memory block is checked at compile time.
+
+use core::{
+    fmt,
+    marker::PhantomData,
+    mem::{self, MaybeUninit},
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+};
+
+use crate::{Node, Pool};
+
+/// A lock-free pool of memory blocks with the size and alignment of `B`
+pub struct UntypedPool<B> {
+    pool: Pool<B>,
+}
+
+impl<B> UntypedPool<B> {
+    /// Creates a new empty pool
+    pub const fn new() -> Self {
+        UntypedPool { pool: Pool::new() }
+    }
+
+    /// Claims a memory block from the pool and moves `val` into it
+    ///
+    /// Returns `val` back when the pool is observed as exhausted
+    ///
+    /// This fails to compile if `T` is larger, or requires a larger alignment, than `B`
+    ///
+    /// ``` compile_fail
+    /// use lifo::untyped::UntypedPool;
+    ///
+    /// let pool: &'static UntypedPool<[u32; 2]> = Box::leak(Box::new(UntypedPool::new()));
+    ///
+    /// let _ = pool.alloc([0u32; 3]);
+    /// ```
+    pub fn alloc<T>(&'static self, val: T) -> Result<UntypedBox<T, B>, T> {
+        #[allow(clippy::let_unit_value)]
+        let () = Fits::<T, B>::ASSERT;
+
+        match self.pool.alloc() {
+            Some(block) => {
+                let node = block.into_node();
+                unsafe { data::<T, B>(node).write(val) }
+
+                Ok(UntypedBox {
+                    _value: PhantomData,
+                    pool: self,
+                    node,
+                })
+            }
+            None => Err(val),
+        }
+    }
+
+    /// Increases the capacity of the pool
+    ///
+    /// See `Pool::grow`
+    pub fn grow(&self, memory: &'static mut [u8]) {
+        self.pool.grow(memory)
+    }
+
+    /// Increases the capacity of the pool using memory that has not been initialized
+    ///
+    /// See `Pool::grow_uninit`
+    pub fn grow_uninit(&self, memory: &'static mut [MaybeUninit<u8>]) {
+        self.pool.grow_uninit(memory)
+    }
+}
+
+impl<B> Default for UntypedPool<B> {
+    fn default() -> Self {
+        UntypedPool::new()
+    }
+}
+
+impl<B> fmt::Debug for UntypedPool<B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UntypedPool")
+            .field("block_size", &mem::size_of::<B>())
+            .field("block_align", &mem::align_of::<B>())
+            .finish()
+    }
+}
+
+struct Fits<T, B> {
+    _types: PhantomData<(T, B)>,
+}
+
+impl<T, B> Fits<T, B> {
+    // evaluated, and checked, when `UntypedPool::<B>::alloc::<T>` is instantiated
+    const ASSERT: () = assert!(
+        mem::size_of::<T>() <= mem::size_of::<B>() && mem::align_of::<T>() <= mem::align_of::<B>(),
+        "value doesn't fit in a memory block of the `UntypedPool`"
+    );
+}
+
+// Returns a pointer to the memory block of `node` as a `T` value
+fn data<T, B>(node: NonNull<Node<B>>) -> *mut T {
+    unsafe { node.as_ref().data.get() as *mut T }
+}
+
+/// A value of type `T` stored in a memory block of an `UntypedPool<B>`
+///
+/// Dropping this box drops the value and returns the memory block to its pool
+pub struct UntypedBox<T, B>
+where
+    B: 'static,
+{
+    _value: PhantomData<T>,
+    pool: &'static UntypedPool<B>,
+    node: NonNull<Node<B>>,
+}
+
+impl<T, B> UntypedBox<T, B> {
+    /// Moves the value out of the memory block and returns the memory block to its pool
+    pub fn into_inner(self) -> T {
+        let val = unsafe { data::<T, B>(self.node).read() };
+        self.pool.pool.release(self.node);
+        mem::forget(self);
+        val
+    }
+}
+
+// NOTE(unsafe) the box owns its value
+unsafe impl<T, B> Send for UntypedBox<T, B> where T: Send {}
+
+unsafe impl<T, B> Sync for UntypedBox<T, B> where T: Sync {}
+
+impl<T, B> Deref for UntypedBox<T, B> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*data::<T, B>(self.node) }
+    }
+}
+
+impl<T, B> DerefMut for UntypedBox<T, B> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *data::<T, B>(self.node) }
+    }
+}
+
+impl<T, B> Drop for UntypedBox<T, B> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(data::<T, B>(self.node)) }
+
+        self.pool.pool.release(self.node)
+    }
+}
+
+impl<T, B> fmt::Debug for UntypedBox<T, B>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        <T as fmt::Debug>::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::UntypedPool;
+    use crate::tests::memory;
+
+    #[test]
+    fn untyped_pool() {
+        static POOL: UntypedPool<[u32; 2]> = UntypedPool::new();
+
+        POOL.grow(memory::<[u32; 2]>(2));
+
+        let mut a = POOL.alloc(1u8).unwrap();
+        let b = POOL.alloc((2u16, 3u32)).unwrap();
+        assert_eq!(POOL.alloc('c').err(), Some('c'));
+
+        *a += 10;
+        assert_eq!((*a, *b), (11, (2, 3)));
+
+        // the freed memory block is reused for a value of a different type
+        assert_eq!(b.into_inner(), (2, 3));
+        assert_eq!(*POOL.alloc(4u32).unwrap(), 4);
+    }
+
+    #[test]
+    fn drop() {
+        static POOL: UntypedPool<[u32; 2]> = UntypedPool::new();
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        struct X(#[allow(dead_code)] u8);
+
+        impl Drop for X {
+            fn drop(&mut self) {
+                COUNT.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        POOL.grow(memory::<[u32; 2]>(1));
+
+        let x = POOL.alloc(X(0)).ok().unwrap();
+
+        // the pool is exhausted; the value is handed back rather than dropped
+        let y = POOL.alloc(X(1)).err().unwrap();
+        assert_eq!(COUNT.load(Ordering::Relaxed), 0);
+
+        core::mem::drop(x);
+        assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+
+        // moving the value out doesn't drop it
+        let x = POOL.alloc(y).ok().unwrap().into_inner();
+        assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+
+        core::mem::drop(x);
+        assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+
+        // both times the memory block was returned to the pool
+        assert!(POOL.alloc(X(2)).is_ok());
+        assert_eq!(COUNT.load(Ordering::Relaxed), 3);
+    }
+}